@@ -75,6 +75,47 @@ fn bench_stalwart(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn bench_small_message(b: &mut Bencher) {
+    let message = concat!(
+        "From: Notifications <noreply@example.com>\r\n",
+        "To: jane@example.com\r\n",
+        "Subject: Your order has shipped\r\n",
+        "Date: Mon, 2 Oct 2023 10:00:00 +0000\r\n",
+        "Message-ID: <order-1234@example.com>\r\n",
+        "Content-Type: text/plain; charset=utf-8\r\n",
+        "\r\n",
+        "Your order #1234 has shipped and will arrive on Friday.\r\n"
+    )
+    .as_bytes();
+
+    b.iter(|| mail_parser::Message::parse(test::black_box(message)));
+}
+
+#[bench]
+fn bench_header_names(b: &mut Bencher) {
+    let names = [
+        "From",
+        "to",
+        "SUBJECT",
+        "Date",
+        "Message-ID",
+        "Content-Transfer-Encoding",
+        "List-Unsubscribe",
+        "Received",
+        "DKIM-Signature",
+        "X-Mailer",
+        "Authentication-Results",
+        "X-Spam-Status",
+    ];
+
+    b.iter(|| {
+        for name in names {
+            test::black_box(mail_parser::HeaderName::parse(test::black_box(name)));
+        }
+    });
+}
+
 // These libraries do not support all RFCs and might be faster
 // on the benchmarks as they do not parse all header fields,
 // do not decode encoded parts or fail while trying to parse the messages.
@@ -107,7 +107,7 @@ R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7
         message.body_text(0).unwrap(),
         concat!(
             "I was thinking about quitting the “exporting” to focus just on the",
-            " “importing”,\nbut then I thought, why not do both? ☺\n"
+            " “importing”,\n\nbut then I thought, why not do both? ☺"
         )
     );
 
@@ -90,6 +90,15 @@ pub fn base64_decode_stream<'x>(
     buf.into()
 }
 
+/// Returns the position of the first byte that is not part of the base64
+/// alphabet, padding or whitespace.
+pub fn base64_error_offset(bytes: &[u8]) -> Option<usize> {
+    bytes.iter().position(|&ch| {
+        BASE64_MAP[0][ch as usize] >= 0x01ffffff
+            && !matches!(ch, b'=' | b' ' | b'\t' | b'\r' | b'\n')
+    })
+}
+
 impl<'x> MessageStream<'x> {
     pub fn decode_base64_mime(&mut self, boundary: &[u8]) -> (usize, Cow<'x, [u8]>) {
         let mut chunk: u32 = 0;
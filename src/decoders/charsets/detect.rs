@@ -0,0 +1,278 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use super::map::charset_decoder;
+
+// Number of bytes examined when detecting a charset.
+const MAX_SAMPLE_LEN: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "full_encoding"), allow(dead_code))]
+enum Language {
+    Alphabetic,
+    Japanese,
+    Korean,
+    Chinese,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Hebrew,
+    Arabic,
+    Han,
+    Kana,
+    Hangul,
+    Other,
+}
+
+// Charsets tried in order, ties are won by the first one.
+static CANDIDATES: &[(&str, Language)] = &[
+    #[cfg(feature = "full_encoding")]
+    ("shift_jis", Language::Japanese),
+    #[cfg(feature = "full_encoding")]
+    ("euc-jp", Language::Japanese),
+    #[cfg(feature = "full_encoding")]
+    ("gbk", Language::Chinese),
+    #[cfg(feature = "full_encoding")]
+    ("big5", Language::Chinese),
+    #[cfg(feature = "full_encoding")]
+    ("euc-kr", Language::Korean),
+    ("windows-1252", Language::Alphabetic),
+    ("iso-8859-15", Language::Alphabetic),
+    ("iso-8859-2", Language::Alphabetic),
+    ("windows-1251", Language::Alphabetic),
+    ("koi8-r", Language::Alphabetic),
+    ("iso-8859-7", Language::Alphabetic),
+    ("windows-1256", Language::Alphabetic),
+    ("windows-1255", Language::Alphabetic),
+];
+
+// The most frequent Han characters in simplified and traditional Chinese,
+// which random byte pairs rarely decode to.
+static FREQUENT_HAN: &str = concat!(
+    "的一是不了在人有我他这個个们們中来來上大为為和国國地到以说說时時要就出会會",
+    "可也你好对對生能而子那得于於着著下自之年过過发發后後作里裡"
+);
+
+// Punctuation and spacing found in text regardless of its language.
+static COMMON_PUNCTUATION: &str = "\u{a0}¡¿«»‘’‚“”„–—…•·・€°§©®™";
+
+/// Guesses the charset of a text lacking a charset label, or whose label
+/// is wrong, using heuristics on the first 16 KiB: UTF-8 when the bytes are
+/// valid UTF-8, otherwise the candidate charset decoding to the most
+/// plausible text. The candidates are Shift_JIS, EUC-JP, GBK, Big5 and
+/// EUC-KR, which require encoding_rs, followed by windows-1252, the
+/// ISO-8859 Latin, Cyrillic and Greek charsets, KOI8-R, windows-1251 and
+/// the Arabic and Hebrew windows charsets.
+///
+/// Returns `None` for ASCII text or when no candidate decodes to plausible
+/// text.
+pub fn detect_charset(bytes: &[u8]) -> Option<&'static str> {
+    let sample = &bytes[..bytes.len().min(MAX_SAMPLE_LEN)];
+    let is_truncated = sample.len() < bytes.len();
+    if sample.is_ascii() {
+        return None;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return Some("utf-8"),
+        Err(err) if is_truncated && err.error_len().is_none() => return Some("utf-8"),
+        Err(_) => (),
+    }
+
+    let mut best = None;
+    let mut best_score = 0;
+    for &(charset, language) in CANDIDATES {
+        let text = if let Some(decoder) = charset_decoder(charset.as_bytes()) {
+            decoder(sample)
+        } else {
+            continue;
+        };
+        // A multi-byte character may be split at the end of the sample
+        let text = if is_truncated {
+            text.trim_end_matches(char::REPLACEMENT_CHARACTER)
+        } else {
+            &text
+        };
+        if text.contains(char::REPLACEMENT_CHARACTER) {
+            continue;
+        }
+        let score = score_text(text, language);
+        if score > best_score {
+            best = Some(charset);
+            best_score = score;
+        }
+    }
+    best
+}
+
+// Scores how plausible a decoded text is: letters of the scripts used by
+// the language in words of a single script and with the usual casing add
+// to the score, while symbols, control characters and words mixing
+// scripts or cases subtract from it.
+fn score_text(text: &str, language: Language) -> i64 {
+    let mut score = 0;
+    let mut chars = text.chars().peekable();
+    let mut word = Vec::new();
+
+    while let Some(ch) = chars.next() {
+        if ch.is_alphabetic() {
+            word.push(ch);
+            if chars.peek().is_some_and(|ch| ch.is_alphabetic()) {
+                continue;
+            }
+            score += score_word(&word, language);
+            word.clear();
+        } else if !ch.is_ascii() {
+            if ch.is_control() {
+                score -= 5;
+            } else if !COMMON_PUNCTUATION.contains(ch)
+                && !matches!(ch, '\u{3000}'..='\u{303f}' | '\u{ff01}'..='\u{ff5e}')
+            {
+                score -= 3;
+            }
+        } else if ch.is_ascii_control() && !ch.is_ascii_whitespace() {
+            score -= 5;
+        }
+    }
+
+    score
+}
+
+fn score_word(word: &[char], language: Language) -> i64 {
+    let mut word_script = None;
+    for &ch in word {
+        let ch_script = match script(ch) {
+            Script::Kana | Script::Hangul => Script::Han,
+            ch_script => ch_script,
+        };
+        if word_script.get_or_insert(ch_script) != &ch_script {
+            return -3 * word.iter().filter(|ch| !ch.is_ascii()).count() as i64;
+        }
+    }
+
+    if word.iter().all(char::is_ascii) {
+        return 0;
+    }
+
+    // Accented letters are a minority in words written in the Latin script
+    let letter_score = if word_script == Some(Script::Latin) {
+        1
+    } else {
+        2
+    };
+    let is_uppercase = word.iter().all(|ch| !ch.is_lowercase());
+    let mut score = 0;
+    for (pos, &ch) in word.iter().enumerate() {
+        if ch.is_uppercase() && pos > 0 && !is_uppercase {
+            score -= 3;
+        }
+        if ch.is_ascii() {
+            continue;
+        }
+        score += match (language, script(ch)) {
+            (_, Script::Other) => -3,
+            (Language::Japanese, Script::Kana) => 3,
+            (Language::Japanese, Script::Han) => 2,
+            (Language::Korean, Script::Hangul) => 3,
+            (Language::Korean, Script::Han) => -1,
+            (Language::Chinese, Script::Han) if FREQUENT_HAN.contains(ch) => 4,
+            (Language::Chinese, Script::Han) => 2,
+            (Language::Alphabetic, Script::Han | Script::Kana | Script::Hangul)
+            | (Language::Japanese | Language::Korean | Language::Chinese, _) => -3,
+            (Language::Alphabetic, _) if ch.is_uppercase() => 1,
+            (Language::Alphabetic, _) => letter_score,
+        };
+    }
+    score
+}
+
+fn script(ch: char) -> Script {
+    match ch {
+        'A'..='Z' | 'a'..='z' | '\u{c0}'..='\u{24f}' | '\u{1e00}'..='\u{1eff}' => Script::Latin,
+        '\u{370}'..='\u{3ff}' | '\u{1f00}'..='\u{1fff}' => Script::Greek,
+        '\u{400}'..='\u{52f}' => Script::Cyrillic,
+        // Vowel points and other diacritics are rarely written
+        '\u{5d0}'..='\u{5f4}' => Script::Hebrew,
+        '\u{620}'..='\u{64a}' | '\u{66e}'..='\u{6d3}' | '\u{750}'..='\u{77f}' => Script::Arabic,
+        '\u{3040}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' => Script::Kana,
+        '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' => Script::Han,
+        '\u{ac00}'..='\u{d7af}' => Script::Hangul,
+        _ => Script::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect_charset;
+
+    #[test]
+    fn detect_charsets() {
+        for (bytes, expected) in [
+            (&b"Plain ASCII text"[..], None),
+            ("Ünïcödé text".as_bytes(), Some("utf-8")),
+            (
+                b"\xa1El \xf1and\xfa comi\xf3 \xf1oquis!",
+                Some("windows-1252"),
+            ),
+            (b"Prix: 5 \xa4 le caf\xe9", Some("iso-8859-15")),
+            (
+                b"Gr\xfc\xdfe aus M\xfcnchen, \x93sch\xf6ne\x94 Stra\xdfe \x96 \xc4rger",
+                Some("windows-1252"),
+            ),
+            (
+                b"Za\xbf\xf3\xb3\xe6 g\xea\xb6l\xb1 ja\xbc\xf1",
+                Some("iso-8859-2"),
+            ),
+            (
+                b"\xcf\xf0\xe8\xe2\xe5\xf2, \xec\xe8\xf0",
+                Some("windows-1251"),
+            ),
+            (b"\xf0\xd2\xc9\xd7\xc5\xd4, \xcd\xc9\xd2", Some("koi8-r")),
+            (
+                b"\xc3\xe5\xe9\xdc \xf3\xef\xf5 \xca\xfc\xf3\xec\xe5",
+                Some("iso-8859-7"),
+            ),
+            (
+                b"\xe3\xd1\xcd\xc8\xc7 \xc8\xc7\xe1\xda\xc7\xe1\xe3",
+                Some("windows-1256"),
+            ),
+            #[cfg(feature = "full_encoding")]
+            (
+                b"\x83n\x83\x8D\x81[\x81E\x83\x8F\x81[\x83\x8B\x83h",
+                Some("shift_jis"),
+            ),
+            #[cfg(feature = "full_encoding")]
+            (
+                b"\xa4\xb3\xa4\xf3\xa4\xcb\xa4\xc1\xa4\xcf\xc0\xa4\xb3\xa6",
+                Some("euc-jp"),
+            ),
+            #[cfg(feature = "full_encoding")]
+            (b"\xc4\xe3\xba\xc3\xa3\xac\xca\xc0\xbd\xe7", Some("gbk")),
+            #[cfg(feature = "full_encoding")]
+            (b"\xa7A\xa6n\xa1A\xa5@\xac\xc9", Some("big5")),
+            #[cfg(feature = "full_encoding")]
+            (
+                b"\xbe\xc8\xb3\xe7\xc7\xcf\xbc\xbc\xbf\xe4 \xbc\xbc\xb0\xe8",
+                Some("euc-kr"),
+            ),
+        ] {
+            assert_eq!(
+                detect_charset(bytes),
+                expected,
+                "{}",
+                String::from_utf8_lossy(bytes)
+            );
+        }
+    }
+}
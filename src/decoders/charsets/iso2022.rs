@@ -0,0 +1,391 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Decoder for the 7-bit ISO-2022 charsets used in e-mail: ISO-2022-JP
+//! (RFC 1468) and its extensions ISO-2022-JP-1 (RFC 2237), ISO-2022-JP-2
+//! (RFC 1554) and ISO-2022-JP-3, ISO-2022-KR (RFC 1557) and ISO-2022-CN
+//! (RFC 1922).
+//!
+//! A single state machine handles all of them as their escape sequences do
+//! not overlap. The double-byte character sets are decoded through their
+//! EUC forms, so JIS X 0213 characters outside JIS X 0208 and the CNS 11643
+//! planes of ISO-2022-CN, which encoding_rs lacks, are replaced by U+FFFD.
+
+#[cfg(feature = "full_encoding")]
+use encoding_rs::{Encoding, EUC_JP, EUC_KR, GBK};
+
+#[cfg(feature = "full_encoding")]
+use super::{
+    multi_byte::multi_byte_decoder, multi_byte_replacements, single_byte::decoder_iso_8859_7,
+};
+
+#[cfg(feature = "full_encoding")]
+const ESC: u8 = 0x1b;
+#[cfg(feature = "full_encoding")]
+const SO: u8 = 0x0e;
+#[cfg(feature = "full_encoding")]
+const SI: u8 = 0x0f;
+
+#[cfg(feature = "full_encoding")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Ascii,
+    JisRoman,
+    JisKatakana,
+    Jis0208,
+    Jis0212,
+    Gb2312,
+    Ksc5601,
+    Latin1,
+    Greek,
+    // CNS 11643 and JIS X 0213 plane 2
+    Unsupported,
+}
+
+#[cfg(feature = "full_encoding")]
+impl Charset {
+    fn is_double_byte(&self) -> bool {
+        matches!(
+            self,
+            Charset::Jis0208
+                | Charset::Jis0212
+                | Charset::Gb2312
+                | Charset::Ksc5601
+                | Charset::Unsupported
+        )
+    }
+}
+
+/// Decodes text in any of the ISO-2022-JP, ISO-2022-KR and ISO-2022-CN
+/// charsets and their extensions.
+pub fn decoder_iso2022(bytes: &[u8]) -> String {
+    #[cfg(feature = "full_encoding")]
+    {
+        let mut decoder = Iso2022Decoder::default();
+        decoder.decode(bytes);
+        decoder.result.shrink_to_fit();
+        decoder.result
+    }
+
+    #[cfg(not(feature = "full_encoding"))]
+    {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Returns whether a charset label names one of the ISO-2022 charsets.
+#[cfg(feature = "full_encoding")]
+pub(crate) fn is_iso2022(charset: &str) -> bool {
+    ["iso-2022-", "csiso2022"].iter().any(|prefix| {
+        charset
+            .get(..prefix.len())
+            .is_some_and(|label| label.eq_ignore_ascii_case(prefix))
+    })
+}
+
+/// Returns the number of replacement characters inserted by
+/// [`decoder_iso2022`], along with the number of bytes they replaced.
+#[cfg(feature = "full_encoding")]
+pub(crate) fn iso2022_replacements(bytes: &[u8]) -> (usize, usize) {
+    let mut decoder = Iso2022Decoder::default();
+    decoder.decode(bytes);
+    decoder.replacements
+}
+
+#[cfg(feature = "full_encoding")]
+struct Iso2022Decoder {
+    result: String,
+    // Replacement characters inserted and the number of bytes they replaced
+    replacements: (usize, usize),
+    // Bytes in EUC form waiting to be decoded with the same encoding.
+    run: Vec<u8>,
+    run_encoding: Option<&'static Encoding>,
+    // Designated charsets
+    g0: Charset,
+    g1: Option<Charset>,
+    g2: Option<Charset>,
+    g3: Option<Charset>,
+    is_shifted: bool,
+}
+
+#[cfg(feature = "full_encoding")]
+impl Default for Iso2022Decoder {
+    fn default() -> Self {
+        Iso2022Decoder {
+            result: String::new(),
+            replacements: (0, 0),
+            run: Vec::new(),
+            run_encoding: None,
+            g0: Charset::Ascii,
+            g1: None,
+            g2: None,
+            g3: None,
+            is_shifted: false,
+        }
+    }
+}
+
+#[cfg(feature = "full_encoding")]
+impl Iso2022Decoder {
+    fn decode(&mut self, bytes: &[u8]) {
+        self.result.reserve(bytes.len() * 2);
+        let mut pos = 0;
+
+        while let Some(&ch) = bytes.get(pos) {
+            pos += 1;
+            match ch {
+                ESC => {
+                    if let Some(len) = self.escape(&bytes[pos..]) {
+                        pos += len;
+                    } else {
+                        self.push_replacement(1);
+                    }
+                }
+                SO => {
+                    // JIS7 uses SO for halfwidth katakana
+                    self.is_shifted = true;
+                }
+                SI => {
+                    self.is_shifted = false;
+                }
+                b'\n' | b'\r' => {
+                    // Shifts do not span lines
+                    self.is_shifted = false;
+                    self.push_char(ch as char);
+                }
+                0x21..=0x7e => {
+                    let charset = if self.is_shifted {
+                        self.g1.unwrap_or(Charset::JisKatakana)
+                    } else {
+                        self.g0
+                    };
+                    if charset.is_double_byte() {
+                        match bytes.get(pos) {
+                            Some(&ch2 @ 0x21..=0x7e) => {
+                                pos += 1;
+                                self.push_double(charset, ch, ch2);
+                            }
+                            _ => self.push_replacement(1),
+                        }
+                    } else {
+                        self.push_single(charset, ch);
+                    }
+                }
+                0xa1..=0xdf => {
+                    // 8-bit halfwidth katakana found in JIS8 text
+                    self.push_single(Charset::JisKatakana, ch & 0x7f);
+                }
+                0x80..=0xff => self.push_replacement(1),
+                _ => self.push_char(ch as char),
+            }
+        }
+
+        self.flush();
+    }
+
+    // Handles the escape sequence following an ESC, returning its length.
+    fn escape(&mut self, bytes: &[u8]) -> Option<usize> {
+        match *bytes {
+            [b'(', final_byte, ..] => {
+                self.g0 = match final_byte {
+                    b'B' => Charset::Ascii,
+                    b'J' => Charset::JisRoman,
+                    b'I' => Charset::JisKatakana,
+                    _ => return None,
+                };
+                Some(2)
+            }
+            [b'$', b'@' | b'B', ..] => {
+                self.g0 = Charset::Jis0208;
+                Some(2)
+            }
+            [b'$', b'A', ..] => {
+                self.g0 = Charset::Gb2312;
+                Some(2)
+            }
+            [b'$', b'(', final_byte, ..] => {
+                self.g0 = match final_byte {
+                    b'B' | b'O' | b'Q' => Charset::Jis0208,
+                    b'D' => Charset::Jis0212,
+                    b'C' => Charset::Ksc5601,
+                    b'A' => Charset::Gb2312,
+                    b'P' => Charset::Unsupported,
+                    _ => return None,
+                };
+                Some(3)
+            }
+            [b'$', b')', final_byte, ..] => {
+                self.g1 = match final_byte {
+                    b'C' => Charset::Ksc5601,
+                    b'A' | b'E' => Charset::Gb2312,
+                    b'G' => Charset::Unsupported,
+                    _ => return None,
+                }
+                .into();
+                Some(3)
+            }
+            [b'$', b'*', b'H', ..] => {
+                self.g2 = Some(Charset::Unsupported);
+                Some(3)
+            }
+            [b'$', b'+', b'I'..=b'M', ..] => {
+                self.g3 = Some(Charset::Unsupported);
+                Some(3)
+            }
+            [b'.', final_byte, ..] => {
+                self.g2 = match final_byte {
+                    b'A' => Charset::Latin1,
+                    b'F' => Charset::Greek,
+                    _ => return None,
+                }
+                .into();
+                Some(2)
+            }
+            [b'&', b'@', ..] => {
+                // Announces the JIS X 0208 revision that follows
+                Some(2)
+            }
+            [shift @ (b'N' | b'O'), ref rest @ ..] => {
+                // Single shifts to G2 or G3
+                let charset = if shift == b'N' { self.g2 } else { self.g3 }?;
+                match (charset.is_double_byte(), rest) {
+                    (true, [ch1 @ 0x21..=0x7e, ch2 @ 0x21..=0x7e, ..]) => {
+                        self.push_double(charset, *ch1, *ch2);
+                        Some(3)
+                    }
+                    (false, [ch @ 0x20..=0x7f, ..]) => {
+                        self.push_single(charset, *ch);
+                        Some(2)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn push_single(&mut self, charset: Charset, ch: u8) {
+        let ch = match (charset, ch) {
+            (Charset::JisRoman, b'\\') => '\u{a5}',
+            (Charset::JisRoman, b'~') => '\u{203e}',
+            (Charset::JisKatakana, 0x21..=0x5f) => {
+                char::from_u32(0xff61 + (ch - 0x21) as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            (Charset::JisKatakana, _) => char::REPLACEMENT_CHARACTER,
+            (Charset::Latin1, _) => (ch | 0x80) as char,
+            (Charset::Greek, _) => decoder_iso_8859_7(&[ch | 0x80])
+                .chars()
+                .next()
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+            _ => ch as char,
+        };
+        if ch != char::REPLACEMENT_CHARACTER {
+            self.push_char(ch);
+        } else {
+            self.push_replacement(1);
+        }
+    }
+
+    fn push_double(&mut self, charset: Charset, ch1: u8, ch2: u8) {
+        let (encoding, prefix) = match charset {
+            Charset::Jis0208 => (EUC_JP, None),
+            Charset::Jis0212 => (EUC_JP, Some(0x8f)),
+            Charset::Gb2312 => (GBK, None),
+            Charset::Ksc5601 => (EUC_KR, None),
+            _ => {
+                self.push_replacement(2);
+                return;
+            }
+        };
+        if self.run_encoding != Some(encoding) {
+            self.flush();
+            self.run_encoding = Some(encoding);
+        }
+        self.run.extend(prefix);
+        self.run.extend([ch1 | 0x80, ch2 | 0x80]);
+    }
+
+    fn push_char(&mut self, ch: char) {
+        self.flush();
+        self.result.push(ch);
+    }
+
+    fn push_replacement(&mut self, replaced: usize) {
+        self.push_char(char::REPLACEMENT_CHARACTER);
+        self.replacements.0 += 1;
+        self.replacements.1 += replaced;
+    }
+
+    fn flush(&mut self) {
+        if let Some(encoding) = self.run_encoding.take() {
+            let text = multi_byte_decoder(encoding.new_decoder_without_bom_handling(), &self.run);
+            if text.contains(char::REPLACEMENT_CHARACTER) {
+                let (count, replaced) = multi_byte_replacements(encoding, &self.run);
+                self.replacements.0 += count;
+                self.replacements.1 += replaced;
+            }
+            self.result.push_str(&text);
+            self.run.clear();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "full_encoding"))]
+mod tests {
+    use super::decoder_iso2022;
+
+    #[test]
+    fn decode_iso2022() {
+        for (input, expected) in [
+            // ISO-2022-JP
+            (
+                &b"\x1b$B%O%m!<!&%o!<%k%I\x1b(B"[..],
+                "\u{30cf}\u{30ed}\u{30fc}\u{30fb}\u{30ef}\u{30fc}\u{30eb}\u{30c9}",
+            ),
+            (
+                b"Subject: \x1b$B$3$s$K$A$O\x1b(B, world\r\n\x1b$@F|K\\\x1b(J\\100~",
+                "Subject: \u{3053}\u{3093}\u{306b}\u{3061}\u{306f}, world\r\n\u{65e5}\u{672c}\u{a5}100\u{203e}",
+            ),
+            // Halfwidth katakana: ESC ( I, SO/SI and 8-bit
+            (
+                b"\x1b(I6@6E\x1b(B \x0e6@6E\x0f \xb6\xc0\xb6\xc5",
+                "\u{ff76}\u{ff80}\u{ff76}\u{ff85} \u{ff76}\u{ff80}\u{ff76}\u{ff85} \u{ff76}\u{ff80}\u{ff76}\u{ff85}",
+            ),
+            // ISO-2022-JP-1: JIS X 0212
+            (b"\x1b$(D*&+!\x1b(B", "\u{1cd}\u{e1}"),
+            // ISO-2022-JP-2: GB 2312, KS C 5601 and ISO-8859-1/7 high parts
+            (
+                b"\x1b$A;6S-\x1b$(C>H3g\x1b(B \x1b.A\x1bNi \x1b.F\x1bNa",
+                "\u{6b22}\u{8fce}\u{c548}\u{b155} \u{e9} \u{3b1}",
+            ),
+            // ISO-2022-KR
+            (
+                b"\x1b$)C\x0e>H3g\x0f hello\r\n\x0e<<0h\x0f",
+                "\u{c548}\u{b155} hello\r\n\u{c138}\u{acc4}",
+            ),
+            // ISO-2022-CN with GB 2312 and an unsupported CNS 11643 plane
+            (
+                b"\x1b$)A\x0e;6S-\x0f!\x1b$*H\x1bN!!",
+                "\u{6b22}\u{8fce}!\u{fffd}",
+            ),
+            // Truncated and unknown sequences
+            (b"\x1b$B%O%", "\u{30cf}\u{fffd}"),
+            (b"a\x1b(Zb", "a\u{fffd}(Zb"),
+        ] {
+            assert_eq!(
+                decoder_iso2022(input),
+                expected,
+                "{:?}",
+                String::from_utf8_lossy(input)
+            );
+        }
+    }
+}
@@ -10,6 +10,7 @@
  */
 
 use super::{
+    iso2022::decoder_iso2022,
     multi_byte::*,
     single_byte::*,
     utf::{decoder_utf16, decoder_utf16_be, decoder_utf16_le, decoder_utf7, decoder_utf8},
@@ -74,9 +75,30 @@ pub fn charset_decoder(charset: &[u8]) -> Option<DecoderFnc> {
         }
     }
 
-    None
+    if ISO2022_LABELS
+        .iter()
+        .any(|label| label.eq_ignore_ascii_case(charset))
+    {
+        Some(decoder_iso2022)
+    } else {
+        label_decoder(charset)
+    }
 }
 
+// ISO-2022 charsets missing from the table, handled by the same decoder
+// as ISO-2022-JP.
+static ISO2022_LABELS: &[&[u8]] = &[
+    b"iso-2022-jp-1",
+    b"iso-2022-jp-2",
+    b"csiso2022jp2",
+    b"iso-2022-jp-3",
+    b"iso-2022-jp-2004",
+    b"iso-2022-kr",
+    b"csiso2022kr",
+    b"iso-2022-cn",
+    b"iso-2022-cn-ext",
+];
+
 pub fn no_op(_bytes: &[u8]) -> String {
     "".to_string()
 }
@@ -106,6 +128,24 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    #[cfg(feature = "full_encoding")]
+    fn decoder_charset_replacement() {
+        for (charset, input, expected) in [
+            ("utf-8", &b"\xff"[..], "\u{fffd}"),
+            ("utf-8", &b"a\xff\xfeb"[..], "a\u{fffd}\u{fffd}b"),
+            ("unicodefeff", &b"a\x00b"[..], "a\u{fffd}"),
+            ("unicodefffe", &b"\x00a\x00"[..], "a\u{fffd}"),
+        ] {
+            assert_eq!(
+                charset_decoder(charset.as_bytes()).unwrap()(input),
+                expected,
+                "{}",
+                charset
+            );
+        }
+    }
 }
 
 // Perfect hashing table for charset names
@@ -1108,7 +1148,7 @@ static FNC_MAP: &[for<'x> fn(&'x [u8]) -> String; 758] = &[
     no_op,
     no_op,
     decoder_euc_jp,
-    decoder_iso2022_jp,
+    decoder_iso2022,
     no_op,
     decoder_macintosh,
     no_op,
@@ -1463,7 +1503,7 @@ static FNC_MAP: &[for<'x> fn(&'x [u8]) -> String; 758] = &[
     no_op,
     no_op,
     no_op,
-    decoder_iso2022_jp,
+    decoder_iso2022,
     no_op,
     no_op,
     no_op,
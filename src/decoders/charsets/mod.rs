@@ -9,6 +9,8 @@
  * except according to those terms.
  */
 
+pub mod detect;
+pub mod iso2022;
 pub mod map;
 pub mod multi_byte;
 pub mod single_byte;
@@ -16,9 +18,76 @@ pub mod utf;
 
 pub type DecoderFnc = fn(&[u8]) -> String;
 
+/// Returns the number of replacement characters found in `text` after
+/// decoding `bytes` using `charset`, along with the number of bytes they
+/// replaced.
+#[cfg_attr(not(feature = "full_encoding"), allow(unused_variables))]
+pub fn charset_replacements(charset: &str, bytes: &[u8], text: &str) -> (usize, usize) {
+    let count = text.matches(char::REPLACEMENT_CHARACTER).count();
+    if count == 0 {
+        return (0, 0);
+    }
+
+    #[cfg(feature = "full_encoding")]
+    {
+        if iso2022::is_iso2022(charset) {
+            return iso2022::iso2022_replacements(bytes);
+        }
+        if let Some(encoding) = encoding_rs::Encoding::for_label_no_replacement(charset.as_bytes())
+            .filter(|encoding| !encoding.is_single_byte())
+        {
+            return multi_byte_replacements(encoding, bytes);
+        }
+    }
+
+    // Single byte charsets replace one byte per character
+    (count, count)
+}
+
+/// Returns the number of replacement characters inserted by lossy UTF-8
+/// decoding, along with the number of bytes they replaced.
+pub fn utf8_replacements(mut bytes: &[u8]) -> (usize, usize) {
+    let (mut count, mut replaced) = (0, 0);
+    while let Err(err) = std::str::from_utf8(bytes) {
+        let invalid_len = err.error_len().unwrap_or(bytes.len() - err.valid_up_to());
+        count += 1;
+        replaced += invalid_len;
+        bytes = &bytes[err.valid_up_to() + invalid_len..];
+    }
+    (count, replaced)
+}
+
+#[cfg(feature = "full_encoding")]
+fn multi_byte_replacements(
+    encoding: &'static encoding_rs::Encoding,
+    mut bytes: &[u8],
+) -> (usize, usize) {
+    use encoding_rs::DecoderResult;
+
+    let mut decoder = encoding.new_decoder();
+    let mut output = String::with_capacity(bytes.len() * 3);
+    let mut count = 0;
+    let mut replaced = 0;
+
+    loop {
+        let (result, read) = decoder.decode_to_string_without_replacement(bytes, &mut output, true);
+        bytes = &bytes[read..];
+        match result {
+            DecoderResult::InputEmpty => break,
+            DecoderResult::OutputFull => output.reserve(bytes.len() * 3 + 16),
+            DecoderResult::Malformed(malformed, _) => {
+                count += 1;
+                replaced += malformed as usize;
+            }
+        }
+    }
+
+    (count, replaced)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::map::charset_decoder;
+    use super::{charset_replacements, map::charset_decoder, utf8_replacements};
 
     #[test]
     fn decode_charset() {
@@ -59,9 +128,23 @@ mod tests {
             #[cfg(feature = "full_encoding")]
             ("iso-2022-jp", b"\x1b$B%O%m!<!&%o!<%k%I\x1b(B".to_vec(),"ハロー・ワールド"),
             #[cfg(feature = "full_encoding")]
+            ("iso-2022-kr", b"\x1b$)C\x0e>H3g\x0f".to_vec(),"안녕"),
+            #[cfg(feature = "full_encoding")]
             ("gbk", b"\xc4\xe3\xba\xc3\xa3\xac\xca\xc0\xbd\xe7".to_vec(),"你好，世界"),
             #[cfg(feature = "full_encoding")]
             ("gb18030", b"\xc4\xe3\xba\xc3\xa3\xac\xca\xc0\xbd\xe7".to_vec(),"你好，世界"),
+
+            // Labels missing from the built-in table
+            #[cfg(feature = "full_encoding")]
+            ("cp1250", b"Zelo rada grem v sla\x9a\xe8i\xe8arno".to_vec(),"Zelo rada grem v slaščičarno"),
+            #[cfg(feature = "full_encoding")]
+            ("x-mac-cyrillic", b"\x8f\xf0\xe8\xe2\xe5\xf2".to_vec(),"Привет"),
+            #[cfg(feature = "full_encoding")]
+            ("koi8-ru", b"\xf0\xd2\xc9\xd7\xa6\xd4 \xf3\xd7\xa6\xd4".to_vec(),"Привіт Світ"),
+            #[cfg(feature = "full_encoding")]
+            ("windows-31j", b"\x83n\x83\x8D\x81[\x81E\x83\x8F\x81[\x83\x8B\x83h".to_vec(),"ハロー・ワールド"),
+            #[cfg(feature = "full_encoding")]
+            ("big5-hkscs", b"\xa7A\xa6n\xa1A\xa5@\xac\xc9".to_vec(),"你好，世界"),
             ];
 
         for input in inputs {
@@ -70,5 +153,38 @@ mod tests {
 
             assert_eq!(decoder(&input.1), input.2);
         }
+
+        // Decoding to a single replacement character would lose the contents
+        assert!(charset_decoder(b"hz-gb-2312").is_none());
+    }
+
+    #[test]
+    fn count_replacements() {
+        assert_eq!(utf8_replacements("Caf\u{e9} \u{fffd}".as_bytes()), (0, 0));
+        assert_eq!(utf8_replacements(b"a\xe2\x82b\xff\xfe"), (3, 4));
+
+        for (charset, bytes, expected_result) in [
+            ("iso-8859-6", b"\xc7\xa1\xa2".to_vec(), (2, 2)),
+            ("windows-1252", b"Caf\xe9".to_vec(), (0, 0)),
+            #[cfg(feature = "full_encoding")]
+            ("shift_jis", b"\x83n\x83".to_vec(), (1, 1)),
+            #[cfg(feature = "full_encoding")]
+            ("euc-kr", b"\xbe\xc8\xb3\xff".to_vec(), (1, 2)),
+            #[cfg(feature = "full_encoding")]
+            (
+                "iso-2022-jp",
+                b"\x1b$B%O\x1b(B\xff\x1b$(P!!".to_vec(),
+                (2, 3),
+            ),
+            #[cfg(feature = "full_encoding")]
+            ("ISO-2022-KR", b"\x1b$)C\x0e>H3\x0f".to_vec(), (1, 1)),
+        ] {
+            let text = charset_decoder(charset.as_bytes()).unwrap()(&bytes);
+            assert_eq!(
+                charset_replacements(charset, &bytes, &text),
+                expected_result,
+                "{charset}"
+            );
+        }
     }
 }
@@ -12,9 +12,15 @@
 #[cfg(feature = "full_encoding")]
 use encoding_rs::*;
 
+use super::{iso2022::decoder_iso2022, DecoderFnc};
+
 #[cfg(feature = "full_encoding")]
-fn multi_byte_decoder(mut decoder: Decoder, bytes: &[u8]) -> String {
-    let mut result = String::with_capacity(bytes.len() * 3);
+pub(super) fn multi_byte_decoder(mut decoder: Decoder, bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(
+        decoder
+            .max_utf8_buffer_length(bytes.len())
+            .unwrap_or(bytes.len() * 3),
+    );
 
     if let (CoderResult::OutputFull, _, _) = decoder.decode_to_string(bytes, &mut result, true) {
         debug_assert!(false, "String full while decoding.")
@@ -24,6 +30,85 @@ fn multi_byte_decoder(mut decoder: Decoder, bytes: &[u8]) -> String {
     result
 }
 
+// Decoders for the encodings of the WHATWG Encoding Standard, used for the
+// labels missing from the built-in table.
+#[cfg(feature = "full_encoding")]
+macro_rules! encoding_decoders {
+    ($($encoding:ident),+ $(,)?) => {
+        fn encoding_decoder(encoding: &'static Encoding) -> Option<DecoderFnc> {
+            $(
+                if encoding == $encoding {
+                    return Some(
+                        (|bytes: &[u8]| multi_byte_decoder($encoding.new_decoder(), bytes))
+                            as DecoderFnc,
+                    );
+                }
+            )+
+            None
+        }
+    };
+}
+
+#[cfg(feature = "full_encoding")]
+encoding_decoders!(
+    BIG5,
+    EUC_JP,
+    EUC_KR,
+    GB18030,
+    GBK,
+    IBM866,
+    ISO_2022_JP,
+    ISO_8859_2,
+    ISO_8859_3,
+    ISO_8859_4,
+    ISO_8859_5,
+    ISO_8859_6,
+    ISO_8859_7,
+    ISO_8859_8,
+    ISO_8859_8_I,
+    ISO_8859_10,
+    ISO_8859_13,
+    ISO_8859_14,
+    ISO_8859_15,
+    ISO_8859_16,
+    KOI8_R,
+    KOI8_U,
+    MACINTOSH,
+    SHIFT_JIS,
+    UTF_16BE,
+    UTF_16LE,
+    UTF_8,
+    WINDOWS_874,
+    WINDOWS_1250,
+    WINDOWS_1251,
+    WINDOWS_1252,
+    WINDOWS_1253,
+    WINDOWS_1254,
+    WINDOWS_1255,
+    WINDOWS_1256,
+    WINDOWS_1257,
+    WINDOWS_1258,
+    X_MAC_CYRILLIC,
+    X_USER_DEFINED,
+);
+
+/// Returns a decoder for a charset label known to encoding_rs, such as
+/// `cp1250`, `windows-31j` or `x-mac-cyrillic`. Labels mapped to the
+/// replacement encoding, such as `hz-gb-2312`, are not supported as their
+/// contents would be lost.
+#[cfg_attr(not(feature = "full_encoding"), allow(unused_variables))]
+pub fn label_decoder(label: &[u8]) -> Option<DecoderFnc> {
+    #[cfg(feature = "full_encoding")]
+    {
+        Encoding::for_label_no_replacement(label).and_then(encoding_decoder)
+    }
+
+    #[cfg(not(feature = "full_encoding"))]
+    {
+        None
+    }
+}
+
 pub fn decoder_shift_jis(bytes: &[u8]) -> String {
     #[cfg(feature = "full_encoding")]
     {
@@ -97,15 +182,7 @@ pub fn decoder_gbk(bytes: &[u8]) -> String {
 }
 
 pub fn decoder_iso2022_jp(bytes: &[u8]) -> String {
-    #[cfg(feature = "full_encoding")]
-    {
-        multi_byte_decoder(ISO_2022_JP.new_decoder(), bytes)
-    }
-
-    #[cfg(not(feature = "full_encoding"))]
-    {
-        String::from_utf8_lossy(bytes).into_owned()
-    }
+    decoder_iso2022(bytes)
 }
 
 pub fn decoder_windows874(bytes: &[u8]) -> String {
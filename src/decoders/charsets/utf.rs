@@ -11,89 +11,57 @@
 
 use std::char::{decode_utf16, REPLACEMENT_CHARACTER};
 
-use crate::decoders::base64::BASE64_MAP;
-
-struct Utf7DecoderState {
-    utf16_bytes: Vec<u16>,
-    pending_byte: Option<u8>,
-    b64_bytes: u32,
+/// Decodes UTF-7 text (RFC 2152).
+pub fn decoder_utf7(bytes: &[u8]) -> String {
+    decode_utf7_(bytes, false)
 }
 
-fn add_utf16_bytes(state: &mut Utf7DecoderState, n_bytes: usize) {
-    debug_assert!(n_bytes < std::mem::size_of::<u32>());
-
-    for byte in state.b64_bytes.to_le_bytes()[0..n_bytes].iter() {
-        if let Some(pending_byte) = state.pending_byte {
-            state
-                .utf16_bytes
-                .push(u16::from_be_bytes([pending_byte, *byte]));
-            state.pending_byte = None;
-        } else {
-            state.pending_byte = Some(*byte);
-        }
-    }
+/// Decodes the modified UTF-7 used by IMAP mailbox names (RFC 3501,
+/// Section 5.1.3), where `&` starts the base64 runs and `,` replaces `/`.
+pub fn decoder_utf7_imap(bytes: &[u8]) -> String {
+    decode_utf7_(bytes, true)
 }
 
-pub fn decoder_utf7(bytes: &[u8]) -> String {
+fn decode_utf7_(bytes: &[u8], is_imap: bool) -> String {
+    let shift = if is_imap { b'&' } else { b'+' };
     let mut result = String::with_capacity(bytes.len());
-    let mut byte_count: u8 = 0;
-    let mut in_b64 = false;
+    let mut pos = 0;
 
-    let mut state = Utf7DecoderState {
-        utf16_bytes: Vec::with_capacity(10),
-        pending_byte: None,
-        b64_bytes: 0,
-    };
+    while let Some(&ch) = bytes.get(pos) {
+        pos += 1;
+        if ch != shift {
+            result.push(char::from(ch));
+            continue;
+        }
 
-    for byte in bytes {
-        if in_b64 {
-            let val = BASE64_MAP[byte_count as usize][*byte as usize];
-
-            if val < 0x01ffffff {
-                byte_count = (byte_count + 1) & 3;
-
-                if byte_count == 1 {
-                    state.b64_bytes = val;
-                } else {
-                    state.b64_bytes |= val;
-
-                    if byte_count == 0 {
-                        add_utf16_bytes(&mut state, 3);
-                    }
-                }
-            } else {
-                match byte_count {
-                    1 | 2 => {
-                        add_utf16_bytes(&mut state, 1);
-                    }
-                    3 => {
-                        add_utf16_bytes(&mut state, 2);
-                    }
-                    _ => (),
-                }
-
-                if !state.utf16_bytes.is_empty() {
-                    result.push_str(
-                        decode_utf16(state.utf16_bytes.drain(..))
-                            .map(|r| r.unwrap_or(REPLACEMENT_CHARACTER))
-                            .collect::<String>()
-                            .as_str(),
-                    );
-                } else if byte_count > 0 || state.pending_byte.is_some() {
-                    result.push(REPLACEMENT_CHARACTER);
-                } else {
-                    result.push('+');
-                    result.push(char::from(*byte));
-                }
-
-                state.pending_byte = None;
-                byte_count = 0;
-                in_b64 = false;
+        let start = pos;
+        let mut bits = 0u32;
+        let mut bit_count = 0;
+        let mut utf16 = Vec::new();
+        while let Some(value) = bytes.get(pos).and_then(|&ch| utf7_base64(ch, is_imap)) {
+            bits = (bits << 6) | value;
+            bit_count += 6;
+            if bit_count >= 16 {
+                bit_count -= 16;
+                utf16.push((bits >> bit_count) as u16);
+                bits &= (1 << bit_count) - 1;
             }
-        } else if byte == &b'+' {
-            in_b64 = true;
+            pos += 1;
+        }
+
+        if pos == start {
+            // "+-" encodes "+", a shift character followed by anything else
+            // is kept as is.
+            result.push(char::from(shift));
         } else {
-            result.push(char::from(*byte));
+            result.extend(decode_utf16(utf16).map(|r| r.unwrap_or(REPLACEMENT_CHARACTER)));
+            if bits != 0 || bit_count >= 6 {
+                result.push(REPLACEMENT_CHARACTER);
+            }
+        }
+        // A "-" ending a base64 run is absorbed
+        if bytes.get(pos) == Some(&b'-') {
+            pos += 1;
         }
     }
 
@@ -101,6 +69,18 @@ pub fn decoder_utf7(bytes: &[u8]) -> String {
     result
 }
 
+fn utf7_base64(ch: u8, is_imap: bool) -> Option<u32> {
+    match ch {
+        b'A'..=b'Z' => Some((ch - b'A') as u32),
+        b'a'..=b'z' => Some((ch - b'a') as u32 + 26),
+        b'0'..=b'9' => Some((ch - b'0') as u32 + 52),
+        b'+' => Some(62),
+        b',' if is_imap => Some(63),
+        b'/' if !is_imap => Some(63),
+        _ => None,
+    }
+}
+
 fn decoder_utf16_(bytes: &[u8], fnc: fn([u8; 2]) -> u16) -> String {
     if bytes.len() >= 2 {
         decode_utf16(bytes.chunks_exact(2).map(|c| fnc([c[0], c[1]])))
@@ -138,7 +118,7 @@ pub fn decoder_utf8(bytes: &[u8]) -> String {
 
 #[cfg(test)]
 mod tests {
-    use crate::decoders::charsets::utf::decoder_utf7;
+    use crate::decoders::charsets::utf::{decoder_utf7, decoder_utf7_imap};
 
     #[test]
     fn decode_utf7() {
@@ -147,7 +127,10 @@ mod tests {
             ("Hi Mom -+Jjo--!", "Hi Mom -☺-!"),
             ("+ZeVnLIqe-", "日本語"),
             ("Item 3 is +AKM-1.", "Item 3 is £1."),
-            ("Plus minus +- -+ +--", "Plus minus +- -+ +--"),
+            ("Plus minus +- -+ +--", "Plus minus + -+ +-"),
+            ("1 +AKM 1 +AKM", "1 \u{a3} 1 \u{a3}"),
+            ("Emoji: +2D3eAA-.", "Emoji: \u{1f600}."),
+            ("Broken: +2D0-.", "Broken: \u{fffd}."),
             (
                 "+APw-ber ihre mi+AN8-liche Lage+ADs- +ACI-wir",
                 "über ihre mißliche Lage; \"wir",
@@ -169,5 +152,17 @@ mod tests {
         for input in inputs {
             assert_eq!(decoder_utf7(input.0.as_bytes()), input.1);
         }
+
+        for (input, expected) in [
+            ("INBOX", "INBOX"),
+            ("&ZeVnLIqe-", "\u{65e5}\u{672c}\u{8a9e}"),
+            ("Entw&APw-rfe/Tom &- Jerry", "Entw\u{fc}rfe/Tom & Jerry"),
+            (
+                "~peter/mail/&U,BTFw-/&ZeVnLIqe-",
+                "~peter/mail/\u{53f0}\u{5317}/\u{65e5}\u{672c}\u{8a9e}",
+            ),
+        ] {
+            assert_eq!(decoder_utf7_imap(input.as_bytes()), expected);
+        }
     }
 }
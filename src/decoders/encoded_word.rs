@@ -85,7 +85,10 @@ impl<'x> MessageStream<'x> {
                 decoder(&bytes).into()
             } else {
                 String::from_utf8(bytes)
-                    .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+                    .unwrap_or_else(|e| match self.word_fallback {
+                        Some(decoder) => decoder(e.as_bytes()),
+                        None => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+                    })
                     .into()
             }
         } else {
@@ -9,7 +9,7 @@
  * except according to those terms.
  */
 
-use std::char::REPLACEMENT_CHARACTER;
+use std::{char::REPLACEMENT_CHARACTER, ops::Range};
 
 pub fn add_html_token(result: &mut String, token: &[u8], add_space: bool) {
     if add_space {
@@ -19,14 +19,10 @@ pub fn add_html_token(result: &mut String, token: &[u8], add_space: bool) {
     if let [b'&', entity @ .., b';'] = token {
         let mut entity_code: u32 = 0;
         if let [b'#', code @ ..] = entity {
-            let (code, radix) = if let [b'x' | b'X', code @ ..] = code {
-                (code, 16)
-            } else {
-                (code, 10)
-            };
-
-            entity_code = std::str::from_utf8(code)
-                .map_or(0, |code| u32::from_str_radix(code, radix).unwrap_or(0));
+            if let Some(code) = numeric_entity_code(code) {
+                result.push(numeric_entity_char(code));
+                return;
+            }
         } else if (2..=31).contains(&entity.len()) {
             let mut hash = entity.len() as u32;
 
@@ -110,150 +106,866 @@ pub fn add_html_token(result: &mut String, token: &[u8], add_space: bool) {
     result.push_str(std::str::from_utf8(token).unwrap());
 }
 
+// Elements skipped along with their contents.
+static SKIP_WITH_CONTENTS: &[&str] = &["head", "script", "style", "title"];
+
+// Elements separated from the surrounding text by a line break.
+static LINE_BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "caption",
+    "center",
+    "dd",
+    "div",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "header",
+    "main",
+    "nav",
+    "section",
+];
+
+// Elements separated from the surrounding text by a blank line.
+static PARAGRAPH_BLOCKS: &[&str] = &[
+    "blockquote",
+    "dl",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "p",
+];
+
+static LIST_BULLETS: &[char] = &['*', '+', 'o', '#'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HtmlFormat {
+    Text,
+    Markdown,
+}
+
+// Text being written, with the line breaks, space and Markdown markup
+// requested so far added only once more text follows.
+#[derive(Default)]
+struct TextWriter {
+    text: String,
+    breaks: usize,
+    space: bool,
+    markup: String,
+}
+
+// Elements rendered into their own writer and then added to the enclosing
+// one, or collecting the cells of a table.
+enum TextBlock {
+    Table(TextTable),
+    Cell,
+    Quote,
+}
+
+struct TextList {
+    number: Option<usize>,
+}
+
+#[derive(Default)]
+struct TextTable {
+    rows: Vec<Vec<String>>,
+}
+
+struct TextLink {
+    href: String,
+    writer: usize,
+    start: usize,
+}
+
+struct HtmlRenderer {
+    format: HtmlFormat,
+    writers: Vec<TextWriter>,
+    blocks: Vec<TextBlock>,
+    lists: Vec<TextList>,
+    links: Vec<TextLink>,
+    pre_depth: usize,
+    code_depth: usize,
+}
+
+/// Converts an HTML document to plain text in the manner of a text mode
+/// browser: paragraphs are separated by blank lines, list items are
+/// prefixed with bullets or numbers, tables are laid out in aligned
+/// columns when their cells fit in a single line, and links are followed
+/// by their URL in angle brackets.
 pub fn html_to_text(input: &str) -> String {
-    let mut result = String::with_capacity(input.len());
-    let input = input.as_bytes();
+    HtmlRenderer::new(HtmlFormat::Text).render(input)
+}
 
-    let mut in_tag = false;
-    let mut in_head = false;
-    let mut in_comment = false;
-
-    let mut is_token_start = true;
-    let mut is_after_space = false;
-    let mut is_tag_close = false;
-    let mut is_new_line = true;
-
-    let mut token_start = 0;
-    let mut token_end = 0;
-
-    let mut tag_token_pos = 0;
-    let mut comment_pos = 0;
-
-    for (pos, ch) in input.iter().enumerate() {
-        if !in_comment {
-            match ch {
-                b'<' => {
-                    if !in_tag && !in_head && !is_token_start {
-                        add_html_token(
-                            &mut result,
-                            &input[token_start..token_end + 1],
-                            is_after_space,
-                        );
-                        is_after_space = false;
-                    }
+/// Converts an HTML document to CommonMark, writing headings, emphasis,
+/// links, images, inline code, code blocks, block quotes and lists in
+/// Markdown syntax. Tables with cells fitting in a single line are written
+/// as GitHub Flavored Markdown tables, other tables as a paragraph per
+/// cell. Text that would be read as Markdown syntax is escaped.
+pub fn html_to_markdown(input: &str) -> String {
+    HtmlRenderer::new(HtmlFormat::Markdown).render(input)
+}
 
-                    tag_token_pos = 0;
-                    in_tag = true;
-                    is_token_start = true;
-                    is_tag_close = false;
-                    continue;
+impl HtmlRenderer {
+    fn new(format: HtmlFormat) -> Self {
+        HtmlRenderer {
+            format,
+            writers: vec![TextWriter::default()],
+            blocks: Vec::new(),
+            lists: Vec::new(),
+            links: Vec::new(),
+            pre_depth: 0,
+            code_depth: 0,
+        }
+    }
+
+    fn render(mut self, input: &str) -> String {
+        let bytes = input.as_bytes();
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            let text_end = bytes[pos..]
+                .iter()
+                .position(|&ch| ch == b'<')
+                .map_or(bytes.len(), |offset| pos + offset);
+            if text_end > pos {
+                let escape = self.format == HtmlFormat::Markdown && self.code_depth == 0;
+                let is_pre = self.pre_depth > 0;
+                let writer = self.writer();
+                if is_pre {
+                    writer.push_preformatted(&input[pos..text_end]);
+                } else {
+                    writer.push_html(&input[pos..text_end], escape);
                 }
-                b'>' if in_tag => {
-                    if tag_token_pos == 1 {
-                        match input.get(token_start..token_end + 1) {
-                            Some(tag)
-                                if tag.eq_ignore_ascii_case(b"br")
-                                    || (tag.eq_ignore_ascii_case(b"p") && is_tag_close) =>
-                            {
-                                result.push('\n');
-                                is_after_space = false;
-                                is_new_line = true;
+                pos = text_end;
+                continue;
+            }
+
+            // Comments, including the empty "<!-->" and "<!--->"
+            if bytes[pos..].starts_with(b"<!--") {
+                pos = if bytes[pos + 4..].starts_with(b">") {
+                    pos + 5
+                } else if bytes[pos + 4..].starts_with(b"->") {
+                    pos + 6
+                } else {
+                    find_ignore_case(bytes, pos + 4, b"-->").map_or(bytes.len(), |end| end + 3)
+                };
+                continue;
+            }
+
+            let tag = parse_text_tag(bytes, pos);
+            pos = tag.end;
+            if SKIP_WITH_CONTENTS.contains(&tag.name.as_str()) {
+                if !tag.is_close && !tag.is_self_closing {
+                    pos = find_ignore_case(bytes, pos, format!("</{}", tag.name).as_bytes())
+                        .map_or(bytes.len(), |end| parse_text_tag(bytes, end).end);
+                }
+            } else if tag.name == "pre" && !tag.is_close {
+                self.start_pre();
+                // Browsers ignore the line break following the start tag
+                if bytes[pos..].starts_with(b"\r\n") {
+                    pos += 2;
+                } else if bytes[pos..].starts_with(b"\n") {
+                    pos += 1;
+                }
+            } else {
+                self.write_tag(&tag, bytes);
+            }
+        }
+
+        self.close_blocks(0);
+        let mut result = self.writers.swap_remove(0).text;
+        result.shrink_to_fit();
+        result
+    }
+
+    fn write_tag(&mut self, tag: &TextTag, bytes: &[u8]) {
+        let is_markdown = self.format == HtmlFormat::Markdown;
+        let name = tag.name.as_str();
+        match name {
+            "br" => {
+                let writer = self.writer();
+                if is_markdown && !writer.text.is_empty() && !writer.text.ends_with('\n') {
+                    // Hard line break
+                    writer.push_raw("  ");
+                }
+                writer.push_line_break();
+            }
+            "pre" => {
+                self.pre_depth = self.pre_depth.saturating_sub(1);
+                let writer = self.writer();
+                if is_markdown {
+                    if !writer.text.ends_with('\n') {
+                        writer.push_line_break();
+                    }
+                    writer.push_raw("```");
+                }
+                writer.push_break(2);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" if is_markdown => {
+                let level = (name.as_bytes()[1] - b'0') as usize;
+                let writer = self.writer();
+                writer.push_break(2);
+                if tag.is_close {
+                    writer.markup.clear();
+                } else {
+                    writer.markup = format!("{} ", "#".repeat(level));
+                }
+            }
+            "hr" if is_markdown => {
+                let writer = self.writer();
+                writer.push_break(2);
+                writer.push_raw("---");
+                writer.push_break(2);
+            }
+            "blockquote" if is_markdown => {
+                if tag.is_close {
+                    if let Some(pos) = self
+                        .blocks
+                        .iter()
+                        .rposition(|block| matches!(block, TextBlock::Quote))
+                    {
+                        self.close_blocks(pos);
+                    }
+                } else {
+                    self.writer().push_break(2);
+                    self.blocks.push(TextBlock::Quote);
+                    self.writers.push(TextWriter::default());
+                }
+            }
+            "b" | "strong" | "i" | "em" | "code" if is_markdown && self.pre_depth == 0 => {
+                let marker = match name {
+                    "b" | "strong" => "**",
+                    "code" => "`",
+                    _ => "*",
+                };
+                if name == "code" {
+                    if tag.is_close {
+                        self.code_depth = self.code_depth.saturating_sub(1);
+                    } else {
+                        self.code_depth += 1;
+                    }
+                }
+                let writer = self.writer();
+                if tag.is_close {
+                    writer.push_closing(marker);
+                } else {
+                    writer.markup.push_str(marker);
+                }
+            }
+            "ul" | "ol" => {
+                // Nested lists are not separated from the item by a blank line
+                let is_top = self.lists.len() == tag.is_close as usize;
+                self.writer().push_break(if is_top { 2 } else { 1 });
+                if tag.is_close {
+                    self.lists.pop();
+                } else {
+                    self.lists.push(TextList {
+                        number: (name == "ol").then(|| {
+                            tag.attribute(bytes, "start")
+                                .and_then(|start| start.trim().parse().ok())
+                                .unwrap_or(1)
+                        }),
+                    });
+                }
+            }
+            "li" => {
+                self.writer().push_break(1);
+                if !tag.is_close {
+                    let depth = self.lists.len().max(1);
+                    let prefix = match self.lists.last_mut() {
+                        Some(TextList {
+                            number: Some(number),
+                        }) => {
+                            *number += 1;
+                            format!("{}.", *number - 1)
+                        }
+                        _ if is_markdown => "-".to_string(),
+                        _ => LIST_BULLETS[(depth - 1) % LIST_BULLETS.len()].to_string(),
+                    };
+                    let indent = if is_markdown {
+                        "    ".repeat(depth - 1)
+                    } else {
+                        "  ".repeat(depth)
+                    };
+                    self.writer().push_raw(&format!("{indent}{prefix} "));
+                }
+            }
+            "table" => {
+                if tag.is_close {
+                    if let Some(pos) = self.table_pos() {
+                        self.close_blocks(pos);
+                    }
+                } else {
+                    self.writer().push_break(1);
+                    self.blocks.push(TextBlock::Table(TextTable::default()));
+                }
+            }
+            "tr" | "td" | "th" => {
+                if let Some(pos) = self.table_pos() {
+                    self.close_blocks(pos + 1);
+                    if let Some(TextBlock::Table(table)) = self.blocks.last_mut() {
+                        if name == "tr" {
+                            if !tag.is_close {
+                                table.rows.push(Vec::new());
                             }
-                            Some(tag) if tag.eq_ignore_ascii_case(b"head") => {
-                                in_head = !is_tag_close;
+                        } else if !tag.is_close {
+                            if table.rows.is_empty() {
+                                table.rows.push(Vec::new());
                             }
-                            _ => (),
+                            self.blocks.push(TextBlock::Cell);
+                            self.writers.push(TextWriter::default());
+                        }
+                    }
+                }
+            }
+            "a" => {
+                if tag.is_close {
+                    if let Some(link) = self.links.pop() {
+                        if link.writer == self.writers.len() {
+                            let format = self.format;
+                            self.writer().end_link(link, format);
+                        }
+                    }
+                } else if let Some(href) = tag.attribute(bytes, "href") {
+                    let href = decode_html_entities(href.trim());
+                    if !href.is_empty()
+                        && !href.starts_with('#')
+                        && !is_script_url(&href)
+                        && !href.chars().any(|ch| ch.is_whitespace())
+                    {
+                        let writers = self.writers.len();
+                        let writer = self.writer();
+                        if is_markdown {
+                            writer.markup.push('[');
                         }
+                        let start = writer.text.len();
+                        self.links.push(TextLink {
+                            href,
+                            writer: writers,
+                            start,
+                        });
                     }
+                }
+            }
+            "img" => {
+                let alt = tag.attribute(bytes, "alt").unwrap_or_default();
+                let src = tag
+                    .attribute(bytes, "src")
+                    .map(|src| decode_html_entities(src.trim()))
+                    .filter(|src| {
+                        !src.is_empty()
+                            && !is_script_url(src)
+                            && !src.chars().any(|ch| ch.is_whitespace())
+                    });
+                let writer = self.writer();
+                match src {
+                    Some(src) if is_markdown => writer.push_text(
+                        &format!(
+                            "![{}]({})",
+                            escape_markdown(&decode_html_entities(alt), false),
+                            markdown_url(&src)
+                        ),
+                        false,
+                    ),
+                    _ => writer.push_html(alt, is_markdown),
+                }
+            }
+            name if PARAGRAPH_BLOCKS.contains(&name) => self.writer().push_break(2),
+            name if LINE_BLOCKS.contains(&name) => self.writer().push_break(1),
+            _ => (),
+        }
+    }
 
-                    in_tag = false;
-                    is_token_start = true;
+    fn start_pre(&mut self) {
+        let is_markdown = self.format == HtmlFormat::Markdown;
+        self.pre_depth += 1;
+        let writer = self.writer();
+        writer.push_break(2);
+        if is_markdown {
+            writer.markup.clear();
+            writer.push_raw("```");
+            writer.push_line_break();
+        }
+    }
 
-                    continue;
+    fn writer(&mut self) -> &mut TextWriter {
+        self.writers.last_mut().unwrap()
+    }
+
+    fn table_pos(&self) -> Option<usize> {
+        self.blocks
+            .iter()
+            .rposition(|block| matches!(block, TextBlock::Table(_)))
+    }
+
+    // Closes the blocks from the innermost down to the one at `pos`, adding
+    // their contents to the enclosing writer or table.
+    fn close_blocks(&mut self, pos: usize) {
+        while self.blocks.len() > pos {
+            match self.blocks.pop().unwrap() {
+                TextBlock::Table(table) => {
+                    let format = self.format;
+                    let writer = self.writer();
+                    writer.push_break(1);
+                    table.write(writer, format);
+                    writer.push_break(1);
                 }
-                b'/' if in_tag => {
-                    if tag_token_pos == 0 {
-                        is_tag_close = true;
+                TextBlock::Cell => {
+                    let cell = self.writers.pop().unwrap();
+                    if let Some(TextBlock::Table(table)) = self.blocks.last_mut() {
+                        if let Some(row) = table.rows.last_mut() {
+                            row.push(cell.text.trim().to_string());
+                        }
                     }
-                    continue;
                 }
-                b'!' if in_tag && tag_token_pos == 0 => {
-                    if let Some(b"--") = input.get(pos + 1..pos + 3) {
-                        in_comment = true;
+                TextBlock::Quote => {
+                    let quote = self.writers.pop().unwrap();
+                    if quote.text.trim().is_empty() {
                         continue;
                     }
-                }
-                b' ' | b'\t' | b'\r' | b'\n' => {
-                    if !in_tag && !in_head {
-                        if !is_token_start {
-                            add_html_token(
-                                &mut result,
-                                &input[token_start..token_end + 1],
-                                is_after_space && !is_new_line,
-                            );
-                            is_new_line = false;
+                    let writer = self.writer();
+                    writer.push_break(2);
+                    for (pos, line) in quote.text.trim_end().split('\n').enumerate() {
+                        if pos > 0 {
+                            writer.push_line_break();
                         }
-                        is_after_space = true;
+                        writer.push_raw(if line.is_empty() { ">" } else { "> " });
+                        writer.push_raw(line);
                     }
+                    writer.push_break(2);
+                }
+            }
+        }
+    }
+}
 
-                    is_token_start = true;
-
-                    continue;
+impl TextWriter {
+    // Adds text, collapsing whitespace and decoding character references.
+    fn push_html(&mut self, html: &str, escape: bool) {
+        for (pos, word) in html.split(|ch: char| ch.is_ascii_whitespace()).enumerate() {
+            if pos > 0 {
+                self.space = true;
+            }
+            if !word.is_empty() {
+                let word = decode_html_entities(word);
+                if escape {
+                    let is_line_start = self.markup.is_empty()
+                        && (self.text.is_empty() || self.text.ends_with('\n') || self.breaks > 0);
+                    self.push_text(&escape_markdown(&word, is_line_start), false);
+                } else {
+                    self.push_text(&word, false);
                 }
-                b'&' if !in_tag && !is_token_start && !in_head => {
-                    add_html_token(
-                        &mut result,
-                        &input[token_start..token_end + 1],
-                        is_after_space && !is_new_line,
-                    );
-                    is_new_line = false;
-                    is_token_start = true;
-                    is_after_space = false;
+            }
+        }
+    }
+
+    fn push_preformatted(&mut self, html: &str) {
+        for (pos, line) in html.split('\n').enumerate() {
+            if pos > 0 {
+                self.push_line_break();
+            }
+            let line = decode_html_entities(line.trim_end_matches('\r'));
+            if !line.is_empty() {
+                self.push_raw(&line);
+            }
+        }
+    }
+
+    fn push_text(&mut self, text: &str, space: bool) {
+        self.space |= space;
+        self.flush();
+        if self.space && !self.text.is_empty() && !self.text.ends_with([' ', '\n']) {
+            self.text.push(' ');
+        }
+        self.space = false;
+        self.text.push_str(&self.markup);
+        self.markup.clear();
+        self.text.push_str(text);
+    }
+
+    // Adds text without collapsing its whitespace, such as a list bullet.
+    fn push_raw(&mut self, text: &str) {
+        self.flush();
+        self.space = false;
+        self.text.push_str(text);
+    }
+
+    // Adds the markup closing an inline element, or discards the markup
+    // opening it when the element is empty.
+    fn push_closing(&mut self, marker: &str) {
+        if self.markup.ends_with(marker) {
+            self.markup.truncate(self.markup.len() - marker.len());
+        } else if !self.text.is_empty() {
+            self.text.push_str(marker);
+        }
+    }
+
+    fn push_break(&mut self, breaks: usize) {
+        self.breaks = self.breaks.max(breaks);
+        self.space = false;
+    }
+
+    fn push_line_break(&mut self) {
+        self.flush();
+        self.space = false;
+        self.text.push('\n');
+    }
+
+    fn end_link(&mut self, link: TextLink, format: HtmlFormat) {
+        let url = link.href.strip_prefix("mailto:").unwrap_or(&link.href);
+        match format {
+            HtmlFormat::Text => {
+                let text = self.text.get(link.start..).unwrap_or_default().trim();
+                if text != url {
+                    self.push_text(&format!("<{}>", link.href), true);
                 }
-                b';' if !in_tag && !is_token_start && !in_head => {
-                    add_html_token(
-                        &mut result,
-                        &input[token_start..pos + 1],
-                        is_after_space && !is_new_line,
-                    );
-                    is_token_start = true;
-                    is_after_space = false;
-                    is_new_line = false;
-                    continue;
+            }
+            HtmlFormat::Markdown if self.markup.ends_with('[') => {
+                self.markup.pop();
+            }
+            HtmlFormat::Markdown => {
+                let label_start = self
+                    .text
+                    .get(link.start..)
+                    .and_then(|text| text.find('['))
+                    .map(|offset| link.start + offset);
+                match label_start {
+                    Some(label_start) if self.text[label_start + 1..].replace('\\', "") == url => {
+                        self.text.truncate(label_start);
+                        self.text.push('<');
+                        self.text.push_str(&link.href);
+                        self.text.push('>');
+                    }
+                    _ => {
+                        self.text.push_str("](");
+                        self.text.push_str(&markdown_url(&link.href));
+                        self.text.push(')');
+                    }
                 }
-                _ => (),
             }
-            if is_token_start {
-                token_start = pos;
-                is_token_start = false;
-                if in_tag {
-                    tag_token_pos += 1;
+        }
+    }
+
+    // Writes the pending line breaks, counting those already written.
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            let written = self
+                .text
+                .bytes()
+                .rev()
+                .take_while(|&ch| ch == b'\n')
+                .count();
+            for _ in written..self.breaks {
+                self.text.push('\n');
+            }
+        }
+        self.breaks = 0;
+    }
+}
+
+impl TextTable {
+    // Writes the table as aligned columns when all its cells fit in a single
+    // line, or otherwise each cell in its own block, as in layout tables.
+    fn write(&self, writer: &mut TextWriter, format: HtmlFormat) {
+        let is_grid = self.rows.iter().any(|row| row.len() > 1)
+            && self.rows.iter().flatten().all(|cell| !cell.contains('\n'));
+
+        if is_grid {
+            let columns = self.rows.iter().map(|row| row.len()).max().unwrap_or(0);
+            let mut widths = vec![0; columns];
+            let rows = self
+                .rows
+                .iter()
+                .filter(|row| !row.is_empty())
+                .map(|row| {
+                    (0..columns)
+                        .map(|column| {
+                            let cell = row.get(column).map_or("", |cell| cell.as_str());
+                            let cell = if format == HtmlFormat::Markdown {
+                                cell.replace('|', "\\|")
+                            } else {
+                                cell.to_string()
+                            };
+                            widths[column] = widths[column].max(cell.chars().count());
+                            cell
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            for (pos, row) in rows.iter().enumerate() {
+                let mut line = String::new();
+                for (column, cell) in row.iter().enumerate() {
+                    let padding = " ".repeat(widths[column] - cell.chars().count());
+                    if format == HtmlFormat::Markdown {
+                        line.push_str("| ");
+                        line.push_str(cell);
+                        line.push_str(&padding);
+                        line.push(' ');
+                    } else {
+                        if column > 0 {
+                            line.push_str("  ");
+                        }
+                        line.push_str(cell);
+                        line.push_str(&padding);
+                    }
+                }
+                if format == HtmlFormat::Markdown {
+                    line.push('|');
+                }
+                writer.push_break(1);
+                writer.push_raw(line.trim_end());
+
+                if pos == 0 && format == HtmlFormat::Markdown {
+                    let mut line = String::new();
+                    for width in &widths {
+                        line.push_str("| ");
+                        line.push_str(&"-".repeat((*width).max(3)));
+                        line.push(' ');
+                    }
+                    line.push('|');
+                    writer.push_break(1);
+                    writer.push_raw(&line);
                 }
             }
-            token_end = pos;
         } else {
-            match ch {
-                b'-' => comment_pos += 1,
-                b'>' if comment_pos == 2 => {
-                    comment_pos = 0;
-                    in_comment = false;
-                    in_tag = false;
-                    is_token_start = true;
+            for cell in self.rows.iter().flatten().filter(|cell| !cell.is_empty()) {
+                writer.push_break(if format == HtmlFormat::Markdown { 2 } else { 1 });
+                writer.push_raw(cell);
+            }
+        }
+    }
+}
+
+// Escapes the characters that would be read as Markdown syntax, and those
+// starting a block when the text is at the start of a line.
+fn escape_markdown(text: &str, is_line_start: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    for (pos, ch) in text.char_indices() {
+        if matches!(ch, '\\' | '`' | '*' | '_' | '[' | ']' | '<')
+            || (is_line_start && pos == 0 && matches!(ch, '#' | '-' | '+' | '=' | '>'))
+            || (is_line_start
+                && matches!(ch, '.' | ')')
+                && pos > 0
+                && text[..pos].bytes().all(|ch| ch.is_ascii_digit()))
+        {
+            result.push('\\');
+        }
+        result.push(ch);
+    }
+    result
+}
+
+// Link destinations containing parentheses are enclosed in angle brackets.
+fn markdown_url(url: &str) -> String {
+    if url.contains(['(', ')', '<', '>']) {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        url.to_string()
+    }
+}
+
+struct TextTag {
+    name: String,
+    is_close: bool,
+    is_self_closing: bool,
+    attributes: Vec<(String, Range<usize>)>,
+    end: usize,
+}
+
+// Parses a tag leniently, allowing whitespace before the name and treating
+// any "<" as the start of a tag, as markup in mail bodies is often sloppy.
+fn parse_text_tag(bytes: &[u8], start: usize) -> TextTag {
+    let mut pos = start + 1;
+    let skip_whitespace = |pos: &mut usize| {
+        while bytes.get(*pos).is_some_and(|ch| ch.is_ascii_whitespace()) {
+            *pos += 1;
+        }
+    };
+    skip_whitespace(&mut pos);
+    let is_close = bytes.get(pos) == Some(&b'/');
+    if is_close {
+        pos += 1;
+        skip_whitespace(&mut pos);
+    }
+    let name_start = pos;
+    while bytes.get(pos).is_some_and(|ch| ch.is_ascii_alphanumeric()) {
+        pos += 1;
+    }
+    let mut tag = TextTag {
+        name: String::from_utf8_lossy(&bytes[name_start..pos]).to_ascii_lowercase(),
+        is_close,
+        is_self_closing: false,
+        attributes: Vec::new(),
+        end: bytes.len(),
+    };
+
+    while let Some(&ch) = bytes.get(pos) {
+        match ch {
+            b'>' => {
+                tag.end = pos + 1;
+                break;
+            }
+            b'/' => {
+                pos += 1;
+                skip_whitespace(&mut pos);
+                tag.is_self_closing = bytes.get(pos) == Some(&b'>');
+            }
+            ch if ch.is_ascii_whitespace() => pos += 1,
+            _ => {
+                let name_start = pos;
+                while bytes.get(pos).is_some_and(|ch| {
+                    !ch.is_ascii_whitespace() && !matches!(ch, b'=' | b'>' | b'/')
+                }) {
+                    pos += 1;
+                }
+                let name = String::from_utf8_lossy(&bytes[name_start..pos]).to_ascii_lowercase();
+                skip_whitespace(&mut pos);
+                let mut value = pos..pos;
+                if bytes.get(pos) == Some(&b'=') {
+                    pos += 1;
+                    skip_whitespace(&mut pos);
+                    match bytes.get(pos) {
+                        Some(&quote @ (b'"' | b'\'')) => {
+                            let value_end = bytes[pos + 1..]
+                                .iter()
+                                .position(|&ch| ch == quote)
+                                .map_or(bytes.len(), |end| pos + 1 + end);
+                            value = pos + 1..value_end;
+                            pos = (value_end + 1).min(bytes.len());
+                        }
+                        _ => {
+                            let value_start = pos;
+                            while bytes
+                                .get(pos)
+                                .is_some_and(|ch| !ch.is_ascii_whitespace() && *ch != b'>')
+                            {
+                                pos += 1;
+                            }
+                            value = value_start..pos;
+                        }
+                    }
                 }
-                _ => comment_pos = 0,
+                tag.attributes.push((name, value));
             }
         }
     }
+    tag
+}
 
-    if !in_tag && !is_token_start && !in_head {
-        add_html_token(
-            &mut result,
-            &input[token_start..token_end + 1],
-            is_after_space && !is_new_line,
-        );
+impl TextTag {
+    fn attribute<'x>(&self, input: &'x [u8], name: &str) -> Option<&'x str> {
+        self.attributes
+            .iter()
+            .find(|(attr_name, _)| attr_name == name)
+            .and_then(|(_, value)| std::str::from_utf8(&input[value.clone()]).ok())
+    }
+}
+
+// Entities that browsers decode even without the terminating semicolon.
+static LEGACY_ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("nbsp", '\u{a0}'),
+    ("copy", '©'),
+    ("reg", '®'),
+];
+
+// Parses the code point of a numeric character reference, without the
+// leading "&#" and trailing ";". Code points out of range are returned as
+// 0x110000.
+fn numeric_entity_code(code: &[u8]) -> Option<u32> {
+    let (code, radix) = if let [b'x' | b'X', code @ ..] = code {
+        (code, 16)
+    } else {
+        (code, 10)
+    };
+    if code.is_empty() {
+        return None;
+    }
+    code.iter().try_fold(0u32, |value, &ch| {
+        Some((value * radix + (ch as char).to_digit(radix)?).min(0x110000))
+    })
+}
+
+// Returns the character of a numeric character reference as the HTML
+// specification does, replacing the invalid code points with U+FFFD and
+// mapping the C1 controls to their Windows-1252 characters.
+fn numeric_entity_char(code: u32) -> char {
+    match code {
+        0x80..=0x9f => C1_REPLACEMENTS[(code - 0x80) as usize],
+        0 | 0xd800..=0xdfff => REPLACEMENT_CHARACTER,
+        _ => char::from_u32(code).unwrap_or(REPLACEMENT_CHARACTER),
     }
+}
+
+static C1_REPLACEMENTS: &[char] = &[
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+/// Decodes the named and numeric character references in a text.
+pub fn decode_html_entities(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut result = String::with_capacity(text.len());
+    let mut pos = 0;
+    while let Some(offset) = bytes[pos..].iter().position(|&ch| ch == b'&') {
+        let start = pos + offset;
+        result.push_str(&text[pos..start]);
+        let name_end = bytes[start + 1..]
+            .iter()
+            .take(32)
+            .position(|ch| !ch.is_ascii_alphanumeric() && *ch != b'#')
+            .map_or(bytes.len().min(start + 33), |end| start + 1 + end);
 
-    result.shrink_to_fit();
+        if bytes.get(name_end) == Some(&b';') && name_end > start + 1 {
+            // Combine the UTF-16 surrogate pairs written as two references
+            // by some encoders, such as "&#55357;&#56832;" for an emoji
+            if let Some(high @ 0xd800..=0xdbff) = bytes[start + 1..name_end]
+                .strip_prefix(b"#")
+                .and_then(numeric_entity_code)
+            {
+                if let Some((low @ 0xdc00..=0xdfff, low_end)) = bytes
+                    .get(name_end + 1..)
+                    .and_then(|next| next.strip_prefix(b"&#"))
+                    .and_then(|next| {
+                        let end = next.iter().position(|&ch| ch == b';')?;
+                        Some((numeric_entity_code(&next[..end])?, name_end + 3 + end))
+                    })
+                {
+                    result.extend(char::from_u32(
+                        0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00),
+                    ));
+                    pos = low_end + 1;
+                    continue;
+                }
+            }
+            add_html_token(&mut result, &bytes[start..name_end + 1], false);
+            pos = name_end + 1;
+        } else if let Some((name, ch)) = LEGACY_ENTITIES
+            .iter()
+            .find(|(name, _)| bytes[start + 1..name_end] == *name.as_bytes())
+        {
+            result.push(*ch);
+            pos = start + 1 + name.len();
+        } else {
+            result.push('&');
+            pos = start + 1;
+        }
+    }
+    result.push_str(&text[pos..]);
     result
 }
 
@@ -276,10 +988,397 @@ pub fn text_to_html(input: &str) -> String {
     String::from_utf8(result).unwrap()
 }
 
+// Elements removed along with their contents.
+static REMOVE_WITH_CONTENTS: &[&str] = &["script"];
+
+// Elements removed while keeping their contents, which browsers display as a
+// fallback.
+static REMOVE_TAG: &[&str] = &[
+    "applet", "base", "embed", "frame", "frameset", "iframe", "object",
+];
+
+static URL_ATTRIBUTES: &[&str] = &[
+    "action",
+    "background",
+    "data",
+    "dynsrc",
+    "formaction",
+    "href",
+    "lowsrc",
+    "poster",
+    "src",
+    "xlink:href",
+];
+
+static IMAGE_ATTRIBUTES: &[&str] = &["background", "poster", "src", "srcset"];
+
+pub(crate) struct HtmlTag {
+    pub name: String,
+    pub start: usize,
+    pub is_close: bool,
+    pub is_self_closing: bool,
+    pub attributes: Vec<(String, Range<usize>, Range<usize>)>,
+    pub end: usize,
+}
+
+/// Removes the active content from an HTML document: script elements,
+/// embedded objects and frames, event handler attributes and script URLs.
+/// Everything else is copied verbatim.
+pub fn sanitize_html(input: &str) -> String {
+    let bytes = input.as_bytes();
+    rewrite_html_tags(input, |tag, result| {
+        if REMOVE_WITH_CONTENTS.contains(&tag.name.as_str()) {
+            if !tag.is_close && !tag.is_self_closing {
+                return find_ignore_case(bytes, tag.end, format!("</{}", tag.name).as_bytes())
+                    .and_then(|end| parse_html_tag(bytes, end))
+                    .map_or(bytes.len(), |tag| tag.end);
+            }
+        } else if !REMOVE_TAG.contains(&tag.name.as_str()) && tag.end <= bytes.len() {
+            write_html_tag(result, input, tag, |name, value| {
+                if name.starts_with("on")
+                    || (URL_ATTRIBUTES.contains(&name) && is_script_url(value))
+                {
+                    HtmlAttribute::Remove
+                } else {
+                    HtmlAttribute::Keep
+                }
+            });
+        }
+        tag.end
+    })
+}
+
+/// Rewrites the URLs of the remote images in an HTML document, such as
+/// tracking pixels, with the URL returned by `rewrite`. Images are removed
+/// when it returns `None`. Returns the rewritten document along with the
+/// URLs of the remote images found.
+pub fn rewrite_remote_images(
+    input: &str,
+    mut rewrite: impl FnMut(&str) -> Option<String>,
+) -> (String, Vec<String>) {
+    let mut urls = Vec::new();
+    let result = rewrite_html_tags(input, |tag, result| {
+        let mut actions = Vec::new();
+        let mut remove_tag = false;
+        for (name, _, value) in &tag.attributes {
+            let value = &input[value.clone()];
+            if !IMAGE_ATTRIBUTES.contains(&name.as_str()) || !is_remote_url(value) {
+                actions.push(HtmlAttribute::Keep);
+            } else if name == "srcset" {
+                urls.extend(
+                    value
+                        .split(',')
+                        .filter_map(|src| src.split_whitespace().next())
+                        .map(|url| url.replace("&amp;", "&")),
+                );
+                actions.push(HtmlAttribute::Remove);
+            } else {
+                let url = value.trim().replace("&amp;", "&");
+                actions.push(match rewrite(&url) {
+                    Some(url) => HtmlAttribute::Replace(url),
+                    None => {
+                        remove_tag |= name == "src" && tag.name == "img";
+                        HtmlAttribute::Remove
+                    }
+                });
+                urls.push(url);
+            }
+        }
+
+        if !remove_tag {
+            write_html_tag_with(result, input, tag, actions);
+        }
+        tag.end
+    });
+    (result, urls)
+}
+
+/// Rewrites the `cid:` URLs (RFC 2392) in an HTML document with the URL
+/// returned by `resolve` for the Content-ID they reference. URLs are left
+/// unchanged when it returns `None`.
+pub fn rewrite_cid_urls(input: &str, mut resolve: impl FnMut(&str) -> Option<String>) -> String {
+    rewrite_html_tags(input, |tag, result| {
+        let actions = tag
+            .attributes
+            .iter()
+            .map(|(_, _, value)| {
+                let value = input[value.clone()].trim();
+                value
+                    .get(..4)
+                    .filter(|scheme| scheme.eq_ignore_ascii_case("cid:"))
+                    .and_then(|_| resolve(&percent_decode(&value[4..])))
+                    .map_or(HtmlAttribute::Keep, HtmlAttribute::Replace)
+            })
+            .collect();
+        write_html_tag_with(result, input, tag, actions);
+        tag.end
+    })
+}
+
+pub(crate) enum HtmlAttribute {
+    Keep,
+    Remove,
+    Replace(String),
+}
+
+// Copies an HTML document, passing the tags outside comments to `rewrite`,
+// which writes their replacement and returns the position where copying
+// resumes.
+pub(crate) fn rewrite_html_tags(
+    input: &str,
+    mut rewrite: impl FnMut(&HtmlTag, &mut String) -> usize,
+) -> String {
+    let bytes = input.as_bytes();
+    let mut result = String::with_capacity(input.len());
+    let mut copy_from = 0;
+    let mut pos = 0;
+
+    while let Some(offset) = bytes[pos..].iter().position(|&ch| ch == b'<') {
+        let tag_start = pos + offset;
+        if bytes[tag_start..].starts_with(b"<!--") {
+            // Comments end at "-->" or "--!>", or right away for "<!-->" and
+            // "<!--->", as they do in browsers.
+            pos = [(tag_start + 2, &b"-->"[..]), (tag_start + 4, &b"--!>"[..])]
+                .iter()
+                .filter_map(|&(from, end)| {
+                    find_ignore_case(bytes, from, end).map(|pos| pos + end.len())
+                })
+                .min()
+                .unwrap_or(bytes.len());
+            continue;
+        }
+        let tag = if let Some(tag) = parse_html_tag(bytes, tag_start) {
+            tag
+        } else {
+            pos = tag_start + 1;
+            continue;
+        };
+
+        result.push_str(&input[copy_from..tag_start]);
+        pos = rewrite(&tag, &mut result).min(bytes.len());
+        copy_from = pos;
+    }
+
+    result.push_str(&input[copy_from..]);
+    result
+}
+
+// Writes a tag applying an action to each of its attributes, copying it
+// verbatim if all of them are kept.
+fn write_html_tag_with(
+    result: &mut String,
+    input: &str,
+    tag: &HtmlTag,
+    actions: Vec<HtmlAttribute>,
+) {
+    if actions
+        .iter()
+        .all(|action| matches!(action, HtmlAttribute::Keep))
+    {
+        result.push_str(&input[tag.start..tag.end.min(input.len())]);
+    } else {
+        let mut actions = actions.into_iter();
+        write_html_tag(result, input, tag, |_, _| {
+            actions.next().unwrap_or(HtmlAttribute::Keep)
+        });
+    }
+}
+
+pub(crate) fn write_html_tag(
+    result: &mut String,
+    input: &str,
+    tag: &HtmlTag,
+    mut attribute: impl FnMut(&str, &str) -> HtmlAttribute,
+) {
+    result.push('<');
+    if tag.is_close {
+        result.push('/');
+    }
+    result.push_str(&tag.name);
+    for (name, range, value) in &tag.attributes {
+        match attribute(name, &input[value.clone()]) {
+            HtmlAttribute::Keep => {
+                result.push(' ');
+                result.push_str(&input[range.clone()]);
+            }
+            HtmlAttribute::Remove => (),
+            HtmlAttribute::Replace(value) => {
+                result.push(' ');
+                result.push_str(&input[range.start..range.start + name.len()]);
+                result.push_str("=\"");
+                result.push_str(&value.replace('&', "&amp;").replace('"', "&quot;"));
+                result.push('"');
+            }
+        }
+    }
+    if tag.is_self_closing {
+        result.push('/');
+    }
+    result.push('>');
+}
+
+pub(crate) fn parse_html_tag(bytes: &[u8], start: usize) -> Option<HtmlTag> {
+    let mut pos = start + 1;
+    let is_close = bytes.get(pos) == Some(&b'/');
+    if is_close {
+        pos += 1;
+    }
+    let name_start = pos;
+    if !bytes.get(pos)?.is_ascii_alphabetic() {
+        return None;
+    }
+    while bytes
+        .get(pos)
+        .is_some_and(|ch| ch.is_ascii_alphanumeric() || matches!(ch, b'-' | b':'))
+    {
+        pos += 1;
+    }
+    let mut tag = HtmlTag {
+        name: String::from_utf8_lossy(&bytes[name_start..pos]).to_ascii_lowercase(),
+        start,
+        is_close,
+        is_self_closing: false,
+        attributes: Vec::new(),
+        end: bytes.len(),
+    };
+
+    loop {
+        match bytes.get(pos) {
+            Some(b'>') => {
+                tag.end = pos + 1;
+                return Some(tag);
+            }
+            Some(b'/') => {
+                tag.is_self_closing = bytes.get(pos + 1) == Some(&b'>');
+                pos += 1;
+            }
+            Some(ch) if ch.is_ascii_whitespace() => {
+                pos += 1;
+            }
+            Some(_) => {
+                let attr_start = pos;
+                pos += 1;
+                while bytes.get(pos).is_some_and(|ch| {
+                    !ch.is_ascii_whitespace() && !matches!(ch, b'=' | b'>' | b'/')
+                }) {
+                    pos += 1;
+                }
+                let name = String::from_utf8_lossy(&bytes[attr_start..pos]).to_ascii_lowercase();
+                let mut value = pos..pos;
+
+                let mut value_start = pos;
+                while bytes
+                    .get(value_start)
+                    .is_some_and(|ch| ch.is_ascii_whitespace())
+                {
+                    value_start += 1;
+                }
+                if bytes.get(value_start) == Some(&b'=') {
+                    value_start += 1;
+                    while bytes
+                        .get(value_start)
+                        .is_some_and(|ch| ch.is_ascii_whitespace())
+                    {
+                        value_start += 1;
+                    }
+                    pos = match bytes.get(value_start) {
+                        Some(&quote @ (b'"' | b'\'')) => {
+                            let value_end = bytes[value_start + 1..]
+                                .iter()
+                                .position(|&ch| ch == quote)
+                                .map_or(bytes.len(), |end| value_start + 1 + end);
+                            value = value_start + 1..value_end;
+                            (value_end + 1).min(bytes.len())
+                        }
+                        _ => {
+                            let mut value_end = value_start;
+                            while bytes
+                                .get(value_end)
+                                .is_some_and(|ch| !ch.is_ascii_whitespace() && *ch != b'>')
+                            {
+                                value_end += 1;
+                            }
+                            value = value_start..value_end;
+                            value_end
+                        }
+                    };
+                }
+                tag.attributes.push((name, attr_start..pos, value));
+            }
+            None => {
+                // Unterminated tag, discarded along with the rest of the input
+                tag.end = bytes.len() + 1;
+                return Some(tag);
+            }
+        }
+    }
+}
+
+// Returns true for URLs that run code when followed, taking into account the
+// whitespace and entities browsers ignore in the scheme.
+fn is_script_url(url: &str) -> bool {
+    let scheme = url
+        .chars()
+        .filter(|ch| !ch.is_ascii_whitespace() && !ch.is_ascii_control())
+        .take_while(|ch| *ch != ':' && *ch != '/')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    scheme.contains('&')
+        || ["javascript", "vbscript", "livescript"].contains(&scheme.as_str())
+        || (scheme == "data"
+            && !url.trim_start().get(5..).is_some_and(|mime| {
+                mime.get(..6)
+                    .is_some_and(|mime| mime.eq_ignore_ascii_case("image/"))
+            }))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match bytes
+            .get(pos + 1..pos + 3)
+            .filter(|_| bytes[pos] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+        {
+            Some(ch) => {
+                result.push(ch);
+                pos += 3;
+            }
+            None => {
+                result.push(bytes[pos]);
+                pos += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+pub(crate) fn is_remote_url(url: &str) -> bool {
+    let url = url.trim_start();
+    url.starts_with("//")
+        || ["http:", "https:"].iter().any(|scheme| {
+            url.get(..scheme.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        })
+}
+
+pub(crate) fn find_ignore_case(bytes: &[u8], start: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(start..)?
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+        .map(|pos| start + pos)
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::decoders::html::{add_html_token, html_to_text, text_to_html};
+    use crate::decoders::html::{
+        add_html_token, decode_html_entities, html_to_markdown, html_to_text,
+        rewrite_remote_images, sanitize_html, text_to_html,
+    };
 
     #[test]
     fn convert_text_to_html() {
@@ -315,7 +1414,7 @@ mod tests {
                     "<p>what is &heartsuit;?</p><p>&#x000DF;&Abreve;&#914;&gamma; ",
                     "don&apos;t hurt me.</p>"
                 ),
-                "what is ♥?\nßĂΒγ don't hurt me.\n",
+                "what is ♥?\n\nßĂΒγ don't hurt me.",
             ),
             (
                 concat!(
@@ -327,11 +1426,46 @@ mod tests {
             ),
             (
                 "   < p >  hello < / p > < p > world < / p >   !!! < br > ",
-                "hello\nworld\n!!!\n",
+                "hello\n\nworld\n\n!!!\n",
             ),
             (
                 " <p>please unsubscribe <a href=#>here</a>.</p> ",
-                "please unsubscribe here.\n",
+                "please unsubscribe here.",
+            ),
+            (
+                concat!(
+                    "<p>Visit <a href=\"https://example.com/?a=1&amp;b=2\">our site</a>, ",
+                    "<a href='https://example.com/'>https://example.com/</a> or write to ",
+                    "<a href=\"mailto:jane@example.com\">jane@example.com</a>.</p>"
+                ),
+                concat!(
+                    "Visit our site <https://example.com/?a=1&b=2>, https://example.com/ ",
+                    "or write to jane@example.com."
+                ),
+            ),
+            (
+                concat!(
+                    "<div>Items:<ul><li>one<li>two<ul><li>nested</li></ul></li></ul>",
+                    "<ol start=\"3\"><li>three</li><li>four</li></ol>done</div>"
+                ),
+                "Items:\n\n  * one\n  * two\n    + nested\n\n  3. three\n  4. four\n\ndone",
+            ),
+            (
+                concat!(
+                    "<table><tr><th>Item</th><th>Qty</th></tr>",
+                    "<tr><td>Apples</td><td>3</td></tr><tr><td>Kiwi</td><td>12</td></table>",
+                    "<table><tr><td><p>Header</p><p>Intro</p></td></tr>",
+                    "<tr><td>Footer</td><td></td></tr></table>"
+                ),
+                "Item    Qty\nApples  3\nKiwi    12\nHeader\n\nIntro\nFooter",
+            ),
+            (
+                "<p>Code:</p><pre>\nfn main() {\n    &lt;x&gt;\n}</pre><p>end</p>",
+                "Code:\n\nfn main() {\n    <x>\n}\n\nend",
+            ),
+            (
+                "<h1>Title</h1>AT&amp;T &copy 2023 &amp &unknown; a & b<br><img alt=\"Logo\">",
+                "Title\n\nAT&T © 2023 & &unknown; a & b\nLogo",
             ),
         ];
 
@@ -340,6 +1474,154 @@ mod tests {
         }
     }
 
+    #[test]
+    fn convert_html_to_markdown() {
+        let inputs = [
+            (
+                "<h1>Release <em>notes</em></h1><p>Hello <b>world</b>, <strong> </strong>see <i>below</i>.</p>",
+                "# Release *notes*\n\nHello **world**, see *below*.",
+            ),
+            (
+                concat!(
+                    "<p>Visit <a href=\"https://example.com/a_(b)\">the <b>site</b></a> or ",
+                    "<a href=\"https://example.com/\">https://example.com/</a>.",
+                    "<a href=\"https://example.com/empty\"></a></p>",
+                    "<img src=\"cid:logo\" alt=\"Logo [1]\">"
+                ),
+                concat!(
+                    "Visit [the **site**](<https://example.com/a_(b)>) or ",
+                    "<https://example.com/>.\n\n![Logo \\[1\\]](cid:logo)"
+                ),
+            ),
+            (
+                "<p>Run <code>cargo *test*</code>:</p><pre><code>fn main() {\n    x &lt; 1;\n}</code></pre>done",
+                "Run `cargo *test*`:\n\n```\nfn main() {\n    x < 1;\n}\n```\n\ndone",
+            ),
+            (
+                "<blockquote><p>First</p><p>Second<br>line</p></blockquote><p>Reply</p>",
+                "> First\n>\n> Second  \n> line\n\nReply",
+            ),
+            (
+                "<ul><li>one<ol><li>a</li><li>b</li></ol></li><li>two</li></ul><hr><p>end</p>",
+                "- one\n    1. a\n    2. b\n- two\n\n---\n\nend",
+            ),
+            (
+                concat!(
+                    "<table><tr><th>Item</th><th>Qty</th></tr>",
+                    "<tr><td>a|b</td><td>3</td></tr></table>"
+                ),
+                "| Item | Qty |\n| ---- | --- |\n| a\\|b | 3   |",
+            ),
+            (
+                "<p># not a heading, 1. not a list *or* _emphasis_ &lt;tag&gt;</p><p>2023. Year</p>",
+                "\\# not a heading, 1. not a list \\*or\\* \\_emphasis\\_ \\<tag>\n\n2023\\. Year",
+            ),
+        ];
+
+        for input in inputs {
+            assert_eq!(
+                html_to_markdown(input.0),
+                input.1,
+                "Failed for '{:?}'",
+                input.0
+            );
+        }
+    }
+
+    #[test]
+    fn sanitize_html_body() {
+        let inputs = [
+            ("<p>hello <b>world</b></p>", "<p>hello <b>world</b></p>"),
+            (
+                "<p onclick=\"steal()\" class=x>hi</p><script>alert(1)</script>!",
+                "<p class=x>hi</p>!",
+            ),
+            (
+                "<SCRIPT type=text/javascript>if (a < b) {}</ScRiPt >done",
+                "done",
+            ),
+            (
+                "<a href=\" JaVa\tScRiPt:alert(1)\" title='t'>x</a><a href=\"https://example.com\">y</a>",
+                "<a title='t'>x</a><a href=\"https://example.com\">y</a>",
+            ),
+            (
+                "<a href=\"java&#115;cript:alert(1)\">x</a><img src=data:image/png;base64,AA>",
+                "<a>x</a><img src=data:image/png;base64,AA>",
+            ),
+            (
+                "<iframe src=\"https://example.com\">fallback</iframe><br/>",
+                "fallback<br/>",
+            ),
+            (
+                "<!-- <script>comment</script> -->1 < 2 <img src=x onerror=alert(1) /",
+                "<!-- <script>comment</script> -->1 < 2 ",
+            ),
+            (
+                "<!--><img src=x onerror=alert(1)>-->",
+                "<!--><img src=x>-->",
+            ),
+            (
+                "<!---><img src=x onerror=alert(1)>-->",
+                "<!---><img src=x>-->",
+            ),
+            (
+                "<!-- x --!><img src=x onerror=alert(1)>-->",
+                "<!-- x --!><img src=x>-->",
+            ),
+            ("<script>unterminated", ""),
+        ];
+
+        for input in inputs {
+            assert_eq!(
+                sanitize_html(input.0),
+                input.1,
+                "Failed for '{:?}'",
+                input.0
+            );
+        }
+    }
+
+    #[test]
+    fn rewrite_html_remote_images() {
+        let html = concat!(
+            "<p background=\"https://example.com/bg.png\">Hi</p>",
+            "<img src=\"cid:logo\"><img width=1 src='HTTPS://t.example.com/p.gif?a=1&amp;b=2'>",
+            "<img src=//example.com/x.png srcset=\"https://example.com/2x.png 2x\" alt=x/>",
+            "<a href=\"https://example.com\">link</a>"
+        );
+
+        let (removed, urls) = rewrite_remote_images(html, |_| None);
+        assert_eq!(
+            removed,
+            concat!(
+                "<p>Hi</p><img src=\"cid:logo\">",
+                "<a href=\"https://example.com\">link</a>"
+            )
+        );
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/bg.png",
+                "HTTPS://t.example.com/p.gif?a=1&b=2",
+                "//example.com/x.png",
+                "https://example.com/2x.png"
+            ]
+        );
+
+        let (proxied, _) = rewrite_remote_images(html, |url| {
+            Some(format!("https://proxy.example.org/?url={}", url.len()))
+        });
+        assert_eq!(
+            proxied,
+            concat!(
+                "<p background=\"https://proxy.example.org/?url=26\">Hi</p>",
+                "<img src=\"cid:logo\"><img width=1 src=\"https://proxy.example.org/?url=35\">",
+                "<img src=\"https://proxy.example.org/?url=19\" alt=x/>",
+                "<a href=\"https://example.com\">link</a>"
+            )
+        );
+    }
+
     #[test]
     fn convert_html_entity() {
         let inputs = [
@@ -351,6 +1633,13 @@ mod tests {
             ("&curvearrowright;", "↷"),
             ("&rarr;", "→"),
             ("&#xFFFFFFF;", "�"),
+            ("&#128512;", "\u{1f600}"),
+            ("&#x1F468;", "\u{1f468}"),
+            ("&#146;", "\u{2019}"),
+            ("&#0;", "\u{fffd}"),
+            ("&#xD83D;", "\u{fffd}"),
+            ("&#99999999999;", "\u{fffd}"),
+            ("&#xyz;", "&#xyz;"),
             ("&hmmm", "&hmmm"),
         ];
 
@@ -359,6 +1648,24 @@ mod tests {
             add_html_token(&mut result, input.0.as_bytes(), false);
             assert_eq!(result, input.1, "Failed for '{:?}", input.0);
         }
+
+        for (input, expected) in [
+            ("&#55357;&#56832; ok", "\u{1f600} ok"),
+            ("&#xD83C;&#xDDEA;&#xD83C;&#xDDF8;", "\u{1f1ea}\u{1f1f8}"),
+            ("&#55357; &#56832;", "\u{fffd} \u{fffd}"),
+            ("&#x1F44D;&#x1F3FD; &amp;", "\u{1f44d}\u{1f3fd} &"),
+        ] {
+            assert_eq!(
+                decode_html_entities(input),
+                expected,
+                "Failed for {:?}",
+                input
+            );
+        }
+        assert_eq!(
+            html_to_text("<p>It&#146;s &#55357;&#56832;</p>"),
+            "It\u{2019}s \u{1f600}"
+        );
     }
 }
 
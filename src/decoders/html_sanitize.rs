@@ -0,0 +1,382 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use super::html::{
+    find_ignore_case, parse_html_tag, rewrite_html_tags, sanitize_html, write_html_tag,
+    HtmlAttribute,
+};
+
+// Elements kept in the sanitized document, other than style sheets. The tags
+// of any other element are removed while keeping their contents, so that no
+// scripts, embedded content, raw text or foreign elements such as SVG remain.
+static ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "acronym",
+    "address",
+    "area",
+    "article",
+    "aside",
+    "b",
+    "bdi",
+    "bdo",
+    "big",
+    "blockquote",
+    "body",
+    "br",
+    "button",
+    "caption",
+    "center",
+    "cite",
+    "code",
+    "col",
+    "colgroup",
+    "dd",
+    "del",
+    "details",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "font",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "i",
+    "img",
+    "input",
+    "ins",
+    "kbd",
+    "label",
+    "legend",
+    "li",
+    "main",
+    "map",
+    "mark",
+    "meta",
+    "nav",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "pre",
+    "q",
+    "rp",
+    "rt",
+    "ruby",
+    "s",
+    "samp",
+    "section",
+    "select",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "tt",
+    "u",
+    "ul",
+    "var",
+    "wbr",
+];
+
+// Elements removed along with their contents, which are never displayed.
+static REMOVE_WITH_CONTENTS: &[&str] = &["style", "title"];
+
+// Attributes kept in the sanitized document, the URLs among them having had
+// their scripts removed by `sanitize_html`.
+static ALLOWED_ATTRIBUTES: &[&str] = &[
+    "abbr",
+    "action",
+    "align",
+    "alt",
+    "background",
+    "bgcolor",
+    "border",
+    "cellpadding",
+    "cellspacing",
+    "charset",
+    "checked",
+    "cite",
+    "class",
+    "color",
+    "cols",
+    "colspan",
+    "coords",
+    "datetime",
+    "dir",
+    "disabled",
+    "face",
+    "formaction",
+    "headers",
+    "height",
+    "href",
+    "hspace",
+    "label",
+    "lang",
+    "method",
+    "multiple",
+    "nowrap",
+    "open",
+    "placeholder",
+    "readonly",
+    "rows",
+    "rowspan",
+    "scope",
+    "selected",
+    "shape",
+    "size",
+    "span",
+    "src",
+    "start",
+    "style",
+    "summary",
+    "title",
+    "type",
+    "usemap",
+    "valign",
+    "value",
+    "vspace",
+    "width",
+];
+
+// Keywords that make a style sheet or style attribute run code, load other
+// style sheets or draw over the page displaying the message.
+static DANGEROUS_CSS: &[&str] = &[
+    "expression",
+    "behavior",
+    "-moz-binding",
+    "javascript",
+    "vbscript",
+    "livescript",
+    "@import",
+    "position:fixed",
+];
+
+/// Makes an HTML document safe to display within a web page. Only a list
+/// of known formatting elements and attributes is kept, on top of the
+/// active content already removed by [`sanitize_html`]. This also removes
+/// refresh directives, form actions pointing outside the document and the
+/// style sheets and style attributes containing dangerous CSS, such as
+/// expressions, bindings or fixed positioning.
+pub fn sanitize(input: &str) -> String {
+    let input = sanitize_html(input);
+    let bytes = input.as_bytes();
+    rewrite_html_tags(&input, |tag, result| {
+        if REMOVE_WITH_CONTENTS.contains(&tag.name.as_str()) {
+            if tag.is_close || tag.is_self_closing {
+                return tag.end;
+            }
+            let (contents_end, end) =
+                find_ignore_case(bytes, tag.end, format!("</{}", tag.name).as_bytes())
+                    .and_then(|end| Some((end, parse_html_tag(bytes, end)?.end)))
+                    .unwrap_or((bytes.len(), bytes.len()));
+            if tag.name == "style" && !is_dangerous_css(&input[tag.end..contents_end]) {
+                result.push_str("<style>");
+                result.push_str(&input[tag.end..contents_end]);
+                result.push_str("</style>");
+            }
+            return end;
+        }
+        if !ALLOWED_TAGS.contains(&tag.name.as_str())
+            || (tag.name == "meta"
+                && tag.attributes.iter().any(|(name, _, value)| {
+                    name == "http-equiv"
+                        && input[value.clone()].trim().eq_ignore_ascii_case("refresh")
+                }))
+        {
+            return tag.end;
+        }
+
+        write_html_tag(result, &input, tag, |name, value| match name {
+            _ if tag.is_close || !ALLOWED_ATTRIBUTES.contains(&name) => HtmlAttribute::Remove,
+            "style" if is_dangerous_css(value) => HtmlAttribute::Remove,
+            "action" | "formaction" if !is_local_action(value) => HtmlAttribute::Remove,
+            _ => HtmlAttribute::Keep,
+        });
+        tag.end
+    })
+}
+
+// Looks for the dangerous keywords after removing the comments, escapes,
+// character references and whitespace browsers ignore or decode.
+fn is_dangerous_css(css: &str) -> bool {
+    let mut normalized = String::with_capacity(css.len());
+    let mut chars = css.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = '\0';
+                for ch in chars.by_ref() {
+                    if last == '*' && ch == '/' {
+                        break;
+                    }
+                    last = ch;
+                }
+            }
+            '\\' => {
+                let mut hex = String::new();
+                while hex.len() < 6 && chars.peek().is_some_and(|ch| ch.is_ascii_hexdigit()) {
+                    hex.push(chars.next().unwrap());
+                }
+                if hex.is_empty() {
+                    normalized.extend(chars.next());
+                } else {
+                    normalized.extend(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32));
+                    if chars.peek().is_some_and(|ch| ch.is_whitespace()) {
+                        chars.next();
+                    }
+                }
+            }
+            '&' if chars.peek() == Some(&'#') => {
+                chars.next();
+                let is_hex = chars.peek().is_some_and(|ch| *ch == 'x' || *ch == 'X');
+                if is_hex {
+                    chars.next();
+                }
+                let mut number = String::new();
+                while chars.peek().is_some_and(|ch| {
+                    if is_hex {
+                        ch.is_ascii_hexdigit()
+                    } else {
+                        ch.is_ascii_digit()
+                    }
+                }) {
+                    number.push(chars.next().unwrap());
+                }
+                if chars.peek() == Some(&';') {
+                    chars.next();
+                }
+                normalized.extend(
+                    u32::from_str_radix(&number, if is_hex { 16 } else { 10 })
+                        .ok()
+                        .and_then(char::from_u32),
+                );
+            }
+            ch if ch.is_whitespace() || ch.is_control() => (),
+            ch => normalized.extend(ch.to_lowercase()),
+        }
+    }
+
+    DANGEROUS_CSS
+        .iter()
+        .any(|keyword| normalized.contains(keyword))
+}
+
+// Forms may only submit to the document itself.
+fn is_local_action(url: &str) -> bool {
+    let url = url.trim();
+    url.is_empty() || url.starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decoders::html_sanitize::sanitize;
+
+    #[test]
+    fn sanitize_html_strict() {
+        for (input, expected) in [
+            (
+                "<p onclick=\"run()\">Hi</p><script>alert(1)</script>",
+                "<p>Hi</p>",
+            ),
+            (
+                concat!(
+                    "<form action=\"https://evil.example.com/login\" method=\"post\">",
+                    "<button formaction='//evil.example.com'>Go</button></form>",
+                    "<form action=\"#top\"></form>"
+                ),
+                concat!(
+                    "<form method=\"post\"><button>Go</button></form>",
+                    "<form action=\"#top\"></form>"
+                ),
+            ),
+            (
+                concat!(
+                    "<p style=\"color: red\">a</p>",
+                    "<p style=\"width: expression(alert(1))\">b</p>",
+                    "<p style=\"width: exp/**/ression(alert(1))\">c</p>",
+                    "<p style=\"background: url(java\\73 cript:alert(1))\">d</p>",
+                    "<div style=\"POSITION : Fixed; top: 0\">e</div>",
+                    "<p style=\"-moz-bind&#105;ng: url(x.xml)\">f</p>"
+                ),
+                concat!(
+                    "<p style=\"color: red\">a</p><p>b</p><p>c</p><p>d</p>",
+                    "<div>e</div><p>f</p>"
+                ),
+            ),
+            (
+                concat!(
+                    "<style>p { color: red }</style>",
+                    "<STYLE>@import url(https://evil.example.com/a.css);</STYLE>",
+                    "<style>p { behavior: url(x.htc) }",
+                ),
+                "<style>p { color: red }</style>",
+            ),
+            (
+                concat!(
+                    "<link rel=\"stylesheet\" href=\"https://evil.example.com/a.css\">",
+                    "<meta http-equiv=\"Refresh\" content=\"0; url=https://evil.example.com\">",
+                    "<meta charset=\"utf-8\"><p>Hi</p>"
+                ),
+                "<meta charset=\"utf-8\"><p>Hi</p>",
+            ),
+            (
+                "<svg><animate attributeName=\"href\" values=\"javascript:alert(1)\"/></svg>",
+                "",
+            ),
+            (
+                "<noscript><p title=\"</noscript><img src=x onerror=alert(1)>\">",
+                "<p title=\"</noscript><img src=x onerror=alert(1)>\">",
+            ),
+            (
+                concat!(
+                    "<html><head><title>Hi</title></head><body>",
+                    "<a href=\"https://example.com\" target=\"_top\" id=\"x\">a</a>",
+                    "<textarea><b>b</b></textarea><marquee>c</marquee>",
+                    "</body></html>"
+                ),
+                concat!(
+                    "<html><head></head><body>",
+                    "<a href=\"https://example.com\">a</a><b>b</b>c",
+                    "</body></html>"
+                ),
+            ),
+        ] {
+            assert_eq!(sanitize(input), expected, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Decompression of the gzip (RFC 1952) and zip files containing DEFLATE
+//! (RFC 1951) streams.
+
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+
+/// Decompresses a gzip file, verifying its checksum. Only the first member
+/// of files with several is decompressed. Returns `None` if the file is
+/// malformed or decompresses to more than `max_size` bytes.
+pub fn gunzip(bytes: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    read_to_limit(GzDecoder::new(bytes), max_size)
+}
+
+/// Returns the names and decompressed contents of the files in a zip
+/// archive, skipping directories, encrypted files, files using compression
+/// methods other than stored and deflate, files that are malformed or
+/// overlap another file, and files that would take the decompressed
+/// contents of all files over `max_size` bytes.
+#[cfg(feature = "dmarc")]
+pub fn unzip(bytes: &[u8], max_size: usize) -> Vec<(String, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut archive = if let Ok(archive) = zip::ZipArchive::new(std::io::Cursor::new(bytes)) {
+        archive
+    } else {
+        return files;
+    };
+    let mut budget = max_size;
+    // The ranges of the files read so far, keyed by their start, so that
+    // several entries pointing to the same data are only extracted once.
+    let mut extents = std::collections::BTreeMap::new();
+
+    for index in 0..archive.len() {
+        let mut file = if let Ok(file) = archive.by_index(index) {
+            file
+        } else {
+            continue;
+        };
+        let start = file.header_start();
+        let end = file.data_start() + file.compressed_size();
+        if file.is_dir()
+            || file.size() > budget as u64
+            || extents
+                .range(..end)
+                .next_back()
+                .is_some_and(|(_, &prev_end)| prev_end > start)
+        {
+            continue;
+        }
+        extents.insert(start, end);
+        if let Some(contents) = read_to_limit(&mut file, budget) {
+            budget -= contents.len();
+            files.push((file.name().to_string(), contents));
+        }
+    }
+
+    files
+}
+
+// Reads a decompressed stream, returning `None` if it fails, such as on a
+// checksum mismatch, or produces more than `max_size` bytes.
+fn read_to_limit(reader: impl Read, max_size: usize) -> Option<Vec<u8>> {
+    let mut output = Vec::new();
+    reader
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut output)
+        .ok()?;
+    Some(output).filter(|output| output.len() <= max_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::gunzip;
+
+    #[cfg(feature = "dmarc")]
+    use super::unzip;
+
+    #[test]
+    fn gunzip_files() {
+        let gzipped =
+            b"\x1f\x8b\x08\x08\x00\x00\x00\x00\x02\x03a.txt\x00\xcbH\xcd\xc9\xc9\x07\x00\x86\xa6\x106\x05\x00\x00\x00";
+        assert_eq!(gunzip(gzipped, 1024).as_deref(), Some(&b"hello"[..]));
+
+        // Bad checksum
+        let mut corrupted = gzipped.to_vec();
+        corrupted[gzipped.len() - 8] ^= 1;
+        assert_eq!(gunzip(&corrupted, 1024), None);
+
+        // Truncated header
+        for len in 0..12 {
+            assert_eq!(gunzip(&gzipped[..len], 1024), None);
+        }
+        assert_eq!(
+            gunzip(b"\x1f\x8b\x08\x04\x00\x00\x00\x00\x00\x03\xff", 1024),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dmarc")]
+    fn unzip_files() {
+        let zipped =
+            b"PK\x03\x04\x14\x00\x00\x00\x00\x00\x13\x0bP]\x00\x00\x00\x00\x00\x00\x00\x00\
+            \x00\x00\x00\x00\x04\x00\x00\x00dir/PK\x03\x04\x14\x00\x00\x00\x00\x00\x00\
+            \x00!\x00\x86\xa6\x106\x05\x00\x00\x00\x05\x00\x00\x00\x05\x00\x00\x00a.txth\
+            elloPK\x03\x04\x14\x00\x00\x00\x08\x00\x00\x00!\x00\x9e#\xfc\xa9\x17\x00\x00\
+            \x00&\x00\x00\x00\x05\x00\x00\x00b.xml\xb3IKMMIJL\xce\xb6\xcbH\xcd\xc9\xc9W@\
+            \x22m\xf4\xe1r\x00PK\x01\x02\x14\x03\x14\x00\x00\x00\x00\x00\x13\x0bP]\x00\
+            \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\
+            \x00\x00\x10\x00\xfdA\x00\x00\x00\x00dir/PK\x01\x02\x14\x03\x14\x00\x00\x00\
+            \x00\x00\x00\x00!\x00\x86\xa6\x106\x05\x00\x00\x00\x05\x00\x00\x00\x05\x00\
+            \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01\x22\x00\x00\x00a.txtPK\x01\
+            \x02\x14\x03\x14\x00\x00\x00\x08\x00\x00\x00!\x00\x9e#\xfc\xa9\x17\x00\x00\
+            \x00&\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01J\
+            \x00\x00\x00b.xmlPK\x05\x06\x00\x00\x00\x00\x03\x00\x03\x00\x98\x00\x00\x00\
+            \x84\x00\x00\x00\x00\x00";
+        assert_eq!(
+            unzip(zipped, 1024),
+            [
+                ("a.txt".to_string(), b"hello".to_vec()),
+                (
+                    "b.xml".to_string(),
+                    b"<feedback>hello hello hello</feedback>".to_vec()
+                ),
+            ]
+        );
+        assert_eq!(
+            unzip(zipped, 10),
+            [("a.txt".to_string(), b"hello".to_vec())]
+        );
+        assert_eq!(unzip(&zipped[..zipped.len() - 1], 1024), []);
+    }
+
+    #[test]
+    #[cfg(feature = "dmarc")]
+    fn unzip_overlapping_files() {
+        // A stored file followed by a central directory listing it `count`
+        // times, as used by zip bombs.
+        let zip_bomb = |count: u16| {
+            let mut zipped = b"PK\x03\x04\x14\x00\x00\x00\x00\x00\x00\x00!\x00\x86\xa6\x106\
+                \x05\x00\x00\x00\x05\x00\x00\x00\x05\x00\x00\x00a.txthello"
+                .to_vec();
+            let directory_offset = zipped.len() as u32;
+            for _ in 0..count {
+                zipped.extend_from_slice(
+                    b"PK\x01\x02\x14\x03\x14\x00\x00\x00\x00\x00\x00\x00!\x00\x86\xa6\x106\
+                    \x05\x00\x00\x00\x05\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x00\
+                    \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00a.txt",
+                );
+            }
+            let directory_size = zipped.len() as u32 - directory_offset;
+            zipped.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+            zipped.extend_from_slice(&count.to_le_bytes());
+            zipped.extend_from_slice(&count.to_le_bytes());
+            zipped.extend_from_slice(&directory_size.to_le_bytes());
+            zipped.extend_from_slice(&directory_offset.to_le_bytes());
+            zipped.extend_from_slice(b"\x00\x00");
+            zipped
+        };
+
+        assert_eq!(
+            unzip(&zip_bomb(1), 1024),
+            [("a.txt".to_string(), b"hello".to_vec())]
+        );
+        assert_eq!(
+            unzip(&zip_bomb(u16::MAX), 1024),
+            [("a.txt".to_string(), b"hello".to_vec())]
+        );
+        assert_eq!(unzip(&zip_bomb(1), 4), []);
+    }
+}
@@ -11,14 +11,77 @@
 
 use std::borrow::Cow;
 
-use crate::parsers::MessageStream;
+use crate::{parsers::MessageStream, Encoding};
 
 pub mod base64;
 pub mod charsets;
 pub mod encoded_word;
 pub mod hex;
 pub mod html;
+#[cfg(feature = "html_sanitize")]
+pub mod html_sanitize;
+#[cfg(any(feature = "dmarc", feature = "decompression"))]
+pub mod inflate;
 pub mod quoted_printable;
+pub mod url;
+pub mod uuencode;
+#[cfg(feature = "decompression")]
+pub mod zstd;
 
 pub type DecodeFnc<'x> = fn(&mut MessageStream<'x>, &[u8]) -> (usize, Cow<'x, [u8]>);
 pub type DecodeWordFnc<'x> = fn(&mut MessageStream<'x>) -> Option<Vec<u8>>;
+
+/// Returns the encoding of a part having the provided Content-Transfer-Encoding
+/// value, or no value, and the function the message parser uses to read its
+/// contents. Unknown encodings are read as 8bit, unless `unknown_as_binary`
+/// is set, in which case `None` is returned and the parser handles the part
+/// as a binary attachment read with [`MessageStream::mime_part`].
+pub fn transfer_decoder<'x>(
+    value: Option<&str>,
+    unknown_as_binary: bool,
+) -> Option<(Encoding, DecodeFnc<'x>)> {
+    match value.map(Encoding::parse) {
+        Some(Some(Encoding::Base64)) => Some((Encoding::Base64, MessageStream::decode_base64_mime)),
+        Some(Some(Encoding::QuotedPrintable)) => Some((
+            Encoding::QuotedPrintable,
+            MessageStream::decode_quoted_printable_mime,
+        )),
+        Some(None) if unknown_as_binary => None,
+        _ => Some((Encoding::None, MessageStream::mime_part)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{decoders::transfer_decoder, parsers::MessageStream, Encoding};
+
+    #[test]
+    fn select_transfer_decoder() {
+        for (value, unknown_as_binary, expected) in [
+            (None, true, Some((Encoding::None, &b"SGVsbG8="[..]))),
+            (Some("Base64"), true, Some((Encoding::Base64, b"Hello"))),
+            (
+                Some("quoted-printable"),
+                true,
+                Some((Encoding::QuotedPrintable, b"SGVsbG8")),
+            ),
+            (Some("8BIT"), true, Some((Encoding::None, b"SGVsbG8="))),
+            (
+                Some("x-uuencode"),
+                false,
+                Some((Encoding::None, b"SGVsbG8=")),
+            ),
+            (Some("x-uuencode"), true, None),
+        ] {
+            assert_eq!(
+                transfer_decoder(value, unknown_as_binary).map(|(encoding, decode_fnc)| {
+                    let (_, bytes) = decode_fnc(&mut MessageStream::new(b"SGVsbG8="), b"");
+                    (encoding, bytes.into_owned())
+                }),
+                expected.map(|(encoding, bytes)| (encoding, bytes.to_vec())),
+                "{:?}",
+                value
+            );
+        }
+    }
+}
@@ -83,6 +83,31 @@ pub fn quoted_printable_decode(bytes: &[u8]) -> Option<Vec<u8>> {
     buf.into()
 }
 
+/// Returns the position of the first '=' that is neither followed by two
+/// hexadecimal digits nor a soft line break.
+pub fn quoted_printable_error_offset(bytes: &[u8]) -> Option<usize> {
+    let mut iter = bytes.iter().enumerate();
+
+    while let Some((pos, &ch)) = iter.next() {
+        if ch == b'=' {
+            let is_escape = bytes.get(pos + 1..pos + 3).is_some_and(|hex| {
+                HEX_MAP[hex[0] as usize] != -1 && HEX_MAP[hex[1] as usize] != -1
+            });
+            if is_escape {
+                iter.nth(1);
+            } else if bytes[pos + 1..]
+                .iter()
+                .find(|ch| !matches!(ch, b' ' | b'\t' | b'\r'))
+                .is_some_and(|&ch| ch != b'\n')
+            {
+                return Some(pos);
+            }
+        }
+    }
+
+    None
+}
+
 #[inline(always)]
 pub fn quoted_printable_decode_char(hex1: u8, hex2: u8) -> Option<u8> {
     #[cfg(feature = "ludicrous_mode")]
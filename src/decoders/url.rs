@@ -0,0 +1,224 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::UrlKind;
+
+use super::html::{decode_html_entities, find_ignore_case, html_to_text, parse_html_tag};
+
+// Prefixes starting a URL in plain text.
+static TEXT_URL_PREFIXES: &[&str] = &["https://", "http://", "ftp://", "mailto:", "www."];
+
+/// Returns the URLs of the links and images of an HTML document, along with
+/// the anchor text of the links and the alternative text of the images.
+/// Links are the `href` attributes of `a` and `area` tags, and images the
+/// `src` attributes of `img` tags and the `background` attributes.
+pub fn html_urls(input: &str) -> Vec<(UrlKind, String, Option<String>)> {
+    let bytes = input.as_bytes();
+    let mut urls = Vec::new();
+    let mut pos = 0;
+
+    while let Some(offset) = bytes[pos..].iter().position(|&ch| ch == b'<') {
+        let tag_start = pos + offset;
+        if bytes[tag_start..].starts_with(b"<!--") {
+            pos = find_ignore_case(bytes, tag_start + 4, b"-->").map_or(bytes.len(), |end| end + 3);
+            continue;
+        }
+        let tag = if let Some(tag) = parse_html_tag(bytes, tag_start).filter(|tag| !tag.is_close) {
+            tag
+        } else {
+            pos = tag_start + 1;
+            continue;
+        };
+        pos = tag.end.min(bytes.len());
+
+        let attribute = |name: &str| {
+            tag.attributes
+                .iter()
+                .find(|(attr_name, _, _)| attr_name == name)
+                .map(|(_, _, value)| decode_html_entities(input[value.clone()].trim()))
+        };
+        match tag.name.as_str() {
+            "a" | "area" => {
+                if let Some(url) = attribute("href").filter(|url| !url.is_empty()) {
+                    let text = (tag.name == "a")
+                        .then(|| {
+                            let end = find_ignore_case(bytes, pos, b"</a").unwrap_or(bytes.len());
+                            html_to_text(&input[pos..end])
+                        })
+                        .or_else(|| attribute("alt"))
+                        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+                        .filter(|text| !text.is_empty());
+                    urls.push((UrlKind::Link, url, text));
+                }
+            }
+            "img" => {
+                if let Some(url) = attribute("src").filter(|url| !url.is_empty()) {
+                    urls.push((UrlKind::Image, url, attribute("alt")));
+                }
+            }
+            _ => (),
+        }
+        if let Some(url) = attribute("background").filter(|url| !url.is_empty()) {
+            urls.push((UrlKind::Image, url, None));
+        }
+    }
+
+    urls
+}
+
+/// Returns the URLs found in a plain text, starting with a scheme such as
+/// `https://` or `mailto:`, or with `www.`.
+pub fn text_urls(input: &str) -> Vec<String> {
+    let bytes = input.as_bytes();
+    let mut urls = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let prefix = if pos == 0 || !bytes[pos - 1].is_ascii_alphanumeric() {
+            TEXT_URL_PREFIXES.iter().find(|prefix| {
+                bytes
+                    .get(pos..pos + prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix.as_bytes()))
+            })
+        } else {
+            None
+        };
+        let prefix = if let Some(prefix) = prefix {
+            prefix
+        } else {
+            pos += 1;
+            continue;
+        };
+
+        let start = pos;
+        let mut end = pos + prefix.len();
+        while bytes.get(end).is_some_and(|&ch| {
+            !ch.is_ascii_whitespace() && !ch.is_ascii_control() && !b"<>\"'`".contains(&ch)
+        }) {
+            end += 1;
+        }
+        pos = end;
+
+        // Punctuation ending a sentence or enclosing the URL is not part of it
+        let mut url = &input[start..end];
+        while url.ends_with(['.', ',', ';', ':', '!', '?', ']', '*', '_'])
+            || (url.ends_with(')') && url.matches('(').count() < url.matches(')').count())
+        {
+            url = &url[..url.len() - 1];
+        }
+        if url.len() > prefix.len() {
+            urls.push(url.to_string());
+        }
+    }
+
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decoders::url::{html_urls, text_urls},
+        BodyUrl, Message, UrlKind,
+    };
+
+    #[test]
+    fn extract_html_urls() {
+        assert_eq!(
+            html_urls(concat!(
+                "<p><a href=\"https://example.com/?a=1&amp;b=2\">Your <b>bank</b>\n",
+                "account</a> <a name=top>x</a><!-- <a href=\"https://hidden.example.com\"> -->",
+                "<A HREF='https://example.org/'><img src=\"cid:logo\" alt=\"Logo\"></A>",
+                "<map><area href=\"https://example.net/\" alt=\"Area\"></map>",
+                "<table background=\"https://example.com/bg.png\">"
+            )),
+            [
+                (
+                    UrlKind::Link,
+                    "https://example.com/?a=1&b=2".to_string(),
+                    Some("Your bank account".to_string())
+                ),
+                (
+                    UrlKind::Link,
+                    "https://example.org/".to_string(),
+                    Some("Logo".to_string())
+                ),
+                (
+                    UrlKind::Image,
+                    "cid:logo".to_string(),
+                    Some("Logo".to_string())
+                ),
+                (
+                    UrlKind::Link,
+                    "https://example.net/".to_string(),
+                    Some("Area".to_string())
+                ),
+                (
+                    UrlKind::Image,
+                    "https://example.com/bg.png".to_string(),
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_text_urls() {
+        assert_eq!(
+            text_urls(concat!(
+                "Visit https://example.com/path?a=1. Or (see http://en.wikipedia.org/wiki/Rust_(language)),\n",
+                "<HTTPS://example.org/a> www.example.net, mailto:jane@example.com!\n",
+                "Not a URL: xhttps://example.com, https:// or a www. prefix"
+            )),
+            [
+                "https://example.com/path?a=1",
+                "http://en.wikipedia.org/wiki/Rust_(language)",
+                "HTTPS://example.org/a",
+                "www.example.net",
+                "mailto:jane@example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_message_urls() {
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/alternative; boundary=\"a\"\n\n",
+                "--a\n",
+                "Content-Type: text/plain\n\n",
+                "Log in at https://example.com/login\n",
+                "--a\n",
+                "Content-Type: text/html\n\n",
+                "<a href=\"https://evil.example.com/\">https://example.com/login</a>\n",
+                "--a--\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            message.extract_urls(),
+            [
+                BodyUrl {
+                    url: "https://example.com/login".to_string(),
+                    kind: UrlKind::Text,
+                    part_id: 1,
+                    text: None,
+                },
+                BodyUrl {
+                    url: "https://evil.example.com/".to_string(),
+                    kind: UrlKind::Link,
+                    part_id: 2,
+                    text: Some("https://example.com/login".to_string()),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::ops::Range;
+
+/// A uuencoded file embedded in a text body.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UuencodedFile<'x> {
+    /// File name, as found in the "begin" line.
+    pub name: &'x str,
+    /// Unix file mode, as found in the "begin" line.
+    pub mode: u32,
+    /// Decoded file contents.
+    pub contents: Vec<u8>,
+    /// Position of the block in the text, from the "begin" line up to and
+    /// including the "end" line.
+    pub range: Range<usize>,
+}
+
+/// Finds and decodes all uuencoded blocks ("begin 644 file.zip" ... "end")
+/// contained in a text.
+pub fn find_uuencoded(text: &str) -> Vec<UuencodedFile<'_>> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    let mut current: Option<UuencodedFile> = None;
+    let mut is_finished = false;
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        if let Some(mut file) = current.take() {
+            if is_finished {
+                if line.trim_end() == "end" {
+                    file.range.end = offset;
+                    result.push(file);
+                    continue;
+                }
+            } else if line.trim_end() == "end" {
+                // Some encoders omit the zero-length line
+                file.range.end = offset;
+                result.push(file);
+                continue;
+            } else if let Some(len) = uudecode_line(line.as_bytes(), &mut file.contents) {
+                is_finished = len == 0;
+                current = file.into();
+                continue;
+            }
+        }
+
+        if let Some((mode, name)) = parse_begin_line(line) {
+            current = UuencodedFile {
+                name,
+                mode,
+                contents: Vec::new(),
+                range: line_start..line_start,
+            }
+            .into();
+            is_finished = false;
+        }
+    }
+
+    result
+}
+
+/// Decodes a single line of uuencoded data, returning the number of decoded
+/// bytes or `None` if the line is not valid.
+pub fn uudecode_line(line: &[u8], result: &mut Vec<u8>) -> Option<usize> {
+    let (&len_ch, data) = line.split_first()?;
+    let len = uu_value(len_ch)? as usize;
+    let encoded_len = len.div_ceil(3) * 4;
+
+    // Trailing spaces are often stripped, pad them back
+    if data.len() > encoded_len + 1
+        || (len > 0 && data.len() < encoded_len.saturating_sub(3))
+        || data.iter().any(|&ch| uu_value(ch).is_none())
+    {
+        return None;
+    }
+
+    let mut decoded = 0;
+    for chunk in (0..encoded_len).step_by(4) {
+        let mut bytes = [0u8; 4];
+        for (pos, byte) in bytes.iter_mut().enumerate() {
+            *byte = uu_value(*data.get(chunk + pos).unwrap_or(&b' '))?;
+        }
+        for byte in [
+            (bytes[0] << 2) | (bytes[1] >> 4),
+            (bytes[1] << 4) | (bytes[2] >> 2),
+            (bytes[2] << 6) | bytes[3],
+        ] {
+            if decoded < len {
+                result.push(byte);
+                decoded += 1;
+            }
+        }
+    }
+
+    Some(len)
+}
+
+fn parse_begin_line(line: &str) -> Option<(u32, &str)> {
+    let (mode, name) = line.strip_prefix("begin ")?.split_once(' ')?;
+    let name = name.trim();
+    if (3..=4).contains(&mode.len()) && !name.is_empty() {
+        Some((u32::from_str_radix(mode, 8).ok()?, name))
+    } else {
+        None
+    }
+}
+
+#[inline(always)]
+fn uu_value(ch: u8) -> Option<u8> {
+    // Backquote is used instead of space by most encoders
+    if (b' '..=b'`').contains(&ch) {
+        Some((ch - b' ') & 0x3f)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decoders::uuencode::{find_uuencoded, uudecode_line};
+
+    #[test]
+    fn decode_uuencode() {
+        for (input, expected_result) in [
+            ("#0V%T\n", "Cat"),
+            ("%=&5S=`H`\n", "test\n"),
+            ("%=&5S=`H\n", "test\n"),
+            ("`\n", ""),
+            (
+                "8:'1T<#HO+W=W=RYW:6MI<&5D:6$N;W)G\n",
+                "http://www.wikipedia.org",
+            ),
+        ] {
+            let mut result = Vec::new();
+            assert!(
+                uudecode_line(input.trim_end().as_bytes(), &mut result).is_some(),
+                "Failed for {:?}",
+                input
+            );
+            assert_eq!(std::str::from_utf8(&result).unwrap(), expected_result);
+        }
+
+        for input in ["hello world", "M" /* too short */, "#0V%T~"] {
+            assert_eq!(uudecode_line(input.as_bytes(), &mut Vec::new()), None);
+        }
+
+        let text = concat!(
+            "Hi, please find the files below.\r\n",
+            "\r\n",
+            "begin 644 cat.txt\r\n",
+            "#0V%T\r\n",
+            "`\r\n",
+            "end\r\n",
+            "begin 644 not a uuencoded block\r\n",
+            "Regards,\r\n",
+            "begin 600 wiki.txt\n",
+            "8:'1T<#HO+W=W=RYW:6MI<&5D:6$N;W)G\n",
+            "end\n",
+            "John\n"
+        );
+        let files = find_uuencoded(text);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].name, "cat.txt");
+        assert_eq!(files[0].mode, 0o644);
+        assert_eq!(files[0].contents, b"Cat");
+        assert_eq!(
+            &text[files[0].range.clone()],
+            "begin 644 cat.txt\r\n#0V%T\r\n`\r\nend\r\n"
+        );
+        assert_eq!(files[1].name, "wiki.txt");
+        assert_eq!(files[1].mode, 0o600);
+        assert_eq!(files[1].contents, b"http://www.wikipedia.org");
+        assert!(text[files[1].range.end..].starts_with("John"));
+    }
+}
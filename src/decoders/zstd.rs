@@ -0,0 +1,105 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Decompression of Zstandard (RFC 8878) frames.
+
+use std::io::Read;
+
+use ruzstd::{frame::ReadFrameHeaderError, frame_decoder::FrameDecoderError, StreamingDecoder};
+
+/// Decompresses the Zstandard frames of a file, skipping skippable frames
+/// and verifying the content checksums. Frames using a dictionary are not
+/// supported. Returns `None` if the file is malformed or decompresses to
+/// more than `max_size` bytes.
+pub fn unzstd(bytes: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut input = bytes;
+
+    loop {
+        match StreamingDecoder::new(&mut input) {
+            Ok(mut decoder) => {
+                let limit = (max_size - output.len()) as u64 + 1;
+                (&mut decoder).take(limit).read_to_end(&mut output).ok()?;
+                if output.len() > max_size
+                    || !decoder.decoder.is_finished()
+                    || decoder
+                        .decoder
+                        .get_checksum_from_data()
+                        .is_some_and(|checksum| {
+                            decoder.decoder.get_calculated_checksum() != Some(checksum)
+                        })
+                {
+                    return None;
+                }
+            }
+            Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame {
+                length,
+                ..
+            })) => {
+                input = input.get(length as usize..)?;
+            }
+            Err(_) => return None,
+        }
+        if input.is_empty() {
+            return Some(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unzstd;
+
+    #[test]
+    fn unzstd_frames() {
+        let huffman_text = format!(
+            "{}abcdefghijklmnopqrstuvwxyz0123456789",
+            "The quick brown fox jumps over the lazy dog. ".repeat(3)
+        );
+        for (compressed, expected) in [
+            // Raw block
+            (&b"(\xb5/\xfd \x05)\x00\x00hello"[..], &b"hello"[..]),
+            // Raw literals and predefined sequence tables
+            (
+                b"(\xb5/\xfd$\x17\x9d\x00\x00hhello world h\x01\x00\xdf\xca\x17<\xf1\xf0\xa9",
+                b"hello world hello world",
+            ),
+            // Huffman coded literals and FSE compressed weights
+            (
+                b"(\xb5/\xfd$\xab\xb5\x02\x00\x12E\x13\x18p\xcb\x03@R\xa1\xdd\xc0&\xb5\xb0\
+                  \xf1\xeb\xf4w$\xd2\xff\x17\x0f\xa5\xff\xe1\x05J\x82\x1cFA\x0cB\x10A\x8f\xd3\
+                  lFg|\xdfV:e\x84\xcf5\xb6Tr\xe0=s(J\xd4\x1bd<\xb7\xe6]\xdf\xba\xa5?\xef\xdc:\
+                  \x9f\x95\xcf\x157\xf6\x06\x97\x01\x00=8\xa92\x0c\xafT\xe3",
+                huffman_text.as_bytes(),
+            ),
+            // Skippable frame followed by two frames
+            (
+                b"P*M\x18\x02\x00\x00\x00ab(\xb5/\xfd \x05)\x00\x00hello(\xb5/\xfd \x05)\x00\x00hello",
+                b"hellohello",
+            ),
+        ] {
+            assert_eq!(unzstd(compressed, 1024).as_deref(), Some(expected));
+        }
+
+        let compressed =
+            b"(\xb5/\xfd$\x17\x9d\x00\x00hhello world h\x01\x00\xdf\xca\x17<\xf1\xf0\xa9";
+        // Size limit
+        assert_eq!(unzstd(compressed, 22), None);
+        // Truncated
+        assert_eq!(unzstd(&compressed[..20], 1024), None);
+        // Bad checksum
+        let mut corrupted = compressed.to_vec();
+        corrupted[compressed.len() - 1] ^= 1;
+        assert_eq!(unzstd(&corrupted, 1024), None);
+        // Not a Zstandard file
+        assert_eq!(unzstd(b"hello world", 1024), None);
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::fmt::{self, Display, Write};
+
+use crate::{Addr, Group, HeaderValue};
+
+use super::encoded_word::{needs_encoding, rfc2047_encode};
+
+impl<'x> Display for Addr<'x> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.name, &self.address) {
+            (Some(name), Some(address)) => {
+                write_phrase(f, name)?;
+                f.write_str(" <")?;
+                write_addr_spec(f, address)?;
+                f.write_char('>')
+            }
+            (None, Some(address)) => write_addr_spec(f, address),
+            (Some(name), None) => write_phrase(f, name),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl<'x> Display for Group<'x> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_phrase(f, self.name.as_deref().unwrap_or_default())?;
+        f.write_char(':')?;
+        for (pos, addr) in self.addresses.iter().enumerate() {
+            f.write_str(if pos > 0 { ", " } else { " " })?;
+            addr.fmt(f)?;
+        }
+        f.write_char(';')
+    }
+}
+
+/// Renders an address header value using the RFC 5322 syntax, including
+/// named and empty groups (e.g. "undisclosed-recipients:;").
+/// Returns `None` if the value does not contain addresses.
+pub fn encode_addresses(value: &HeaderValue<'_>) -> Option<String> {
+    match value {
+        HeaderValue::Address(addr) => addr.to_string().into(),
+        HeaderValue::AddressList(list) => join(list).into(),
+        HeaderValue::Group(group) => group.to_string().into(),
+        HeaderValue::GroupList(list) => join(list).into(),
+        _ => None,
+    }
+}
+
+fn join<T: Display>(items: &[T]) -> String {
+    let mut result = String::new();
+    for (pos, item) in items.iter().enumerate() {
+        if pos > 0 {
+            result.push_str(", ");
+        }
+        let _ = write!(result, "{item}");
+    }
+    result
+}
+
+fn write_addr_spec(f: &mut fmt::Formatter<'_>, address: &str) -> fmt::Result {
+    match address.rsplit_once('@') {
+        Some((local_part, domain)) if needs_quoting(local_part) => {
+            f.write_char('"')?;
+            for ch in local_part.chars() {
+                if ch == '"' || ch == '\\' {
+                    f.write_char('\\')?;
+                }
+                f.write_char(ch)?;
+            }
+            write!(f, "\"@{domain}")
+        }
+        _ => f.write_str(address),
+    }
+}
+
+// Returns `true` if the local-part is neither quoted nor a dot-atom
+fn needs_quoting(local_part: &str) -> bool {
+    let is_quoted =
+        local_part.len() > 1 && local_part.starts_with('"') && local_part.ends_with('"');
+    let is_dot_atom = local_part
+        .split('.')
+        .all(|atom| !atom.is_empty() && atom.bytes().all(|ch| ch >= 0x80 || is_atext(ch)));
+
+    !is_quoted && !is_dot_atom
+}
+
+fn write_phrase(f: &mut fmt::Formatter<'_>, phrase: &str) -> fmt::Result {
+    if needs_encoding(phrase) {
+        f.write_str(&rfc2047_encode(phrase, 0))
+    } else if !phrase.is_empty()
+        && phrase
+            .split(' ')
+            .all(|word| !word.is_empty() && word.bytes().all(is_atext))
+    {
+        f.write_str(phrase)
+    } else {
+        f.write_char('"')?;
+        for ch in phrase.chars() {
+            if ch == '"' || ch == '\\' {
+                f.write_char('\\')?;
+            }
+            f.write_char(ch)?;
+        }
+        f.write_char('"')
+    }
+}
+
+// RFC 5322, Section 3.2.3
+#[inline(always)]
+pub(crate) fn is_atext(ch: u8) -> bool {
+    ch.is_ascii_alphanumeric()
+        || matches!(
+            ch,
+            b'!' | b'#'
+                | b'$'
+                | b'%'
+                | b'&'
+                | b'\''
+                | b'*'
+                | b'+'
+                | b'-'
+                | b'/'
+                | b'='
+                | b'?'
+                | b'^'
+                | b'_'
+                | b'`'
+                | b'{'
+                | b'|'
+                | b'}'
+                | b'~'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parsers::MessageStream, Addr, Group, HeaderValue};
+
+    use super::encode_addresses;
+
+    #[test]
+    fn encode_address_values() {
+        for (value, expected_result) in [
+            (
+                HeaderValue::Address(Addr::new("John Doe".into(), "jdoe@machine.example")),
+                "John Doe <jdoe@machine.example>",
+            ),
+            (
+                HeaderValue::Address(Addr::new(None, "jdoe@machine.example")),
+                "jdoe@machine.example",
+            ),
+            (
+                HeaderValue::AddressList(vec![
+                    Addr::new("Joe Q. Public".into(), "john.q.public@example.com"),
+                    Addr::new("Giant; \"Big\" Box".into(), "sysservices@example.net"),
+                    Addr::new("John Smîth".into(), "john@example.com"),
+                ]),
+                concat!(
+                    "\"Joe Q. Public\" <john.q.public@example.com>, ",
+                    "\"Giant; \\\"Big\\\" Box\" <sysservices@example.net>, ",
+                    "=?utf-8?Q?John_Sm=C3=AEth?= <john@example.com>"
+                ),
+            ),
+            (
+                HeaderValue::AddressList(vec![
+                    Addr::new("John Smith".into(), "\"john smith\"@example.com"),
+                    Addr::new(None, "\"john \\\"x\\\" smith\"@example.com"),
+                ]),
+                concat!(
+                    "John Smith <\"john smith\"@example.com>, ",
+                    "\"john \\\"x\\\" smith\"@example.com"
+                ),
+            ),
+            (
+                HeaderValue::Group(Group::new("undisclosed-recipients", vec![])),
+                "undisclosed-recipients:;",
+            ),
+            (
+                HeaderValue::GroupList(vec![
+                    Group::new(
+                        "Colleagues",
+                        vec![Addr::new("James Smythe".into(), "james@vandelay.com")],
+                    ),
+                    Group::new(
+                        "Friends",
+                        vec![
+                            Addr::new(None, "jane@example.com"),
+                            Addr::new(None, "john@example.com"),
+                        ],
+                    ),
+                    Group::new("Empty", vec![]),
+                ]),
+                concat!(
+                    "Colleagues: James Smythe <james@vandelay.com>;, ",
+                    "Friends: jane@example.com, john@example.com;, Empty:;"
+                ),
+            ),
+        ] {
+            let result = encode_addresses(&value).unwrap();
+            assert_eq!(result, expected_result);
+
+            // Make sure the result parses back to the original value
+            assert_eq!(
+                MessageStream::new(format!("{result}\n").as_bytes()).parse_address(),
+                value,
+                "Failed to parse {result:?}"
+            );
+        }
+
+        assert_eq!(encode_addresses(&HeaderValue::Text("text".into())), None);
+
+        // Local parts that are not dot-atoms are quoted
+        for (address, expected_result) in [
+            ("john smith@example.com", "\"john smith\"@example.com"),
+            ("john\"smith@example.com", "\"john\\\"smith\"@example.com"),
+            ("john..smith@example.com", "\"john..smith\"@example.com"),
+            ("jöhn.smith@example.com", "jöhn.smith@example.com"),
+        ] {
+            assert_eq!(Addr::new(None, address).to_string(), expected_result);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+static CHARPAD: u8 = b'=';
+static CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes a byte slice as Base64 without line breaks.
+pub fn base64_encode(input: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(input.len().div_ceil(3) * 4);
+
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0] as usize;
+        let b1 = chunk.get(1).copied().unwrap_or(0) as usize;
+        let b2 = chunk.get(2).copied().unwrap_or(0) as usize;
+
+        buf.push(CHARS[b0 >> 2]);
+        buf.push(CHARS[((b0 & 0x03) << 4) | (b1 >> 4)]);
+        buf.push(if chunk.len() > 1 {
+            CHARS[((b1 & 0x0f) << 2) | (b2 >> 6)]
+        } else {
+            CHARPAD
+        });
+        buf.push(if chunk.len() > 2 {
+            CHARS[b2 & 0x3f]
+        } else {
+            CHARPAD
+        });
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decoders::base64::base64_decode;
+
+    use super::base64_encode;
+
+    #[test]
+    fn encode_base64() {
+        for (input, expected_result) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+            ("☕ tables", "4piVIHRhYmxlcw=="),
+        ] {
+            let result = base64_encode(input.as_bytes());
+            assert_eq!(
+                std::str::from_utf8(&result).unwrap(),
+                expected_result,
+                "Failed for {input:?}"
+            );
+            assert_eq!(base64_decode(&result).unwrap(), input.as_bytes());
+        }
+    }
+}
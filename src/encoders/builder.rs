@@ -0,0 +1,1120 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    borrow::Cow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+};
+
+use crate::{DateTime, HeaderName, RfcHeader};
+
+use super::{
+    base64::base64_encode,
+    encoded_word::{needs_encoding, rfc2047_encode, rfc2047_encode_as, EncodingType},
+    quoted_printable::{prefers_quoted_printable, quoted_printable_encode},
+    BASE64_LINE_LEN, MAX_LINE_LEN,
+};
+
+// Maximum length of a parameter section on its own folded line.
+const MAX_PARAM_LEN: usize = 74;
+
+/// Builds a MIME message from a set of header fields, text and HTML bodies,
+/// inline images and attachments.
+///
+/// The multipart structure is derived from the bodies and files added:
+/// a text and an HTML body are placed in a multipart/alternative part, the
+/// HTML body and its inline images in a multipart/related part, and the
+/// attachments are added next to the bodies in a multipart/mixed part.
+#[derive(Debug, Default, Clone)]
+pub struct MessageBuilder<'x> {
+    headers: Vec<(Cow<'x, str>, Cow<'x, str>)>,
+    text_body: Option<Cow<'x, str>>,
+    html_body: Option<Cow<'x, str>>,
+    inline_images: Vec<(String, Attachment<'x>)>,
+    attachments: Vec<Attachment<'x>>,
+    header_order: HeaderOrder,
+    filename_encoding: FilenameEncoding,
+    seed: Option<u64>,
+}
+
+/// Order in which the header fields of a message are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderOrder {
+    /// Write the header fields in the order they were added.
+    #[default]
+    Preserve,
+    /// Write the trace fields first, followed by the originator, destination,
+    /// identification and informational fields, any other fields and finally
+    /// the MIME fields. Fields in the same group keep the order in which they
+    /// were added.
+    Canonical,
+}
+
+/// How non-ASCII file names of attachments and inline images are written.
+/// ASCII names are always written as quoted `name` and `filename`
+/// parameters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FilenameEncoding {
+    /// Write the Content-Disposition `filename` parameter with RFC 2231
+    /// and the Content-Type `name` parameter with RFC 2047 encoded-words,
+    /// which older clients such as Outlook expect.
+    #[default]
+    Both,
+    /// Write both parameters with RFC 2231.
+    Rfc2231,
+    /// Write both parameters with RFC 2047 encoded-words.
+    Rfc2047,
+}
+
+/// A file added to a message, either as an attachment or as an inline
+/// image referenced from the HTML body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment<'x> {
+    pub content_type: Cow<'x, str>,
+    pub name: Cow<'x, str>,
+    pub contents: Cow<'x, [u8]>,
+}
+
+/// A message built once and written many times, with header fields and
+/// placeholders in the text and HTML bodies replaced for each recipient.
+/// The attachments and inline images are encoded when the template is
+/// created and the encoded bytes are shared by every variant written.
+#[derive(Debug, Clone)]
+pub struct MessageTemplate<'x> {
+    headers: Vec<(&'x str, &'x str)>,
+    header_order: HeaderOrder,
+    chunks: Vec<Chunk<'x>>,
+    boundaries: Vec<String>,
+}
+
+/// The changes made to a [`MessageTemplate`] for one recipient.
+#[derive(Debug, Default, Clone)]
+pub struct Variant<'y> {
+    headers: Vec<(Cow<'y, str>, Cow<'y, str>)>,
+    replacements: Vec<(Cow<'y, str>, Cow<'y, str>)>,
+}
+
+// A serialized message, split at the text bodies which are only encoded
+// once the placeholders they contain have been replaced.
+#[derive(Debug, Clone)]
+enum Chunk<'x> {
+    Encoded(Vec<u8>),
+    Text {
+        subtype: &'static str,
+        text: &'x str,
+    },
+}
+
+// Source of the random values used in boundaries and Content-IDs, seeded
+// in deterministic mode.
+struct Rng(Option<u64>);
+
+enum MimePart<'x> {
+    Text {
+        subtype: &'static str,
+        text: &'x str,
+    },
+    Single {
+        headers: Vec<String>,
+        contents: Cow<'x, [u8]>,
+        is_base64: bool,
+    },
+    Multipart {
+        subtype: &'static str,
+        parts: Vec<MimePart<'x>>,
+    },
+}
+
+impl<'x> MessageBuilder<'x> {
+    pub fn new() -> Self {
+        MessageBuilder::default()
+    }
+
+    /// Adds a header field, writing the value as RFC 2047 encoded-words
+    /// when it contains non-ASCII characters.
+    pub fn header(mut self, name: impl Into<Cow<'x, str>>, value: impl Into<Cow<'x, str>>) -> Self {
+        let name = name.into();
+        let value = value.into();
+        let value = if needs_encoding(&value) {
+            rfc2047_encode(&value, name.len() + 2).into()
+        } else {
+            value
+        };
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Adds a header field with a value that is written verbatim, such as
+    /// an address list rendered with
+    /// [`encode_addresses`](super::address::encode_addresses).
+    pub fn raw_header(
+        mut self,
+        name: impl Into<Cow<'x, str>>,
+        value: impl Into<Cow<'x, str>>,
+    ) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Adds a Date header field in RFC 5322 format, using the time zone of
+    /// the date. Use [`DateTime::to_timezone`] to write it in another zone.
+    pub fn date(self, date: &DateTime) -> Self {
+        self.raw_header("Date", date.to_rfc822())
+    }
+
+    /// Generates the MIME boundaries and Content-IDs from a seed rather than
+    /// randomly, so that the same message is produced on every run. Meant
+    /// for tests; the seed has to be set before adding inline images.
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the order in which the header fields are written.
+    pub fn header_order(mut self, header_order: HeaderOrder) -> Self {
+        self.header_order = header_order;
+        self
+    }
+
+    /// Sets how non-ASCII file names are written.
+    pub fn filename_encoding(mut self, filename_encoding: FilenameEncoding) -> Self {
+        self.filename_encoding = filename_encoding;
+        self
+    }
+
+    /// Sets the plain text body.
+    pub fn text_body(mut self, text: impl Into<Cow<'x, str>>) -> Self {
+        self.text_body = Some(text.into());
+        self
+    }
+
+    /// Sets the HTML body.
+    pub fn html_body(mut self, html: impl Into<Cow<'x, str>>) -> Self {
+        self.html_body = Some(html.into());
+        self
+    }
+
+    /// Sets the HTML body along with the images it displays. The HTML refers
+    /// to each image as "cid:" followed by the image name, for example
+    /// `<img src="cid:logo.png">`. A unique Content-ID is generated for each
+    /// image and the references in the HTML are rewritten to use it.
+    pub fn html_body_with_images(
+        mut self,
+        html: impl AsRef<str>,
+        images: impl IntoIterator<Item = Attachment<'x>>,
+    ) -> Self {
+        let prefix = format!("{:016x}", Rng(self.seed).next_u64());
+        self.inline_images = images
+            .into_iter()
+            .enumerate()
+            .map(|(pos, image)| (format!("part{}.{}@mail-parser", pos + 1, prefix), image))
+            .collect();
+        self.html_body = Some(rewrite_cids(html.as_ref(), &self.inline_images).into());
+        self
+    }
+
+    /// Adds an attachment.
+    pub fn attachment(
+        mut self,
+        content_type: impl Into<Cow<'x, str>>,
+        name: impl Into<Cow<'x, str>>,
+        contents: impl Into<Cow<'x, [u8]>>,
+    ) -> Self {
+        self.attachments
+            .push(Attachment::new(content_type, name, contents));
+        self
+    }
+
+    /// Returns the Content-IDs generated for the inline images, along with
+    /// the image names.
+    pub fn content_ids(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.inline_images
+            .iter()
+            .map(|(cid, image)| (image.name.as_ref(), cid.as_str()))
+    }
+
+    /// Writes the message.
+    pub fn write_to(&self, output: impl Write) -> io::Result<()> {
+        self.template().write_to(&Variant::new(), output)
+    }
+
+    /// Encodes the message as a template to be written once per recipient.
+    pub fn template(&self) -> MessageTemplate<'_> {
+        let mut chunks = Vec::new();
+        let mut boundaries = Vec::new();
+        write_part(
+            &mut chunks,
+            &self.build(),
+            &mut Rng(self.seed),
+            &mut boundaries,
+        );
+        MessageTemplate {
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_ref(), value.as_ref()))
+                .collect(),
+            header_order: self.header_order,
+            chunks,
+            boundaries,
+        }
+    }
+
+    /// Returns the message as a byte vector.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write_to(&mut output).ok();
+        output
+    }
+
+    fn build(&self) -> MimePart<'_> {
+        let text_part = self.text_body.as_ref().map(|text| MimePart::Text {
+            subtype: "plain",
+            text,
+        });
+        let html_part = self.html_body.as_ref().map(|html| {
+            let html_part = MimePart::Text {
+                subtype: "html",
+                text: html,
+            };
+            if !self.inline_images.is_empty() {
+                MimePart::Multipart {
+                    subtype: "related",
+                    parts: std::iter::once(html_part)
+                        .chain(self.inline_images.iter().map(|(cid, image)| {
+                            file_mime_part(image, Some(cid), self.filename_encoding)
+                        }))
+                        .collect(),
+                }
+            } else {
+                html_part
+            }
+        });
+
+        let body_part = match (text_part, html_part) {
+            (Some(text_part), Some(html_part)) => Some(MimePart::Multipart {
+                subtype: "alternative",
+                parts: vec![text_part, html_part],
+            }),
+            (text_part, html_part) => text_part.or(html_part),
+        };
+
+        if !self.attachments.is_empty() {
+            MimePart::Multipart {
+                subtype: "mixed",
+                parts: body_part
+                    .into_iter()
+                    .chain(
+                        self.attachments.iter().map(|attachment| {
+                            file_mime_part(attachment, None, self.filename_encoding)
+                        }),
+                    )
+                    .collect(),
+            }
+        } else {
+            body_part.unwrap_or(MimePart::Text {
+                subtype: "plain",
+                text: "",
+            })
+        }
+    }
+}
+
+impl<'x> Attachment<'x> {
+    pub fn new(
+        content_type: impl Into<Cow<'x, str>>,
+        name: impl Into<Cow<'x, str>>,
+        contents: impl Into<Cow<'x, [u8]>>,
+    ) -> Self {
+        Attachment {
+            content_type: content_type.into(),
+            name: name.into(),
+            contents: contents.into(),
+        }
+    }
+}
+
+impl<'x> MessageTemplate<'x> {
+    /// Writes the message with the changes of a variant.
+    pub fn write_to(&self, variant: &Variant<'_>, mut output: impl Write) -> io::Result<()> {
+        let mut headers = Vec::with_capacity(self.headers.len() + variant.headers.len());
+        for (pos, (name, value)) in self.headers.iter().enumerate() {
+            if !variant.has_header(name) {
+                headers.push((*name, *value));
+            } else if !self.headers[..pos]
+                .iter()
+                .any(|(prev_name, _)| prev_name.eq_ignore_ascii_case(name))
+            {
+                // Replaced fields are written where the first one was
+                headers.extend(
+                    variant
+                        .headers
+                        .iter()
+                        .filter(|(variant_name, _)| variant_name.eq_ignore_ascii_case(name))
+                        .map(|(name, value)| (name.as_ref(), value.as_ref())),
+                );
+            }
+        }
+        headers.extend(
+            variant
+                .headers
+                .iter()
+                .filter(|(name, _)| {
+                    !self
+                        .headers
+                        .iter()
+                        .any(|(template_name, _)| template_name.eq_ignore_ascii_case(name))
+                })
+                .map(|(name, value)| (name.as_ref(), value.as_ref())),
+        );
+        if self.header_order == HeaderOrder::Canonical {
+            headers.sort_by_key(|(name, _)| header_group(name));
+        }
+        for (name, value) in headers {
+            write!(output, "{name}: {value}\r\n")?;
+        }
+        output.write_all(b"MIME-Version: 1.0\r\n")?;
+
+        for chunk in &self.chunks {
+            match chunk {
+                Chunk::Encoded(bytes) => output.write_all(bytes)?,
+                Chunk::Text { subtype, text } => {
+                    let mut part = Vec::new();
+                    write_text_part(&mut part, subtype, &variant.apply(text), &self.boundaries);
+                    output.write_all(&part)?;
+                }
+            }
+        }
+        output.write_all(b"\r\n")
+    }
+
+    /// Returns the message with the changes of a variant as a byte vector.
+    pub fn to_bytes(&self, variant: &Variant<'_>) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write_to(variant, &mut output).ok();
+        output
+    }
+}
+
+impl<'y> Variant<'y> {
+    pub fn new() -> Self {
+        Variant::default()
+    }
+
+    /// Sets a header field, replacing the fields with the same name in the
+    /// template. Calling it again with the same name adds another field.
+    /// Non-ASCII values are written as RFC 2047 encoded-words.
+    pub fn header(mut self, name: impl Into<Cow<'y, str>>, value: impl Into<Cow<'y, str>>) -> Self {
+        let name = name.into();
+        let value = value.into();
+        let value = if needs_encoding(&value) {
+            rfc2047_encode(&value, name.len() + 2).into()
+        } else {
+            value
+        };
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Sets a header field with a value that is written verbatim.
+    pub fn raw_header(
+        mut self,
+        name: impl Into<Cow<'y, str>>,
+        value: impl Into<Cow<'y, str>>,
+    ) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Replaces a placeholder in the text and HTML bodies. The value is
+    /// inserted as is, so values placed in the HTML body have to be escaped
+    /// by the caller.
+    pub fn replace(
+        mut self,
+        placeholder: impl Into<Cow<'y, str>>,
+        value: impl Into<Cow<'y, str>>,
+    ) -> Self {
+        let placeholder = placeholder.into();
+        if !placeholder.is_empty() {
+            self.replacements.push((placeholder, value.into()));
+        }
+        self
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|(variant_name, _)| variant_name.eq_ignore_ascii_case(name))
+    }
+
+    // Replaces all the placeholders in a single pass, so that placeholders
+    // found in the values inserted are left alone.
+    fn apply<'z>(&self, text: &'z str) -> Cow<'z, str> {
+        let mut result = String::new();
+        let mut last_pos = 0;
+        while let Some((pos, placeholder, value)) = self
+            .replacements
+            .iter()
+            .filter_map(|(placeholder, value)| {
+                text[last_pos..]
+                    .find(placeholder.as_ref())
+                    .map(|pos| (last_pos + pos, placeholder, value))
+            })
+            .min_by_key(|(pos, _, _)| *pos)
+        {
+            result.push_str(&text[last_pos..pos]);
+            result.push_str(value);
+            last_pos = pos + placeholder.len();
+        }
+
+        if last_pos > 0 {
+            result.push_str(&text[last_pos..]);
+            result.into()
+        } else {
+            text.into()
+        }
+    }
+}
+
+// Text bodies are written as 7bit when possible. Otherwise, such as when
+// they contain a boundary of the template after the placeholders are
+// replaced, they are written as quoted-printable, or as base64 when that is
+// shorter.
+fn write_text_part(output: &mut Vec<u8>, subtype: &str, text: &str, boundaries: &[String]) {
+    let is_7bit = text.is_ascii()
+        && text.lines().all(|line| line.len() <= MAX_LINE_LEN)
+        && !boundaries
+            .iter()
+            .any(|boundary| text.contains(boundary.as_str()));
+    let is_quoted_printable = !is_7bit && prefers_quoted_printable(text.as_bytes());
+    output.extend_from_slice(
+        format!(
+            "Content-Type: text/{subtype}; charset=utf-8\r\nContent-Transfer-Encoding: {}\r\n\r\n",
+            if is_7bit {
+                "7bit"
+            } else if is_quoted_printable {
+                "quoted-printable"
+            } else {
+                "base64"
+            }
+        )
+        .as_bytes(),
+    );
+    if is_quoted_printable {
+        output.extend_from_slice(&quoted_printable_encode(text.as_bytes()));
+    } else if !is_7bit {
+        write_base64(output, text.as_bytes());
+    } else if text.contains('\n') && !text.contains("\r\n") {
+        output.extend_from_slice(text.replace('\n', "\r\n").as_bytes());
+    } else {
+        output.extend_from_slice(text.as_bytes());
+    }
+}
+
+fn file_mime_part<'x>(
+    file: &'x Attachment<'x>,
+    content_id: Option<&str>,
+    filename_encoding: FilenameEncoding,
+) -> MimePart<'x> {
+    let mut content_type = format!("Content-Type: {}", file.content_type);
+    let mut disposition = String::from(if content_id.is_some() {
+        "Content-Disposition: inline"
+    } else {
+        "Content-Disposition: attachment"
+    });
+    if !file.name.is_empty() {
+        let (name_encoding, filename_encoding) = match filename_encoding {
+            FilenameEncoding::Both => (FilenameEncoding::Rfc2047, FilenameEncoding::Rfc2231),
+            encoding => (encoding, encoding),
+        };
+        write_param(&mut content_type, "name", &file.name, name_encoding);
+        write_param(&mut disposition, "filename", &file.name, filename_encoding);
+    }
+    let mut headers = vec![
+        content_type,
+        "Content-Transfer-Encoding: base64".to_string(),
+        disposition,
+    ];
+    if let Some(content_id) = content_id {
+        headers.push(format!("Content-ID: <{content_id}>"));
+    }
+
+    MimePart::Single {
+        headers,
+        contents: file.contents.as_ref().into(),
+        is_base64: true,
+    }
+}
+
+fn write_part<'x>(
+    output: &mut Vec<Chunk<'x>>,
+    part: &MimePart<'x>,
+    rng: &mut Rng,
+    boundaries: &mut Vec<String>,
+) {
+    match part {
+        MimePart::Text { subtype, text } => output.push(Chunk::Text { subtype, text }),
+        MimePart::Single {
+            headers,
+            contents,
+            is_base64,
+        } => {
+            let mut bytes = Vec::new();
+            for header in headers {
+                bytes.extend_from_slice(header.as_bytes());
+                bytes.extend_from_slice(b"\r\n");
+            }
+            bytes.extend_from_slice(b"\r\n");
+            if *is_base64 {
+                write_base64(&mut bytes, contents);
+            } else {
+                bytes.extend_from_slice(contents);
+            }
+            write_bytes(output, &bytes);
+        }
+        MimePart::Multipart { subtype, parts } => {
+            let parts = parts
+                .iter()
+                .map(|part| {
+                    let mut output = Vec::new();
+                    write_part(&mut output, part, rng, boundaries);
+                    output
+                })
+                .collect::<Vec<_>>();
+
+            // "=_" never occurs in base64 or quoted-printable contents, the
+            // children are scanned anyway in case of 7bit or 8bit contents.
+            let boundary = loop {
+                let boundary = format!("=_{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+                if !parts.iter().flatten().any(|chunk| {
+                    match chunk {
+                        Chunk::Encoded(bytes) => bytes.as_slice(),
+                        Chunk::Text { text, .. } => text.as_bytes(),
+                    }
+                    .windows(boundary.len())
+                    .any(|window| window == boundary.as_bytes())
+                }) {
+                    break boundary;
+                }
+            };
+
+            write_bytes(
+                output,
+                format!("Content-Type: multipart/{subtype}; boundary=\"{boundary}\"\r\n\r\n")
+                    .as_bytes(),
+            );
+            for part in parts {
+                write_bytes(output, format!("--{boundary}\r\n").as_bytes());
+                for chunk in part {
+                    match chunk {
+                        Chunk::Encoded(bytes) => write_bytes(output, &bytes),
+                        chunk => output.push(chunk),
+                    }
+                }
+                write_bytes(output, b"\r\n");
+            }
+            write_bytes(output, format!("--{boundary}--").as_bytes());
+            boundaries.push(boundary);
+        }
+    }
+}
+
+// Appends bytes to the last chunk, adding a new one after a text body.
+fn write_bytes(output: &mut Vec<Chunk<'_>>, bytes: &[u8]) {
+    if let Some(Chunk::Encoded(last)) = output.last_mut() {
+        last.extend_from_slice(bytes);
+    } else {
+        output.push(Chunk::Encoded(bytes.to_vec()));
+    }
+}
+
+fn write_base64(output: &mut Vec<u8>, contents: &[u8]) {
+    let encoded = base64_encode(contents);
+    for (pos, line) in encoded.chunks(BASE64_LINE_LEN).enumerate() {
+        if pos > 0 {
+            output.extend_from_slice(b"\r\n");
+        }
+        output.extend_from_slice(line);
+    }
+}
+
+// Appends a parameter to a header field, folding the line before it when
+// it would exceed the recommended line length. Non-ASCII values are written
+// with RFC 2231, split into continuations if needed, or as adjacent RFC 2047
+// encoded-words, which can't be folded since decoders keep the whitespace
+// inside quoted values.
+fn write_param(header: &mut String, name: &str, value: &str, encoding: FilenameEncoding) {
+    let line_len = header.len() - header.rfind("\r\n ").map_or(0, |pos| pos + 2);
+    let mut param = String::with_capacity(name.len() + value.len() + 4);
+
+    if value.is_ascii() {
+        param.push_str(name);
+        param.push_str("=\"");
+        for ch in value.chars() {
+            if matches!(ch, '"' | '\\') {
+                param.push('\\');
+            }
+            param.push(ch);
+        }
+        param.push('"');
+    } else if encoding == FilenameEncoding::Rfc2047 {
+        param.push_str(name);
+        param.push_str("=\"");
+        param.push_str(&rfc2047_encode_as(value, EncodingType::Base64, 0).replace("\r\n ", ""));
+        param.push('"');
+    } else {
+        // Percent-encoded characters, sections are split between them
+        let encoded = value
+            .chars()
+            .map(|ch| {
+                let mut buf = [0u8; 4];
+                ch.encode_utf8(&mut buf)
+                    .bytes()
+                    .map(|ch| {
+                        if ch.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&ch) {
+                            (ch as char).to_string()
+                        } else {
+                            format!("%{ch:02X}")
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+
+        if name.len() + encoded.iter().map(|ch| ch.len()).sum::<usize>() + 9 <= MAX_PARAM_LEN {
+            param.push_str(name);
+            param.push_str("*=utf-8''");
+            param.extend(encoded);
+        } else {
+            let mut section = 0;
+            let mut section_len = MAX_PARAM_LEN;
+            for ch in encoded {
+                if section_len + ch.len() > MAX_PARAM_LEN {
+                    if section > 0 {
+                        param.push_str(";\r\n ");
+                    }
+                    let prefix = if section == 0 {
+                        format!("{name}*0*=utf-8''")
+                    } else {
+                        format!("{name}*{section}*=")
+                    };
+                    section_len = prefix.len();
+                    param.push_str(&prefix);
+                    section += 1;
+                }
+                section_len += ch.len();
+                param.push_str(&ch);
+            }
+        }
+    }
+
+    if line_len + param.find("\r\n").unwrap_or(param.len()) + 2 > BASE64_LINE_LEN {
+        header.push_str(";\r\n ");
+    } else {
+        header.push_str("; ");
+    }
+    header.push_str(&param);
+}
+
+// Replaces the "cid:name" references to the inline images in an HTML body
+// with the generated Content-IDs.
+fn rewrite_cids(html: &str, images: &[(String, Attachment<'_>)]) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut last_pos = 0;
+
+    for (pos, _) in html.match_indices("cid:") {
+        let name_start = pos + 4;
+        if name_start < last_pos {
+            continue;
+        }
+        let name_end = html[name_start..]
+            .find(|ch: char| matches!(ch, '"' | '\'' | '(' | ')' | '<' | '>') || ch.is_whitespace())
+            .map_or(html.len(), |end| name_start + end);
+        if let Some((cid, _)) = images
+            .iter()
+            .find(|(_, image)| image.name == html[name_start..name_end])
+        {
+            result.push_str(&html[last_pos..name_start]);
+            result.push_str(cid);
+            last_pos = name_end;
+        }
+    }
+    result.push_str(&html[last_pos..]);
+
+    result
+}
+
+// Returns the position of a header field in the canonical order.
+fn header_group(name: &str) -> u8 {
+    match HeaderName::parse(name) {
+        Some(HeaderName::Rfc(header)) => match header {
+            RfcHeader::ReturnPath
+            | RfcHeader::Received
+            | RfcHeader::ResentDate
+            | RfcHeader::ResentFrom
+            | RfcHeader::ResentSender
+            | RfcHeader::ResentTo
+            | RfcHeader::ResentCc
+            | RfcHeader::ResentBcc
+            | RfcHeader::ResentMessageId => 0,
+            RfcHeader::Date | RfcHeader::From | RfcHeader::Sender | RfcHeader::ReplyTo => 1,
+            RfcHeader::To | RfcHeader::Cc | RfcHeader::Bcc => 2,
+            RfcHeader::MessageId | RfcHeader::InReplyTo | RfcHeader::References => 3,
+            RfcHeader::Subject | RfcHeader::Comments | RfcHeader::Keywords => 4,
+            RfcHeader::ListArchive
+            | RfcHeader::ListHelp
+            | RfcHeader::ListId
+            | RfcHeader::ListOwner
+            | RfcHeader::ListPost
+            | RfcHeader::ListSubscribe
+            | RfcHeader::ListUnsubscribe => 5,
+            RfcHeader::MimeVersion
+            | RfcHeader::ContentDescription
+            | RfcHeader::ContentId
+            | RfcHeader::ContentLanguage
+            | RfcHeader::ContentLocation
+            | RfcHeader::ContentTransferEncoding
+            | RfcHeader::ContentType
+            | RfcHeader::ContentDisposition => 6,
+        },
+        _ => 5,
+    }
+}
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        match &mut self.0 {
+            Some(state) => {
+                // SplitMix64
+                *state = state.wrapping_add(0x9e3779b97f4a7c15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                z ^ (z >> 31)
+            }
+            None => RandomState::new().build_hasher().finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        encoders::builder::{Attachment, FilenameEncoding, HeaderOrder, MessageBuilder, Variant},
+        Addr, HeaderValue, Message, MimeHeaders,
+    };
+
+    #[test]
+    fn build_html_with_inline_images() {
+        let builder = MessageBuilder::new()
+            .header("From", "jane@example.com")
+            .header("Subject", "Monthly réport")
+            .text_body("Hello,\nsee the chart below.")
+            .html_body_with_images(
+                concat!(
+                    "<p>Hello,</p><img src=\"cid:chart.png\">",
+                    "<img src='cid:logo.png'><img src=\"cid:other.png\">"
+                ),
+                [
+                    Attachment::new("image/png", "chart.png", &b"\x89PNG chart"[..]),
+                    Attachment::new("image/png", "logo.png", &b"\x89PNG logo"[..]),
+                ],
+            )
+            .attachment("application/pdf", "réport.pdf", &b"%PDF-1.4"[..]);
+        let content_ids = builder
+            .content_ids()
+            .map(|(name, cid)| (name.to_string(), cid.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(content_ids.len(), 2);
+        assert_ne!(content_ids[0].1, content_ids[1].1);
+
+        let raw_message = builder.to_bytes();
+        let message = Message::parse(&raw_message).unwrap();
+
+        assert_eq!(message.subject(), Some("Monthly réport"));
+        assert_eq!(
+            message.from(),
+            &HeaderValue::Address(Addr::new(None, "jane@example.com"))
+        );
+
+        let structure = message
+            .parts
+            .iter()
+            .map(|part| {
+                let ct = part.content_type().unwrap();
+                format!("{}/{}", ct.ctype(), ct.subtype().unwrap_or_default())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            structure,
+            [
+                "multipart/mixed",
+                "multipart/alternative",
+                "text/plain",
+                "multipart/related",
+                "text/html",
+                "image/png",
+                "image/png",
+                "application/pdf"
+            ]
+        );
+
+        assert_eq!(
+            message.body_text(0).unwrap(),
+            "Hello,\r\nsee the chart below."
+        );
+        assert_eq!(
+            message.body_html(0).unwrap(),
+            format!(
+                concat!(
+                    "<p>Hello,</p><img src=\"cid:{}\">",
+                    "<img src='cid:{}'><img src=\"cid:other.png\">"
+                ),
+                content_ids[0].1, content_ids[1].1
+            )
+        );
+        for ((name, cid), part_id) in content_ids.iter().zip([5, 6]) {
+            let part = &message.parts[part_id];
+            assert_eq!(part.content_id(), Some(cid.as_str()));
+            assert_eq!(part.attachment_name(), Some(name.as_str()));
+        }
+        assert_eq!(message.parts[5].contents(), b"\x89PNG chart");
+        assert_eq!(message.parts[7].attachment_name(), Some("réport.pdf"));
+        assert_eq!(message.parts[7].contents(), b"%PDF-1.4");
+    }
+
+    #[test]
+    fn build_deterministic_boundaries() {
+        let build = |text: &str| {
+            MessageBuilder::new()
+                .deterministic(1)
+                .text_body(text.to_string())
+                .attachment("application/octet-stream", "file.bin", &b"\x00\x01"[..])
+                .to_bytes()
+        };
+        let boundary = |raw_message: &[u8]| {
+            Message::parse(raw_message).unwrap().parts[0]
+                .content_type()
+                .unwrap()
+                .attribute("boundary")
+                .unwrap()
+                .to_string()
+        };
+
+        let raw_message = build("Hello");
+        assert_eq!(raw_message, build("Hello"));
+
+        // Contents containing the generated boundary force a new one
+        let first_boundary = boundary(&raw_message);
+        let text = format!("--{first_boundary}\r\n\r\n--{first_boundary}--");
+        let raw_message = build(&text);
+        assert_ne!(boundary(&raw_message), first_boundary);
+
+        let message = Message::parse(&raw_message).unwrap();
+        assert_eq!(message.body_text(0).unwrap(), text);
+        assert_eq!(message.parts[2].contents(), b"\x00\x01");
+    }
+
+    #[test]
+    fn build_header_order() {
+        let builder = MessageBuilder::new()
+            .header("Subject", "Hi")
+            .header("X-Mailer", "test")
+            .header("To", "john@example.com")
+            .header("Message-ID", "<1@example.com>")
+            .header("Received", "from a by b")
+            .header("From", "jane@example.com")
+            .header("Content-Language", "en")
+            .header("Return-Path", "<jane@example.com>")
+            .header("Date", "Sat, 20 Nov 2021 14:22:01 -0800")
+            .text_body("Hi");
+
+        let header_names = |builder: &MessageBuilder<'_>| {
+            Message::parse(&builder.to_bytes()).unwrap().parts[0]
+                .headers
+                .iter()
+                .map(|header| header.name.as_str().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            header_names(&builder),
+            [
+                "Subject",
+                "X-Mailer",
+                "To",
+                "Message-ID",
+                "Received",
+                "From",
+                "Content-Language",
+                "Return-Path",
+                "Date",
+                "MIME-Version",
+                "Content-Type",
+                "Content-Transfer-Encoding"
+            ]
+        );
+        assert_eq!(
+            header_names(&builder.header_order(HeaderOrder::Canonical)),
+            [
+                "Received",
+                "Return-Path",
+                "From",
+                "Date",
+                "To",
+                "Message-ID",
+                "Subject",
+                "X-Mailer",
+                "Content-Language",
+                "MIME-Version",
+                "Content-Type",
+                "Content-Transfer-Encoding"
+            ]
+        );
+    }
+
+    #[test]
+    fn build_single_part() {
+        let raw_message = MessageBuilder::new()
+            .header("Subject", "Hi")
+            .html_body("<p>Hi</p>")
+            .to_bytes();
+        assert_eq!(
+            String::from_utf8(raw_message).unwrap(),
+            concat!(
+                "Subject: Hi\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: text/html; charset=utf-8\r\n",
+                "Content-Transfer-Encoding: 7bit\r\n",
+                "\r\n",
+                "<p>Hi</p>\r\n"
+            )
+        );
+    }
+
+    #[test]
+    fn build_attachment_names() {
+        let raw_message = MessageBuilder::new()
+            .deterministic(1)
+            .text_body("See attached.")
+            .attachment("application/pdf", "réport.pdf", &b"%PDF-1.4"[..])
+            .attachment("text/plain", "notes.txt", &b"notes"[..])
+            .to_bytes();
+        let raw_message = String::from_utf8(raw_message).unwrap();
+        assert!(raw_message.contains(concat!(
+            "Content-Type: application/pdf; name=\"=?utf-8?B?csOpcG9ydC5wZGY=?=\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "Content-Disposition: attachment; filename*=utf-8''r%C3%A9port.pdf\r\n"
+        )));
+        assert!(raw_message.contains(concat!(
+            "Content-Type: text/plain; name=\"notes.txt\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "Content-Disposition: attachment; filename=\"notes.txt\"\r\n"
+        )));
+
+        let name = "Informe trimestral de ventas — 第一四半期の売上報告書 (versión final).pdf";
+        for encoding in [
+            FilenameEncoding::Both,
+            FilenameEncoding::Rfc2231,
+            FilenameEncoding::Rfc2047,
+        ] {
+            let raw_message = MessageBuilder::new()
+                .filename_encoding(encoding)
+                .attachment("application/pdf", name, &b"%PDF-1.4"[..])
+                .to_bytes();
+            // Encoded-words in parameters can't be folded
+            let max_line_len = if encoding == FilenameEncoding::Rfc2231 {
+                78
+            } else {
+                998
+            };
+            assert!(
+                raw_message
+                    .split(|&ch| ch == b'\n')
+                    .all(|line| line.len() <= max_line_len),
+                "{:?}",
+                encoding
+            );
+
+            let message = Message::parse(&raw_message).unwrap();
+            let attachment = message.attachment(0).unwrap();
+            assert_eq!(attachment.attachment_name(), Some(name), "{encoding:?}");
+            assert_eq!(
+                attachment.content_type().unwrap().attribute("name"),
+                Some(name),
+                "{encoding:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn build_template_variants() {
+        let builder = MessageBuilder::new()
+            .deterministic(3)
+            .header("From", "news@example.com")
+            .header("To", "placeholder@example.com")
+            .header("Subject", "Newsletter")
+            .text_body("Hi {name},\nunsubscribe: {url}")
+            .html_body("<p>Hi {name}</p><a href=\"{url}\">Unsubscribe</a>")
+            .attachment("application/pdf", "issue.pdf", &b"%PDF-1.4"[..]);
+        let template = builder.template();
+
+        // Without changes the template writes the same message as the builder
+        assert_eq!(template.to_bytes(&Variant::new()), builder.to_bytes());
+
+        let raw_message = template.to_bytes(
+            &Variant::new()
+                .header("To", "Jose <jose@example.com>")
+                .header("X-Campaign", "42")
+                .replace("{name}", "José {url}")
+                .replace("{url}", "https://example.com/u/1"),
+        );
+        let message = Message::parse(&raw_message).unwrap();
+        assert_eq!(
+            message.to(),
+            &HeaderValue::Address(Addr::new(Some("Jose"), "jose@example.com"))
+        );
+        assert_eq!(message.header_raw("X-Campaign"), Some(" 42\r\n"));
+        assert_eq!(
+            message.body_text(0).unwrap(),
+            "Hi José {url},\r\nunsubscribe: https://example.com/u/1"
+        );
+        assert!(std::str::from_utf8(&raw_message)
+            .unwrap()
+            .contains("Content-Transfer-Encoding: quoted-printable\r\n\r\nHi Jos=C3=A9"));
+        assert_eq!(
+            message.body_html(0).unwrap(),
+            "<p>Hi José {url}</p><a href=\"https://example.com/u/1\">Unsubscribe</a>"
+        );
+        assert_eq!(message.attachment(0).unwrap().contents(), b"%PDF-1.4");
+        let raw_text = std::str::from_utf8(&raw_message).unwrap();
+        assert_eq!(raw_text.matches("To: ").count(), 1);
+        assert!(raw_text.find("To: ").unwrap() < raw_text.find("Subject: ").unwrap());
+
+        // Values containing a boundary force the body to be encoded
+        let boundary = message.parts[0]
+            .content_type()
+            .unwrap()
+            .attribute("boundary")
+            .unwrap()
+            .to_string();
+        let raw_message =
+            template.to_bytes(&Variant::new().replace("{name}", format!("\r\n--{boundary}--\r\n")));
+        let message = Message::parse(&raw_message).unwrap();
+        assert!(message
+            .body_text(0)
+            .unwrap()
+            .contains(&format!("--{boundary}--")));
+        assert_eq!(message.attachment(0).unwrap().contents(), b"%PDF-1.4");
+    }
+}
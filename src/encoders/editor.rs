@@ -0,0 +1,360 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{io, ops::Range};
+
+use crate::{Message, MessagePart, MessagePartId};
+
+use super::{
+    base64::base64_encode,
+    encoded_word::{needs_encoding, rfc2047_encode},
+    BASE64_LINE_LEN, MAX_LINE_LEN,
+};
+
+/// Applies edits to a parsed message and writes it back, copying all the
+/// bytes not affected by an edit verbatim from the raw message.
+///
+/// Edits refer to the headers and parts of the message as parsed, removing
+/// a part discards any other edit made to it.
+pub struct MessageEditor<'x> {
+    message: &'x Message<'x>,
+    edits: Vec<(Range<usize>, Vec<u8>)>,
+    line_ending: &'static str,
+}
+
+impl<'x> MessageEditor<'x> {
+    pub fn new(message: &'x Message<'x>) -> Self {
+        MessageEditor {
+            message,
+            edits: Vec::new(),
+            line_ending: if message.raw_message.windows(2).any(|w| w == b"\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            },
+        }
+    }
+
+    /// Adds a header field at the top of the message. Non-ASCII values are
+    /// written as RFC 2047 encoded-words. Names containing a line break or
+    /// a colon are ignored.
+    pub fn add_header(&mut self, name: &str, value: &str) -> &mut Self {
+        if let Some(header) = self.format_header(name, value) {
+            let offset = self.message.parts[0].offset_header;
+            self.edits.push((offset..offset, header));
+        }
+        self
+    }
+
+    /// Replaces the first header field with this name in place and removes
+    /// the others. The header is added at the end of the header block if the
+    /// message does not have it. Names containing a line break or a colon are
+    /// ignored.
+    pub fn set_header(&mut self, name: &str, value: &str) -> &mut Self {
+        let header = if let Some(header) = self.format_header(name, value) {
+            header
+        } else {
+            return self;
+        };
+        let mut headers = self.header_ranges(name);
+
+        if !headers.is_empty() {
+            let first = headers.remove(0);
+            self.edit(first, header);
+            for range in headers {
+                self.edit(range, Vec::new());
+            }
+        } else {
+            let offset = header_block_end(&self.message.parts[0]);
+            self.edits.push((offset..offset, header));
+        }
+        self
+    }
+
+    /// Removes all the header fields with this name.
+    pub fn remove_header(&mut self, name: &str) -> &mut Self {
+        for range in self.header_ranges(name) {
+            self.edit(range, Vec::new());
+        }
+        self
+    }
+
+    /// Removes a part, including its delimiter line, from its enclosing
+    /// multipart. Returns `false` if the part is the root part or was not
+    /// found in the raw message.
+    pub fn remove_part(&mut self, part_id: MessagePartId) -> bool {
+        let part = match self.message.parts.get(part_id) {
+            Some(part) if part_id > 0 && part.offset_header < part.offset_body => part,
+            _ => return false,
+        };
+
+        let raw_message = self.message.raw_message.as_ref();
+        let delimiter_start = match raw_message
+            .get(..part.offset_header.saturating_sub(1))
+            .and_then(|bytes| bytes.iter().rposition(|&ch| ch == b'\n'))
+        {
+            Some(pos) if raw_message[pos + 1..].starts_with(b"--") => pos,
+            _ => return false,
+        };
+        let start = if delimiter_start > 0 && raw_message[delimiter_start - 1] == b'\r' {
+            delimiter_start - 1
+        } else {
+            delimiter_start
+        };
+
+        self.edit(start..part.offset_end, Vec::new());
+        true
+    }
+
+    /// Replaces the contents of a part with a plain text notice, keeping
+    /// the part in place. The Content-* headers of the part are replaced as
+    /// well, while other headers are preserved. Returns `false` if the part
+    /// does not exist.
+    pub fn redact_part(&mut self, part_id: MessagePartId, notice: &str) -> bool {
+        self.replace_part(part_id, "text/plain", notice)
+    }
+
+    /// Replaces the contents of a part with a UTF-8 text of the given
+    /// content type, such as `text/html`. The Content-* headers of the part
+    /// are replaced as well, while other headers are preserved. Returns
+    /// `false` if the part does not exist.
+    pub fn replace_part(
+        &mut self,
+        part_id: MessagePartId,
+        content_type: &str,
+        contents: &str,
+    ) -> bool {
+        let part = match self.message.parts.get(part_id) {
+            Some(part) if part.offset_header < part.offset_body => part,
+            _ => return false,
+        };
+
+        for header in &part.headers {
+            if header
+                .name
+                .as_str()
+                .get(..8)
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case("content-"))
+            {
+                self.edit(header.raw_range(), Vec::new());
+            }
+        }
+
+        let is_base64 = contents.lines().any(|line| line.len() > MAX_LINE_LEN);
+        let offset = header_block_end(part);
+        let mut headers = format!(
+            "Content-Type: {content_type}; charset=utf-8{nl}Content-Transfer-Encoding: {}{nl}",
+            if is_base64 {
+                "base64"
+            } else if contents.is_ascii() {
+                "7bit"
+            } else {
+                "8bit"
+            },
+            nl = self.line_ending
+        );
+        if part_id == 0 && part.headers.is_empty() {
+            headers.push_str(self.line_ending);
+        }
+        self.edits.push((offset..offset, headers.into_bytes()));
+
+        let contents = if is_base64 {
+            base64_encode(contents.as_bytes())
+                .chunks(BASE64_LINE_LEN)
+                .collect::<Vec<_>>()
+                .join(self.line_ending.as_bytes())
+        } else {
+            contents.as_bytes().to_vec()
+        };
+        self.edit(part.raw_body_range(), contents);
+        true
+    }
+
+    /// Writes the edited message.
+    pub fn write_to(&self, mut output: impl io::Write) -> io::Result<()> {
+        let raw_message = self.message.raw_message.as_ref();
+        let mut edits = self.edits.iter().collect::<Vec<_>>();
+        // Insertions go before the edits replacing the bytes that follow them
+        edits.sort_by_key(|(range, _)| (range.start, !range.is_empty()));
+
+        let mut pos = 0;
+        for (range, bytes) in edits {
+            if range.start < pos || range.end > raw_message.len() {
+                // Edit inside a removed part
+                continue;
+            }
+            output.write_all(&raw_message[pos..range.start])?;
+            output.write_all(bytes)?;
+            pos = range.end;
+        }
+        output.write_all(&raw_message[pos..])
+    }
+
+    /// Returns the edited message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.message.raw_message.len());
+        self.write_to(&mut output).ok();
+        output
+    }
+
+    fn edit(&mut self, range: Range<usize>, bytes: Vec<u8>) {
+        if let Some((_, existing)) = self
+            .edits
+            .iter_mut()
+            .find(|(existing, _)| !range.is_empty() && existing == &range)
+        {
+            *existing = bytes;
+        } else {
+            self.edits.push((range, bytes));
+        }
+    }
+
+    fn header_ranges(&self, name: &str) -> Vec<Range<usize>> {
+        self.message.parts[0]
+            .headers
+            .iter()
+            .filter(|header| header.name.as_str().eq_ignore_ascii_case(name))
+            .map(|header| header.raw_range())
+            .collect()
+    }
+
+    fn format_header(&self, name: &str, value: &str) -> Option<Vec<u8>> {
+        if name.contains(['\r', '\n', ':']) {
+            return None;
+        }
+
+        let mut header = String::with_capacity(name.len() + value.len() + 4);
+        header.push_str(name);
+        header.push_str(": ");
+        if needs_encoding(value) {
+            let value = rfc2047_encode(value, name.len() + 2);
+            if self.line_ending == "\n" {
+                header.push_str(&value.replace("\r\n", "\n"));
+            } else {
+                header.push_str(&value);
+            }
+        } else {
+            header.push_str(value);
+        }
+        header.push_str(self.line_ending);
+        Some(header.into_bytes())
+    }
+}
+
+// Offset where new headers can be appended to the header block of a part.
+fn header_block_end(part: &MessagePart<'_>) -> usize {
+    part.headers
+        .iter()
+        .map(|header| header.offset_end)
+        .max()
+        .unwrap_or(part.offset_header)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{encoders::editor::MessageEditor, Message};
+
+    #[test]
+    fn edit_message() {
+        let raw_message = concat!(
+            "From: jane@example.com\r\n",
+            "To: john@example.com\r\n",
+            "Subject: Report\r\n",
+            "X-Spam-Score: 1.0\r\n",
+            "X-Spam-Score: 2.0\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Please find the report attached.\r\n",
+            "--b\r\n",
+            "Content-Type: application/octet-stream; name=\"report.exe\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "TVqQAAMAAAAEAAAA\r\n",
+            "--b\r\n",
+            "Content-Type: text/html\r\n",
+            "Content-Description: Signature\r\n",
+            "X-Part-Id: 3\r\n",
+            "\r\n",
+            "<p>Jane</p>\r\n",
+            "--b--\r\n"
+        );
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+
+        // No edits, the message is written verbatim
+        assert_eq!(
+            MessageEditor::new(&message).to_bytes(),
+            raw_message.as_bytes()
+        );
+
+        let mut editor = MessageEditor::new(&message);
+        editor
+            .add_header("Received", "from mx.example.com")
+            .set_header("X-Spam-Score", "5.0")
+            .set_header("Subject", "[SUSPICIOUS] Réport")
+            .remove_header("To");
+        assert!(editor.remove_part(2));
+        assert!(editor.redact_part(3, "Removed"));
+        assert!(!editor.remove_part(0));
+
+        assert_eq!(
+            String::from_utf8(editor.to_bytes()).unwrap(),
+            concat!(
+                "Received: from mx.example.com\r\n",
+                "From: jane@example.com\r\n",
+                "Subject: =?utf-8?Q?=5BSUSPICIOUS=5D_R=C3=A9port?=\r\n",
+                "X-Spam-Score: 5.0\r\n",
+                "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+                "\r\n",
+                "--b\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Please find the report attached.\r\n",
+                "--b\r\n",
+                "X-Part-Id: 3\r\n",
+                "Content-Type: text/plain; charset=utf-8\r\n",
+                "Content-Transfer-Encoding: 7bit\r\n",
+                "\r\n",
+                "Removed\r\n",
+                "--b--\r\n"
+            )
+        );
+
+        let edited = editor.to_bytes();
+        let message = Message::parse(&edited).unwrap();
+        assert_eq!(message.subject(), Some("[SUSPICIOUS] Réport"));
+        assert_eq!(message.attachments.len(), 0);
+        assert_eq!(message.body_text(1).unwrap(), "Removed");
+    }
+
+    #[test]
+    fn edit_first_header() {
+        let message = Message::parse(b"Subject: Hello\nTo: john@example.com\n\nHi\n").unwrap();
+
+        let mut editor = MessageEditor::new(&message);
+        editor
+            .set_header("Subject", "Bye")
+            .add_header("X-Added", "yes");
+        assert_eq!(
+            String::from_utf8(editor.to_bytes()).unwrap(),
+            "X-Added: yes\nSubject: Bye\nTo: john@example.com\n\nHi\n"
+        );
+
+        let mut editor = MessageEditor::new(&message);
+        editor.add_header("X-Added", "yes").remove_header("Subject");
+        assert_eq!(
+            String::from_utf8(editor.to_bytes()).unwrap(),
+            "X-Added: yes\nTo: john@example.com\n\nHi\n"
+        );
+    }
+}
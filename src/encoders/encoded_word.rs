@@ -0,0 +1,229 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use super::base64::base64_encode;
+
+// RFC 2047, Section 2: An 'encoded-word' may not be more than 75 characters long
+// and lines containing encoded-words should be limited to 76 characters.
+const MAX_WORD_LEN: usize = 75;
+const MAX_ENCODED_LINE_LEN: usize = 76;
+
+// Length of "=?utf-8?X?" plus "?="
+const WORD_OVERHEAD: usize = 12;
+
+static HEX_CHARS: &[u8] = b"0123456789ABCDEF";
+
+/// Encoding used for the text of an RFC 2047 encoded-word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingType {
+    Base64,
+    QuotedPrintable,
+}
+
+/// Returns `true` if the text can't be placed in a header field as-is and
+/// has to be written as one or more encoded-words.
+pub fn needs_encoding(text: &str) -> bool {
+    text.contains("=?")
+        || text
+            .as_bytes()
+            .iter()
+            .any(|&ch| !(0x20..0x7f).contains(&ch) && ch != b'\t')
+}
+
+/// Returns the encoding that produces the shortest output for the text.
+pub fn encoding_type(text: &str) -> EncodingType {
+    let (safe, unsafe_) = text
+        .as_bytes()
+        .iter()
+        .fold((0usize, 0usize), |(safe, unsafe_), &ch| {
+            if is_q_safe(ch) {
+                (safe + 1, unsafe_)
+            } else {
+                (safe, unsafe_ + 1)
+            }
+        });
+
+    if safe + unsafe_ * 3 <= (safe + unsafe_).div_ceil(3) * 4 {
+        EncodingType::QuotedPrintable
+    } else {
+        EncodingType::Base64
+    }
+}
+
+/// Encodes a header value as a sequence of UTF-8 encoded-words, choosing
+/// the most compact encoding. See [`rfc2047_encode_as`].
+pub fn rfc2047_encode(text: &str, offset: usize) -> String {
+    rfc2047_encode_as(text, encoding_type(text), offset)
+}
+
+/// Encodes a header value as a sequence of UTF-8 encoded-words.
+///
+/// Encoded-words are never longer than 75 characters, are split only on
+/// character boundaries and are separated by a folding CRLF followed by a
+/// space, so that no line exceeds 76 characters. `offset` is the number of
+/// characters already written on the first line (for example, the length
+/// of "Subject: ").
+pub fn rfc2047_encode_as(text: &str, encoding: EncodingType, offset: usize) -> String {
+    let mut result = String::with_capacity(text.len() * 2);
+    let mut word: Vec<u8> = Vec::with_capacity(MAX_WORD_LEN);
+    let mut word_len = 0;
+    let mut line_len = offset;
+
+    if line_len + WORD_OVERHEAD + 4 > MAX_ENCODED_LINE_LEN && line_len > 1 {
+        // Not even a single character fits on the first line
+        result.push_str("\r\n ");
+        line_len = 1;
+    }
+
+    for ch in text.chars() {
+        let mut buf = [0u8; 4];
+        let bytes = ch.encode_utf8(&mut buf).as_bytes();
+
+        if !word.is_empty()
+            && encoded_len(&word, word_len, bytes, encoding) + WORD_OVERHEAD
+                > std::cmp::min(MAX_WORD_LEN, MAX_ENCODED_LINE_LEN - line_len)
+        {
+            add_word(&mut result, &word, encoding);
+            result.push_str("\r\n ");
+            word.clear();
+            word_len = 0;
+            line_len = 1;
+        }
+
+        word_len = encoded_len(&word, word_len, bytes, encoding);
+        word.extend_from_slice(bytes);
+    }
+
+    if !word.is_empty() {
+        add_word(&mut result, &word, encoding);
+    }
+
+    result
+}
+
+// Returns the encoded length of the word after appending a character
+fn encoded_len(word: &[u8], word_len: usize, bytes: &[u8], encoding: EncodingType) -> usize {
+    match encoding {
+        EncodingType::Base64 => (word.len() + bytes.len()).div_ceil(3) * 4,
+        EncodingType::QuotedPrintable => {
+            word_len
+                + bytes
+                    .iter()
+                    .map(|&ch| if is_q_safe(ch) { 1 } else { 3 })
+                    .sum::<usize>()
+        }
+    }
+}
+
+fn add_word(result: &mut String, word: &[u8], encoding: EncodingType) {
+    match encoding {
+        EncodingType::Base64 => {
+            result.push_str("=?utf-8?B?");
+            result.push_str(std::str::from_utf8(&base64_encode(word)).unwrap_or_default());
+        }
+        EncodingType::QuotedPrintable => {
+            result.push_str("=?utf-8?Q?");
+            for &ch in word {
+                if ch == b' ' {
+                    result.push('_');
+                } else if is_q_safe(ch) {
+                    result.push(ch as char);
+                } else {
+                    result.push('=');
+                    result.push(HEX_CHARS[(ch >> 4) as usize] as char);
+                    result.push(HEX_CHARS[(ch & 0x0f) as usize] as char);
+                }
+            }
+        }
+    }
+    result.push_str("?=");
+}
+
+// RFC 2047, Section 5 (3): the most restrictive set, valid in any header context.
+#[inline(always)]
+fn is_q_safe(ch: u8) -> bool {
+    ch.is_ascii_alphanumeric() || matches!(ch, b' ' | b'!' | b'*' | b'+' | b'-' | b'/')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parsers::MessageStream, HeaderValue};
+
+    use super::{needs_encoding, rfc2047_encode, rfc2047_encode_as, EncodingType};
+
+    #[test]
+    fn encode_rfc2047() {
+        for (input, encoding, expected_result) in [
+            (
+                "Keith Moore",
+                EncodingType::QuotedPrintable,
+                "=?utf-8?Q?Keith_Moore?=",
+            ),
+            (
+                "Olle Järnefors",
+                EncodingType::QuotedPrintable,
+                "=?utf-8?Q?Olle_J=C3=A4rnefors?=",
+            ),
+            ("☺", EncodingType::Base64, "=?utf-8?B?4pi6?="),
+            (
+                "a=?b_c",
+                EncodingType::QuotedPrintable,
+                "=?utf-8?Q?a=3D=3Fb=5Fc?=",
+            ),
+        ] {
+            assert_eq!(rfc2047_encode_as(input, encoding, 0), expected_result);
+        }
+    }
+
+    #[test]
+    fn encode_rfc2047_long_values() {
+        for input in [
+            concat!(
+                "Why not both importing AND exporting? ☺ Ħéłłø ŵøŕłđ, ",
+                "this is a rather long subject line that needs to be folded"
+            ),
+            concat!(
+                "長沮、桀溺耦而耕，孔子過之，使子路問津焉。長沮曰：「夫執輿者為誰？」",
+                "子路曰：「為孔丘。」曰：「是魯孔丘與？」曰：「是也。」曰：「是知津矣。」"
+            ),
+            "ℌ𝔢𝔩𝔭 𝔪𝔢 𝔢𝔵𝔭𝔬𝔯𝔱 𝔪𝔶 𝔟𝔬𝔬𝔨 𝔭𝔩𝔢𝔞𝔰𝔢! ℌ𝔢𝔩𝔭 𝔪𝔢 𝔢𝔵𝔭𝔬𝔯𝔱 𝔪𝔶 𝔟𝔬𝔬𝔨 𝔭𝔩𝔢𝔞𝔰𝔢!",
+        ] {
+            assert!(needs_encoding(input));
+
+            for encoding in [EncodingType::Base64, EncodingType::QuotedPrintable] {
+                for offset in [0, "Subject: ".len(), 70] {
+                    let encoded = rfc2047_encode_as(input, encoding, offset);
+
+                    for (line_num, line) in encoded.split("\r\n").enumerate() {
+                        let line_len = line.len() + if line_num == 0 { offset } else { 0 };
+                        assert!(line_len <= 76, "Line too long: {:?}", line);
+                        for word in line.split_whitespace() {
+                            assert!(word.len() <= 75, "Word too long: {:?}", word);
+                        }
+                    }
+
+                    assert_eq!(
+                        MessageStream::new(format!("{encoded}\r\n").as_bytes())
+                            .parse_unstructured(),
+                        HeaderValue::Text(input.into()),
+                        "Failed to decode {encoded:?}"
+                    );
+                }
+            }
+
+            assert_eq!(
+                MessageStream::new(format!("{}\n", rfc2047_encode(input, 0)).as_bytes())
+                    .parse_unstructured(),
+                HeaderValue::Text(input.into()),
+            );
+        }
+    }
+}
@@ -0,0 +1,27 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+pub mod address;
+pub mod base64;
+pub mod builder;
+pub mod editor;
+pub mod encoded_word;
+pub mod pipeline;
+pub mod quoted_printable;
+pub mod trace;
+
+// RFC 2045, Section 6.8: encoded lines must not exceed 76 characters.
+pub(crate) const BASE64_LINE_LEN: usize = 76;
+
+// RFC 5322, Section 2.1.1: lines must not exceed 998 characters and should
+// not exceed 78 characters.
+pub(crate) const MAX_LINE_LEN: usize = 998;
+pub(crate) const FOLD_LINE_LEN: usize = 78;
@@ -0,0 +1,508 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{borrow::Cow, io};
+
+use crate::{
+    decoders::html::{rewrite_remote_images, sanitize_html},
+    Message, MessagePart, MessagePartId, MimeHeaders, PartType,
+};
+
+use super::editor::MessageEditor;
+
+/// A set of transformations applied to a message in a single pass, such as
+/// removing attachments, sanitizing the HTML bodies and editing header
+/// fields. Bytes not affected by any step are copied verbatim.
+///
+/// Regardless of the order in which the steps are added, attachments are
+/// removed first, then the remaining HTML bodies are sanitized and finally
+/// the header fields are edited. Header edits are applied in the order they
+/// were added, so a later step overrides the earlier ones for the same field.
+#[derive(Default)]
+pub struct Pipeline<'x> {
+    strip_attachments: Vec<PartPredicate<'x>>,
+    sanitize_html: bool,
+    remote_images: Option<RemoteImages<'x>>,
+    header_edits: Vec<HeaderEdit<'x>>,
+}
+
+/// Changes made by a pipeline to a message.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PipelineReport {
+    /// Ids of the attachments removed.
+    pub removed_attachments: Vec<MessagePartId>,
+    /// Ids of the HTML bodies that were rewritten.
+    pub rewritten_html: Vec<MessagePartId>,
+    /// URLs of the remote images removed or proxied.
+    pub remote_images: Vec<String>,
+}
+
+type PartPredicate<'x> = Box<dyn Fn(&MessagePart<'_>) -> bool + 'x>;
+
+enum RemoteImages<'x> {
+    Remove,
+    Proxy(Box<dyn Fn(&str) -> String + 'x>),
+}
+
+enum HeaderEdit<'x> {
+    Add(Cow<'x, str>, Cow<'x, str>),
+    Set(Cow<'x, str>, Cow<'x, str>),
+    Remove(Cow<'x, str>),
+}
+
+impl<'x> Pipeline<'x> {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    /// Returns a pipeline producing a copy of a message that is safe to
+    /// forward: risky attachments, as defined by [`is_risky_attachment`],
+    /// are removed, the HTML bodies are sanitized and their remote images
+    /// removed.
+    pub fn safe_forward() -> Self {
+        Pipeline::new()
+            .strip_attachments(is_risky_attachment)
+            .sanitize_html()
+            .remove_remote_images()
+    }
+
+    /// Removes the attachments for which the predicate returns true.
+    pub fn strip_attachments(mut self, predicate: impl Fn(&MessagePart<'_>) -> bool + 'x) -> Self {
+        self.strip_attachments.push(Box::new(predicate));
+        self
+    }
+
+    /// Removes the active content, such as scripts and event handlers, from
+    /// the HTML bodies of the message.
+    pub fn sanitize_html(mut self) -> Self {
+        self.sanitize_html = true;
+        self
+    }
+
+    /// Removes the remote images, such as tracking pixels, from the HTML
+    /// bodies of the message.
+    pub fn remove_remote_images(mut self) -> Self {
+        self.remote_images = Some(RemoteImages::Remove);
+        self
+    }
+
+    /// Replaces the URLs of the remote images in the HTML bodies of the
+    /// message with the ones returned by `proxy`.
+    pub fn proxy_remote_images(mut self, proxy: impl Fn(&str) -> String + 'x) -> Self {
+        self.remote_images = Some(RemoteImages::Proxy(Box::new(proxy)));
+        self
+    }
+
+    /// Adds a header field at the top of the message.
+    pub fn add_header(
+        mut self,
+        name: impl Into<Cow<'x, str>>,
+        value: impl Into<Cow<'x, str>>,
+    ) -> Self {
+        self.header_edits
+            .push(HeaderEdit::Add(name.into(), value.into()));
+        self
+    }
+
+    /// Replaces all the header fields with this name with a single one.
+    pub fn set_header(
+        mut self,
+        name: impl Into<Cow<'x, str>>,
+        value: impl Into<Cow<'x, str>>,
+    ) -> Self {
+        self.header_edits
+            .push(HeaderEdit::Set(name.into(), value.into()));
+        self
+    }
+
+    /// Removes all the header fields with this name.
+    pub fn remove_header(mut self, name: impl Into<Cow<'x, str>>) -> Self {
+        self.header_edits.push(HeaderEdit::Remove(name.into()));
+        self
+    }
+
+    /// Applies the steps to a message and returns the editor holding the
+    /// resulting edits, which can be extended before writing the message.
+    pub fn apply<'y>(&self, message: &'y Message<'y>) -> MessageEditor<'y> {
+        self.apply_with_report(message).0
+    }
+
+    /// Applies the steps to a message and returns the editor holding the
+    /// resulting edits along with a report of the changes made.
+    pub fn apply_with_report<'y>(
+        &self,
+        message: &'y Message<'y>,
+    ) -> (MessageEditor<'y>, PipelineReport) {
+        let mut editor = MessageEditor::new(message);
+        let mut report = PipelineReport::default();
+
+        if !self.strip_attachments.is_empty() {
+            for &part_id in &message.attachments {
+                if message.parts.get(part_id).is_some_and(|part| {
+                    self.strip_attachments
+                        .iter()
+                        .any(|predicate| predicate(part))
+                }) && editor.remove_part(part_id)
+                {
+                    report.removed_attachments.push(part_id);
+                }
+            }
+        }
+
+        if self.sanitize_html || self.remote_images.is_some() {
+            for &part_id in &message.html_body {
+                let html = match message.parts.get(part_id) {
+                    Some(MessagePart {
+                        body: PartType::Html(html),
+                        ..
+                    }) if !report.removed_attachments.contains(&part_id) => html.as_ref(),
+                    _ => continue,
+                };
+
+                let mut rewritten = if self.sanitize_html {
+                    Cow::from(sanitize_html(html))
+                } else {
+                    Cow::from(html)
+                };
+                if let Some(remote_images) = &self.remote_images {
+                    let (result, urls) =
+                        rewrite_remote_images(&rewritten, |url| match remote_images {
+                            RemoteImages::Remove => None,
+                            RemoteImages::Proxy(proxy) => Some(proxy(url)),
+                        });
+                    rewritten = result.into();
+                    report.remote_images.extend(urls);
+                }
+
+                if rewritten != html {
+                    editor.replace_part(part_id, "text/html", &rewritten);
+                    report.rewritten_html.push(part_id);
+                }
+            }
+        }
+
+        // Later edits override the earlier ones for the same field
+        let mut added: Vec<(&str, &str)> = Vec::new();
+        let mut replaced: Vec<(&str, Option<&str>)> = Vec::new();
+        for edit in &self.header_edits {
+            match edit {
+                HeaderEdit::Add(name, value) => added.push((name, value)),
+                HeaderEdit::Set(name, _) | HeaderEdit::Remove(name) => {
+                    added.retain(|(added_name, _)| !added_name.eq_ignore_ascii_case(name));
+                    replaced.retain(|(replaced_name, _)| !replaced_name.eq_ignore_ascii_case(name));
+                    replaced.push((
+                        name,
+                        match edit {
+                            HeaderEdit::Set(_, value) => Some(value),
+                            _ => None,
+                        },
+                    ));
+                }
+            }
+        }
+        for (name, value) in added {
+            editor.add_header(name, value);
+        }
+        for (name, value) in replaced {
+            if let Some(value) = value {
+                editor.set_header(name, value);
+            } else {
+                editor.remove_header(name);
+            }
+        }
+
+        (editor, report)
+    }
+
+    /// Applies the steps to a message and writes the result.
+    pub fn run_to(&self, message: &Message<'_>, output: impl io::Write) -> io::Result<()> {
+        self.apply(message).write_to(output)
+    }
+
+    /// Applies the steps to a message and returns the result.
+    pub fn run(&self, message: &Message<'_>) -> Vec<u8> {
+        self.apply(message).to_bytes()
+    }
+
+    /// Applies the steps to a message and returns the result along with a
+    /// report of the changes made.
+    pub fn run_with_report(&self, message: &Message<'_>) -> (Vec<u8>, PipelineReport) {
+        let (editor, report) = self.apply_with_report(message);
+        (editor.to_bytes(), report)
+    }
+}
+
+static RISKY_EXTENSIONS: &[&str] = &[
+    "ade",
+    "adp",
+    "app",
+    "application",
+    "appx",
+    "bat",
+    "cab",
+    "chm",
+    "cmd",
+    "com",
+    "cpl",
+    "dll",
+    "dmg",
+    "docm",
+    "exe",
+    "gadget",
+    "hta",
+    "img",
+    "inf",
+    "ins",
+    "iso",
+    "isp",
+    "jar",
+    "js",
+    "jse",
+    "lnk",
+    "mde",
+    "msc",
+    "msi",
+    "msix",
+    "msp",
+    "mst",
+    "pif",
+    "potm",
+    "ppam",
+    "ppsm",
+    "pptm",
+    "ps1",
+    "psc1",
+    "reg",
+    "scf",
+    "scr",
+    "sct",
+    "shb",
+    "shs",
+    "sys",
+    "vb",
+    "vbe",
+    "vbs",
+    "vhd",
+    "vhdx",
+    "vxd",
+    "wsc",
+    "wsf",
+    "wsh",
+    "xlam",
+    "xll",
+    "xlsm",
+];
+
+static RISKY_CONTENT_TYPES: &[&str] = &[
+    "application/hta",
+    "application/java-archive",
+    "application/javascript",
+    "application/x-dosexec",
+    "application/x-ms-installer",
+    "application/x-msdos-program",
+    "application/x-msdownload",
+    "application/x-msi",
+    "application/x-sh",
+    "text/javascript",
+];
+
+/// Returns true for the attachments that can run code when opened, based
+/// on their file extension or content type.
+pub fn is_risky_attachment(part: &MessagePart<'_>) -> bool {
+    if part.attachment_name().is_some_and(|name| {
+        name.rsplit_once('.').is_some_and(|(_, extension)| {
+            RISKY_EXTENSIONS
+                .iter()
+                .any(|risky| risky.eq_ignore_ascii_case(extension.trim_end()))
+        })
+    }) {
+        return true;
+    }
+
+    part.content_type().is_some_and(|content_type| {
+        let subtype = content_type.subtype().unwrap_or_default();
+        RISKY_CONTENT_TYPES.iter().any(|risky| {
+            risky
+                .split_once('/')
+                .is_some_and(|(risky_type, risky_subtype)| {
+                    risky_type.eq_ignore_ascii_case(content_type.ctype())
+                        && risky_subtype.eq_ignore_ascii_case(subtype)
+                })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        encoders::pipeline::{Pipeline, PipelineReport},
+        Message, MimeHeaders,
+    };
+
+    #[test]
+    fn run_pipeline() {
+        let raw_message = concat!(
+            "From: jane@example.com\r\n",
+            "Subject: Invoice\r\n",
+            "X-Mailer: Example 1.0\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/html\r\n",
+            "\r\n",
+            "<p onmouseover=\"track()\">See attached.</p><script>run()</script>\r\n",
+            "--b\r\n",
+            "Content-Type: application/pdf; name=\"invoice.pdf\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "JVBERi0xLjQ=\r\n",
+            "--b\r\n",
+            "Content-Type: application/octet-stream; name=\"invoice.exe\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "TVqQAAMAAAAEAAAA\r\n",
+            "--b--\r\n"
+        );
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+
+        // No steps, the message is written verbatim
+        assert_eq!(Pipeline::new().run(&message), raw_message.as_bytes());
+
+        let pipeline = Pipeline::new()
+            .add_header("X-Gateway", "first")
+            .strip_attachments(|part| {
+                part.attachment_name()
+                    .is_some_and(|name| name.ends_with(".exe"))
+            })
+            .sanitize_html()
+            .add_header("X-Gateway", "second")
+            .add_header("X-Scanned", "yes")
+            .set_header("Subject", "[External] Invoice")
+            .remove_header("X-Mailer")
+            .remove_header("X-Gateway")
+            .add_header("X-Gateway", "third");
+
+        assert_eq!(
+            String::from_utf8(pipeline.run(&message)).unwrap(),
+            concat!(
+                "X-Scanned: yes\r\n",
+                "X-Gateway: third\r\n",
+                "From: jane@example.com\r\n",
+                "Subject: [External] Invoice\r\n",
+                "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+                "\r\n",
+                "--b\r\n",
+                "Content-Type: text/html; charset=utf-8\r\n",
+                "Content-Transfer-Encoding: 7bit\r\n",
+                "\r\n",
+                "<p>See attached.</p>\r\n",
+                "--b\r\n",
+                "Content-Type: application/pdf; name=\"invoice.pdf\"\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "JVBERi0xLjQ=\r\n",
+                "--b--\r\n"
+            )
+        );
+
+        let mut editor = pipeline.apply(&message);
+        editor.add_header("Received", "from gateway");
+        let edited = editor.to_bytes();
+        let message = Message::parse(&edited).unwrap();
+        assert_eq!(message.attachments.len(), 1);
+        assert_eq!(message.body_html(0).unwrap(), "<p>See attached.</p>");
+        assert_eq!(message.header_raw("Received"), Some(" from gateway\r\n"));
+
+        // Headers added above a removed first header field
+        let message = Message::parse(b"Subject: Hello\nTo: john@example.com\n\nHi\n").unwrap();
+        assert_eq!(
+            String::from_utf8(
+                Pipeline::new()
+                    .add_header("X-Scanned", "yes")
+                    .remove_header("Subject")
+                    .run(&message)
+            )
+            .unwrap(),
+            "X-Scanned: yes\nTo: john@example.com\n\nHi\n"
+        );
+
+        // Header names with line breaks or colons are ignored
+        assert_eq!(
+            String::from_utf8(
+                Pipeline::new()
+                    .add_header("X-Injected: yes\nX-Other", "no")
+                    .set_header("Subject:", "Bye")
+                    .run(&message)
+            )
+            .unwrap(),
+            "Subject: Hello\nTo: john@example.com\n\nHi\n"
+        );
+    }
+
+    #[test]
+    fn safe_forward() {
+        let raw_message = concat!(
+            "From: attacker@example.com\n",
+            "Subject: Urgent\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\n",
+            "\n",
+            "--b\n",
+            "Content-Type: text/html\n",
+            "\n",
+            "<p>Open <a href=\"javascript:go()\">this</a></p>",
+            "<img src=\"https://t.example.com/open.gif\" width=1>\n",
+            "--b\n",
+            "Content-Type: application/octet-stream\n",
+            "Content-Disposition: attachment; filename=\"invoice.pdf.js\"\n",
+            "\n",
+            "alert(1)\n",
+            "--b\n",
+            "Content-Type: application/x-msdownload\n",
+            "\n",
+            "MZ\n",
+            "--b\n",
+            "Content-Type: application/pdf; name=\"invoice.pdf\"\n",
+            "\n",
+            "%PDF-1.4\n",
+            "--b--\n"
+        );
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+
+        let (forwarded, report) = Pipeline::safe_forward().run_with_report(&message);
+        assert_eq!(
+            report,
+            PipelineReport {
+                removed_attachments: vec![2, 3],
+                rewritten_html: vec![1],
+                remote_images: vec!["https://t.example.com/open.gif".to_string()],
+            }
+        );
+        let forwarded = Message::parse(&forwarded).unwrap();
+        assert_eq!(forwarded.body_html(0).unwrap(), "<p>Open <a>this</a></p>");
+        assert_eq!(forwarded.attachments.len(), 1);
+        assert_eq!(
+            forwarded.attachment(0).unwrap().attachment_name(),
+            Some("invoice.pdf")
+        );
+
+        let (proxied, report) = Pipeline::new()
+            .proxy_remote_images(|url| format!("https://proxy.example.org/{}", url.len()))
+            .run_with_report(&message);
+        assert_eq!(report.rewritten_html, [1]);
+        assert_eq!(
+            Message::parse(&proxied).unwrap().body_html(0).unwrap(),
+            concat!(
+                "<p>Open <a href=\"javascript:go()\">this</a></p>",
+                "<img src=\"https://proxy.example.org/30\" width=1>"
+            )
+        );
+    }
+}
@@ -0,0 +1,172 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+// RFC 2045, Section 6.7: encoded lines must not exceed 76 characters,
+// including the "=" of a soft line break.
+const QP_LINE_LEN: usize = 76;
+
+static HEX_CHARS: &[u8] = b"0123456789ABCDEF";
+
+/// Encodes text as quoted-printable (RFC 2045, Section 6.7), writing its
+/// line breaks, either LF or CRLF, as CRLF. Besides the bytes that can't be
+/// written literally, this encodes the spaces and tabs at the end of a line,
+/// which transports may strip, and the dots at the start of a line, which
+/// SMTP servers may mistake for the end of the data. Lines longer than 76
+/// characters are wrapped with soft line breaks, never splitting an escape
+/// sequence.
+pub fn quoted_printable_encode(input: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(input.len() + input.len() / 8);
+    let mut line_len = 0;
+    let mut pos = 0;
+
+    while let Some(&ch) = input.get(pos) {
+        if ch == b'\n' || (ch == b'\r' && input.get(pos + 1) == Some(&b'\n')) {
+            result.extend_from_slice(b"\r\n");
+            line_len = 0;
+            pos += if ch == b'\r' { 2 } else { 1 };
+            continue;
+        }
+
+        let is_line_end = matches!(input.get(pos + 1), None | Some(b'\n'))
+            || input[pos + 1..].starts_with(b"\r\n");
+        let is_encoded = match ch {
+            b'=' => true,
+            b' ' | b'\t' => is_line_end,
+            b'.' => line_len == 0,
+            0x21..=0x7e => false,
+            _ => true,
+        };
+        let len = if is_encoded { 3 } else { 1 };
+
+        // Leave room for the "=" of a soft line break, unless this is the
+        // last character of the line
+        if line_len + len > QP_LINE_LEN - 1 && !(is_line_end && line_len + len <= QP_LINE_LEN) {
+            result.extend_from_slice(b"=\r\n");
+            line_len = 0;
+            continue;
+        }
+
+        if is_encoded {
+            result.push(b'=');
+            result.push(HEX_CHARS[(ch >> 4) as usize]);
+            result.push(HEX_CHARS[(ch & 0x0f) as usize]);
+        } else {
+            result.push(ch);
+        }
+        line_len += len;
+        pos += 1;
+    }
+
+    result
+}
+
+/// Returns `true` when text is shorter encoded as quoted-printable than
+/// as base64, which is the case for text mostly made of ASCII characters.
+pub fn prefers_quoted_printable(input: &[u8]) -> bool {
+    let encoded = input
+        .iter()
+        .filter(|&&ch| ch == b'=' || (ch >= 0x7f) || (ch < 0x20 && !b"\t\r\n".contains(&ch)))
+        .count();
+    input.len() + 2 * encoded <= input.len().div_ceil(3) * 4
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decoders::quoted_printable::quoted_printable_decode;
+
+    use super::{prefers_quoted_printable, quoted_printable_encode};
+
+    #[test]
+    fn encode_quoted_printable() {
+        for (input, expected_result) in [
+            ("", ""),
+            ("hello world", "hello world"),
+            ("a=b", "a=3Db"),
+            ("Café", "Caf=C3=A9"),
+            ("trailing \nspaces\t\r\nend ", "trailing=20\r\nspaces=09\r\nend=20"),
+            ("inner spaces \t ok", "inner spaces \t ok"),
+            (".\n..dots\nmid.dle", "=2E\r\n=2E.dots\r\nmid.dle"),
+            ("bare\rcr", "bare=0Dcr"),
+            (
+                concat!(
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do ",
+                    "eiusmod tempor incididunt ut labore et dolore magna aliqua."
+                ),
+                concat!(
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tem=\r\n",
+                    "por incididunt ut labore et dolore magna aliqua."
+                ),
+            ),
+            (
+                concat!(
+                    "0123456789012345678901234567890123456789012345678901234567890123456789",
+                    "01234é"
+                ),
+                concat!(
+                    "0123456789012345678901234567890123456789012345678901234567890123456789",
+                    "01234=\r\n=C3=A9"
+                ),
+            ),
+            (
+                "012345678901234567890123456789012345678901234567890123456789012345678901234",
+                "012345678901234567890123456789012345678901234567890123456789012345678901234",
+            ),
+            (
+                "0123456789012345678901234567890123456789012345678901234567890123456789012345",
+                "0123456789012345678901234567890123456789012345678901234567890123456789012345",
+            ),
+            (
+                "012345678901234567890123456789012345678901234567890123456789012345678901234.",
+                "012345678901234567890123456789012345678901234567890123456789012345678901234.",
+            ),
+            (
+                "012345678901234567890123456789012345678901234567890123456789012345678901234.x",
+                "012345678901234567890123456789012345678901234567890123456789012345678901234=\r\n=2Ex",
+            ),
+            (
+                "0123456789012345678901234567890123456789012345678901234567890123456789012345.",
+                "012345678901234567890123456789012345678901234567890123456789012345678901234=\r\n5.",
+            ),
+            (
+                "012345678901234567890123456789012345678901234567890123456789012345678901234\n.",
+                "012345678901234567890123456789012345678901234567890123456789012345678901234\r\n=2E",
+            ),
+        ] {
+            let result = quoted_printable_encode(input.as_bytes());
+            assert_eq!(
+                std::str::from_utf8(&result).unwrap(),
+                expected_result,
+                "{:?}",
+                input
+            );
+            assert!(result
+                .split(|&ch| ch == b'\n')
+                .all(|line| line.len() <= 77));
+            assert_eq!(
+                quoted_printable_decode(&result).unwrap(),
+                input.replace("\r\n", "\n").as_bytes(),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn select_quoted_printable() {
+        assert!(prefers_quoted_printable(b"Hello, world"));
+        assert!(prefers_quoted_printable(
+            "Le café est prêt, venez le boire avant qu'il refroidisse.".as_bytes()
+        ));
+        assert!(!prefers_quoted_printable("Café crème brûlée".as_bytes()));
+        assert!(!prefers_quoted_printable("Привет, мир".as_bytes()));
+        assert!(!prefers_quoted_printable("你好世界".as_bytes()));
+    }
+}
@@ -0,0 +1,235 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+};
+
+use crate::DateTime;
+
+use super::FOLD_LINE_LEN;
+
+/// A Received trace field, RFC 5321 Section 4.4, to be added at the top of
+/// a message by a relay.
+///
+/// The clauses are written in the order from, by, via, with, id and for,
+/// followed by the date. Lines are folded between clauses when they would
+/// exceed 78 characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Received<'x> {
+    from: Option<Cow<'x, str>>,
+    from_info: Option<Cow<'x, str>>,
+    by: Cow<'x, str>,
+    via: Option<Cow<'x, str>>,
+    with: Option<Cow<'x, str>>,
+    id: Option<Cow<'x, str>>,
+    for_address: Option<Cow<'x, str>>,
+    date: DateTime,
+}
+
+impl<'x> Received<'x> {
+    /// Creates a Received field for the host receiving the message and the
+    /// time it was received.
+    pub fn new(by: impl Into<Cow<'x, str>>, date: DateTime) -> Self {
+        Received {
+            from: None,
+            from_info: None,
+            by: by.into(),
+            via: None,
+            with: None,
+            id: None,
+            for_address: None,
+            date,
+        }
+    }
+
+    /// Sets the name the client gave in its EHLO or HELO command.
+    pub fn from(mut self, helo: impl Into<Cow<'x, str>>) -> Self {
+        self.from = Some(helo.into());
+        self
+    }
+
+    /// Sets the TCP information written as a comment after the from clause,
+    /// usually the reverse DNS name and the address of the client such as
+    /// `mail.example.com [192.0.2.1]`.
+    pub fn from_info(mut self, info: impl Into<Cow<'x, str>>) -> Self {
+        self.from_info = Some(info.into());
+        self
+    }
+
+    /// Sets the link used, such as `TCP`.
+    pub fn via(mut self, link: impl Into<Cow<'x, str>>) -> Self {
+        self.via = Some(link.into());
+        self
+    }
+
+    /// Sets the protocol used, such as `ESMTP` or `ESMTPS`.
+    pub fn with(mut self, protocol: impl Into<Cow<'x, str>>) -> Self {
+        self.with = Some(protocol.into());
+        self
+    }
+
+    /// Sets the queue identifier assigned to the message.
+    pub fn id(mut self, id: impl Into<Cow<'x, str>>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the recipient the message was received for, written enclosed
+    /// in angle brackets.
+    pub fn for_address(mut self, address: impl Into<Cow<'x, str>>) -> Self {
+        self.for_address = Some(address.into());
+        self
+    }
+
+    /// Returns the header field, including the trailing line ending.
+    pub fn to_header(&self) -> String {
+        self.format("\r\n")
+    }
+
+    /// Writes the header field followed by the raw message, which is copied
+    /// verbatim. The field uses the same line endings as the message.
+    pub fn write_to(&self, raw_message: &[u8], mut output: impl Write) -> io::Result<()> {
+        let line_ending = match raw_message.iter().position(|&ch| ch == b'\n') {
+            Some(pos) if pos == 0 || raw_message[pos - 1] != b'\r' => "\n",
+            _ => "\r\n",
+        };
+        output.write_all(self.format(line_ending).as_bytes())?;
+        output.write_all(raw_message)
+    }
+
+    /// Returns the raw message with the header field added at the top.
+    pub fn prepend_to(&self, raw_message: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(raw_message.len() + 256);
+        self.write_to(raw_message, &mut output).ok();
+        output
+    }
+
+    fn format(&self, line_ending: &str) -> String {
+        let mut clauses = Vec::with_capacity(7);
+        if let Some(from) = &self.from {
+            clauses.push(format!("from {}", clause_value(from)));
+        }
+        if let Some(info) = &self.from_info {
+            // Parentheses would end or nest the comment
+            clauses.push(format!(
+                "({})",
+                clause_value(info).replace(['(', ')', '\\'], "")
+            ));
+        }
+        clauses.push(format!("by {}", clause_value(&self.by)));
+        for (name, value) in [("via", &self.via), ("with", &self.with), ("id", &self.id)] {
+            if let Some(value) = value {
+                clauses.push(format!("{name} {}", clause_value(value)));
+            }
+        }
+        if let Some(address) = &self.for_address {
+            clauses.push(format!(
+                "for <{}>",
+                clause_value(address.trim_start_matches('<').trim_end_matches('>'))
+            ));
+        }
+        if let Some(last) = clauses.last_mut() {
+            last.push(';');
+        }
+        clauses.push(self.date.to_rfc822());
+
+        let mut header = String::from("Received:");
+        let mut line_len = header.len();
+        for clause in clauses {
+            if line_len + clause.len() + 1 > FOLD_LINE_LEN && line_len > 0 {
+                header.push_str(line_ending);
+                line_len = 0;
+            }
+            header.push(' ');
+            header.push_str(&clause);
+            line_len += clause.len() + 1;
+        }
+        header.push_str(line_ending);
+        header
+    }
+}
+
+// Clause values are single words, any whitespace or control character
+// would break the field or allow injecting others.
+fn clause_value(value: &str) -> Cow<'_, str> {
+    if value
+        .chars()
+        .any(|ch| ch.is_whitespace() || ch.is_control())
+    {
+        value
+            .split(|ch: char| ch.is_whitespace() || ch.is_control())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+            .into()
+    } else {
+        value.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{encoders::trace::Received, DateTime, HeaderValue, Message};
+
+    #[test]
+    fn prepend_received() {
+        let date = DateTime::parse_rfc822("Tue, 1 Jul 2003 10:52:37 +0200").unwrap();
+        let received = Received::new("mx.example.org", date.clone())
+            .from("client.example.com")
+            .from_info("mail.example.com [192.0.2.1]")
+            .with("ESMTPS")
+            .id("4Bq3nT1kZ2z9")
+            .for_address("<john@example.org>");
+        assert_eq!(
+            received.to_header(),
+            concat!(
+                "Received: from client.example.com (mail.example.com [192.0.2.1])\r\n",
+                " by mx.example.org with ESMTPS id 4Bq3nT1kZ2z9 for <john@example.org>;\r\n",
+                " Tue, 1 Jul 2003 10:52:37 +0200\r\n"
+            )
+        );
+
+        // The raw message is copied verbatim, using its line endings
+        for raw_message in [
+            &b"Subject: Hi\r\nFrom: jane@example.com\r\n\r\nHello\r\n"[..],
+            &b"Subject: Hi\nFrom: jane@example.com\n\nHello\n"[..],
+        ] {
+            let output = received.prepend_to(raw_message);
+            assert!(output.ends_with(raw_message));
+            let header = &output[..output.len() - raw_message.len()];
+            assert_eq!(
+                header.windows(2).any(|w| w == b"\r\n"),
+                raw_message.windows(2).any(|w| w == b"\r\n")
+            );
+
+            let message = Message::parse(&output).unwrap();
+            assert_eq!(message.subject(), Some("Hi"));
+            assert_eq!(message.body_text(0).unwrap().trim_end(), "Hello");
+            assert!(matches!(
+                message.received(),
+                HeaderValue::Text(text) if text.contains("for <john@example.org>")
+            ));
+        }
+
+        // Line breaks in clause values can't add other fields
+        let received =
+            Received::new("mx.example.org\r\nBcc: evil@example.com", date).from_info("a) (b");
+        assert_eq!(
+            received.to_header(),
+            concat!(
+                "Received: (a b) by mx.example.org Bcc: evil@example.com;\r\n",
+                " Tue, 1 Jul 2003 10:52:37 +0200\r\n"
+            )
+        );
+    }
+}
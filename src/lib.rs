@@ -14,7 +14,7 @@
 //! _mail-parser_ is an **e-mail parsing library** written in Rust that fully conforms to the Internet Message Format standard (_RFC 5322_), the
 //! Multipurpose Internet Mail Extensions (MIME; _RFC 2045 - 2049_) as well as many other [internet messaging RFCs](#conformed-rfcs).
 //!
-//! It also supports decoding messages in [41 different character sets](#supported-character-sets) including obsolete formats such as UTF-7.
+//! It also supports decoding messages in [44 different character sets](#supported-character-sets) including obsolete formats such as UTF-7.
 //! All Unicode (UTF-*) and single-byte character sets are handled internally by the library while support for legacy multi-byte encodings of Chinese
 //! and Japanese languages such as BIG5 or ISO-2022-JP is provided by the optional dependency [encoding_rs](https://crates.io/crates/encoding_rs).
 //!
@@ -54,12 +54,13 @@
 //! - [RFC 2392 - Content-ID and Message-ID Uniform Resource Locators](https://datatracker.ietf.org/doc/html/rfc2392)
 //! - [RFC 3282 - Content Language Headers](https://datatracker.ietf.org/doc/html/rfc3282)
 //! - [RFC 6532 - Internationalized Email Headers](https://datatracker.ietf.org/doc/html/rfc6532)
+//! - [RFC 6533 - Internationalized Delivery Status and Disposition Notifications](https://datatracker.ietf.org/doc/html/rfc6533)
 //! - [RFC 2152 - UTF-7 - A Mail-Safe Transformation Format of Unicode](https://datatracker.ietf.org/doc/html/rfc2152)
 //! - [RFC 2369 - The Use of URLs as Meta-Syntax for Core Mail List Commands and their Transport through Message Header Fields](https://datatracker.ietf.org/doc/html/rfc2369)
 //! - [RFC 2919 - List-Id: A Structured Field and Namespace for the Identification of Mailing Lists](https://datatracker.ietf.org/doc/html/rfc2919)
 //! - [RFC 3339 - Date and Time on the Internet: Timestamps](https://datatracker.ietf.org/doc/html/rfc3339)
 //! - [RFC 8621 - The JSON Meta Application Protocol (JMAP) for Mail (Section 4.1.4)](https://datatracker.ietf.org/doc/html/rfc8621#section-4.1.4)
-//! - [RFC 5957 - Internet Message Access Protocol - SORT and THREAD Extensions (Section 2.1)](https://datatracker.ietf.org/doc/html/rfc5256#section-2.1)
+//! - [RFC 5256 - Internet Message Access Protocol - SORT and THREAD Extensions (Sections 2.1 and 2.2)](https://datatracker.ietf.org/doc/html/rfc5256)
 //!
 //! ## Supported Character Sets
 //!
@@ -105,9 +106,18 @@
 //! - GB18030
 //! - GBK
 //! - ISO-2022-JP
+//! - ISO-2022-JP-1, ISO-2022-JP-2, ISO-2022-JP-3
+//! - ISO-2022-KR
+//! - ISO-2022-CN (GB 2312 only, CNS 11643 is not supported)
 //! - WINDOWS-874
 //! - IBM-866
 //!
+//! When encoding_rs is enabled, charsets missing from the lists above are
+//! looked up in its label registry, which covers all the encodings of the
+//! [WHATWG Encoding Standard](https://encoding.spec.whatwg.org/) and their
+//! aliases, such as `cp1250`, `windows-31j`, `x-mac-cyrillic` or
+//! `big5-hkscs`.
+//!
 //! ## Usage Example
 //!
 //! ```
@@ -208,7 +218,7 @@
 //!        message.body_text(0).unwrap(),
 //!        concat!(
 //!            "I was thinking about quitting the “exporting” to focus just on the",
-//!            " “importing”,\nbut then I thought, why not do both? ☺\n"
+//!            " “importing”,\n\nbut then I thought, why not do both? ☺"
 //!        )
 //!    );
 //!
@@ -249,26 +259,59 @@
 //!    println!("{}", serde_yaml::to_string(&message).unwrap());
 //!```
 pub mod decoders;
+pub mod encoders;
 pub mod mailbox;
 pub mod parsers;
+pub mod thread;
 
 use std::{
     borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
     convert::TryInto,
     fmt::{self, Display},
-    hash::Hash,
+    hash::{Hash, Hasher},
+    net::{Ipv4Addr, Ipv6Addr},
+    ops::Range,
+    sync::Arc,
 };
 
-use decoders::html::{html_to_text, text_to_html};
+use decoders::html::{html_to_markdown, html_to_text, rewrite_cid_urls, text_to_html};
 use parsers::{
-    fields::thread::thread_name,
+    fields::{
+        address::{
+            parse_address_domain, parse_address_local_part, parse_address_normalized,
+            unquote_local_part, validate_rfc5321,
+        },
+        content_type::{parse_raw_parameters, sanitize_file_name},
+        thread::thread_name,
+    },
     preview::{preview_html, preview_text},
+    render::strip_document,
     MessageStream,
 };
 #[cfg(feature = "serde_support")]
 use serde::{Deserialize, Serialize};
 
 /// An RFC5322/RFC822 message.
+///
+/// With the `serde_support` feature, a message can be serialized and later
+/// deserialized into a `Message<'static>` that owns all its data, for
+/// example to cache parsed messages. Field and variant names are part of
+/// the stable serialized format. The raw message and the transfer encoding
+/// of the parts are not serialized, so methods that read the raw message,
+/// such as [`Message::raw_bytes`], find no data on a deserialized
+/// message.
+///
+/// ```
+/// use mail_parser::Message;
+///
+/// let message = Message::parse(b"Subject: Hello\r\n\r\nHello, world!").unwrap();
+/// let json = serde_json::to_vec(&message).unwrap();
+///
+/// let cached: Message<'static> = serde_json::from_reader(json.as_slice()).unwrap();
+/// assert_eq!(cached.subject(), Some("Hello"));
+/// assert_eq!(cached.body_text(0).unwrap(), "Hello, world!");
+/// ```
 #[derive(Debug, Default, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Message<'x> {
@@ -280,11 +323,878 @@ pub struct Message<'x> {
     pub attachments: Vec<MessagePartId>,
 
     #[cfg_attr(feature = "serde_support", serde(default))]
-    #[cfg_attr(feature = "serde_support", serde(borrow))]
     pub parts: Vec<MessagePart<'x>>,
 
     #[cfg_attr(feature = "serde_support", serde(skip))]
     pub raw_message: Cow<'x, [u8]>,
+
+    /// Non-fatal problems found while parsing the message.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// A non-fatal problem found while parsing a message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum ParseWarning {
+    /// The boundary of a multipart part is equal to or a prefix of the
+    /// boundary of an enclosing multipart, or the other way around.
+    /// Delimiters are assigned to the innermost multipart with a matching
+    /// boundary, and a delimiter is only matched when it is followed by
+    /// the end of the line or "--".
+    BoundaryCollision {
+        /// Offset of the multipart part headers in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+    },
+    /// The part lacks a Content-Transfer-Encoding header but its contents
+    /// were detected and decoded as base64 or quoted-printable.
+    DetectedEncoding {
+        /// Offset of the part body in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+        encoding: Encoding,
+    },
+    /// The part would contain parts nested beyond the maximum depth set in
+    /// [`ParseOptions::max_depth`] and was returned as a binary attachment.
+    MaxDepthExceeded {
+        /// Offset of the part headers in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+    },
+    /// The maximum number of parts set in [`ParseOptions::max_parts`] was
+    /// reached, the remaining contents were not parsed.
+    MaxPartsExceeded {
+        /// Offset of the first part not parsed in the raw message.
+        offset: usize,
+    },
+    /// A header field value longer than [`ParseOptions::max_header_len`]
+    /// was truncated before being parsed.
+    HeaderTruncated {
+        /// Offset of the header field value in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+        /// Length of the header field value before truncation.
+        length: usize,
+    },
+    /// The header section of the part ended with a line made only of
+    /// whitespace rather than an empty line.
+    WhitespaceHeaderSeparator {
+        /// Offset of the whitespace line in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+    },
+    /// The header section of the part was not followed by an empty line,
+    /// the body was assumed to start at the first line that is not a
+    /// header field.
+    MissingHeaderSeparator {
+        /// Offset of the part body in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+    },
+    /// The header section of the part starts with a folded line, which has
+    /// no field to continue. The line was parsed as a new field.
+    InvalidHeaderFolding {
+        /// Offset of the folded line in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+    },
+    /// The multipart part was not closed by its final delimiter before the
+    /// end of the message or of its enclosing multipart.
+    MissingBoundaryTerminator {
+        /// Offset where the part was closed in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+    },
+    /// The Content-Transfer-Encoding of the part is not one of those
+    /// defined in RFC 2045. The contents were returned undecoded, as a
+    /// binary attachment when [`ParseOptions::unknown_encoding_as_binary`]
+    /// is enabled.
+    UnknownTransferEncoding {
+        /// Offset of the header field value in the raw message.
+        offset: usize,
+        part_id: MessagePartId,
+        encoding: String,
+    },
+    /// The text part declares a charset not supported by this library. It
+    /// was decoded with [`ParseOptions::charset_fallback`] when set, or as
+    /// UTF-8 otherwise.
+    UnknownCharset {
+        /// Offset of the Content-Type header field value in the raw
+        /// message.
+        offset: usize,
+        part_id: MessagePartId,
+        charset: String,
+    },
+    /// The contents of the part could not be decoded without loss.
+    DecodeError {
+        part_id: MessagePartId,
+        error: DecodeError,
+    },
+    /// The [`PartScanner`] set in [`ParseOptions::part_scanner`] stopped
+    /// parsing after scanning the part, the remaining contents were not
+    /// parsed.
+    ScanStopped {
+        part_id: MessagePartId,
+        /// Reason returned by the scanner.
+        reason: String,
+    },
+}
+
+/// A problem found while decoding the contents of a part.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum DecodeError {
+    /// The part contains invalid base64 data and was returned undecoded.
+    InvalidBase64 {
+        /// Offset of the first invalid byte in the raw message, or of the
+        /// end of the part when the part was truncated.
+        offset: usize,
+    },
+    /// The part contains an invalid quoted-printable sequence and was
+    /// returned undecoded.
+    InvalidQuotedPrintable {
+        /// Offset of the first invalid sequence in the raw message, or of
+        /// the end of the part when the part was truncated.
+        offset: usize,
+    },
+    /// Characters that could not be converted from the part charset were
+    /// replaced with U+FFFD.
+    CharsetReplacement {
+        /// Number of replacement characters inserted.
+        count: usize,
+        /// Number of bytes of the original contents that were replaced.
+        bytes: usize,
+    },
+}
+
+/// A compact summary of a message, for building mailbox list views.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct MessageSummary<'x> {
+    /// First address of the From header field.
+    pub from: Option<&'x Addr<'x>>,
+    /// Number of addresses in the To header field.
+    pub to_count: usize,
+    pub subject: Option<&'x str>,
+    pub date: Option<&'x DateTime>,
+    pub message_id: Option<&'x str>,
+    /// Thread keys: base subject, In-Reply-To and References message ids.
+    pub thread_name: Option<&'x str>,
+    pub in_reply_to: Vec<&'x str>,
+    pub references: Vec<&'x str>,
+    /// `true` if at least one part is not inline or has a file name.
+    pub has_attachments: bool,
+    pub preview: Option<Cow<'x, str>>,
+    /// Size of the raw message in bytes.
+    pub size: usize,
+}
+
+/// The Resent-* header fields added each time a message was reintroduced
+/// into the transport system, as described in RFC 5322 (Section 3.6.6).
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ResentBlock<'x> {
+    pub date: Option<&'x DateTime>,
+    pub from: Option<&'x HeaderValue<'x>>,
+    pub sender: Option<&'x HeaderValue<'x>>,
+    pub to: Option<&'x HeaderValue<'x>>,
+    pub cc: Option<&'x HeaderValue<'x>>,
+    pub bcc: Option<&'x HeaderValue<'x>>,
+    pub message_id: Option<&'x str>,
+}
+
+/// An originator header field whose value differs between a message and the
+/// original message it forwards or returns, see
+/// [`Message::originator_diff`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct HeaderDiff<'x> {
+    /// The From, Date, Message-ID or Subject header field.
+    pub header: RfcHeader,
+    /// The value in the outer message, or `None` when missing.
+    pub outer: Option<&'x HeaderValue<'x>>,
+    /// The value in the original message, or `None` when missing.
+    pub inner: Option<&'x HeaderValue<'x>>,
+}
+
+/// Signals commonly used by spam and abuse classifiers, extracted from a
+/// message by [`Message::features`].
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct MessageFeatures {
+    /// Size in bytes of the plain text body parts.
+    pub text_size: usize,
+    /// Size in bytes of the HTML body parts.
+    pub html_size: usize,
+    /// Fraction of the body size that is HTML, from 0 for plain text only
+    /// to 1 for HTML only.
+    pub html_ratio: f64,
+    /// Number of URLs in the body parts, see [`Message::extract_urls`].
+    pub url_count: usize,
+    /// Number of distinct host names in the URLs of the body parts.
+    pub domain_count: usize,
+    /// Number of images of the HTML body parts loaded from remote servers.
+    pub remote_image_count: usize,
+    pub attachment_count: usize,
+    /// Number of attachments that can run code when opened, see
+    /// [`is_risky_attachment`](encoders::pipeline::is_risky_attachment).
+    pub risky_attachment_count: usize,
+    /// `true` when a Reply-To address has a domain other than that of the
+    /// From address.
+    pub reply_to_mismatch: bool,
+    /// The From, To, Date, Message-ID and Subject header fields missing
+    /// from the message.
+    pub missing_headers: Vec<RfcHeader>,
+}
+
+/// The header fields a [`PartitionKey`] can be derived from, see
+/// [`Message::partition_key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PartitionSource {
+    /// The domain of the first From address, in lowercase and without a
+    /// trailing dot.
+    SenderDomain,
+    /// The list identifier in the List-ID header field, in lowercase.
+    ListId,
+    /// The message id of the first message of the conversation, taken from
+    /// the References, In-Reply-To or Message-ID header fields.
+    Thread,
+}
+
+/// A stable key for distributing the processing of messages across
+/// workers, returned by [`Message::partition_key`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PartitionKey {
+    /// The header fields the key was derived from.
+    pub source: PartitionSource,
+    /// The normalized key.
+    pub value: String,
+}
+
+/// A URL found in a body part by [`Message::extract_urls`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyUrl {
+    /// The URL, with the HTML character references decoded.
+    pub url: String,
+    pub kind: UrlKind,
+    /// The part the URL was found in.
+    pub part_id: MessagePartId,
+    /// The anchor text of a link or the alternative text of an image.
+    pub text: Option<String>,
+}
+
+/// Where a URL was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UrlKind {
+    /// The target of an HTML link.
+    Link,
+    /// The source of an HTML image.
+    Image,
+    /// A URL in a plain text part.
+    Text,
+}
+
+/// Resource bounds enforced by [`Message::validate`]. Messages exceeding
+/// any of them are reported as unparseable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationLimits {
+    /// Maximum size of the raw message in bytes.
+    pub max_size: Option<usize>,
+    /// Maximum size of the message header in bytes, including the blank line
+    /// that ends it.
+    pub max_header_size: Option<usize>,
+    /// Maximum nesting depth of multipart and message/rfc822 parts, see
+    /// [`ParseOptions::max_depth`].
+    pub max_depth: Option<usize>,
+    /// Maximum number of parts, including the parts of nested messages.
+    pub max_parts: Option<usize>,
+}
+
+/// Outcome of [`Message::validate`], along with some metadata of the
+/// message for logging the decision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validation {
+    pub verdict: Verdict,
+    /// Size of the raw message in bytes.
+    pub size: usize,
+    /// Size of the message header in bytes, including the blank line that
+    /// ends it.
+    pub header_size: usize,
+    /// Number of parts, including the parts of nested messages.
+    pub part_count: usize,
+    pub message_id: Option<String>,
+    /// First address of the From header field.
+    pub from: Option<String>,
+}
+
+/// Whether a message can be accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The message was parsed without problems.
+    Parseable,
+    /// The message was parsed but some of its parts are malformed, as
+    /// described by the warnings, including those of nested messages.
+    Recoverable(Vec<ParseWarning>),
+    /// The message has no header fields or exceeds one of the limits.
+    Unparseable(ValidationError),
+}
+
+/// The reason a message is unparseable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// No header fields were found.
+    NoHeaders,
+    /// The raw message exceeds [`ValidationLimits::max_size`].
+    TooLarge,
+    /// The header exceeds [`ValidationLimits::max_header_size`].
+    HeaderTooLarge,
+    /// The parts are nested beyond [`ValidationLimits::max_depth`].
+    TooDeep {
+        /// Offset of the part headers in the raw message.
+        offset: usize,
+    },
+    /// The message has more than [`ValidationLimits::max_parts`] parts.
+    TooManyParts {
+        /// Offset of the first part not parsed in the raw message.
+        offset: usize,
+    },
+}
+
+/// A problem found by [`Message::lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    pub severity: LintSeverity,
+    /// The part the problem was found in, the root part for the problems
+    /// of the message header.
+    pub part_id: MessagePartId,
+    pub kind: LintKind,
+}
+
+/// How serious a [`LintFinding`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintSeverity {
+    /// The message does not follow the RFC rules and may be rejected or
+    /// displayed incorrectly.
+    Error,
+    /// The message is valid but does not follow common practice, which
+    /// may affect its deliverability.
+    Warning,
+}
+
+/// The kind of problem found by [`Message::lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// The message has no Date header field, required by RFC 5322.
+    MissingDate,
+    /// The Date header field could not be parsed.
+    InvalidDate,
+    /// The message has no From header field, required by RFC 5322.
+    MissingFrom,
+    /// The message has no Message-ID header field.
+    MissingMessageId,
+    /// The message uses MIME without a MIME-Version header field.
+    MissingMimeVersion,
+    /// A multipart boundary is empty, longer than 70 characters or has
+    /// characters not allowed by RFC 2046.
+    InvalidBoundary { boundary: String },
+    /// A line exceeds the 998 characters allowed by RFC 5322, excluding
+    /// the line ending.
+    LineTooLong {
+        /// Offset of the line in the raw message.
+        offset: usize,
+        length: usize,
+    },
+    /// The message has an HTML body without a plain text alternative.
+    MissingTextAlternative,
+    /// A part declared as 7bit, explicitly or by omitting its
+    /// Content-Transfer-Encoding, contains 8-bit or NUL bytes.
+    Unencoded8Bit {
+        /// Offset of the first such byte in the raw message.
+        offset: usize,
+    },
+}
+
+/// Attachment rules checked by [`Message::attachment_policy_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttachmentLimits {
+    /// Maximum number of attachments.
+    pub max_count: Option<usize>,
+    /// Maximum decoded size of an attachment in bytes.
+    pub max_size: Option<usize>,
+    /// Maximum decoded size of all the attachments in bytes.
+    pub max_total_size: Option<usize>,
+    /// File name extensions of the rejected attachments, without the
+    /// leading dot.
+    pub blocked_extensions: Vec<String>,
+    /// File name extensions of the only accepted attachments, without the
+    /// leading dot, or any when empty.
+    pub allowed_extensions: Vec<String>,
+}
+
+/// Outcome of [`Message::attachment_policy_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttachmentReport {
+    /// Number of attachments of the message.
+    pub count: usize,
+    /// Decoded size of all the attachments in bytes.
+    pub total_size: usize,
+    /// The rules broken by the message, empty when it is accepted.
+    pub violations: Vec<AttachmentViolation>,
+}
+
+/// A rule of [`AttachmentLimits`] broken by a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachmentViolation {
+    /// The message has more than [`AttachmentLimits::max_count`]
+    /// attachments.
+    TooMany { count: usize },
+    /// The attachment is larger than [`AttachmentLimits::max_size`].
+    TooLarge { part_id: MessagePartId, size: usize },
+    /// The attachments are larger than [`AttachmentLimits::max_total_size`].
+    TotalTooLarge { size: usize },
+    /// The file name extension of the attachment is blocked or not in the
+    /// allowed extensions. For attachments of nested messages, the part is
+    /// the nested message.
+    Extension {
+        part_id: MessagePartId,
+        file_name: String,
+    },
+}
+
+/// Options that control how a message is parsed.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Decode the uuencoded blocks found inside text/plain parts and add
+    /// them to the message as binary attachments.
+    pub decode_uuencode: bool,
+    /// Decoder invoked for the text parts declaring a charset not supported
+    /// by this library. See [`ParseOptions::charset_fallback`].
+    pub charset_fallback: Option<CharsetFallback>,
+    /// Options used to parse nested message/rfc822 parts.
+    pub nested_messages: NestedOptions,
+    /// Detect and decode base64 or quoted-printable contents in parts
+    /// lacking a Content-Transfer-Encoding header.
+    pub detect_encoding: bool,
+    /// Detect the charset of the text parts lacking a charset label or
+    /// whose label produces replacement characters, see
+    /// [`detect_charset`](decoders::charsets::detect::detect_charset).
+    pub detect_charset: bool,
+    /// Corrections applied to the Content-Type of the parts with a file
+    /// name, see [`ParseOptions::correct_mime_type`].
+    pub mime_type_corrections: Vec<MimeTypeCorrection>,
+    /// Content types of the parts returned without decoding, see
+    /// [`ParseOptions::skip_content_type`].
+    pub skipped_content_types: Vec<String>,
+    /// Content types of the only parts decoded, or all of them when empty,
+    /// see [`ParseOptions::decode_content_type`].
+    pub decoded_content_types: Vec<String>,
+    /// Maximum nesting depth of multipart and message/rfc822 parts, where
+    /// the root part has depth 0. Parts that would contain parts beyond this
+    /// depth are returned as binary attachments instead.
+    pub max_depth: Option<usize>,
+    /// Maximum number of parts to parse, including the parts of nested
+    /// messages. The contents following the last parsed part are ignored.
+    pub max_parts: Option<usize>,
+    /// Maximum length in bytes of a header field value. Longer values are
+    /// truncated without splitting a UTF-8 sequence or an encoded word, and
+    /// the remainder of the field is skipped.
+    pub max_header_len: Option<usize>,
+    /// Decode the transfer encoding of binary parts. When disabled, the
+    /// contents of binary parts are returned as found in the raw message,
+    /// see [`MessagePart::encoding`].
+    pub decode_attachments: bool,
+    /// Add the HTML parts to the text body and the other way around when a
+    /// message lacks one of the alternatives.
+    pub generate_alternatives: bool,
+    /// Treat the parts with an unknown Content-Transfer-Encoding as binary
+    /// attachments rather than attempting to parse them.
+    pub unknown_encoding_as_binary: bool,
+    /// Parse the Date and Resent-Date header fields on a best-effort basis,
+    /// see [`DateTime::parse_lenient`].
+    pub lenient_dates: bool,
+    /// Collect the [`ContentStatistics`] of each part while decoding it.
+    pub content_statistics: bool,
+    /// Keep the preamble and epilogue of multipart parts, see
+    /// [`ParseOptions::preserve_preamble`].
+    pub preserve_preamble: bool,
+    /// Charset of the RFC 2047 encoded words declaring an unknown charset,
+    /// see [`ParseOptions::encoded_word_charset`].
+    pub encoded_word_charset: Option<String>,
+    /// Scanner invoked with each part once decoded, see
+    /// [`ParseOptions::part_scanner`].
+    pub part_scanner: Option<Arc<dyn PartScanner>>,
+    /// Maximum size in bytes of a compressed message once decompressed by
+    /// [`Message::parse_from_reader`], 64 MiB by default.
+    pub max_decompressed_size: usize,
+}
+
+/// Replaces the declared MIME type of a part having a file name with the
+/// provided extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MimeTypeCorrection {
+    /// Declared type, such as "application/octet-stream", or "*" to match
+    /// any type.
+    pub declared: String,
+    /// File name extension, without the leading dot.
+    pub extension: String,
+    /// The type to use instead, such as "application/pdf".
+    pub effective: String,
+}
+
+/// Options used to parse the messages nested inside message/rfc822 parts.
+#[derive(Debug, Default, Clone)]
+pub enum NestedOptions {
+    /// Use the same options as the parent message.
+    #[default]
+    Inherit,
+    /// Use the default options, ignoring the ones set on the parent message.
+    Default,
+    /// Use a different set of options, which also apply to the messages
+    /// nested inside the nested message unless they specify otherwise.
+    Custom(Box<ParseOptions>),
+}
+
+/// Application provided decoder for unsupported charsets.
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub struct CharsetFallback(pub Arc<dyn Fn(&str, &[u8]) -> Option<String> + Send + Sync>);
+
+/// Application provided inspection of the parts of a message while it is
+/// parsed, such as a virus or content scanner.
+pub trait PartScanner: Send + Sync {
+    /// Receives each part, other than multipart and message/rfc822 parts,
+    /// along with its ID, once its contents are decoded. The part headers
+    /// and contents are available through the [`MimeHeaders`] methods and
+    /// [`MessagePart::contents`].
+    fn scan(&self, part_id: MessagePartId, part: &MessagePart<'_>) -> ScanVerdict;
+}
+
+/// The result of scanning a part with a [`PartScanner`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    /// Continue parsing the message.
+    Continue,
+    /// Stop parsing the message for the provided reason, which is returned
+    /// in [`ParseWarning::ScanStopped`].
+    Stop(String),
+}
+
+impl ParseOptions {
+    pub const fn new() -> Self {
+        ParseOptions {
+            decode_uuencode: false,
+            charset_fallback: None,
+            nested_messages: NestedOptions::Inherit,
+            detect_encoding: false,
+            detect_charset: false,
+            mime_type_corrections: Vec::new(),
+            skipped_content_types: Vec::new(),
+            decoded_content_types: Vec::new(),
+            max_depth: None,
+            max_parts: None,
+            max_header_len: None,
+            decode_attachments: true,
+            generate_alternatives: true,
+            unknown_encoding_as_binary: false,
+            lenient_dates: false,
+            content_statistics: false,
+            preserve_preamble: false,
+            encoded_word_charset: None,
+            part_scanner: None,
+            max_decompressed_size: 64 * 1024 * 1024,
+        }
+    }
+
+    /// Sets a closure that receives the charset label and the raw bytes of
+    /// the text parts using a charset this library cannot decode, allowing
+    /// the application to delegate decoding to iconv, ICU or similar.
+    /// Returning `None` falls back to lossy UTF-8 decoding.
+    pub fn charset_fallback(
+        mut self,
+        decoder: impl Fn(&str, &[u8]) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.charset_fallback = Some(CharsetFallback(Arc::new(decoder)));
+        self
+    }
+
+    /// Sets a scanner that inspects each part as soon as its contents are
+    /// decoded, avoiding a second pass over the message. Parsing stops at
+    /// the first part the scanner returns [`ScanVerdict::Stop`] for, which
+    /// is recorded as a [`ParseWarning::ScanStopped`] warning, see
+    /// [`Message::scan_stopped`]. Unless [`ParseOptions::nested_messages`]
+    /// sets other options, the parts of nested messages are scanned too.
+    pub fn part_scanner(mut self, scanner: impl PartScanner + 'static) -> Self {
+        self.part_scanner = Some(Arc::new(scanner));
+        self
+    }
+}
+
+impl ParseOptions {
+    /// Sets the options used to parse nested message/rfc822 parts.
+    pub fn nested_messages(mut self, nested_messages: NestedOptions) -> Self {
+        self.nested_messages = nested_messages;
+        self
+    }
+
+    /// Adds a MIME type correction, making the parts declared as `declared`
+    /// with a file name ending in `.extension` have the `effective` type.
+    /// Matching is case insensitive and the parts are parsed according to
+    /// their effective type.
+    pub fn correct_mime_type(
+        mut self,
+        declared: impl Into<String>,
+        extension: impl Into<String>,
+        effective: impl Into<String>,
+    ) -> Self {
+        self.mime_type_corrections.push(MimeTypeCorrection {
+            declared: declared.into(),
+            extension: extension.into(),
+            effective: effective.into(),
+        });
+        self
+    }
+
+    /// Adds a content type, such as "video/mp4", "video/*" or "video", whose
+    /// parts are returned as binary attachments with their contents as found
+    /// in the raw message, without decoding their transfer encoding,
+    /// charset or nested message. Multipart parts are always parsed.
+    pub fn skip_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.skipped_content_types.push(content_type.into());
+        self
+    }
+
+    /// Adds a content type, such as "text/plain", "text/*" or "text", to the
+    /// only ones whose parts are decoded. Once a content type is added, the
+    /// parts of any other type are handled as in
+    /// [`ParseOptions::skip_content_type`].
+    pub fn decode_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.decoded_content_types.push(content_type.into());
+        self
+    }
+
+    /// Sets the maximum nesting depth of multipart and message/rfc822 parts.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets the maximum number of parts to parse.
+    pub fn max_parts(mut self, max_parts: usize) -> Self {
+        self.max_parts = Some(max_parts);
+        self
+    }
+
+    /// Sets the maximum length of a header field value.
+    pub fn max_header_len(mut self, max_header_len: usize) -> Self {
+        self.max_header_len = Some(max_header_len);
+        self
+    }
+
+    /// Sets the maximum size of a compressed message once decompressed.
+    pub fn max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
+
+    /// Whether to decode the uuencoded blocks found inside text/plain parts
+    /// and add them to the message as binary attachments.
+    pub fn decode_uuencode(mut self, decode_uuencode: bool) -> Self {
+        self.decode_uuencode = decode_uuencode;
+        self
+    }
+
+    /// Whether to detect and decode base64 or quoted-printable contents in
+    /// parts lacking a Content-Transfer-Encoding header.
+    pub fn detect_encoding(mut self, detect_encoding: bool) -> Self {
+        self.detect_encoding = detect_encoding;
+        self
+    }
+
+    /// Whether to detect the charset of the text parts lacking a charset
+    /// label or whose label produces replacement characters.
+    pub fn detect_charset(mut self, detect_charset: bool) -> Self {
+        self.detect_charset = detect_charset;
+        self
+    }
+
+    /// Whether to decode the transfer encoding of binary parts.
+    pub fn decode_attachments(mut self, decode_attachments: bool) -> Self {
+        self.decode_attachments = decode_attachments;
+        self
+    }
+
+    /// Whether to use the HTML body as the text body, or the other way
+    /// around, when a message lacks one of them.
+    pub fn generate_alternatives(mut self, generate_alternatives: bool) -> Self {
+        self.generate_alternatives = generate_alternatives;
+        self
+    }
+
+    /// Whether to treat the parts with an unknown transfer encoding as
+    /// binary attachments.
+    pub fn unknown_encoding_as_binary(mut self, unknown_encoding_as_binary: bool) -> Self {
+        self.unknown_encoding_as_binary = unknown_encoding_as_binary;
+        self
+    }
+
+    /// Whether to parse malformed Date and Resent-Date header fields, such
+    /// as dates with two-digit years or without a time zone, on a
+    /// best-effort basis.
+    pub fn lenient_dates(mut self, lenient_dates: bool) -> Self {
+        self.lenient_dates = lenient_dates;
+        self
+    }
+
+    /// Whether to collect the 8-bit byte count and entropy of the decoded
+    /// contents of each part, see [`MessagePart::statistics`]. These are
+    /// computed as each part is decoded, avoiding a second pass over the
+    /// message.
+    pub fn content_statistics(mut self, content_statistics: bool) -> Self {
+        self.content_statistics = content_statistics;
+        self
+    }
+
+    /// Whether to keep the text preceding the first boundary (preamble) and
+    /// following the closing boundary (epilogue) of multipart parts, see
+    /// [`MessagePart::preamble`] and [`MessagePart::epilogue`]. Both are
+    /// discarded by default, as RFC 2046 asks.
+    pub fn preserve_preamble(mut self, preserve_preamble: bool) -> Self {
+        self.preserve_preamble = preserve_preamble;
+        self
+    }
+
+    /// Sets the charset, such as "euc-kr" or "windows-1258", used to decode
+    /// the RFC 2047 encoded words of the header fields that declare a
+    /// charset this library does not support and are not valid UTF-8.
+    /// Without it, such words are decoded as UTF-8 with replacement
+    /// characters. Typically the charset of the user's locale.
+    pub fn encoded_word_charset(mut self, charset: impl Into<String>) -> Self {
+        self.encoded_word_charset = Some(charset.into());
+        self
+    }
+
+    /// Returns the options used to parse the messages nested in this one.
+    pub fn nested(&self) -> &ParseOptions {
+        static DEFAULT_OPTIONS: ParseOptions = ParseOptions::new();
+
+        match &self.nested_messages {
+            NestedOptions::Inherit => self,
+            NestedOptions::Default => &DEFAULT_OPTIONS,
+            NestedOptions::Custom(options) => options,
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions::new()
+    }
+}
+
+impl AttachmentLimits {
+    /// Returns limits that accept any attachment.
+    pub const fn new() -> Self {
+        AttachmentLimits {
+            max_count: None,
+            max_size: None,
+            max_total_size: None,
+            blocked_extensions: Vec::new(),
+            allowed_extensions: Vec::new(),
+        }
+    }
+
+    /// Sets the maximum number of attachments.
+    pub fn max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self
+    }
+
+    /// Sets the maximum size of an attachment.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Sets the maximum size of all the attachments.
+    pub fn max_total_size(mut self, max_total_size: usize) -> Self {
+        self.max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Adds a rejected file name extension, such as "exe".
+    pub fn block_extension(mut self, extension: impl Into<String>) -> Self {
+        self.blocked_extensions.push(extension.into());
+        self
+    }
+
+    /// Adds an accepted file name extension, such as "pdf". Once added,
+    /// the attachments whose file name has any other extension, or none,
+    /// are rejected.
+    pub fn allow_extension(mut self, extension: impl Into<String>) -> Self {
+        self.allowed_extensions.push(extension.into());
+        self
+    }
+}
+
+impl ValidationLimits {
+    /// Returns the default limits: no maximum size, a 64 KiB header, a depth
+    /// of 32 and 1000 parts.
+    pub const fn new() -> Self {
+        ValidationLimits {
+            max_size: None,
+            max_header_size: Some(64 * 1024),
+            max_depth: Some(32),
+            max_parts: Some(1000),
+        }
+    }
+
+    /// Sets the maximum size of the raw message.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Sets the maximum size of the message header.
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        self.max_header_size = Some(max_header_size);
+        self
+    }
+
+    /// Sets the maximum nesting depth.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets the maximum number of parts.
+    pub fn max_parts(mut self, max_parts: usize) -> Self {
+        self.max_parts = Some(max_parts);
+        self
+    }
+}
+
+impl Default for ValidationLimits {
+    fn default() -> Self {
+        ValidationLimits::new()
+    }
+}
+
+impl fmt::Debug for CharsetFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CharsetFallback")
+    }
+}
+
+impl fmt::Debug for dyn PartScanner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PartScanner")
+    }
+}
+
+impl<F> PartScanner for F
+where
+    F: Fn(MessagePartId, &MessagePart<'_>) -> ScanVerdict + Send + Sync,
+{
+    fn scan(&self, part_id: MessagePartId, part: &MessagePart<'_>) -> ScanVerdict {
+        self(part_id, part)
+    }
 }
 
 /// MIME Message Part
@@ -295,13 +1205,33 @@ pub struct MessagePart<'x> {
     pub headers: Vec<Header<'x>>,
     pub is_encoding_problem: bool,
     #[cfg_attr(feature = "serde_support", serde(default))]
-    #[cfg_attr(feature = "serde_support", serde(borrow))]
     pub body: PartType<'x>,
     #[cfg_attr(feature = "serde_support", serde(skip))]
     pub encoding: Encoding,
     pub offset_header: usize,
     pub offset_body: usize,
     pub offset_end: usize,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub line_count: usize,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub statistics: Option<ContentStatistics>,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub preamble: Option<Cow<'x, [u8]>>,
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    pub epilogue: Option<Cow<'x, [u8]>>,
+}
+
+/// Statistics of the decoded contents of a part, collected while parsing
+/// when [`ParseOptions::content_statistics`] is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ContentStatistics {
+    /// Number of decoded bytes.
+    pub size: usize,
+    /// Number of decoded bytes with the high bit set.
+    pub eight_bit_bytes: usize,
+    /// Shannon entropy of the decoded bytes, in bits per byte, from 0 for
+    /// repetitive data to 8 for random or encrypted data.
+    pub entropy: f64,
 }
 
 /// MIME Part encoding type
@@ -315,6 +1245,26 @@ pub enum Encoding {
     Base64 = 2,
 }
 
+impl Encoding {
+    /// Parses a Content-Transfer-Encoding value, returning `None` for values
+    /// other than "base64", "quoted-printable", "7bit", "8bit" and "binary".
+    pub fn parse(value: &str) -> Option<Encoding> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("base64") {
+            Some(Encoding::Base64)
+        } else if value.eq_ignore_ascii_case("quoted-printable") {
+            Some(Encoding::QuotedPrintable)
+        } else if ["7bit", "8bit", "binary"]
+            .iter()
+            .any(|known| value.eq_ignore_ascii_case(known))
+        {
+            Some(Encoding::None)
+        } else {
+            None
+        }
+    }
+}
+
 impl From<u8> for Encoding {
     fn from(v: u8) -> Self {
         match v {
@@ -345,11 +1295,9 @@ pub enum PartType<'x> {
     Html(Cow<'x, str>),
 
     /// Any other part type that is not text.
-    #[cfg_attr(feature = "serde_support", serde(borrow))]
     Binary(Cow<'x, [u8]>),
 
     /// Any inline binary data that.
-    #[cfg_attr(feature = "serde_support", serde(borrow))]
     InlineBinary(Cow<'x, [u8]>),
 
     /// Nested RFC5322 message.
@@ -359,6 +1307,32 @@ pub enum PartType<'x> {
     Multipart(Vec<MessagePartId>),
 }
 
+/// The preferred format of the body returned by [`Message::best_body`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyPreference {
+    /// Return the body as HTML.
+    Html,
+    /// Return the body as plain text.
+    Text,
+}
+
+/// The kind of a text part, see [`MessagePart::text_kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    /// A text/plain part, or a text part without a Content-Type.
+    Plain,
+    /// A text/html part.
+    Html,
+    /// An iCalendar object (RFC 5545) in a text/calendar or application/ics
+    /// part.
+    Calendar,
+    /// A vCard (RFC 6350) in a text/vcard, text/x-vcard or text/directory
+    /// part.
+    VCard,
+    /// Any other text part.
+    Other,
+}
+
 impl<'x> Default for PartType<'x> {
     fn default() -> Self {
         PartType::Multipart(Vec::with_capacity(0))
@@ -386,6 +1360,46 @@ impl<'x> Addr<'x> {
         }
     }
 
+    /// Parses a single address such as "John Doe <john@example.com>",
+    /// returning `None` if the value is not exactly one address.
+    pub fn parse(value: &'x str) -> Option<Addr<'x>> {
+        match MessageStream::new(value.as_bytes()).parse_address() {
+            HeaderValue::Address(addr) if addr.address.is_some() => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Validates an address, without a display name or angle brackets,
+    /// against the mailbox syntax of RFC 5321 as extended by RFC 6531 to
+    /// allow UTF-8.
+    pub fn is_valid_rfc5321(address: &str) -> Result<(), AddressError> {
+        validate_rfc5321(address)
+    }
+
+    /// Returns the domain part of the address, with address literals
+    /// such as `[192.168.1.1]` or `[IPv6:::1]` parsed as IP addresses.
+    pub fn domain(&self) -> Option<Domain<'_>> {
+        Domain::parse(parse_address_domain(self.address.as_deref()?)?).into()
+    }
+
+    /// Returns the local part of the address as found in the message,
+    /// including the quotes of a quoted local part.
+    pub fn local_part(&self) -> Option<&str> {
+        parse_address_local_part(self.address.as_deref()?)
+    }
+
+    /// Returns the local part of the address without the quotes and
+    /// escapes of a quoted local part.
+    pub fn unquoted_local_part(&self) -> Option<Cow<'_, str>> {
+        unquote_local_part(self.local_part()?).into()
+    }
+
+    /// Returns the address with the domain name in lowercase and the local
+    /// part unquoted unless quoting is required, for comparing addresses.
+    pub fn normalized_address(&self) -> Option<String> {
+        parse_address_normalized(self.address.as_deref()?)
+    }
+
     pub fn into_owned<'y>(self) -> Addr<'y> {
         Addr {
             name: self.name.map(|s| s.into_owned().into()),
@@ -394,6 +1408,51 @@ impl<'x> Addr<'x> {
     }
 }
 
+/// The domain of an email address.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Domain<'x> {
+    /// A domain name, i.e. "example.com"
+    Name(&'x str),
+    /// An IPv4 address literal, i.e. `[192.168.1.1]`
+    Ipv4(Ipv4Addr),
+    /// An IPv6 address literal, i.e. `[IPv6:::1]`
+    Ipv6(Ipv6Addr),
+    /// Any other address literal, without the enclosing brackets.
+    Literal(&'x str),
+}
+
+/// The reason an address is invalid, see [`Addr::is_valid_rfc5321`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    /// The address is empty.
+    Empty,
+    /// The address is longer than 254 octets.
+    TooLong,
+    /// The address lacks an at-sign.
+    MissingAtSign,
+    /// The local part is empty.
+    EmptyLocalPart,
+    /// The local part is longer than 64 octets.
+    LocalPartTooLong,
+    /// A quoted local part is not terminated, or a quote appears in an
+    /// unquoted local part.
+    UnbalancedQuotes,
+    /// The local part contains a forbidden character or misplaced dot.
+    InvalidLocalPart {
+        /// Offset of the invalid character in the address.
+        offset: usize,
+    },
+    /// The domain is empty.
+    EmptyDomain,
+    /// A domain label is empty, too long or contains a forbidden character.
+    InvalidDomain {
+        /// Offset of the invalid label in the address.
+        offset: usize,
+    },
+    /// The address literal is not an IPv4, IPv6 or general address literal.
+    InvalidDomainLiteral,
+}
+
 /// An RFC5322 address group.
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
@@ -423,6 +1482,51 @@ impl<'x> Group<'x> {
     }
 }
 
+/// A typed view of an address header field, holding either a list of
+/// addresses or a list of address groups.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Address<'a, 'x> {
+    List(&'a [Addr<'x>]),
+    Group(&'a [Group<'x>]),
+}
+
+impl<'a, 'x> Address<'a, 'x> {
+    /// Returns the first address, including those in groups.
+    pub fn first(&self) -> Option<&'a Addr<'x>> {
+        match self {
+            Address::List(list) => list.first(),
+            Address::Group(groups) => groups.iter().flat_map(|group| &group.addresses).next(),
+        }
+    }
+
+    /// Returns an iterator over all the addresses, including those in
+    /// groups, in the order they appear in the field.
+    pub fn iter(&self) -> impl Iterator<Item = &'a Addr<'x>> + 'a {
+        let (list, groups): (&'a [Addr<'x>], &'a [Group<'x>]) = match *self {
+            Address::List(list) => (list, &[]),
+            Address::Group(groups) => (&[], groups),
+        };
+        list.iter()
+            .chain(groups.iter().flat_map(|group| group.addresses.iter()))
+    }
+
+    /// Returns the address list, or `None` if the field holds groups.
+    pub fn as_list(&self) -> Option<&'a [Addr<'x>]> {
+        match self {
+            Address::List(list) => Some(list),
+            Address::Group(_) => None,
+        }
+    }
+
+    /// Returns the address groups, or `None` if the field holds a list.
+    pub fn as_group(&self) -> Option<&'a [Group<'x>]> {
+        match self {
+            Address::Group(groups) => Some(groups),
+            Address::List(_) => None,
+        }
+    }
+}
+
 /// A message header.
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
@@ -460,6 +1564,18 @@ impl<'x> Header<'x> {
         self.offset_field
     }
 
+    /// Returns the byte range of the whole header field in the raw message,
+    /// from the start of the name up to and including the line ending.
+    pub fn raw_range(&self) -> Range<usize> {
+        self.offset_field..self.offset_end
+    }
+
+    /// Returns the byte range of the unparsed header value in the raw
+    /// message, including any folding and the line ending.
+    pub fn raw_value_range(&self) -> Range<usize> {
+        self.offset_start..self.offset_end
+    }
+
     /// Returns an owned version of the header
     pub fn into_owned<'y>(self) -> Header<'y> {
         Header {
@@ -747,6 +1863,7 @@ pub enum HeaderForm {
     Raw,
     Text,
     Addresses,
+    StrictAddresses,
     GroupedAddresses,
     MessageIds,
     Date,
@@ -819,6 +1936,26 @@ impl<'x> HeaderValue<'x> {
         }
     }
 
+    pub fn as_address(&self) -> Option<Address<'_, 'x>> {
+        match self {
+            HeaderValue::Address(addr) => Some(Address::List(std::slice::from_ref(addr))),
+            HeaderValue::AddressList(list) => Some(Address::List(list)),
+            HeaderValue::Group(group) => Some(Address::Group(std::slice::from_ref(group))),
+            HeaderValue::GroupList(list) => Some(Address::Group(list)),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over all the addresses of an address field,
+    /// whether it holds a single address, a list or groups of addresses.
+    /// Empty groups such as "undisclosed-recipients:;" yield no addresses,
+    /// and other values yield none.
+    pub fn flatten_addresses(&self) -> impl Iterator<Item = &Addr<'x>> + '_ {
+        self.as_address()
+            .into_iter()
+            .flat_map(|address| address.iter())
+    }
+
     pub fn into_owned<'y>(self) -> HeaderValue<'y> {
         match self {
             HeaderValue::Address(addr) => HeaderValue::Address(addr.into_owned()),
@@ -904,6 +2041,30 @@ pub struct ContentType<'x> {
     pub attributes: Option<Vec<(Cow<'x, str>, Cow<'x, str>)>>,
 }
 
+/// A parameter of a Content-Type or Content-Disposition header field as
+/// written in the raw message, see [`Message::raw_parameters`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RawParameter<'x> {
+    /// Name of the parameter, including any RFC 2231 section number and
+    /// "*" suffix, such as "title*0*".
+    pub name: Cow<'x, str>,
+    /// Value of the parameter, including any quotes, escapes, comments and
+    /// RFC 2231 or RFC 2047 encoding.
+    pub value: Cow<'x, str>,
+    /// Offset of the parameter name in the raw message.
+    pub offset: usize,
+}
+
+/// Reply and forward subject prefixes, such as "Odp:" or "Tr:", removed
+/// from subjects in addition to the built-in ones when computing their base
+/// subject, see [`SubjectPrefixes::thread_name`] and
+/// [`Threader::with_prefixes`](thread::Threader::with_prefixes).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubjectPrefixes {
+    reply: Vec<String>,
+    forward: Vec<String>,
+}
+
 /// An RFC5322 datetime.
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
@@ -919,35 +2080,508 @@ pub struct DateTime {
     pub tz_minute: u8,
 }
 
-impl<'x> Message<'x> {
-    /// Returns the root message part
-    pub fn root_part(&self) -> &MessagePart<'x> {
-        &self.parts[0]
-    }
+/// The S/MIME structure of a message, as described in RFC8551. No
+/// cryptographic verification or decryption is performed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SmimeInfo<'x> {
+    pub smime_type: SmimeType,
+    /// Id of the multipart/signed or application/pkcs7-mime part.
+    pub part_id: MessagePartId,
+    /// Id of the part protected by a detached signature.
+    pub content_part_id: Option<MessagePartId>,
+    /// Id of the application/pkcs7-signature part.
+    pub signature_part_id: Option<MessagePartId>,
+    /// The decoded CMS object, either the detached signature or the
+    /// application/pkcs7-mime contents.
+    pub cms: &'x [u8],
+    /// The micalg parameter of a multipart/signed part.
+    pub micalg: Option<&'x str>,
+}
 
-    /// Returns a parsed header.
-    pub fn header(&self, header: &str) -> Option<&HeaderValue> {
-        self.parts[0].headers.header(header).map(|h| &h.value)
-    }
+/// The type of an S/MIME protected part.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SmimeType {
+    /// multipart/signed with a detached signature.
+    DetachedSignature,
+    SignedData,
+    EnvelopedData,
+    AuthEnvelopedData,
+    CompressedData,
+    CertsOnly,
+    /// application/pkcs7-mime with a missing or unknown smime-type.
+    Unknown,
+}
 
-    /// Removed a parsed header and returns its value.
-    pub fn remove_header(&mut self, header: &str) -> Option<HeaderValue> {
-        let headers = &mut self.parts[0].headers;
-        headers
-            .iter()
-            .position(|h| h.name.as_str() == header)
-            .map(|pos| headers.swap_remove(pos).value)
-    }
+/// The PGP/MIME structure of a message, as described in RFC3156. No
+/// cryptographic verification or decryption is performed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PgpInfo<'x> {
+    pub pgp_type: PgpType,
+    /// Id of the multipart/signed or multipart/encrypted part.
+    pub part_id: MessagePartId,
+    /// Id of the signed part, or of the application/octet-stream part
+    /// holding the encrypted message.
+    pub content_part_id: MessagePartId,
+    /// Id of the application/pgp-signature part.
+    pub signature_part_id: Option<MessagePartId>,
+    /// The OpenPGP signature or encrypted message, usually ASCII armored.
+    pub data: &'x [u8],
+    /// The micalg parameter of a multipart/signed part.
+    pub micalg: Option<&'x str>,
+}
 
-    /// Removed a parsed RFC heade and returns its value.
-    pub fn remove_header_rfc(&mut self, header: RfcHeader) -> Option<HeaderValue> {
-        let headers = &mut self.parts[0].headers;
-        headers
-            .iter()
-            .position(|h| matches!(&h.name, HeaderName::Rfc(header_name) if header_name == &header))
+/// The type of a PGP/MIME protected part.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PgpType {
+    Signed,
+    Encrypted,
+}
+
+/// An RFC8098 Message Disposition Notification.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct Mdn<'x> {
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub reporting_ua: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub mdn_gateway: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub original_recipient: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub final_recipient: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub original_message_id: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub disposition: Option<Disposition<'x>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub failure: Vec<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub warning: Vec<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub error: Vec<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub extension_fields: Vec<(Cow<'x, str>, Cow<'x, str>)>,
+}
+
+/// An RFC5965 Abuse Reporting Format feedback report.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct FeedbackReport<'x> {
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub feedback_type: Option<FeedbackType<'x>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub user_agent: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub version: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub arrival_date: Option<DateTime>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub incidents: Option<u32>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub original_envelope_id: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub original_mail_from: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub original_rcpt_to: Vec<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub reporting_mta: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub source_ip: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub source_port: Option<u16>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub authentication_results: Vec<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub reported_domain: Vec<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub reported_uri: Vec<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub extension_fields: Vec<(Cow<'x, str>, Cow<'x, str>)>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum FeedbackType<'x> {
+    Abuse,
+    AuthFailure,
+    Fraud,
+    NotSpam,
+    Virus,
+    Other,
+    Unknown(Cow<'x, str>),
+}
+
+/// The reference to external data of a message/external-body part
+/// (RFC 2046, Section 5.2.3), see [`MessagePart::external_body`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct ExternalBody<'x> {
+    pub access_type: AccessType<'x>,
+    /// Date after which the external data may no longer exist.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub expiration: Option<DateTime>,
+    /// Size of the external data in octets.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub size: Option<u64>,
+    /// Whether the external data may be modified, "read" or "read-write".
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub permission: Option<Cow<'x, str>>,
+    /// File name, for the FTP, TFTP, local file and mail server access
+    /// types.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub name: Option<Cow<'x, str>>,
+    /// Host name of the FTP or TFTP server, or domain of the hosts where
+    /// the local file is accessible.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub site: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub directory: Option<Cow<'x, str>>,
+    /// Transfer mode, such as "image" or "ascii", for FTP and TFTP.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub mode: Option<Cow<'x, str>>,
+    /// Address of the mail server the body must be requested from.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub server: Option<Cow<'x, str>>,
+    /// Subject of the request sent to the mail server.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub subject: Option<Cow<'x, str>>,
+    /// URL of the external data (RFC 2017), with whitespace removed.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub url: Option<Cow<'x, str>>,
+    /// Header fields of the external data, such as its Content-Type and
+    /// Content-ID.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub headers: Vec<Header<'x>>,
+    /// Text following the header fields, which is the body of the request
+    /// for the mail server access type.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub body: Cow<'x, str>,
+}
+
+/// How the data of a message/external-body part is accessed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum AccessType<'x> {
+    Ftp,
+    AnonFtp,
+    Tftp,
+    LocalFile,
+    MailServer,
+    Url,
+    ContentId,
+    Unknown(Cow<'x, str>),
+}
+
+/// The XML of a DMARC aggregate report (RFC 7489, Section 7.2) attached to
+/// a message, see [`Message::dmarc_report_xml`].
+#[cfg(feature = "dmarc")]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct DmarcReportXml {
+    /// Id of the message part containing the report.
+    pub part_id: MessagePartId,
+    /// Name of the report file, taken from the zip archive for zipped
+    /// reports and from the part otherwise.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub file_name: Option<String>,
+    /// The decompressed XML document.
+    pub xml: Vec<u8>,
+}
+
+/// The JSON of an SMTP TLS report (RFC 8460) attached to a message, see
+/// [`Message::tls_report_json`].
+#[cfg(feature = "dmarc")]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct TlsReportJson {
+    /// Id of the message part containing the report.
+    pub part_id: MessagePartId,
+    /// Name of the report file.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub file_name: Option<String>,
+    /// The decompressed JSON document.
+    pub json: Vec<u8>,
+}
+
+/// A DMARC aggregate report, as defined in RFC 7489, Appendix C. Values
+/// are kept as found in the report, with whitespace trimmed.
+#[cfg(feature = "dmarc")]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct DmarcReport {
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub version: Option<String>,
+    pub org_name: String,
+    pub email: String,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub extra_contact_info: Option<String>,
+    pub report_id: String,
+    /// Start of the reporting period, in seconds since the UNIX epoch.
+    pub date_begin: u64,
+    /// End of the reporting period, in seconds since the UNIX epoch.
+    pub date_end: u64,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub errors: Vec<String>,
+    pub policy_published: DmarcPolicyPublished,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub records: Vec<DmarcRecord>,
+}
+
+/// The DMARC policy found by the reporter for the domain of a report.
+#[cfg(feature = "dmarc")]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct DmarcPolicyPublished {
+    pub domain: String,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub adkim: Option<String>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub aspf: Option<String>,
+    pub p: String,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub sp: Option<String>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub pct: Option<u32>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub fo: Option<String>,
+}
+
+/// A row of a DMARC aggregate report, counting the messages sent from an
+/// IP address with the same identifiers and authentication results.
+#[cfg(feature = "dmarc")]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct DmarcRecord {
+    pub source_ip: String,
+    pub count: u64,
+    pub disposition: String,
+    /// DKIM result of the DMARC evaluation, `pass` or `fail`.
+    pub dkim: String,
+    /// SPF result of the DMARC evaluation, `pass` or `fail`.
+    pub spf: String,
+    /// Reasons for a disposition other than the published policy, as
+    /// pairs of type and comment.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub reasons: Vec<(String, Option<String>)>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub envelope_to: Option<String>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub envelope_from: Option<String>,
+    pub header_from: String,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub dkim_results: Vec<DmarcAuthResult>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub spf_results: Vec<DmarcAuthResult>,
+}
+
+/// A DKIM or SPF result of a DMARC aggregate report row.
+#[cfg(feature = "dmarc")]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct DmarcAuthResult {
+    pub domain: String,
+    /// The DKIM selector.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub selector: Option<String>,
+    /// The SPF scope, `helo` or `mfrom`.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub scope: Option<String>,
+    pub result: String,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub human_result: Option<String>,
+}
+
+/// An RFC5545 event found in a text/calendar part.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct CalendarEvent<'x> {
+    /// Id of the message part containing the event.
+    pub part_id: MessagePartId,
+    /// The iTIP method of the calendar object containing the event.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub method: Option<CalendarMethod<'x>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub uid: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub sequence: Option<u32>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub status: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub summary: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub location: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub dtstart: Option<CalendarTime<'x>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub dtend: Option<CalendarTime<'x>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub organizer: Option<CalendarAddress<'x>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub attendees: Vec<CalendarAddress<'x>>,
+}
+
+/// An RFC5546 iTIP method.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum CalendarMethod<'x> {
+    Publish,
+    Request,
+    Reply,
+    Add,
+    Cancel,
+    Refresh,
+    Counter,
+    DeclineCounter,
+    Other(Cow<'x, str>),
+}
+
+/// An RFC5545 DATE or DATE-TIME value.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct CalendarTime<'x> {
+    /// The value as it appears in the calendar, i.e. "20220101T100000Z".
+    pub value: Cow<'x, str>,
+    /// The TZID parameter, if any.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub tzid: Option<Cow<'x, str>>,
+}
+
+/// An RFC5545 ORGANIZER or ATTENDEE property.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct CalendarAddress<'x> {
+    /// The CN parameter.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub name: Option<Cow<'x, str>>,
+    /// The calendar user address, without the "mailto:" prefix.
+    pub address: Cow<'x, str>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub role: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub partstat: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub rsvp: Option<bool>,
+}
+
+/// A contact parsed from a text/vcard part.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct VCard<'x> {
+    /// Id of the message part containing the contact.
+    pub part_id: MessagePartId,
+    /// The FN (formatted name) property.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub name: Option<Cow<'x, str>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub emails: Vec<VCardValue<'x>>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub phones: Vec<VCardValue<'x>>,
+    /// The ORG property components, organization name first followed by
+    /// the organizational units.
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub organization: Vec<Cow<'x, str>>,
+}
+
+/// A vCard EMAIL or TEL property.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct VCardValue<'x> {
+    pub value: Cow<'x, str>,
+    /// The TYPE parameter values, such as "work" or "cell".
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub types: Vec<Cow<'x, str>>,
+}
+
+/// An RFC8098 Disposition field.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct Disposition<'x> {
+    pub action_mode: ActionMode,
+    pub sending_mode: SendingMode,
+    pub disposition_type: DispositionType<'x>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    pub modifiers: Vec<Cow<'x, str>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum ActionMode {
+    ManualAction,
+    AutomaticAction,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum SendingMode {
+    MdnSentManually,
+    MdnSentAutomatically,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum DispositionType<'x> {
+    Displayed,
+    Deleted,
+    Dispatched,
+    Processed,
+    Other(Cow<'x, str>),
+}
+
+impl<'x> Message<'x> {
+    /// Returns the root message part
+    pub fn root_part(&self) -> &MessagePart<'x> {
+        &self.parts[0]
+    }
+
+    /// Returns a parsed header.
+    pub fn header(&self, header: &str) -> Option<&HeaderValue> {
+        self.parts[0].headers.header(header).map(|h| &h.value)
+    }
+
+    /// Removed a parsed header and returns its value.
+    pub fn remove_header(&mut self, header: &str) -> Option<HeaderValue> {
+        let headers = &mut self.parts[0].headers;
+        headers
+            .iter()
+            .position(|h| h.name.as_str() == header)
             .map(|pos| headers.swap_remove(pos).value)
     }
 
+    /// Removed a parsed RFC heade and returns its value.
+    pub fn remove_header_rfc(&mut self, header: RfcHeader) -> Option<HeaderValue> {
+        let headers = &mut self.parts[0].headers;
+        headers
+            .iter()
+            .position(|h| matches!(&h.name, HeaderName::Rfc(header_name) if header_name == &header))
+            .map(|pos| headers.swap_remove(pos).value)
+    }
+
+    /// Returns the parameters of the first Content-Type or
+    /// Content-Disposition header field of a part as written in the raw
+    /// message, in their original order and including the parameters this
+    /// library does not interpret. Their decoded values, with RFC 2231
+    /// continuations merged, are available from
+    /// [`ContentType::attributes`].
+    pub fn raw_parameters(
+        &self,
+        part_id: MessagePartId,
+        header: RfcHeader,
+    ) -> Vec<RawParameter<'_>> {
+        self.parts
+            .get(part_id)
+            .and_then(|part| {
+                part.headers
+                    .iter()
+                    .find(|h| h.name == HeaderName::Rfc(header))
+            })
+            .and_then(|h| {
+                self.raw_message
+                    .get(h.offset_start..h.offset_end)
+                    .map(|value| parse_raw_parameters(value, h.offset_start))
+            })
+            .unwrap_or_default()
+    }
+
     /// Returns the raw header.
     pub fn header_raw(&self, header_name: &str) -> Option<&str> {
         self.parts[0]
@@ -970,6 +2604,9 @@ impl<'x> Message<'x> {
                             ),
                             HeaderForm::Text => MessageStream::new(bytes).parse_unstructured(),
                             HeaderForm::Addresses => MessageStream::new(bytes).parse_address(),
+                            HeaderForm::StrictAddresses => {
+                                MessageStream::new(bytes).parse_address_strict()
+                            }
                             HeaderForm::GroupedAddresses => {
                                 MessageStream::new(bytes).parse_address()
                             }
@@ -1022,6 +2659,76 @@ impl<'x> Message<'x> {
             .unwrap_or_default()
     }
 
+    /// Returns the non-fatal problems found while parsing this message,
+    /// excluding those of its nested messages, in the order they were
+    /// found.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Returns the errors found while decoding a part of this message.
+    pub fn decode_errors(&self, part_id: MessagePartId) -> impl Iterator<Item = &DecodeError> + '_ {
+        self.warnings
+            .iter()
+            .filter_map(move |warning| match warning {
+                ParseWarning::DecodeError {
+                    part_id: error_part_id,
+                    error,
+                } if *error_part_id == part_id => Some(error),
+                _ => None,
+            })
+    }
+
+    /// Returns the part ID and reason for which a [`PartScanner`] stopped
+    /// parsing this message or one of its nested messages, if any.
+    pub fn scan_stopped(&self) -> Option<(MessagePartId, &str)> {
+        self.warnings
+            .iter()
+            .find_map(|warning| match warning {
+                ParseWarning::ScanStopped { part_id, reason } => Some((*part_id, reason.as_str())),
+                _ => None,
+            })
+            .or_else(|| {
+                self.parts.iter().find_map(|part| match &part.body {
+                    PartType::Message(nested) => nested.scan_stopped(),
+                    _ => None,
+                })
+            })
+    }
+
+    /// Returns the number of bytes of a text part that could not be
+    /// converted from its charset and were replaced with U+FFFD. A high
+    /// number relative to the part size suggests a mislabeled charset.
+    pub fn replaced_bytes(&self, part_id: MessagePartId) -> usize {
+        self.decode_errors(part_id)
+            .map(|error| match error {
+                DecodeError::CharsetReplacement { bytes, .. } => *bytes,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Returns the raw bytes of a range obtained from a header or part of
+    /// this message. The parts of a nested message that was transfer encoded
+    /// refer to the raw bytes of the nested message instead.
+    pub fn raw_bytes(&self, range: Range<usize>) -> Option<&[u8]> {
+        self.raw_message.get(range)
+    }
+
+    /// Returns the signed part of a multipart/signed part (RFC 1847), either
+    /// S/MIME or PGP/MIME, exactly as it appears in the raw message,
+    /// including its header fields and its original transfer encoding. These
+    /// are the bytes the signature is computed over, once their line endings
+    /// are converted to CRLF.
+    pub fn signed_content(&self, part_id: MessagePartId) -> Option<&[u8]> {
+        let part = self.parts.get(part_id)?;
+        if !part.is_content_type("multipart", "signed") {
+            return None;
+        }
+        let content_part = self.parts.get(*part.sub_parts()?.first()?)?;
+        self.raw_bytes(content_part.raw_range())
+    }
+
     /// Returns the BCC header field
     pub fn bcc(&self) -> &HeaderValue {
         self.parts[0]
@@ -1054,6 +2761,31 @@ impl<'x> Message<'x> {
             .and_then(|header| header.as_datetime_ref())
     }
 
+    /// Returns the Date header field when it contains a valid date, or
+    /// otherwise the date of the newest Received header field.
+    pub fn received_date(&self) -> Option<DateTime> {
+        self.date()
+            .filter(|date| date.is_valid())
+            .cloned()
+            .or_else(|| {
+                self.parts[0]
+                    .headers
+                    .iter()
+                    .filter(|header| matches!(header.name, HeaderName::Rfc(RfcHeader::Received)))
+                    .find_map(|header| {
+                        let value = self
+                            .raw_message
+                            .get(header.offset_start..header.offset_end)?;
+                        match MessageStream::new(value).parse_date() {
+                            HeaderValue::DateTime(date) if date.is_valid() => Some(date),
+                            _ => DateTime::parse_lenient(
+                                std::str::from_utf8(value).ok()?.rsplit(';').next()?,
+                            ),
+                        }
+                    })
+            })
+    }
+
     /// Returns the From header field
     pub fn from(&self) -> &HeaderValue {
         self.parts[0]
@@ -1186,7 +2918,7 @@ impl<'x> Message<'x> {
     pub fn resent_cc(&self) -> &HeaderValue {
         self.parts[0]
             .headers
-            .rfc(&RfcHeader::ResentTo)
+            .rfc(&RfcHeader::ResentCc)
             .unwrap_or(&HeaderValue::Empty)
     }
 
@@ -1206,6 +2938,56 @@ impl<'x> Message<'x> {
             .unwrap_or(&HeaderValue::Empty)
     }
 
+    /// Returns the Resent-* header fields grouped by resend event, in the
+    /// order they appear in the message, which is from the most recent to
+    /// the oldest event. A block ends at the first header field that is not
+    /// a Resent-* field or repeats a field of the block.
+    pub fn resent_blocks(&self) -> Vec<ResentBlock<'_>> {
+        let mut blocks = Vec::new();
+        let mut block: Option<ResentBlock<'_>> = None;
+        let mut seen = Vec::new();
+
+        for header in &self.parts[0].headers {
+            let rfc_name = match header.name {
+                HeaderName::Rfc(
+                    rfc_name @ (RfcHeader::ResentDate
+                    | RfcHeader::ResentFrom
+                    | RfcHeader::ResentSender
+                    | RfcHeader::ResentTo
+                    | RfcHeader::ResentCc
+                    | RfcHeader::ResentBcc
+                    | RfcHeader::ResentMessageId),
+                ) => rfc_name,
+                _ => {
+                    blocks.extend(block.take());
+                    seen.clear();
+                    continue;
+                }
+            };
+
+            if seen.contains(&rfc_name) {
+                blocks.extend(block.take());
+                seen.clear();
+            }
+            seen.push(rfc_name);
+
+            let current = block.get_or_insert_with(ResentBlock::default);
+            let value = &header.value;
+            match rfc_name {
+                RfcHeader::ResentDate => current.date = value.as_datetime_ref(),
+                RfcHeader::ResentFrom => current.from = Some(value),
+                RfcHeader::ResentSender => current.sender = Some(value),
+                RfcHeader::ResentTo => current.to = Some(value),
+                RfcHeader::ResentCc => current.cc = Some(value),
+                RfcHeader::ResentBcc => current.bcc = Some(value),
+                _ => current.message_id = value.as_text_ref(),
+            }
+        }
+        blocks.extend(block);
+
+        blocks
+    }
+
     /// Returns all Resent-Message-ID header fields
     pub fn resent_message_id(&self) -> &HeaderValue {
         self.parts[0]
@@ -1276,6 +3058,45 @@ impl<'x> Message<'x> {
         thread_name(self.subject()?).into()
     }
 
+    /// Returns the decoded subject with its whitespace collapsed and the
+    /// reply and forward prefixes, such as "Re:", "Fwd:", "AW:" or
+    /// `[list]`, removed, for threading and deduplicating messages. See
+    /// [`Message::thread_name`].
+    pub fn subject_normalized(&self) -> Option<String> {
+        self.subject_normalized_with(&SubjectPrefixes::default())
+    }
+
+    /// Returns the normalized subject, also removing the localized reply and
+    /// forward prefixes in `prefixes`, such as "Antwort" or "Odg". See
+    /// [`Message::subject_normalized`].
+    pub fn subject_normalized_with(&self, prefixes: &SubjectPrefixes) -> Option<String> {
+        prefixes.normalize_subject(self.subject()?).into()
+    }
+
+    /// Returns `true` if the message header contains UTF-8, as allowed by
+    /// RFC 6532 in addresses and other header values, which requires the
+    /// SMTPUTF8 extension (RFC 6531) to relay the message. Headers of body
+    /// parts and nested `message/global` messages are not considered.
+    pub fn requires_smtputf8(&self) -> bool {
+        self.parts.first().is_some_and(|root| {
+            self.raw_message
+                .get(root.offset_header..root.offset_body)
+                .is_some_and(|header| !header.is_ascii())
+        })
+    }
+
+    /// Returns the Message Disposition Notification contained in a
+    /// `multipart/report; report-type=disposition-notification` message.
+    pub fn mdn(&self) -> Option<Mdn<'_>> {
+        Mdn::parse(self.report_part("disposition-notification", "disposition-notification")?)
+    }
+
+    /// Returns the Abuse Reporting Format report contained in a
+    /// `multipart/report; report-type=feedback-report` message.
+    pub fn feedback_report(&self) -> Option<FeedbackReport<'_>> {
+        FeedbackReport::parse(self.report_part("feedback-report", "feedback-report")?)
+    }
+
     /// Returns the To header field
     pub fn to(&self) -> &HeaderValue {
         self.parts[0]
@@ -1284,6 +3105,80 @@ impl<'x> Message<'x> {
             .unwrap_or(&HeaderValue::Empty)
     }
 
+    /// Returns the addresses in the From header field
+    pub fn from_address(&self) -> Option<Address<'_, '_>> {
+        self.address(RfcHeader::From)
+    }
+
+    /// Returns the addresses in the Sender header field
+    pub fn sender_address(&self) -> Option<Address<'_, '_>> {
+        self.address(RfcHeader::Sender)
+    }
+
+    /// Returns the addresses in the Reply-To header field
+    pub fn reply_to_address(&self) -> Option<Address<'_, '_>> {
+        self.address(RfcHeader::ReplyTo)
+    }
+
+    /// Returns the addresses in the To header field
+    pub fn to_address(&self) -> Option<Address<'_, '_>> {
+        self.address(RfcHeader::To)
+    }
+
+    /// Returns the addresses in the CC header field
+    pub fn cc_address(&self) -> Option<Address<'_, '_>> {
+        self.address(RfcHeader::Cc)
+    }
+
+    /// Returns the addresses in the BCC header field
+    pub fn bcc_address(&self) -> Option<Address<'_, '_>> {
+        self.address(RfcHeader::Bcc)
+    }
+
+    /// Returns the Original-From header field, added by mailing lists that
+    /// rewrite the From header field of the messages they distribute
+    pub fn original_from(&self) -> HeaderValue<'_> {
+        self.first_header_as("Original-From", HeaderForm::Addresses)
+    }
+
+    /// Returns the X-Original-Sender header field, added by mailing lists and
+    /// forwarding services to record the original author of a message
+    pub fn x_original_sender(&self) -> HeaderValue<'_> {
+        self.first_header_as("X-Original-Sender", HeaderForm::Addresses)
+    }
+
+    /// Returns the address in the Original-Recipient header field without
+    /// its address type, i.e. "user@domain" for "rfc822;user@domain"
+    pub fn original_recipient(&self) -> Option<&str> {
+        let value = self.header_raw("Original-Recipient")?;
+        let address = value.split_once(';').map_or(value, |(_, address)| address);
+        Some(address.trim()).filter(|address| !address.is_empty())
+    }
+
+    fn first_header_as(&self, header_name: &str, form: HeaderForm) -> HeaderValue<'_> {
+        self.header_as(header_name, form)
+            .into_iter()
+            .next()
+            .unwrap_or(HeaderValue::Empty)
+    }
+
+    /// Returns the message ids in the In-Reply-To header field
+    pub fn in_reply_to_ids(&self) -> Vec<&str> {
+        self.in_reply_to().as_text_list().unwrap_or_default()
+    }
+
+    /// Returns the message ids in the References header field
+    pub fn reference_ids(&self) -> Vec<&str> {
+        self.references().as_text_list().unwrap_or_default()
+    }
+
+    fn address(&self, header: RfcHeader) -> Option<Address<'_, '_>> {
+        self.parts[0]
+            .headers
+            .rfc(&header)
+            .and_then(|value| value.as_address())
+    }
+
     /// Returns a preview of the message body
     pub fn body_preview(&self, preview_len: usize) -> Option<Cow<'x, str>> {
         if !self.text_body.is_empty() {
@@ -1305,6 +3200,113 @@ impl<'x> Message<'x> {
         }
     }
 
+    /// Returns a message body part converted to Markdown, see
+    /// [`decoders::html::html_to_markdown`].
+    pub fn body_markdown(&'x self, pos: usize) -> Option<String> {
+        self.body_html(pos).map(|html| html_to_markdown(&html))
+    }
+
+    /// Returns a message body part as text/html with the `cid:` URLs that
+    /// reference parts of the message replaced with the URL returned by
+    /// `resolve`, such as the location where the part is served from.
+    pub fn body_html_resolved(
+        &'x self,
+        pos: usize,
+        mut resolve: impl FnMut(&'x MessagePart<'x>) -> Option<String>,
+    ) -> Option<Cow<'x, str>> {
+        let html = self.body_html(pos)?;
+        Some(
+            rewrite_cid_urls(&html, |content_id| {
+                self.part_by_content_id(content_id).and_then(&mut resolve)
+            })
+            .into(),
+        )
+    }
+
+    /// Returns a message body part as text/html with the `cid:` URLs that
+    /// reference parts of the message replaced with `data:` URIs holding the
+    /// contents of the parts.
+    pub fn body_html_inlined(&'x self, pos: usize) -> Option<Cow<'x, str>> {
+        self.body_html_resolved(pos, parsers::render::data_uri)
+    }
+
+    /// Returns a message body part as text/html that is safe to display
+    /// within a web page, see [`html_sanitize::sanitize`](decoders::html_sanitize::sanitize).
+    #[cfg(feature = "html_sanitize")]
+    pub fn body_html_sanitized(&'x self, pos: usize) -> Option<String> {
+        self.body_html(pos)
+            .map(|html| decoders::html_sanitize::sanitize(&html))
+    }
+
+    /// Returns the URLs found in the text and HTML body parts, in the order
+    /// of the parts. The links and images of HTML parts are returned along
+    /// with their anchor or alternative text, while plain text parts are
+    /// scanned for URLs.
+    pub fn extract_urls(&self) -> Vec<BodyUrl> {
+        let mut part_ids = self
+            .text_body
+            .iter()
+            .chain(self.html_body.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        part_ids.sort_unstable();
+        part_ids.dedup();
+
+        let mut urls = Vec::new();
+        for part_id in part_ids {
+            match self.parts.get(part_id).map(|part| &part.body) {
+                Some(PartType::Text(text)) => {
+                    urls.extend(
+                        decoders::url::text_urls(text)
+                            .into_iter()
+                            .map(|url| BodyUrl {
+                                url,
+                                kind: UrlKind::Text,
+                                part_id,
+                                text: None,
+                            }),
+                    );
+                }
+                Some(PartType::Html(html)) => {
+                    urls.extend(decoders::url::html_urls(html).into_iter().map(
+                        |(kind, url, text)| BodyUrl {
+                            url,
+                            kind,
+                            part_id,
+                            text,
+                        },
+                    ));
+                }
+                _ => (),
+            }
+        }
+        urls
+    }
+
+    /// Returns the part with the given Content-ID, which may be enclosed in
+    /// angle brackets.
+    pub fn part_by_content_id(&self, content_id: &str) -> Option<&MessagePart<'x>> {
+        let content_id = content_id
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>');
+        self.parts
+            .iter()
+            .find(|part| part.content_id() == Some(content_id))
+    }
+
+    /// Returns the first part with the given Content-Location, which may be
+    /// enclosed in angle brackets.
+    pub fn part_by_content_location(&self, location: &str) -> Option<&MessagePart<'x>> {
+        let location = location
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>');
+        self.parts
+            .iter()
+            .find(|part| part.content_location() == Some(location))
+    }
+
     /// Returns a message body part as text/plain
     pub fn body_text(&'x self, pos: usize) -> Option<Cow<'x, str>> {
         let part = self.parts.get(*self.text_body.get(pos)?)?;
@@ -1315,6 +3317,59 @@ impl<'x> Message<'x> {
         }
     }
 
+    /// Returns the body a mail client would display, in the preferred
+    /// format. All the parts of the preferred body are joined, so that the
+    /// inline parts of a multipart/mixed, such as a text part followed by
+    /// an HTML signature, are displayed in full, and the parts in the other
+    /// format are converted. HTML bodies are joined without their document
+    /// structure. When the preferred body is missing or blank, the other
+    /// body is converted instead.
+    pub fn best_body(&'x self, preference: BodyPreference) -> Option<Cow<'x, str>> {
+        let lists = match preference {
+            BodyPreference::Html => [&self.html_body, &self.text_body],
+            BodyPreference::Text => [&self.text_body, &self.html_body],
+        };
+
+        for list in lists {
+            let mut bodies = list.iter().filter_map(|&part_id| {
+                match (&self.parts.get(part_id)?.body, preference) {
+                    (PartType::Html(body) | PartType::Text(body), _) if body.trim().is_empty() => {
+                        None
+                    }
+                    (PartType::Html(body), BodyPreference::Html)
+                    | (PartType::Text(body), BodyPreference::Text) => {
+                        Some(Cow::Borrowed(body.as_ref()))
+                    }
+                    (PartType::Text(text), BodyPreference::Html) => Some(text_to_html(text).into()),
+                    (PartType::Html(html), BodyPreference::Text) => Some(html_to_text(html).into()),
+                    _ => None,
+                }
+            });
+
+            if let Some(body) = bodies.next() {
+                return Some(match bodies.next() {
+                    Some(next_body) => {
+                        let strip = |body: Cow<'_, str>| match preference {
+                            BodyPreference::Html => strip_document(&body),
+                            BodyPreference::Text => body.into_owned(),
+                        };
+                        let mut body = strip(body);
+                        for next_body in std::iter::once(next_body).chain(bodies) {
+                            if !body.ends_with('\n') {
+                                body.push('\n');
+                            }
+                            body.push_str(&strip(next_body));
+                        }
+                        body.into()
+                    }
+                    None => body,
+                });
+            }
+        }
+
+        None
+    }
+
     /// Returns a message part by position
     pub fn part(&self, pos: usize) -> Option<&MessagePart> {
         self.parts.get(pos)
@@ -1365,6 +3420,66 @@ impl<'x> Message<'x> {
         AttachmentIterator::new(self)
     }
 
+    /// Returns an Iterator over the name, Content-Type and contents of the
+    /// attachments of the message. Attached messages are not returned
+    /// themselves, their attachments are returned instead, at any depth.
+    /// See [`AttachmentTreeIterator::deduplicate`] to skip identical
+    /// attachments.
+    pub fn attachments_iter(&'x self) -> AttachmentTreeIterator<'x> {
+        AttachmentTreeIterator {
+            stack: vec![(self, self.attachments.iter())],
+            seen: None,
+        }
+    }
+
+    /// Returns an Iterator over the text body resolved to the parts it
+    /// refers to, skipping the ids that don't refer to a text, HTML or
+    /// binary part of this message
+    pub fn text_body_refs(&'x self) -> BodyRefIterator<'x> {
+        BodyRefIterator {
+            message: self,
+            list: self.text_body.iter(),
+        }
+    }
+
+    /// Returns an Iterator over the HTML body resolved to the parts it
+    /// refers to, skipping the ids that don't refer to a text, HTML or
+    /// binary part of this message
+    pub fn html_body_refs(&'x self) -> BodyRefIterator<'x> {
+        BodyRefIterator {
+            message: self,
+            list: self.html_body.iter(),
+        }
+    }
+
+    /// Returns an Iterator over the MIME tree of the message in document
+    /// order, without descending into nested messages.
+    pub fn parts(&'x self) -> PartIterator<'x> {
+        PartIterator::new(self, false)
+    }
+
+    /// Returns an Iterator over the MIME tree of the message in document
+    /// order, including the parts of nested messages.
+    pub fn walk(&'x self) -> PartIterator<'x> {
+        PartIterator::new(self, true)
+    }
+
+    /// Returns the MIME tree of the message, including the parts of nested
+    /// messages, as nodes linked to their children in document order. Parts
+    /// nested more than 1000 levels deep are left out.
+    pub fn mime_tree(&'x self) -> MimeNode<'x> {
+        MimeNode::new(self, 0)
+    }
+
+    /// Returns the id of the multipart part containing a part, or `None`
+    /// for the root part. Only the parts of this message are searched, the
+    /// root part of a nested message has no parent within that message.
+    pub fn parent_id(&self, part_id: MessagePartId) -> Option<MessagePartId> {
+        self.parts
+            .iter()
+            .position(|part| matches!(&part.body, PartType::Multipart(children) if children.contains(&part_id)))
+    }
+
     /// Returns an owned version of the message
     pub fn into_owned<'y>(self) -> Message<'y> {
         Message {
@@ -1373,6 +3488,7 @@ impl<'x> Message<'x> {
             attachments: self.attachments,
             parts: self.parts.into_iter().map(|p| p.into_owned()).collect(),
             raw_message: self.raw_message.into_owned().into(),
+            warnings: self.warnings,
         }
     }
 }
@@ -1383,7 +3499,7 @@ pub trait MimeHeaders<'x> {
     fn content_description(&self) -> Option<&str>;
     /// Returns the Content-Disposition field
     fn content_disposition(&self) -> Option<&ContentType>;
-    /// Returns the Content-ID field
+    /// Returns the Content-ID field, without the angle brackets
     fn content_id(&self) -> Option<&str>;
     /// Returns the Content-Encoding field
     fn content_transfer_encoding(&self) -> Option<&str>;
@@ -1391,7 +3507,8 @@ pub trait MimeHeaders<'x> {
     fn content_type(&self) -> Option<&ContentType>;
     /// Returns the Content-Language field
     fn content_language(&self) -> &HeaderValue;
-    /// Returns the Content-Location field
+    /// Returns the Content-Location field, without the enclosing angle
+    /// brackets or quotes and the whitespace added when folding it
     fn content_location(&self) -> Option<&str>;
     /// Returns the attachment name, if any.
     fn attachment_name(&self) -> Option<&str> {
@@ -1399,6 +3516,21 @@ pub trait MimeHeaders<'x> {
             .and_then(|cd| cd.attribute("filename"))
             .or_else(|| self.content_type().and_then(|ct| ct.attribute("name")))
     }
+    /// Returns the attachment name from either the filename parameter of the
+    /// Content-Disposition field or the name parameter of the Content-Type
+    /// field, with RFC 2231 continuations and RFC 2047 encoded words decoded
+    /// and sanitized for use as a file name, see
+    /// [`sanitize_file_name`].
+    fn attachment_file_name(&self) -> Option<Cow<'_, str>> {
+        self.content_disposition()
+            .and_then(|cd| cd.attribute("filename"))
+            .and_then(sanitize_file_name)
+            .or_else(|| {
+                self.content_type()
+                    .and_then(|ct| ct.attribute("name"))
+                    .and_then(sanitize_file_name)
+            })
+    }
     // Returns true is the content type matches
     fn is_content_type(&self, type_: &str, subtype: &str) -> bool {
         self.content_type().map_or(false, |ct| {
@@ -1465,6 +3597,12 @@ impl<'x> MimeHeaders<'x> for Message<'x> {
 impl<'x> MessagePart<'x> {
     /// Returns the body part's contents as a `u8` slice
     pub fn contents(&'x self) -> &'x [u8] {
+        self.body_bytes()
+    }
+
+    // Same as `contents`, without tying the borrow to the lifetime of the
+    // parsed data.
+    pub(crate) fn body_bytes(&self) -> &[u8] {
         match &self.body {
             PartType::Text(text) | PartType::Html(text) => text.as_bytes(),
             PartType::Binary(bin) | PartType::InlineBinary(bin) => bin.as_ref(),
@@ -1513,11 +3651,54 @@ impl<'x> MessagePart<'x> {
         }
     }
 
+    /// Returns the size of the decoded body of this part, which for
+    /// multipart parts and nested messages is the size of their raw body.
+    pub fn size(&self) -> usize {
+        match &self.body {
+            PartType::Multipart(_) => self.encoded_size(),
+            PartType::Message(_) if self.encoding == Encoding::None => self.encoded_size(),
+            _ => self.len(),
+        }
+    }
+
+    /// Returns the size of the undecoded body of this part in the raw
+    /// message.
+    pub fn encoded_size(&self) -> usize {
+        self.offset_end.saturating_sub(self.offset_body)
+    }
+
+    /// Returns the number of lines of the undecoded body of this part, as
+    /// reported in the IMAP BODYSTRUCTURE of text parts and nested messages.
+    pub fn lines(&self) -> usize {
+        self.line_count
+    }
+
     /// Returns `true` when the body part MIME type is text/*
     pub fn is_text(&self) -> bool {
         matches!(self.body, PartType::Text(_) | PartType::Html(_))
     }
 
+    /// Returns the kind of a text part, or `None` if the part is not text.
+    /// Calendar and vCard parts are decoded like any other text part,
+    /// honoring their charset parameter.
+    pub fn text_kind(&self) -> Option<TextKind> {
+        match &self.body {
+            PartType::Html(_) => Some(TextKind::Html),
+            PartType::Text(_) => Some(
+                match self
+                    .content_type()
+                    .map(|ct| (ct.ctype(), ct.subtype().unwrap_or_default()))
+                {
+                    None | Some(("text", "plain")) => TextKind::Plain,
+                    Some(("text", "calendar") | ("application", "ics")) => TextKind::Calendar,
+                    Some(("text", "vcard" | "x-vcard" | "directory")) => TextKind::VCard,
+                    Some(_) => TextKind::Other,
+                },
+            ),
+            _ => None,
+        }
+    }
+
     /// Returns `true` when the body part MIME type is text/tml
     pub fn is_text_html(&self) -> bool {
         matches!(self.body, PartType::Html(_))
@@ -1528,6 +3709,33 @@ impl<'x> MessagePart<'x> {
         matches!(self.body, PartType::Binary(_) | PartType::InlineBinary(_))
     }
 
+    /// Returns the media type of a binary part detected from the magic
+    /// number of its decoded contents, or `None` if the part is not binary
+    /// or its contents match no known signature.
+    #[cfg(feature = "content_sniffing")]
+    pub fn detected_media_type(&self) -> Option<&'static str> {
+        match &self.body {
+            PartType::Binary(bin) | PartType::InlineBinary(bin) => {
+                parsers::sniff::detect_media_type(bin)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns `true` when the detected media type of a binary part, see
+    /// [`MessagePart::detected_media_type`], contradicts its declared
+    /// Content-Type, such as an executable declared as application/pdf.
+    #[cfg(feature = "content_sniffing")]
+    pub fn is_media_type_mismatch(&self) -> bool {
+        match (self.content_type(), self.detected_media_type()) {
+            (Some(ct), Some(detected)) => !parsers::sniff::is_media_type_consistent(
+                &format!("{}/{}", ct.ctype(), ct.subtype().unwrap_or_default()),
+                detected,
+            ),
+            _ => false,
+        }
+    }
+
     /// Returns `true` when the part is multipart
     pub fn is_multipart(&self) -> bool {
         matches!(self.body, PartType::Multipart(_))
@@ -1548,6 +3756,22 @@ impl<'x> MessagePart<'x> {
         &self.headers
     }
 
+    /// Returns the raw text preceding the first boundary of a multipart
+    /// part, without the line break before the boundary. Only available
+    /// when [`ParseOptions::preserve_preamble`] is enabled and the preamble
+    /// is not empty.
+    pub fn preamble(&self) -> Option<&[u8]> {
+        self.preamble.as_deref()
+    }
+
+    /// Returns the raw text following the closing boundary of a multipart
+    /// part, up to the end of its parent part. Only available when
+    /// [`ParseOptions::preserve_preamble`] is enabled and the epilogue is
+    /// not empty.
+    pub fn epilogue(&self) -> Option<&[u8]> {
+        self.epilogue.as_deref()
+    }
+
     /// Returns the body raw length
     pub fn raw_len(&self) -> usize {
         self.offset_end.saturating_sub(self.offset_header)
@@ -1568,6 +3792,24 @@ impl<'x> MessagePart<'x> {
         self.offset_end
     }
 
+    /// Returns the byte range of this part in the raw message, headers
+    /// included.
+    pub fn raw_range(&self) -> Range<usize> {
+        self.offset_header..self.offset_end
+    }
+
+    /// Returns the byte range of the header block of this part in the raw
+    /// message.
+    pub fn raw_header_range(&self) -> Range<usize> {
+        self.offset_header..self.offset_body
+    }
+
+    /// Returns the byte range of the undecoded body of this part in the raw
+    /// message.
+    pub fn raw_body_range(&self) -> Range<usize> {
+        self.offset_body..self.offset_end
+    }
+
     /// Returns an owned version of the this part
     pub fn into_owned<'y>(self) -> MessagePart<'y> {
         MessagePart {
@@ -1585,6 +3827,10 @@ impl<'x> MessagePart<'x> {
             offset_header: self.offset_header,
             offset_body: self.offset_body,
             offset_end: self.offset_end,
+            line_count: self.line_count,
+            statistics: self.statistics,
+            preamble: self.preamble.map(|v| v.into_owned().into()),
+            epilogue: self.epilogue.map(|v| v.into_owned().into()),
         }
     }
 }
@@ -1644,16 +3890,44 @@ pub trait GetHeader {
     fn header(&self, name: &str) -> Option<&Header>;
 }
 
+impl<'x> GetHeader for [Header<'x>] {
+    fn rfc(&self, name: &RfcHeader) -> Option<&HeaderValue<'x>> {
+        headers_rfc(self, name)
+    }
+
+    fn header(&self, name: &str) -> Option<&Header<'x>> {
+        headers_find(self, name)
+    }
+}
+
 impl<'x> GetHeader for Vec<Header<'x>> {
     fn rfc(&self, name: &RfcHeader) -> Option<&HeaderValue<'x>> {
-        self.iter()
-            .rev()
-            .find(|header| matches!(&header.name, HeaderName::Rfc(rfc_name) if rfc_name == name))
-            .map(|header| &header.value)
+        headers_rfc(self, name)
     }
 
-    fn header(&self, name: &str) -> Option<&Header> {
-        self.iter()
+    fn header(&self, name: &str) -> Option<&Header<'x>> {
+        headers_find(self, name)
+    }
+}
+
+fn headers_rfc<'y, 'x>(headers: &'y [Header<'x>], name: &RfcHeader) -> Option<&'y HeaderValue<'x>> {
+    headers
+        .iter()
+        .rev()
+        .find(|header| matches!(&header.name, HeaderName::Rfc(rfc_name) if rfc_name == name))
+        .map(|header| &header.value)
+}
+
+fn headers_find<'y, 'x>(headers: &'y [Header<'x>], name: &str) -> Option<&'y Header<'x>> {
+    // RFC header fields are matched by their parsed name
+    if let Some(HeaderName::Rfc(rfc_name)) = HeaderName::parse(name) {
+        headers
+            .iter()
+            .rev()
+            .find(|header| matches!(&header.name, HeaderName::Rfc(name) if name == &rfc_name))
+    } else {
+        headers
+            .iter()
             .rev()
             .find(|header| header.name.as_str().eq_ignore_ascii_case(name))
     }
@@ -1706,6 +3980,370 @@ impl<'x> Iterator for AttachmentIterator<'x> {
     }
 }
 
+/// Iterator over the attachments of a message and its attached messages,
+/// see [`Message::attachments_iter`].
+pub struct AttachmentTreeIterator<'x> {
+    stack: Vec<(&'x Message<'x>, std::slice::Iter<'x, MessagePartId>)>,
+    seen: Option<HashMap<u64, Vec<&'x [u8]>>>,
+}
+
+impl<'x> AttachmentTreeIterator<'x> {
+    /// Skips the attachments with the same contents as an attachment
+    /// already returned, regardless of their name and Content-Type.
+    pub fn deduplicate(mut self) -> Self {
+        self.seen = Some(HashMap::new());
+        self
+    }
+}
+
+impl<'x> Iterator for AttachmentTreeIterator<'x> {
+    type Item = (Option<&'x str>, Option<&'x ContentType<'x>>, &'x [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (message, part_ids) = self.stack.last_mut()?;
+            let message: &'x Message<'x> = message;
+            let part = if let Some(part_id) = part_ids.next() {
+                if let Some(part) = message.parts.get(*part_id) {
+                    part
+                } else {
+                    continue;
+                }
+            } else {
+                self.stack.pop();
+                continue;
+            };
+
+            if let PartType::Message(nested) = &part.body {
+                self.stack.push((nested, nested.attachments.iter()));
+                continue;
+            }
+
+            let contents = part.contents();
+            if let Some(seen) = &mut self.seen {
+                let mut hasher = DefaultHasher::new();
+                contents.hash(&mut hasher);
+                let same_hash = seen.entry(hasher.finish()).or_default();
+                if same_hash.contains(&contents) {
+                    continue;
+                }
+                same_hash.push(contents);
+            }
+
+            return Some((part.attachment_name(), part.content_type(), contents));
+        }
+    }
+}
+
+/// A part visited while traversing the MIME tree of a message, along with
+/// the message it belongs to and its depth in the tree, starting at zero
+/// for the root part.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PartRef<'x> {
+    /// A multipart part, visited before its children.
+    Multipart {
+        message: &'x Message<'x>,
+        part_id: MessagePartId,
+        depth: usize,
+    },
+    /// A nested message, visited before its parts when walking the tree.
+    Message {
+        message: &'x Message<'x>,
+        part_id: MessagePartId,
+        depth: usize,
+        nested: &'x Message<'x>,
+    },
+    /// A text, HTML or binary part.
+    Leaf {
+        message: &'x Message<'x>,
+        part_id: MessagePartId,
+        depth: usize,
+    },
+}
+
+impl<'x> PartRef<'x> {
+    /// Returns the message the part belongs to
+    pub fn message(&self) -> &'x Message<'x> {
+        match self {
+            PartRef::Multipart { message, .. }
+            | PartRef::Message { message, .. }
+            | PartRef::Leaf { message, .. } => message,
+        }
+    }
+
+    /// Returns the id of the part within its message
+    pub fn part_id(&self) -> MessagePartId {
+        match self {
+            PartRef::Multipart { part_id, .. }
+            | PartRef::Message { part_id, .. }
+            | PartRef::Leaf { part_id, .. } => *part_id,
+        }
+    }
+
+    /// Returns the depth of the part in the MIME tree
+    pub fn depth(&self) -> usize {
+        match self {
+            PartRef::Multipart { depth, .. }
+            | PartRef::Message { depth, .. }
+            | PartRef::Leaf { depth, .. } => *depth,
+        }
+    }
+
+    /// Returns the part
+    pub fn part(&self) -> &'x MessagePart<'x> {
+        &self.message().parts[self.part_id()]
+    }
+
+    /// Returns the Content-Type header field of the part
+    pub fn content_type(&self) -> Option<&'x ContentType<'x>> {
+        self.part().content_type()
+    }
+}
+
+/// A part of the MIME tree of a message along with its children, see
+/// [`Message::mime_tree`]. The child of a nested message is the root part
+/// of that message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MimeNode<'x> {
+    /// The message the part belongs to.
+    pub message: &'x Message<'x>,
+    pub part_id: MessagePartId,
+    pub children: Vec<MimeNode<'x>>,
+}
+
+// Deepest MIME tree returned by [`Message::mime_tree`], as its nodes are
+// dropped recursively.
+const MAX_MIME_TREE_DEPTH: usize = 1000;
+
+impl<'x> MimeNode<'x> {
+    fn new(message: &'x Message<'x>, part_id: MessagePartId) -> Self {
+        // Depth-first traversal, the nodes of the children of each part are
+        // kept at the end of `nodes` until the part itself is built
+        let mut nodes = Vec::new();
+        let mut stack = vec![(
+            message,
+            part_id,
+            MimeNode::child_ids(message, part_id).into_iter(),
+            0,
+        )];
+        while let Some((message, part_id, children, start)) = stack.last_mut() {
+            if let Some((child_message, child_id)) = children.next() {
+                let children = if stack.len() + 1 < MAX_MIME_TREE_DEPTH {
+                    MimeNode::child_ids(child_message, child_id)
+                } else {
+                    Vec::new()
+                };
+                stack.push((child_message, child_id, children.into_iter(), nodes.len()));
+                continue;
+            }
+
+            let node = MimeNode {
+                message,
+                part_id: *part_id,
+                children: nodes.split_off(*start),
+            };
+            stack.pop();
+            nodes.push(node);
+        }
+
+        nodes.pop().unwrap_or(MimeNode {
+            message,
+            part_id,
+            children: Vec::new(),
+        })
+    }
+
+    // Returns the children of a part, or the root part of a nested message.
+    fn child_ids(
+        message: &'x Message<'x>,
+        part_id: MessagePartId,
+    ) -> Vec<(&'x Message<'x>, MessagePartId)> {
+        match message.parts.get(part_id).map(|part| &part.body) {
+            Some(PartType::Multipart(children)) => children
+                .iter()
+                .map(|&child_id| (message, child_id))
+                .collect(),
+            Some(PartType::Message(nested)) if !nested.parts.is_empty() => vec![(nested, 0)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the part
+    pub fn part(&self) -> &'x MessagePart<'x> {
+        &self.message.parts[self.part_id]
+    }
+
+    /// Returns the number of parts in this subtree, including this one
+    pub fn part_count(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            count += 1;
+            stack.extend(&node.children);
+        }
+        count
+    }
+}
+
+/// An entry of the text or HTML body of a message resolved to the part it
+/// refers to. Either list may contain parts of the other type when
+/// alternatives are generated, as well as the inline binary parts, such as
+/// images, displayed between the text parts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyRef<'x> {
+    /// A text/plain part.
+    Text {
+        part_id: MessagePartId,
+        part: &'x MessagePart<'x>,
+        text: &'x str,
+    },
+    /// A text/html part.
+    Html {
+        part_id: MessagePartId,
+        part: &'x MessagePart<'x>,
+        html: &'x str,
+    },
+    /// A binary part displayed inline.
+    InlineBinary {
+        part_id: MessagePartId,
+        part: &'x MessagePart<'x>,
+        contents: &'x [u8],
+    },
+}
+
+impl<'x> BodyRef<'x> {
+    fn new(message: &'x Message<'x>, part_id: MessagePartId) -> Option<Self> {
+        let part = message.parts.get(part_id)?;
+        Some(match &part.body {
+            PartType::Text(text) => BodyRef::Text {
+                part_id,
+                part,
+                text: text.as_ref(),
+            },
+            PartType::Html(html) => BodyRef::Html {
+                part_id,
+                part,
+                html: html.as_ref(),
+            },
+            PartType::Binary(contents) | PartType::InlineBinary(contents) => {
+                BodyRef::InlineBinary {
+                    part_id,
+                    part,
+                    contents: contents.as_ref(),
+                }
+            }
+            PartType::Message(_) | PartType::Multipart(_) => return None,
+        })
+    }
+
+    /// Returns the id of the part within the message
+    pub fn part_id(&self) -> MessagePartId {
+        match self {
+            BodyRef::Text { part_id, .. }
+            | BodyRef::Html { part_id, .. }
+            | BodyRef::InlineBinary { part_id, .. } => *part_id,
+        }
+    }
+
+    /// Returns the part
+    pub fn part(&self) -> &'x MessagePart<'x> {
+        match self {
+            BodyRef::Text { part, .. }
+            | BodyRef::Html { part, .. }
+            | BodyRef::InlineBinary { part, .. } => part,
+        }
+    }
+
+    /// Returns the contents of the part, decoded as text for the text and
+    /// HTML parts
+    pub fn contents(&self) -> &'x [u8] {
+        match self {
+            BodyRef::Text { text, .. } => text.as_bytes(),
+            BodyRef::Html { html, .. } => html.as_bytes(),
+            BodyRef::InlineBinary { contents, .. } => contents,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct BodyRefIterator<'x> {
+    message: &'x Message<'x>,
+    list: std::slice::Iter<'x, MessagePartId>,
+}
+
+impl<'x> Iterator for BodyRefIterator<'x> {
+    type Item = BodyRef<'x>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let message = self.message;
+        self.list
+            .by_ref()
+            .find_map(|&part_id| BodyRef::new(message, part_id))
+    }
+}
+
+#[doc(hidden)]
+pub struct PartIterator<'x> {
+    stack: Vec<(&'x Message<'x>, MessagePartId, usize)>,
+    walk_nested: bool,
+}
+
+impl<'x> PartIterator<'x> {
+    fn new(message: &'x Message<'x>, walk_nested: bool) -> PartIterator<'x> {
+        PartIterator {
+            stack: vec![(message, 0, 0)],
+            walk_nested,
+        }
+    }
+}
+
+impl<'x> Iterator for PartIterator<'x> {
+    type Item = PartRef<'x>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (message, part_id, depth) = self.stack.pop()?;
+            let part = if let Some(part) = message.parts.get(part_id) {
+                part
+            } else {
+                continue;
+            };
+
+            return Some(match &part.body {
+                PartType::Multipart(children) => {
+                    self.stack.extend(
+                        children
+                            .iter()
+                            .rev()
+                            .map(|&child_id| (message, child_id, depth + 1)),
+                    );
+                    PartRef::Multipart {
+                        message,
+                        part_id,
+                        depth,
+                    }
+                }
+                PartType::Message(nested) => {
+                    if self.walk_nested {
+                        self.stack.push((nested, 0, depth + 1));
+                    }
+                    PartRef::Message {
+                        message,
+                        part_id,
+                        depth,
+                        nested,
+                    }
+                }
+                _ => PartRef::Leaf {
+                    message,
+                    part_id,
+                    depth,
+                },
+            });
+        }
+    }
+}
+
 /// An RFC2047 Content-Type or RFC2183 Content-Disposition MIME header field.
 impl<'x> ContentType<'x> {
     /// Returns the type
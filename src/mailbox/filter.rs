@@ -0,0 +1,286 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::ops::Range;
+
+use crate::{parsers::MessageStream, Domain, GetHeader, Header, HeaderValue, RfcHeader};
+
+use super::{maildir, mbox};
+
+/// A message read from a mailbox.
+pub trait MailboxMessage {
+    /// Returns the raw message.
+    fn contents(&self) -> &[u8];
+    /// Returns the date the message was added to the mailbox in seconds
+    /// since UNIX epoch, or zero when unknown.
+    fn internal_date(&self) -> u64;
+}
+
+impl MailboxMessage for mbox::Message {
+    fn contents(&self) -> &[u8] {
+        self.contents()
+    }
+
+    fn internal_date(&self) -> u64 {
+        self.internal_date()
+    }
+}
+
+impl MailboxMessage for maildir::Message {
+    fn contents(&self) -> &[u8] {
+        self.contents()
+    }
+
+    fn internal_date(&self) -> u64 {
+        self.internal_date()
+    }
+}
+
+/// Criteria that the messages read from a mailbox must match, evaluated by
+/// parsing their header fields only.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MessageFilter {
+    /// Range of dates, in seconds since UNIX epoch, of the matching
+    /// messages. The Date header field is used, or the internal date when
+    /// it is missing or invalid.
+    pub date_range: Option<Range<i64>>,
+    /// Domains of the From address of the matching messages, including
+    /// their subdomains. Any domain matches when empty.
+    pub sender_domains: Vec<String>,
+    /// Whether the matching messages have attachments, see
+    /// [`MessageFilter::has_attachment`].
+    pub has_attachment: Option<bool>,
+}
+
+/// Iterator over the messages of a mailbox matching a [`MessageFilter`].
+/// Errors are passed through.
+pub struct FilteredMessages<I> {
+    messages: I,
+    filter: MessageFilter,
+}
+
+impl MessageFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches the messages dated between `start`, inclusive, and `end`,
+    /// exclusive, in seconds since UNIX epoch.
+    pub fn date_range(mut self, start: i64, end: i64) -> Self {
+        self.date_range = Some(start..end);
+        self
+    }
+
+    /// Matches the messages sent from `domain` or any of its subdomains.
+    /// Can be called multiple times to match any of several domains.
+    pub fn sender_domain(mut self, domain: impl Into<String>) -> Self {
+        self.sender_domains.push(domain.into());
+        self
+    }
+
+    /// Matches the messages with or without attachments. As only the header
+    /// fields are parsed, a message is considered to have attachments when
+    /// its Content-Type is multipart/mixed or its root part is itself an
+    /// attachment.
+    pub fn has_attachment(mut self, has_attachment: bool) -> Self {
+        self.has_attachment = Some(has_attachment);
+        self
+    }
+
+    /// Returns the messages matching the filter.
+    pub fn filter<I, M, E>(self, messages: I) -> FilteredMessages<I::IntoIter>
+    where
+        I: IntoIterator<Item = Result<M, E>>,
+        M: MailboxMessage,
+    {
+        FilteredMessages {
+            messages: messages.into_iter(),
+            filter: self,
+        }
+    }
+
+    /// Returns `true` if a message read from a mailbox matches the filter.
+    pub fn matches(&self, message: &impl MailboxMessage) -> bool {
+        self.matches_raw(message.contents(), message.internal_date())
+    }
+
+    /// Returns `true` if a raw message matches the filter, using
+    /// `internal_date` when it lacks a valid Date header field.
+    pub fn matches_raw(&self, raw_message: &[u8], internal_date: u64) -> bool {
+        let mut headers = Vec::new();
+        MessageStream::new(raw_message).parse_headers(&mut headers);
+
+        if let Some(date_range) = &self.date_range {
+            let date = match headers.rfc(&RfcHeader::Date) {
+                Some(HeaderValue::DateTime(date)) if date.is_valid() => date.to_timestamp(),
+                _ => internal_date as i64,
+            };
+            if !date_range.contains(&date) {
+                return false;
+            }
+        }
+
+        if !self.sender_domains.is_empty() && !self.matches_sender(&headers) {
+            return false;
+        }
+
+        match self.has_attachment {
+            Some(has_attachment) => has_attachment == has_attachments(&headers),
+            None => true,
+        }
+    }
+
+    fn matches_sender(&self, headers: &[Header<'_>]) -> bool {
+        headers
+            .rfc(&RfcHeader::From)
+            .into_iter()
+            .flat_map(|value| value.flatten_addresses())
+            .filter_map(|addr| match addr.domain()? {
+                Domain::Name(domain) => Some(domain),
+                _ => None,
+            })
+            .any(|domain| {
+                let domain = domain.as_bytes();
+                self.sender_domains.iter().any(|filter| {
+                    let filter = filter.trim_start_matches('.').as_bytes();
+                    domain.len() >= filter.len()
+                        && domain[domain.len() - filter.len()..].eq_ignore_ascii_case(filter)
+                        && (domain.len() == filter.len()
+                            || domain[domain.len() - filter.len() - 1] == b'.')
+                })
+            })
+    }
+}
+
+fn has_attachments(headers: &[Header<'_>]) -> bool {
+    let content_type = headers
+        .rfc(&RfcHeader::ContentType)
+        .and_then(|value| value.as_content_type_ref());
+    content_type.is_some_and(|ct| {
+        ct.ctype().eq_ignore_ascii_case("multipart")
+            && ct
+                .subtype()
+                .is_some_and(|st| st.eq_ignore_ascii_case("mixed"))
+    }) || headers
+        .rfc(&RfcHeader::ContentDisposition)
+        .and_then(|value| value.as_content_type_ref())
+        .is_some_and(|cd| cd.is_attachment())
+}
+
+impl<I, M, E> Iterator for FilteredMessages<I>
+where
+    I: Iterator<Item = Result<M, E>>,
+    M: MailboxMessage,
+{
+    type Item = Result<M, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.messages.next()? {
+                Ok(message) if !self.filter.matches(&message) => continue,
+                result => return Some(result),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mailbox::mbox::MessageIterator;
+
+    use super::MessageFilter;
+
+    #[test]
+    fn filter_mbox() {
+        let mbox = concat!(
+            "From art@vandelay.com Sat Jan  3 01:05:34 1996\n",
+            "From: Art Vandelay <art@vandelay.com>\n",
+            "Date: Sat, 20 Nov 2021 14:22:01 -0800\n",
+            "Subject: Latex\n",
+            "\n",
+            "Imports\n",
+            "\n",
+            "From george@mail.vandelay.com Tue Jul 23 19:39:23 2002\n",
+            "From: george@mail.vandelay.com\n",
+            "Subject: No date\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\n",
+            "\n",
+            "--b--\n",
+            "\n",
+            "From jerry@example.com Tue Aug  6 13:34:34 2002\n",
+            "From: jerry@example.com\n",
+            "Date: Mon, 1 Jan 2024 00:00:00 +0000\n",
+            "Subject: Other domain\n",
+            "\n",
+            "Hello\n",
+            "\n",
+            "From kramer@caf\u{e9} Wed Aug  7 13:34:34 2002\n",
+            "From: kramer@caf\u{e9}\n",
+            "Date: Mon, 1 Jan 2024 00:00:00 +0000\n",
+            "Subject: Non-ASCII domain\n",
+            "\n",
+            "Hello\n",
+        );
+        let subjects = |filter: MessageFilter| {
+            filter
+                .filter(MessageIterator::new(mbox.as_bytes()))
+                .map(|message| {
+                    let message = message.unwrap();
+                    let contents = std::str::from_utf8(message.contents()).unwrap();
+                    contents
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Subject: "))
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            subjects(MessageFilter::new()),
+            ["Latex", "No date", "Other domain", "Non-ASCII domain"]
+        );
+        assert_eq!(
+            subjects(MessageFilter::new().sender_domain("vandelay.com")),
+            ["Latex", "No date"]
+        );
+        assert_eq!(
+            subjects(
+                MessageFilter::new()
+                    .sender_domain("VANDELAY.COM")
+                    .has_attachment(false)
+            ),
+            ["Latex"]
+        );
+        assert_eq!(
+            subjects(MessageFilter::new().sender_domain("andelay.com")),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            subjects(MessageFilter::new().sender_domain("x")),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            subjects(MessageFilter::new().sender_domain("caf\u{e9}")),
+            ["Non-ASCII domain"]
+        );
+        // The internal date from the mbox separator is used when the Date
+        // header field is missing
+        assert_eq!(
+            subjects(MessageFilter::new().date_range(1000000000, 1100000000)),
+            ["No date"]
+        );
+        assert_eq!(
+            subjects(MessageFilter::new().date_range(1600000000, i64::MAX)),
+            ["Latex", "Other domain", "Non-ASCII domain"]
+        );
+    }
+}
@@ -1,2 +1,3 @@
+pub mod filter;
 pub mod maildir;
 pub mod mbox;
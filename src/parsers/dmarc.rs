@@ -0,0 +1,527 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{
+    decoders::{
+        html::decode_html_entities,
+        inflate::{gunzip, unzip},
+    },
+    DmarcAuthResult, DmarcPolicyPublished, DmarcRecord, DmarcReport, DmarcReportXml, Message,
+    MimeHeaders,
+};
+
+// Largest decompressed report accepted, guarding against zip bombs.
+pub(crate) const MAX_REPORT_SIZE: usize = 32 * 1024 * 1024;
+
+// Deepest nesting of XML elements accepted, well above that of aggregate
+// reports.
+const MAX_XML_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportFormat {
+    Zip,
+    Gzip,
+    Xml,
+}
+
+impl Message<'_> {
+    /// Returns the decompressed XML of the DMARC aggregate reports
+    /// (RFC 7489, Section 7.2) attached to the message. Reports are found
+    /// in `application/zip` and `application/gzip` parts, or in parts with
+    /// a `.zip`, `.gz` or `.xml` file name, and in uncompressed XML parts.
+    /// Only XML documents whose root element is `feedback` are returned,
+    /// and files decompressing to more than 32 MiB are ignored.
+    pub fn dmarc_report_xml(&self) -> Vec<DmarcReportXml> {
+        let mut reports = Vec::new();
+
+        for (part_id, part) in self.parts.iter().enumerate() {
+            if part.is_multipart() || part.is_message() {
+                continue;
+            }
+            let file_name = part.attachment_name();
+            let format = if let Some(format) = part
+                .content_type()
+                .and_then(|ct| match (ct.ctype(), ct.subtype()) {
+                    ("application", Some("zip" | "x-zip" | "x-zip-compressed")) => {
+                        Some(ReportFormat::Zip)
+                    }
+                    ("application", Some("gzip" | "x-gzip" | "x-gunzip")) => {
+                        Some(ReportFormat::Gzip)
+                    }
+                    ("application" | "text", Some("xml")) => Some(ReportFormat::Xml),
+                    _ => None,
+                })
+                .or_else(|| {
+                    let file_name = file_name?.to_ascii_lowercase();
+                    if file_name.ends_with(".zip") {
+                        Some(ReportFormat::Zip)
+                    } else if file_name.ends_with(".gz") {
+                        Some(ReportFormat::Gzip)
+                    } else if file_name.ends_with(".xml") {
+                        Some(ReportFormat::Xml)
+                    } else {
+                        None
+                    }
+                }) {
+                format
+            } else {
+                continue;
+            };
+
+            let contents = part.contents();
+            match format {
+                ReportFormat::Zip => {
+                    for (name, xml) in unzip(contents, MAX_REPORT_SIZE) {
+                        if is_aggregate_report(&xml) {
+                            reports.push(DmarcReportXml {
+                                part_id,
+                                file_name: Some(name),
+                                xml,
+                            });
+                        }
+                    }
+                }
+                ReportFormat::Gzip | ReportFormat::Xml => {
+                    let xml = if format == ReportFormat::Gzip {
+                        gunzip(contents, MAX_REPORT_SIZE)
+                    } else {
+                        Some(contents.to_vec())
+                    };
+                    if let Some(xml) = xml.filter(|xml| is_aggregate_report(xml)) {
+                        reports.push(DmarcReportXml {
+                            part_id,
+                            file_name: file_name.map(|name| name.to_string()),
+                            xml,
+                        });
+                    }
+                }
+            }
+        }
+
+        reports
+    }
+
+    /// Returns the DMARC aggregate reports attached to the message, see
+    /// [`Message::dmarc_report_xml`]. Reports that cannot be parsed are
+    /// skipped.
+    pub fn dmarc_reports(&self) -> Vec<DmarcReport> {
+        self.dmarc_report_xml()
+            .iter()
+            .filter_map(|report| DmarcReport::parse(&report.xml))
+            .collect()
+    }
+}
+
+// Returns true if the root element of an XML document is `feedback`.
+fn is_aggregate_report(xml: &[u8]) -> bool {
+    let mut pos = 0;
+    while let Some(offset) = xml[pos..].iter().position(|&ch| ch == b'<') {
+        let start = pos + offset + 1;
+        match xml.get(start) {
+            Some(b'?' | b'!') => {
+                let end = if xml[start..].starts_with(b"!--") {
+                    find(xml, start, b"-->")
+                } else {
+                    find(xml, start, b">")
+                };
+                pos = if let Some(end) = end {
+                    end
+                } else {
+                    return false;
+                };
+            }
+            _ => {
+                let name_end = xml[start..]
+                    .iter()
+                    .position(|ch| ch.is_ascii_whitespace() || b"/>".contains(ch))
+                    .map_or(xml.len(), |end| start + end);
+                let name = &xml[start..name_end];
+                return name
+                    .iter()
+                    .rposition(|&ch| ch == b':')
+                    .map_or(name, |colon| &name[colon + 1..])
+                    == b"feedback";
+            }
+        }
+    }
+    false
+}
+
+fn find(bytes: &[u8], start: usize, needle: &[u8]) -> Option<usize> {
+    bytes[start..]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| start + pos + needle.len())
+}
+
+impl DmarcReport {
+    /// Parses the XML of a DMARC aggregate report, returning `None` if it
+    /// is not well-formed or lacks the report metadata or the published
+    /// policy.
+    pub fn parse(xml: &[u8]) -> Option<DmarcReport> {
+        let root = XmlElement::parse(std::str::from_utf8(xml).ok()?)?;
+        if root.name != "feedback" {
+            return None;
+        }
+        let metadata = root.child("report_metadata")?;
+        let policy = root.child("policy_published")?;
+        let date_range = metadata.child("date_range");
+
+        Some(DmarcReport {
+            version: root.text_of("version"),
+            org_name: metadata.text_of("org_name").unwrap_or_default(),
+            email: metadata.text_of("email").unwrap_or_default(),
+            extra_contact_info: metadata.text_of("extra_contact_info"),
+            report_id: metadata.text_of("report_id").unwrap_or_default(),
+            date_begin: date_range
+                .and_then(|range| range.text_of("begin")?.parse().ok())
+                .unwrap_or_default(),
+            date_end: date_range
+                .and_then(|range| range.text_of("end")?.parse().ok())
+                .unwrap_or_default(),
+            errors: metadata
+                .children("error")
+                .map(|error| error.text.trim().to_string())
+                .collect(),
+            policy_published: DmarcPolicyPublished {
+                domain: policy.text_of("domain").unwrap_or_default(),
+                adkim: policy.text_of("adkim"),
+                aspf: policy.text_of("aspf"),
+                p: policy.text_of("p").unwrap_or_default(),
+                sp: policy.text_of("sp"),
+                pct: policy.text_of("pct").and_then(|pct| pct.parse().ok()),
+                fo: policy.text_of("fo"),
+            },
+            records: root.children("record").map(DmarcRecord::parse).collect(),
+        })
+    }
+}
+
+impl DmarcRecord {
+    fn parse(record: &XmlElement) -> DmarcRecord {
+        let row = record.child("row");
+        let evaluated = row.and_then(|row| row.child("policy_evaluated"));
+        let identifiers = record.child("identifiers");
+        let auth_results = record.child("auth_results");
+        let text_of = |element: Option<&XmlElement>, name: &str| {
+            element.and_then(|element| element.text_of(name))
+        };
+
+        DmarcRecord {
+            source_ip: text_of(row, "source_ip").unwrap_or_default(),
+            count: text_of(row, "count")
+                .and_then(|count| count.parse().ok())
+                .unwrap_or_default(),
+            disposition: text_of(evaluated, "disposition").unwrap_or_default(),
+            dkim: text_of(evaluated, "dkim").unwrap_or_default(),
+            spf: text_of(evaluated, "spf").unwrap_or_default(),
+            reasons: evaluated
+                .into_iter()
+                .flat_map(|evaluated| evaluated.children("reason"))
+                .map(|reason| {
+                    (
+                        reason.text_of("type").unwrap_or_default(),
+                        reason.text_of("comment"),
+                    )
+                })
+                .collect(),
+            envelope_to: text_of(identifiers, "envelope_to"),
+            envelope_from: text_of(identifiers, "envelope_from"),
+            header_from: text_of(identifiers, "header_from").unwrap_or_default(),
+            dkim_results: auth_results
+                .into_iter()
+                .flat_map(|results| results.children("dkim"))
+                .map(DmarcAuthResult::parse)
+                .collect(),
+            spf_results: auth_results
+                .into_iter()
+                .flat_map(|results| results.children("spf"))
+                .map(DmarcAuthResult::parse)
+                .collect(),
+        }
+    }
+}
+
+impl DmarcAuthResult {
+    fn parse(result: &XmlElement) -> DmarcAuthResult {
+        DmarcAuthResult {
+            domain: result.text_of("domain").unwrap_or_default(),
+            selector: result.text_of("selector"),
+            scope: result.text_of("scope"),
+            result: result.text_of("result").unwrap_or_default(),
+            human_result: result.text_of("human_result"),
+        }
+    }
+}
+
+// An element of an XML document, with its namespace prefix and attributes
+// removed and the text of its content concatenated.
+#[derive(Debug, Default)]
+struct XmlElement {
+    name: String,
+    text: String,
+    children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    fn parse(xml: &str) -> Option<XmlElement> {
+        let bytes = xml.as_bytes();
+        let mut stack: Vec<XmlElement> = Vec::new();
+        let mut pos = 0;
+
+        while let Some(offset) = bytes[pos..].iter().position(|&ch| ch == b'<') {
+            let start = pos + offset;
+            if let Some(element) = stack.last_mut() {
+                element
+                    .text
+                    .push_str(&decode_html_entities(&xml[pos..start]));
+            }
+
+            if bytes[start..].starts_with(b"<![CDATA[") {
+                let end = find(bytes, start, b"]]>")?;
+                stack.last_mut()?.text.push_str(&xml[start + 9..end - 3]);
+                pos = end;
+            } else if bytes[start..].starts_with(b"<!--") {
+                pos = find(bytes, start, b"-->")?;
+            } else if matches!(bytes.get(start + 1), Some(b'?' | b'!')) {
+                pos = find(bytes, start, b">")?;
+            } else {
+                let end = find(bytes, start, b">")?;
+                let tag = &xml[start + 1..end - 1];
+                let name = tag
+                    .trim_start_matches('/')
+                    .split(|ch: char| ch.is_ascii_whitespace() || ch == '/')
+                    .next()
+                    .unwrap_or_default();
+                let name = name.rsplit(':').next().unwrap_or_default();
+                pos = end;
+
+                if tag.starts_with('/') {
+                    let element = stack.pop()?;
+                    if element.name != name {
+                        return None;
+                    }
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => return Some(element),
+                    }
+                } else {
+                    let element = XmlElement {
+                        name: name.to_string(),
+                        ..Default::default()
+                    };
+                    if !tag.ends_with('/') {
+                        if stack.len() >= MAX_XML_DEPTH {
+                            return None;
+                        }
+                        stack.push(element);
+                    } else if let Some(parent) = stack.last_mut() {
+                        parent.children.push(element);
+                    } else {
+                        return Some(element);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|element| element.name == name)
+    }
+
+    fn children<'x>(&'x self, name: &'x str) -> impl Iterator<Item = &'x XmlElement> + 'x {
+        self.children
+            .iter()
+            .filter(move |element| element.name == name)
+    }
+
+    fn text_of(&self, name: &str) -> Option<String> {
+        self.child(name)
+            .map(|element| element.text.trim().to_string())
+            .filter(|text| !text.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DmarcAuthResult, DmarcPolicyPublished, DmarcRecord, DmarcReport, DmarcReportXml, Message,
+    };
+
+    use super::{XmlElement, MAX_XML_DEPTH};
+
+    #[test]
+    fn dmarc_report_xml() {
+        let message = Message::parse(concat!(
+            "From: noreply-dmarc@example.com\n",
+            "Subject: Report Domain: example.org\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\n",
+            "\n",
+            "--b\n",
+            "Content-Type: text/plain\n",
+            "\n",
+            "DMARC aggregate report.\n",
+            "--b\n",
+            "Content-Type: application/zip\n",
+            "Content-Disposition: attachment; filename=\"example.com!example.org!1700000000!1700086400.zip\"\n",
+            "Content-Transfer-Encoding: base64\n",
+            "\n",
+            "UEsDBBQAAAAIAEELUF2D3vK5GgIAAJ0EAAAxAAAAZXhhbXBsZS5jb20hZXhhbXBsZS5vcmchMTcw\n",
+            "MDAwMDAwMCExNzAwMDg2NDAwLnhtbH1UTW/bMAy951e4OezmqEm7rcsYdcGG/YLuVBSGYtOOVusD\n",
+            "kpw2/36UZTvJEDSXkI8U+R5JGB7fVZsd0Hlp9Ga+XNzOM9SlqaRuNvM/T7/zh3n2yGdwk+fZtmkc\n",
+            "NiJg5tAaF7I8p0iNWO1E+ZpRJe03887ptcRQr61wQvk1wWvt15USrsxX1IDPsgyGlpw6AhudGEil\n",
+            "C4VBVCKIiBFqXFNooZDju1C2xUVpFLAJTUmohGy5NlSiPeZ9wx8X+SkhJQ99ZMWXq7v7z9knyvue\n",
+            "lQbYKZIyiQYWTugGOeywkUT66+3wA5YQQF0l+OHLfYSjD+zsadTGrogDa1pZHgvb7Vrp9zh1NURW\n",
+            "T3pJKpVLGIjqVSrugCUDhLd178b/9Nxyh3+xDMAsB1sGvoysogHessTmWmeaS2ncSMKZt2SR7U3n\n",
+            "Siyk5ctvtMbFarEEdgLHtNJ0OvA7YMkY4aEXHkTb0VCqMRCVSm+NlyFegDYaSecZAr1EK7wnvFcb\n",
+            "Rda0SOo+yh02Knx8EI4WeW3cm3AVVsB6n3gphUQIbp5//to+bZ9Jc3h54ZHoEGFDhZEzu06a8sax\n",
+            "gKzopawlHXC8gQO2xmJRO6MuN3cZgj2KCt2VvPMAsPPqqZ/owr5w6Ls2+IlnGstHB+OxpVswjvu4\n",
+            "s9GBVGgY7uAMU562TiP+uHRJsriKjKl074x105KmuqfjZP/LiFnp7ICN3xM++wdQSwMEFAAAAAgA\n",
+            "QQtQXVELKKAgAAAAHgAAAAoAAAByZWFkbWUueG1ss7GvyM1RKEstKs7Mz7NVMtQzULK3sylKTUzJ\n",
+            "TdW3AwBQSwECFAMUAAAACABBC1Bdg97yuRoCAACdBAAAMQAAAAAAAAAAAAAAgAEAAAAAZXhhbXBs\n",
+            "ZS5jb20hZXhhbXBsZS5vcmchMTcwMDAwMDAwMCExNzAwMDg2NDAwLnhtbFBLAQIUAxQAAAAIAEEL\n",
+            "UF1RCyigIAAAAB4AAAAKAAAAAAAAAAAAAACAAWkCAAByZWFkbWUueG1sUEsFBgAAAAACAAIAlwAA\n",
+            "ALECAAAAAA==\n",
+            "--b\n",
+            "Content-Type: application/octet-stream; name=\"example.com!example.org!1700000000!1700086400.xml.gz\"\n",
+            "Content-Transfer-Encoding: base64\n",
+            "\n",
+            "H4sIAAAAAAACA31UTW/bMAy951e4OezmqEm7rcsYdcGG/YLuVBSGYtOOVusDkpw2/36UZTvJEDSX\n",
+            "kI8U+R5JGB7fVZsd0Hlp9Ga+XNzOM9SlqaRuNvM/T7/zh3n2yGdwk+fZtmkcNiJg5tAaF7I8p0iN\n",
+            "WO1E+ZpRJe03887ptcRQr61wQvk1wWvt15USrsxX1IDPsgyGlpw6AhudGEilC4VBVCKIiBFqXFNo\n",
+            "oZDju1C2xUVpFLAJTUmohGy5NlSiPeZ9wx8X+SkhJQ99ZMWXq7v7z9knyvuelQbYKZIyiQYWTugG\n",
+            "OeywkUT66+3wA5YQQF0l+OHLfYSjD+zsadTGrogDa1pZHgvb7Vrp9zh1NURWT3pJKpVLGIjqVSru\n",
+            "gCUDhLd178b/9Nxyh3+xDMAsB1sGvoysogHessTmWmeaS2ncSMKZt2SR7U3nSiyk5ctvtMbFarEE\n",
+            "dgLHtNJ0OvA7YMkY4aEXHkTb0VCqMRCVSm+NlyFegDYaSecZAr1EK7wnvFcbRda0SOo+yh02Knx8\n",
+            "EI4WeW3cm3AVVsB6n3gphUQIbp5//to+bZ9Jc3h54ZHoEGFDhZEzu06a8saxgKzopawlHXC8gQO2\n",
+            "xmJRO6MuN3cZgj2KCt2VvPMAsPPqqZ/owr5w6Ls2+IlnGstHB+OxpVswjvu4s9GBVGgY7uAMU562\n",
+            "TiP+uHRJsriKjKl074x105KmuqfjZP/LiFnp7ICN3xM++weD3vK5nQQAAA==\n",
+            "--b--\n",
+            "\n",
+        )
+        .as_bytes()).unwrap();
+        let xml = REPORT_XML.as_bytes().to_vec();
+        assert_eq!(
+            message.dmarc_report_xml(),
+            [
+                DmarcReportXml {
+                    part_id: 2,
+                    file_name: Some(
+                        "example.com!example.org!1700000000!1700086400.xml".to_string()
+                    ),
+                    xml: xml.clone(),
+                },
+                DmarcReportXml {
+                    part_id: 3,
+                    file_name: Some(
+                        "example.com!example.org!1700000000!1700086400.xml.gz".to_string()
+                    ),
+                    xml,
+                },
+            ]
+        );
+
+        assert_eq!(
+            message.dmarc_reports(),
+            vec![
+                DmarcReport {
+                    version: Some("1.0".to_string()),
+                    org_name: "example.com".to_string(),
+                    email: "noreply-dmarc@example.com".to_string(),
+                    extra_contact_info: None,
+                    report_id: "12345 & co".to_string(),
+                    date_begin: 1700000000,
+                    date_end: 1700086400,
+                    errors: vec![],
+                    policy_published: DmarcPolicyPublished {
+                        domain: "example.org".to_string(),
+                        adkim: Some("r".to_string()),
+                        aspf: Some("r".to_string()),
+                        p: "reject".to_string(),
+                        sp: None,
+                        pct: Some(100),
+                        fo: None,
+                    },
+                    records: vec![DmarcRecord {
+                        source_ip: "192.0.2.1".to_string(),
+                        count: 3,
+                        disposition: "none".to_string(),
+                        dkim: "pass".to_string(),
+                        spf: "fail".to_string(),
+                        reasons: vec![("forwarded".to_string(), Some("list".to_string()))],
+                        envelope_to: None,
+                        envelope_from: Some("example.org".to_string()),
+                        header_from: "example.org".to_string(),
+                        dkim_results: vec![DmarcAuthResult {
+                            domain: "example.org".to_string(),
+                            selector: Some("s1".to_string()),
+                            scope: None,
+                            result: "pass".to_string(),
+                            human_result: None,
+                        }],
+                        spf_results: vec![DmarcAuthResult {
+                            domain: "example.org".to_string(),
+                            selector: None,
+                            scope: Some("mfrom".to_string()),
+                            result: "fail".to_string(),
+                            human_result: None,
+                        }],
+                    }],
+                };
+                2
+            ]
+        );
+    }
+
+    #[test]
+    fn xml_nesting_limit() {
+        let nested = |depth: usize| format!("{}{}", "<a>".repeat(depth), "</a>".repeat(depth));
+
+        assert!(XmlElement::parse(&nested(MAX_XML_DEPTH)).is_some());
+        assert!(XmlElement::parse(&nested(MAX_XML_DEPTH + 1)).is_none());
+        assert!(XmlElement::parse(&nested(1_000_000)).is_none());
+    }
+
+    const REPORT_XML: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n",
+    "<!-- Aggregate report -->\n",
+    "<feedback xmlns=\"urn:ietf:params:xml:ns:dmarc-2.0\">\n",
+    "  <version>1.0</version>\n",
+    "  <report_metadata>\n",
+    "    <org_name>example.com</org_name>\n",
+    "    <email>noreply-dmarc@example.com</email>\n",
+    "    <report_id>12345 &amp; co</report_id>\n",
+    "    <date_range><begin>1700000000</begin><end>1700086400</end></date_range>\n",
+    "  </report_metadata>\n",
+    "  <policy_published>\n",
+    "    <domain>example.org</domain><adkim>r</adkim><aspf>r</aspf>\n",
+    "    <p>reject</p><pct>100</pct><sp/>\n",
+    "  </policy_published>\n",
+    "  <record>\n",
+    "    <row>\n",
+    "      <source_ip>192.0.2.1</source_ip>\n",
+    "      <count>3</count>\n",
+    "      <policy_evaluated>\n",
+    "        <disposition>none</disposition><dkim>pass</dkim><spf>fail</spf>\n",
+    "        <reason><type>forwarded</type><comment><![CDATA[list]]></comment></reason>\n",
+    "      </policy_evaluated>\n",
+    "    </row>\n",
+    "    <identifiers><envelope_from>example.org</envelope_from><header_from>example.org</header_from></identifiers>\n",
+    "    <auth_results>\n",
+    "      <dkim><domain>example.org</domain><selector>s1</selector><result>pass</result></dkim>\n",
+    "      <spf><domain>example.org</domain><scope>mfrom</scope><result>fail</result></spf>\n",
+    "    </auth_results>\n",
+    "  </record>\n",
+    "</feedback>\n",
+);
+}
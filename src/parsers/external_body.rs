@@ -0,0 +1,169 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::borrow::Cow;
+
+use crate::{AccessType, DateTime, ExternalBody, MessagePart, MimeHeaders};
+
+use super::MessageStream;
+
+impl<'x> MessagePart<'x> {
+    /// Returns the reference to external data of a message/external-body
+    /// part, or `None` for other parts or when the access-type parameter is
+    /// missing.
+    pub fn external_body(&self) -> Option<ExternalBody<'_>> {
+        let content_type = self.content_type()?;
+        if !content_type.ctype().eq_ignore_ascii_case("message")
+            || !content_type
+                .subtype()
+                .is_some_and(|st| st.eq_ignore_ascii_case("external-body"))
+        {
+            return None;
+        }
+
+        let contents = self.body_bytes();
+        let mut headers = Vec::new();
+        let mut stream = MessageStream::new(contents);
+        stream.parse_headers(&mut headers);
+        let body = contents.get(stream.offset()..).unwrap_or_default();
+
+        let attribute = |name: &str| content_type.attribute(name).map(Cow::Borrowed);
+
+        Some(ExternalBody {
+            access_type: AccessType::parse(content_type.attribute("access-type")?),
+            expiration: content_type
+                .attribute("expiration")
+                .and_then(DateTime::parse_rfc822),
+            size: content_type
+                .attribute("size")
+                .and_then(|size| size.trim().parse().ok()),
+            permission: attribute("permission"),
+            name: attribute("name"),
+            site: attribute("site"),
+            directory: attribute("directory"),
+            mode: attribute("mode"),
+            server: attribute("server"),
+            subject: attribute("subject"),
+            url: content_type.attribute("url").map(|url| {
+                if url.contains(|ch: char| ch.is_ascii_whitespace()) {
+                    url.split_ascii_whitespace().collect::<String>().into()
+                } else {
+                    url.into()
+                }
+            }),
+            headers,
+            body: String::from_utf8_lossy(body),
+        })
+    }
+}
+
+impl<'x> AccessType<'x> {
+    pub fn parse(value: &'x str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ftp" => AccessType::Ftp,
+            "anon-ftp" => AccessType::AnonFtp,
+            "tftp" => AccessType::Tftp,
+            "local-file" => AccessType::LocalFile,
+            "mail-server" => AccessType::MailServer,
+            "url" => AccessType::Url,
+            "content-id" => AccessType::ContentId,
+            _ => AccessType::Unknown(value.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AccessType, GetHeader, HeaderValue, Message, RfcHeader};
+
+    #[test]
+    fn parse_external_body() {
+        let message = Message::parse(
+            concat!(
+                "From: nsb@bellcore.com\r\n",
+                "Subject: External data\r\n",
+                "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+                "\r\n",
+                "--b\r\n",
+                "Content-Type: message/external-body; access-type=local-file;\r\n",
+                "   name=\"/u/nsb/Me.jpeg\"; site=\"thumper.bellcore.com\";\r\n",
+                "   expiration=\"Fri, 14 Jun 1991 19:13:14 -0400\"; size=1024000\r\n",
+                "\r\n",
+                "Content-Type: image/jpeg\r\n",
+                "Content-ID: <id42@guppylake.bellcore.com>\r\n",
+                "Content-Transfer-Encoding: binary\r\n",
+                "\r\n",
+                "THIS IS NOT REALLY THE BODY!\r\n",
+                "--b\r\n",
+                "Content-Type: message/external-body; access-type=URL;\r\n",
+                "   URL*0=\"ftp://\";\r\n",
+                "   URL*1=\"ftp.example.com/pub/ report.pdf\"\r\n",
+                "\r\n",
+                "Content-Type: application/pdf\r\n",
+                "\r\n",
+                "--b\r\n",
+                "Content-Type: message/external-body; access-type=mail-server;\r\n",
+                "   server=\"listserv@bogus.bitnet\"\r\n",
+                "\r\n",
+                "Content-Type: application/postscript\r\n",
+                "\r\n",
+                "get rfc-xxxx doc\r\n",
+                "--b\r\n",
+                "Content-Type: message/external-body\r\n",
+                "\r\n",
+                "--b--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let local_file = message.parts[1].external_body().unwrap();
+        assert_eq!(local_file.access_type, AccessType::LocalFile);
+        assert_eq!(local_file.name.as_deref(), Some("/u/nsb/Me.jpeg"));
+        assert_eq!(local_file.site.as_deref(), Some("thumper.bellcore.com"));
+        assert_eq!(
+            local_file.expiration.unwrap().to_rfc3339(),
+            "1991-06-14T19:13:14-04:00"
+        );
+        assert_eq!(local_file.size, Some(1024000));
+        assert_eq!(local_file.url, None);
+        assert_eq!(
+            local_file
+                .headers
+                .rfc(&RfcHeader::ContentType)
+                .and_then(|ct| ct.as_content_type_ref())
+                .map(|ct| (ct.ctype(), ct.subtype())),
+            Some(("image", Some("jpeg")))
+        );
+        assert_eq!(
+            local_file.headers.rfc(&RfcHeader::ContentId),
+            Some(&HeaderValue::Text("id42@guppylake.bellcore.com".into()))
+        );
+        assert_eq!(local_file.body, "THIS IS NOT REALLY THE BODY!");
+
+        let url = message.parts[2].external_body().unwrap();
+        assert_eq!(url.access_type, AccessType::Url);
+        assert_eq!(
+            url.url.as_deref(),
+            Some("ftp://ftp.example.com/pub/report.pdf")
+        );
+        assert_eq!(url.body, "");
+
+        let mail_server = message.parts[3].external_body().unwrap();
+        assert_eq!(mail_server.access_type, AccessType::MailServer);
+        assert_eq!(mail_server.server.as_deref(), Some("listserv@bogus.bitnet"));
+        assert_eq!(mail_server.body, "get rfc-xxxx doc");
+
+        // The access-type parameter is required
+        assert_eq!(message.parts[4].external_body(), None);
+        assert_eq!(message.parts[0].external_body(), None);
+    }
+}
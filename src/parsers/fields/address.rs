@@ -11,7 +11,9 @@
 
 use std::borrow::Cow;
 
-use crate::{parsers::MessageStream, Addr, Group, HeaderValue};
+use std::net::IpAddr;
+
+use crate::{parsers::MessageStream, Addr, AddressError, Domain, Group, HeaderValue};
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 enum AddressState {
@@ -28,6 +30,9 @@ pub struct AddressParser<'x> {
     is_token_email: bool,
     is_token_start: bool,
     is_escaped: bool,
+    is_strict: bool,
+    is_literal: bool,
+    quote_start: usize,
 
     name_tokens: Vec<Cow<'x, str>>,
     mail_tokens: Vec<Cow<'x, str>>,
@@ -81,6 +86,34 @@ impl<'x> AddressParser<'x> {
         }
     }
 
+    // Moves a quoted string followed by an at-sign from the name
+    // to the address, i.e. "john smith"@example.com
+    fn add_quoted_local_part(&mut self) {
+        let mut local_part = String::from("\"");
+        for token in self
+            .name_tokens
+            .drain(self.quote_start.min(self.name_tokens.len())..)
+        {
+            for ch in token.chars() {
+                if ch == '"' || ch == '\\' {
+                    local_part.push('\\');
+                }
+                local_part.push(ch);
+            }
+        }
+        local_part.push('"');
+
+        while self
+            .name_tokens
+            .last()
+            .is_some_and(|token| token.trim().is_empty())
+        {
+            self.name_tokens.pop();
+        }
+
+        self.mail_tokens.push(local_part.into());
+    }
+
     pub fn add_address(&mut self) {
         let has_mail = !self.mail_tokens.is_empty();
         let has_name = !self.name_tokens.is_empty();
@@ -96,22 +129,22 @@ impl<'x> AddressParser<'x> {
                     )
                     .into(),
                 ),
-                address: concat_tokens(&mut self.mail_tokens).into(),
+                address: normalize_address(concat_tokens(&mut self.mail_tokens)).into(),
             }
         } else if has_name && has_mail {
             Addr {
                 name: concat_tokens(&mut self.name_tokens).into(),
-                address: concat_tokens(&mut self.mail_tokens).into(),
+                address: normalize_address(concat_tokens(&mut self.mail_tokens)).into(),
             }
         } else if has_mail && has_comment {
             Addr {
                 name: concat_tokens(&mut self.comment_tokens).into(),
-                address: concat_tokens(&mut self.mail_tokens).into(),
+                address: normalize_address(concat_tokens(&mut self.mail_tokens)).into(),
             }
         } else if has_mail {
             Addr {
                 name: None,
-                address: concat_tokens(&mut self.mail_tokens).into(),
+                address: normalize_address(concat_tokens(&mut self.mail_tokens)).into(),
             }
         } else if has_name && has_comment {
             Addr {
@@ -196,7 +229,20 @@ impl<'x> AddressParser<'x> {
 }
 
 impl<'x> MessageStream<'x> {
+    /// Parses an address header field. Encoded-words are decoded anywhere in
+    /// a display name, including inside quoted strings, as most MUAs do.
     pub fn parse_address(&mut self) -> HeaderValue<'x> {
+        self.parse_address_(false)
+    }
+
+    /// Parses an address header field, decoding encoded-words only where
+    /// RFC 2047 (Section 5) allows them: as whole words of a display name
+    /// or inside comments, but never inside quoted strings or addr-specs.
+    pub fn parse_address_strict(&mut self) -> HeaderValue<'x> {
+        self.parse_address_(true)
+    }
+
+    fn parse_address_(&mut self, is_strict: bool) -> HeaderValue<'x> {
         let mut parser = AddressParser {
             token_start: 0,
             token_end: 0,
@@ -204,6 +250,9 @@ impl<'x> MessageStream<'x> {
             is_token_email: false,
             is_token_start: true,
             is_escaped: false,
+            is_strict,
+            is_literal: false,
+            quote_start: 0,
 
             name_tokens: Vec::with_capacity(3),
             mail_tokens: Vec::with_capacity(3),
@@ -231,6 +280,17 @@ impl<'x> MessageStream<'x> {
                         break;
                     }
                 }
+                b'\\' if parser.state == AddressState::Address && !parser.is_escaped => {
+                    // Keep the quoted-pairs of quoted local-parts
+                    parser.is_escaped = true;
+                    if parser.token_start == 0 {
+                        parser.token_start = self.offset();
+                        parser.token_end = parser.token_start;
+                    } else {
+                        parser.token_end = self.offset();
+                    }
+                    continue;
+                }
                 b'\\' if parser.state != AddressState::Name && !parser.is_escaped => {
                     if parser.token_start > 0 {
                         if parser.state == AddressState::Quote {
@@ -241,7 +301,7 @@ impl<'x> MessageStream<'x> {
                     parser.is_escaped = true;
                     continue;
                 }
-                b',' if parser.state == AddressState::Name => {
+                b',' if parser.state == AddressState::Name && !parser.is_literal => {
                     parser.add_token(self, false);
                     parser.add_address();
                     continue;
@@ -262,11 +322,15 @@ impl<'x> MessageStream<'x> {
                         parser.state_stack.push(AddressState::Name);
                         parser.state = AddressState::Quote;
                         parser.add_token(self, false);
+                        parser.quote_start = parser.name_tokens.len();
                         continue;
                     }
                     AddressState::Quote => {
                         parser.add_token(self, false);
                         parser.state = parser.state_stack.pop().unwrap();
+                        if parser.state == AddressState::Name && self.peek_char(b'@') {
+                            parser.add_quoted_local_part();
+                        }
                         continue;
                     }
                     _ => (),
@@ -274,9 +338,38 @@ impl<'x> MessageStream<'x> {
                 b'@' if parser.state == AddressState::Name => {
                     parser.is_token_email = true;
                 }
-                b'=' if parser.is_token_start && !parser.is_escaped && self.peek_char(b'?') => {
+                b'[' if parser.state == AddressState::Name && parser.is_token_email => {
+                    parser.is_literal = true;
+                }
+                b']' if parser.is_literal => {
+                    parser.is_literal = false;
+                }
+                b'=' if parser.is_token_start
+                    && !parser.is_escaped
+                    && self.peek_char(b'?')
+                    && parser.state != AddressState::Address
+                    && (!parser.is_strict || parser.state != AddressState::Quote) =>
+                {
                     self.checkpoint();
-                    if let Some(token) = self.decode_rfc2047() {
+                    if let Some(token) = self.decode_rfc2047().filter(|_| {
+                        // Encoded-words have to be separated from adjacent text
+                        !parser.is_strict
+                            || matches!(
+                                self.peek(),
+                                None | Some(
+                                    b' ' | b'\t'
+                                        | b'\r'
+                                        | b'\n'
+                                        | b'<'
+                                        | b','
+                                        | b';'
+                                        | b':'
+                                        | b'"'
+                                        | b'('
+                                        | b')'
+                                )
+                            )
+                    }) {
                         let add_space = parser.state != AddressState::Quote; // Make borrow-checker happy
                         parser.add_token(self, add_space);
                         (if parser.state != AddressState::Comment {
@@ -323,13 +416,16 @@ impl<'x> MessageStream<'x> {
                         continue;
                     }
                 }
-                b':' if parser.state == AddressState::Name && !parser.is_escaped => {
+                b':' if parser.state == AddressState::Name
+                    && !parser.is_escaped
+                    && !parser.is_literal =>
+                {
                     parser.add_group();
                     parser.add_token(self, false);
                     parser.add_group_details();
                     continue;
                 }
-                b';' if parser.state == AddressState::Name => {
+                b';' if parser.state == AddressState::Name && !parser.is_literal => {
                     parser.add_token(self, false);
                     parser.add_address();
                     parser.add_group();
@@ -354,6 +450,8 @@ impl<'x> MessageStream<'x> {
             }
         }
 
+        // Values not ending with a line feed
+        parser.add_token(self, false);
         parser.add_address();
 
         if parser.group_name.is_some() || !parser.result.is_empty() {
@@ -375,6 +473,64 @@ impl<'x> MessageStream<'x> {
     }
 }
 
+// Removes obsolete source routes (RFC 5322, Section 4.4) such as
+// "@relay1,@relay2:user@domain" and the whitespace allowed around the
+// dots and the at-sign by the obs-local-part and obs-domain syntax.
+fn normalize_address(address: Cow<'_, str>) -> Cow<'_, str> {
+    let mut route_end = 0;
+    if address.starts_with('@') {
+        let mut in_literal = false;
+        for (pos, ch) in address.char_indices() {
+            match ch {
+                '[' => in_literal = true,
+                ']' => in_literal = false,
+                ':' if !in_literal => {
+                    route_end = pos + 1;
+                    break;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let has_space = address[route_end..].contains([' ', '\t']);
+    if route_end == 0 && !has_space {
+        return address;
+    } else if !has_space {
+        return match address {
+            Cow::Borrowed(address) => address[route_end..].trim_start().into(),
+            Cow::Owned(address) => address[route_end..].trim_start().to_string().into(),
+        };
+    }
+
+    let address = address[route_end..].trim();
+    let mut result = String::with_capacity(address.len());
+    let mut in_quote = false;
+    let mut is_escaped = false;
+    let mut pending_space = false;
+
+    for ch in address.chars() {
+        match ch {
+            ' ' | '\t' if !in_quote => {
+                pending_space = true;
+                continue;
+            }
+            '"' if !is_escaped => in_quote = !in_quote,
+            _ => (),
+        }
+        is_escaped = ch == '\\' && !is_escaped;
+        if pending_space {
+            if !matches!(ch, '.' | '@') && !result.ends_with(['.', '@']) {
+                result.push(' ');
+            }
+            pending_space = false;
+        }
+        result.push(ch);
+    }
+
+    result.into()
+}
+
 fn concat_tokens<'x>(tokens: &mut Vec<Cow<'x, str>>) -> Cow<'x, str> {
     if tokens.len() == 1 {
         tokens.pop().unwrap()
@@ -385,36 +541,255 @@ fn concat_tokens<'x>(tokens: &mut Vec<Cow<'x, str>>) -> Cow<'x, str> {
     }
 }
 
+impl<'x> Domain<'x> {
+    /// Parses the domain part of an address, recognizing RFC5321 address
+    /// literals such as `[192.168.1.1]` and `[IPv6:2001:db8::1]`.
+    pub fn parse(domain: &'x str) -> Domain<'x> {
+        if let Some(literal) = domain
+            .strip_prefix('[')
+            .and_then(|literal| literal.strip_suffix(']'))
+        {
+            let literal = literal.trim();
+            if let Some(ipv6) = literal
+                .get(..5)
+                .filter(|tag| tag.eq_ignore_ascii_case("IPv6:"))
+                .and_then(|_| literal.get(5..))
+            {
+                if let Ok(ip) = ipv6.trim().parse() {
+                    return Domain::Ipv6(ip);
+                }
+            } else if let Ok(ip) = literal.parse() {
+                return Domain::Ipv4(ip);
+            }
+            Domain::Literal(literal)
+        } else {
+            Domain::Name(domain)
+        }
+    }
+
+    /// Returns the IP address of an address literal
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            Domain::Ipv4(ip) => Some(IpAddr::V4(*ip)),
+            Domain::Ipv6(ip) => Some(IpAddr::V6(*ip)),
+            Domain::Name(_) | Domain::Literal(_) => None,
+        }
+    }
+
+    /// Returns `true` if the domain is an address literal
+    pub fn is_literal(&self) -> bool {
+        !matches!(self, Domain::Name(_))
+    }
+}
+
+/// Validates a mailbox as defined by RFC 5321 (Section 4.1.2), extended by
+/// RFC 6531 to allow UTF-8 in the local part and the domain.
+pub fn validate_rfc5321(addr: &str) -> Result<(), AddressError> {
+    if addr.is_empty() {
+        return Err(AddressError::Empty);
+    }
+    if addr.len() > 254 {
+        return Err(AddressError::TooLong);
+    }
+
+    // Local part, a dot-string or a quoted string
+    let bytes = addr.as_bytes();
+    let mut pos = 0;
+    if bytes[0] == b'"' {
+        pos = 1;
+        loop {
+            match bytes.get(pos) {
+                Some(b'"') => {
+                    pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    if !bytes.get(pos + 1).is_some_and(|ch| (32..=126).contains(ch)) {
+                        return Err(AddressError::InvalidLocalPart { offset: pos + 1 });
+                    }
+                    pos += 2;
+                }
+                Some(&ch) if ch == b' ' || ch == b'!' || (35..=126).contains(&ch) || ch >= 0x80 => {
+                    pos += 1;
+                }
+                Some(_) => return Err(AddressError::InvalidLocalPart { offset: pos }),
+                None => return Err(AddressError::UnbalancedQuotes),
+            }
+        }
+    } else {
+        while let Some(&ch) = bytes.get(pos) {
+            match ch {
+                b'@' => break,
+                b'.' if pos == 0 || bytes[pos - 1] == b'.' || bytes.get(pos + 1) == Some(&b'@') => {
+                    return Err(AddressError::InvalidLocalPart { offset: pos });
+                }
+                b'"' => return Err(AddressError::UnbalancedQuotes),
+                _ if ch.is_ascii_alphanumeric()
+                    || ch >= 0x80
+                    || b".!#$%&'*+-/=?^_`{|}~".contains(&ch) => {}
+                _ => return Err(AddressError::InvalidLocalPart { offset: pos }),
+            }
+            pos += 1;
+        }
+    }
+    match bytes.get(pos) {
+        Some(b'@') if pos == 0 => return Err(AddressError::EmptyLocalPart),
+        Some(b'@') if pos > 64 => return Err(AddressError::LocalPartTooLong),
+        Some(b'@') => pos += 1,
+        Some(_) => return Err(AddressError::InvalidLocalPart { offset: pos }),
+        None => return Err(AddressError::MissingAtSign),
+    }
+
+    // Domain name or address literal
+    let domain = &addr[pos..];
+    if domain.is_empty() {
+        return Err(AddressError::EmptyDomain);
+    } else if let Some(literal) = domain.strip_prefix('[') {
+        let is_valid =
+            literal
+                .strip_suffix(']')
+                .is_some_and(|literal| match Domain::parse(domain) {
+                    Domain::Ipv4(_) | Domain::Ipv6(_) => !literal.contains([' ', '\t']),
+                    _ => literal.split_once(':').is_some_and(|(tag, content)| {
+                        !tag.eq_ignore_ascii_case("IPv6")
+                            && is_ldh_label(tag)
+                            && !content.is_empty()
+                            && content
+                                .bytes()
+                                .all(|ch| (33..=90).contains(&ch) || (94..=126).contains(&ch))
+                    }),
+                });
+        return if is_valid {
+            Ok(())
+        } else {
+            Err(AddressError::InvalidDomainLiteral)
+        };
+    }
+
+    for label in domain.split('.') {
+        if label.len() > 63 || !is_ldh_label(label) {
+            return Err(AddressError::InvalidDomain {
+                offset: label.as_ptr() as usize - addr.as_ptr() as usize,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// Returns true if a domain label is made of letters, digits and hyphens,
+// not starting or ending with a hyphen. UTF-8 labels are accepted.
+fn is_ldh_label(label: &str) -> bool {
+    !label.is_empty()
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label
+            .bytes()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == b'-' || ch >= 0x80)
+}
+
 pub fn parse_address_local_part(addr: &str) -> Option<&str> {
-    let addr = addr.as_bytes();
-    let mut iter = addr.iter().enumerate();
-    while let Some((pos, &ch)) = iter.next() {
-        if ch == b'@' {
-            return if pos > 0 && iter.next().is_some() {
-                std::str::from_utf8(addr.get(..pos)?).ok()
-            } else {
-                None
-            };
-        } else if !ch.is_ascii() {
-            return None;
+    let pos = address_at_sign(addr)?;
+    if pos > 0 && pos + 1 < addr.len() {
+        addr.get(..pos)
+    } else {
+        None
+    }
+}
+
+/// Removes the quotes and quoted-pair escapes of a quoted local-part, i.e.
+/// `"john \"x\" smith"` becomes `john "x" smith`.
+pub fn unquote_local_part(local_part: &str) -> Cow<'_, str> {
+    if !local_part.contains('"') {
+        return local_part.into();
+    }
+
+    let mut result = String::with_capacity(local_part.len());
+    let mut is_escaped = false;
+    for ch in local_part.chars() {
+        match ch {
+            '\\' if !is_escaped => is_escaped = true,
+            '"' if !is_escaped => (),
+            _ => {
+                result.push(ch);
+                is_escaped = false;
+            }
         }
     }
+    result.into()
+}
 
-    None
+/// Returns a normalized form of an address suitable for comparisons, with
+/// the quotes removed from the local-part unless they are required and the
+/// domain name in lowercase. The local-part keeps its case.
+pub fn parse_address_normalized(addr: &str) -> Option<String> {
+    let local_part = unquote_local_part(parse_address_local_part(addr)?);
+    let domain = parse_address_domain(addr)?;
+    let mut result = String::with_capacity(addr.len());
+
+    if is_dot_atom(&local_part) {
+        result.push_str(&local_part);
+    } else {
+        result.push('"');
+        for ch in local_part.chars() {
+            if ch == '"' || ch == '\\' {
+                result.push('\\');
+            }
+            result.push(ch);
+        }
+        result.push('"');
+    }
+    result.push('@');
+    if domain.starts_with('[') {
+        result.push_str(domain);
+    } else {
+        result.push_str(&domain.trim_end_matches('.').to_lowercase());
+    }
+
+    result.into()
+}
+
+// Returns true if the text is a dot-atom (RFC 5322 and RFC 6532) that does
+// not need to be quoted.
+fn is_dot_atom(text: &str) -> bool {
+    !text.is_empty()
+        && text.split('.').all(|atom| {
+            !atom.is_empty()
+                && atom.chars().all(|ch| {
+                    ch.is_ascii_alphanumeric()
+                        || !ch.is_ascii()
+                        || "!#$%&'*+-/=?^_`{|}~".contains(ch)
+                })
+        })
 }
 
 pub fn parse_address_domain(addr: &str) -> Option<&str> {
-    let addr = addr.as_bytes();
-    for (pos, &ch) in addr.iter().enumerate() {
-        if ch == b'@' {
-            return if pos > 0 && pos + 1 < addr.len() {
-                std::str::from_utf8(addr.get(pos + 1..)?).ok()
-            } else {
-                None
-            };
-        } else if !ch.is_ascii() {
-            return None;
+    let pos = address_at_sign(addr)?;
+    if pos > 0 && pos + 1 < addr.len() {
+        addr.get(pos + 1..)
+    } else {
+        None
+    }
+}
+
+// Returns the position of the at-sign separating the local-part from the domain,
+// skipping any at-signs contained in a quoted local-part.
+fn address_at_sign(addr: &str) -> Option<usize> {
+    let mut in_quote = false;
+    let mut is_escaped = false;
+
+    for (pos, &ch) in addr.as_bytes().iter().enumerate() {
+        match ch {
+            b'\\' if in_quote && !is_escaped => {
+                is_escaped = true;
+                continue;
+            }
+            b'"' if !is_escaped => in_quote = !in_quote,
+            b'@' if !in_quote => return Some(pos),
+            _ if !ch.is_ascii() => return None,
+            _ => (),
         }
+        is_escaped = false;
     }
 
     None
@@ -615,8 +990,8 @@ mod tests {
                     "---\n",
                     "AddressList:\n",
                     "  - name: Mary Smith\n",
-                    "    address: \"@node.test:mary@example.net\"\n",
-                    "  - address: jdoe@test  . example\n"
+                    "    address: mary@example.net\n",
+                    "  - address: jdoe@test.example\n"
                 ),
             ),
             (
@@ -625,7 +1000,7 @@ mod tests {
                     "---\n",
                     "Address:\n",
                     "  name: John Doe (comment)\n",
-                    "  address: jdoe@machine.  example\n"
+                    "  address: jdoe@machine.example\n"
                 ),
             ),
             (
@@ -1034,4 +1409,370 @@ mod tests {
             assert_eq!(result, expected, "Failed for '{:?}'", input.0);
         }
     }
+
+    #[test]
+    fn parse_addresses_strict() {
+        use super::*;
+
+        for (input, expected_lenient, expected_strict) in [
+            (
+                "=?utf-8?Q?J=C3=B6rg?= Smith <jorg@example.com>\n",
+                Addr::new("Jörg Smith".into(), "jorg@example.com"),
+                Addr::new("Jörg Smith".into(), "jorg@example.com"),
+            ),
+            (
+                "\"=?utf-8?Q?J=C3=B6rg?=\" <jorg@example.com>\n",
+                Addr::new("Jörg".into(), "jorg@example.com"),
+                Addr::new("=?utf-8?Q?J=C3=B6rg?=".into(), "jorg@example.com"),
+            ),
+            (
+                "jorg@example.com (=?utf-8?Q?J=C3=B6rg?=)\n",
+                Addr::new("Jörg".into(), "jorg@example.com"),
+                Addr::new("Jörg".into(), "jorg@example.com"),
+            ),
+            (
+                "Smith=?utf-8?Q?J=C3=B6rg?= <jorg@example.com>\n",
+                Addr::new("Smith=?utf-8?Q?J=C3=B6rg?=".into(), "jorg@example.com"),
+                Addr::new("Smith=?utf-8?Q?J=C3=B6rg?=".into(), "jorg@example.com"),
+            ),
+            (
+                "=?utf-8?Q?J=C3=B6rg?=Smith <jorg@example.com>\n",
+                Addr::new("Jörg Smith".into(), "jorg@example.com"),
+                Addr::new("=?utf-8?Q?J=C3=B6rg?=Smith".into(), "jorg@example.com"),
+            ),
+            (
+                "Jorg <=?utf-8?Q?a?=@example.com>\n",
+                Addr::new("Jorg".into(), "=?utf-8?Q?a?=@example.com"),
+                Addr::new("Jorg".into(), "=?utf-8?Q?a?=@example.com"),
+            ),
+        ] {
+            assert_eq!(
+                MessageStream::new(input.as_bytes()).parse_address(),
+                HeaderValue::Address(expected_lenient),
+                "Failed for {input:?}"
+            );
+            assert_eq!(
+                MessageStream::new(input.as_bytes()).parse_address_strict(),
+                HeaderValue::Address(expected_strict),
+                "Failed for {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_obsolete_addresses() {
+        use super::*;
+
+        for (input, expected_result) in [
+            (
+                "Joe <@relay1,@relay2:user@example.com>\n",
+                HeaderValue::Address(Addr::new("Joe".into(), "user@example.com")),
+            ),
+            (
+                "<@relay1.example, @[IPv6:2001:db8::1]:user@example.com>\n",
+                HeaderValue::Address(Addr::new(None, "user@example.com")),
+            ),
+            (
+                "<@a.b:user@example.com>, Jane <jane@example.com>\n",
+                HeaderValue::AddressList(vec![
+                    Addr::new(None, "user@example.com"),
+                    Addr::new("Jane".into(), "jane@example.com"),
+                ]),
+            ),
+            (
+                "john . doe @ example . com\n",
+                HeaderValue::Address(Addr::new(None, "john.doe@example.com")),
+            ),
+            (
+                "Joe <john . doe @ example . com>\n",
+                HeaderValue::Address(Addr::new("Joe".into(), "john.doe@example.com")),
+            ),
+            (
+                "john.doe@example.com (Joe), jane@example . com\n",
+                HeaderValue::AddressList(vec![
+                    Addr::new("Joe".into(), "john.doe@example.com"),
+                    Addr::new(None, "jane@example.com"),
+                ]),
+            ),
+            (
+                "undisclosed: <@x.y:a@b.c>, c@d;\n",
+                HeaderValue::Group(Group::new(
+                    "undisclosed",
+                    vec![Addr::new(None, "a@b.c"), Addr::new(None, "c@d")],
+                )),
+            ),
+        ] {
+            assert_eq!(
+                MessageStream::new(input.as_bytes()).parse_address(),
+                expected_result,
+                "Failed for {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn flatten_addresses() {
+        use super::*;
+
+        for (input, expected_result) in [
+            ("john@example.com\n", vec!["john@example.com"]),
+            ("john@example.com, jane@example.com\n", vec!["john@example.com", "jane@example.com"]),
+            ("undisclosed-recipients:;\n", vec![]),
+            (
+                "john@example.com, Friends: jane@example.com, joe@example.com;, Empty:;, bill@example.com\n",
+                vec!["john@example.com", "jane@example.com", "joe@example.com", "bill@example.com"],
+            ),
+        ] {
+            let value = MessageStream::new(input.as_bytes()).parse_address();
+            assert_eq!(
+                value
+                    .flatten_addresses()
+                    .filter_map(|addr| addr.address.as_deref())
+                    .collect::<Vec<_>>(),
+                expected_result,
+                "Failed for {input:?}"
+            );
+        }
+
+        assert_eq!(
+            HeaderValue::Text("john@example.com".into())
+                .flatten_addresses()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn validate_addresses() {
+        use super::*;
+
+        assert_eq!(
+            Addr::parse("John Doe <john@example.com>"),
+            Some(Addr::new("John Doe".into(), "john@example.com"))
+        );
+        assert_eq!(
+            Addr::parse("jane@example.com (Jane)"),
+            Some(Addr::new("Jane".into(), "jane@example.com"))
+        );
+        assert_eq!(Addr::parse("john@example.com, jane@example.com"), None);
+        assert_eq!(Addr::parse("John Doe"), None);
+        assert_eq!(Addr::parse(""), None);
+
+        for (input, expected_result) in [
+            ("john.doe@example.com", Ok(())),
+            ("\"john smith\"@example.com", Ok(())),
+            ("\"john \\\"x\\\" smith\"@example.com", Ok(())),
+            ("user+tag@sub-domain.example.com", Ok(())),
+            ("user@[192.168.1.1]", Ok(())),
+            ("user@[IPv6:2001:db8::1]", Ok(())),
+            ("user@[x-tag:content]", Ok(())),
+            ("j\u{f6}rg@b\u{fc}cher.example", Ok(())),
+            ("", Err(AddressError::Empty)),
+            ("john.example.com", Err(AddressError::MissingAtSign)),
+            ("@example.com", Err(AddressError::EmptyLocalPart)),
+            ("john@", Err(AddressError::EmptyDomain)),
+            ("\"john@example.com", Err(AddressError::UnbalancedQuotes)),
+            ("jo\"hn@example.com", Err(AddressError::UnbalancedQuotes)),
+            (
+                "john smith@example.com",
+                Err(AddressError::InvalidLocalPart { offset: 4 }),
+            ),
+            (
+                ".john@example.com",
+                Err(AddressError::InvalidLocalPart { offset: 0 }),
+            ),
+            (
+                "john..doe@example.com",
+                Err(AddressError::InvalidLocalPart { offset: 5 }),
+            ),
+            (
+                "john.@example.com",
+                Err(AddressError::InvalidLocalPart { offset: 4 }),
+            ),
+            (
+                "\"john\"doe@example.com",
+                Err(AddressError::InvalidLocalPart { offset: 6 }),
+            ),
+            (
+                "john@example..com",
+                Err(AddressError::InvalidDomain { offset: 13 }),
+            ),
+            (
+                "john@-example.com",
+                Err(AddressError::InvalidDomain { offset: 5 }),
+            ),
+            (
+                "john@exa_mple.com",
+                Err(AddressError::InvalidDomain { offset: 5 }),
+            ),
+            ("user@[300.1.1.1]", Err(AddressError::InvalidDomainLiteral)),
+            ("user@[IPv6:zz::1]", Err(AddressError::InvalidDomainLiteral)),
+            ("user@[192.168.1.1", Err(AddressError::InvalidDomainLiteral)),
+        ] {
+            assert_eq!(
+                Addr::is_valid_rfc5321(input),
+                expected_result,
+                "Failed for {input:?}"
+            );
+        }
+        assert_eq!(
+            Addr::is_valid_rfc5321(&format!("{}@example.com", "a".repeat(65))),
+            Err(AddressError::LocalPartTooLong)
+        );
+        assert_eq!(
+            Addr::is_valid_rfc5321(&format!("john@{}.com", "a".repeat(64))),
+            Err(AddressError::InvalidDomain { offset: 5 })
+        );
+        assert_eq!(
+            Addr::is_valid_rfc5321(&format!("john@{}.com", "a.".repeat(125))),
+            Err(AddressError::TooLong)
+        );
+    }
+
+    #[test]
+    fn parse_quoted_local_parts() {
+        use super::*;
+
+        for (input, expected_result) in [
+            (
+                "\"john smith\"@example.com\n",
+                HeaderValue::Address(Addr::new(None, "\"john smith\"@example.com")),
+            ),
+            (
+                "Joe <\"john smith\"@example.com>\n",
+                HeaderValue::Address(Addr::new("Joe".into(), "\"john smith\"@example.com")),
+            ),
+            (
+                "<\"john \\\"x\\\" smith\"@example.com>\n",
+                HeaderValue::Address(Addr::new(None, "\"john \\\"x\\\" smith\"@example.com")),
+            ),
+            (
+                "\"john \\\"x\\\" smith\"@example.com\n",
+                HeaderValue::Address(Addr::new(None, "\"john \\\"x\\\" smith\"@example.com")),
+            ),
+            (
+                "\"john@smith\"@example.com, jane@example.com\n",
+                HeaderValue::AddressList(vec![
+                    Addr::new(None, "\"john@smith\"@example.com"),
+                    Addr::new(None, "jane@example.com"),
+                ]),
+            ),
+            (
+                "\"john.smith\"@example.com (Comment)\n",
+                HeaderValue::Address(Addr::new("Comment".into(), "\"john.smith\"@example.com")),
+            ),
+        ] {
+            assert_eq!(
+                MessageStream::new(input.as_bytes()).parse_address(),
+                expected_result,
+                "Failed for {input:?}"
+            );
+        }
+
+        assert_eq!(
+            parse_address_local_part("\"john@smith\"@example.com"),
+            Some("\"john@smith\"")
+        );
+        assert_eq!(
+            parse_address_domain("\"john@smith\"@example.com"),
+            Some("example.com")
+        );
+        assert_eq!(
+            parse_address_domain("\"john\\\"@smith\"@example.com"),
+            Some("example.com")
+        );
+
+        for (input, expected_unquoted, expected_normalized) in [
+            ("John.Doe@Example.COM", "John.Doe", "John.Doe@example.com"),
+            ("\"john\"@example.com.", "john", "john@example.com"),
+            (
+                "\"john smith\"@Example.com",
+                "john smith",
+                "\"john smith\"@example.com",
+            ),
+            (
+                "\"john \\\"x\\\" smith\"@example.com",
+                "john \"x\" smith",
+                "\"john \\\"x\\\" smith\"@example.com",
+            ),
+            (
+                "\"john@smith\"@EXAMPLE.com",
+                "john@smith",
+                "\"john@smith\"@example.com",
+            ),
+            ("user@[IPv6:::1]", "user", "user@[IPv6:::1]"),
+        ] {
+            let addr = Addr::new(None, input);
+            assert_eq!(
+                addr.unquoted_local_part().as_deref(),
+                Some(expected_unquoted),
+                "Failed for {input:?}"
+            );
+            assert_eq!(
+                addr.normalized_address().as_deref(),
+                Some(expected_normalized),
+                "Failed for {input:?}"
+            );
+        }
+        assert_eq!(Addr::new(None, "invalid").normalized_address(), None);
+    }
+
+    #[test]
+    fn parse_domain_literals() {
+        use super::*;
+        use std::net::{Ipv4Addr, Ipv6Addr};
+
+        for (input, expected_address, expected_domain) in [
+            (
+                "user@[192.168.1.1]\n",
+                "user@[192.168.1.1]",
+                Domain::Ipv4(Ipv4Addr::new(192, 168, 1, 1)),
+            ),
+            (
+                "user@[IPv6:::1], jane@example.com\n",
+                "user@[IPv6:::1]",
+                Domain::Ipv6(Ipv6Addr::LOCALHOST),
+            ),
+            (
+                "Joe <user@[ipv6:2001:db8::1]>\n",
+                "user@[ipv6:2001:db8::1]",
+                Domain::Ipv6("2001:db8::1".parse().unwrap()),
+            ),
+            (
+                "Group: user@[IPv6:::1];\n",
+                "user@[IPv6:::1]",
+                Domain::Ipv6(Ipv6Addr::LOCALHOST),
+            ),
+            (
+                "user@[x-tag:whatever]\n",
+                "user@[x-tag:whatever]",
+                Domain::Literal("x-tag:whatever"),
+            ),
+            (
+                "user@[300.1.1.1]\n",
+                "user@[300.1.1.1]",
+                Domain::Literal("300.1.1.1"),
+            ),
+            (
+                "user@example.com\n",
+                "user@example.com",
+                Domain::Name("example.com"),
+            ),
+        ] {
+            let addr = match MessageStream::new(input.as_bytes()).parse_address() {
+                HeaderValue::Address(addr) => addr,
+                HeaderValue::AddressList(mut list) => list.remove(0),
+                HeaderValue::Group(mut group) => group.addresses.remove(0),
+                _ => panic!("Unexpected result for {:?}", input),
+            };
+            assert_eq!(addr.address.as_deref(), Some(expected_address));
+            assert_eq!(addr.domain(), Some(expected_domain), "Failed for {input:?}");
+        }
+
+        assert_eq!(
+            Domain::parse("[10.0.0.1]").ip(),
+            Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)))
+        );
+        assert!(!Domain::parse("example.com").is_literal());
+    }
 }
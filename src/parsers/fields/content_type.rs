@@ -13,8 +13,8 @@ use std::borrow::Cow;
 
 use crate::{
     decoders::{charsets::map::charset_decoder, hex::decode_hex},
-    parsers::MessageStream,
-    ContentType, HeaderValue,
+    parsers::{trim_ascii, MessageStream},
+    ContentType, HeaderValue, RawParameter,
 };
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -512,13 +512,108 @@ impl<'x> MessageStream<'x> {
         HeaderValue::Empty
     }
 }
+
+// Splits the raw value of a Content-Type or Content-Disposition header field
+// into its parameters, skipping the type and the segments lacking a "=".
+pub(crate) fn parse_raw_parameters(value: &[u8], offset: usize) -> Vec<RawParameter<'_>> {
+    let mut parameters = Vec::new();
+    let mut segment_start = None;
+    let mut in_quote = false;
+    let mut is_escaped = false;
+    let mut comment_depth = 0;
+
+    for (pos, ch) in value
+        .iter()
+        .copied()
+        .enumerate()
+        .chain([(value.len(), b';')])
+    {
+        if is_escaped && pos < value.len() {
+            is_escaped = false;
+            continue;
+        }
+        match ch {
+            b'\\' if in_quote || comment_depth > 0 => is_escaped = true,
+            b'"' if comment_depth == 0 => in_quote = !in_quote,
+            b'(' if !in_quote => comment_depth += 1,
+            b')' if !in_quote && comment_depth > 0 => comment_depth -= 1,
+            b';' if (!in_quote && comment_depth == 0) || pos == value.len() => {
+                if let Some(start) = segment_start {
+                    if let Some(parameter) = raw_parameter(&value[start..pos], offset + start) {
+                        parameters.push(parameter);
+                    }
+                }
+                segment_start = Some(pos + 1);
+            }
+            _ => (),
+        }
+    }
+
+    parameters
+}
+
+fn raw_parameter(segment: &[u8], offset: usize) -> Option<RawParameter<'_>> {
+    let eq_pos = segment.iter().position(|&ch| ch == b'=')?;
+    let name_start = segment.iter().position(|ch| !ch.is_ascii_whitespace())?;
+    let name = trim_ascii(&segment[name_start..eq_pos]);
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(RawParameter {
+        name: String::from_utf8_lossy(name),
+        value: String::from_utf8_lossy(trim_ascii(&segment[eq_pos + 1..])),
+        offset: offset + name_start,
+    })
+}
+
+/// Makes an attachment file name safe to use on a file system, returning
+/// `None` if nothing usable remains. Directory components, control and
+/// bidirectional formatting characters are removed, characters reserved on
+/// Windows are replaced by `_` and leading or trailing dots and whitespace
+/// are trimmed.
+pub fn sanitize_file_name(name: &str) -> Option<Cow<'_, str>> {
+    let name = name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim_matches(|ch: char| ch.is_whitespace() || ch == '.');
+    let is_removed = |ch: char| {
+        ch.is_control()
+            || matches!(ch, '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+    };
+    let is_reserved = |ch: char| matches!(ch, '<' | '>' | ':' | '"' | '|' | '?' | '*');
+
+    let name: Cow<str> = if name.chars().any(|ch| is_removed(ch) || is_reserved(ch)) {
+        let name = name
+            .chars()
+            .filter(|&ch| !is_removed(ch))
+            .map(|ch| if is_reserved(ch) { '_' } else { ch })
+            .collect::<String>();
+        let trimmed = name.trim_matches(|ch: char| ch.is_whitespace() || ch == '.');
+        if trimmed.len() != name.len() {
+            trimmed.to_string().into()
+        } else {
+            name.into()
+        }
+    } else {
+        name.into()
+    };
+
+    if !name.is_empty() {
+        Some(name)
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{borrow::Cow, collections::HashMap};
 
     use serde::{Deserialize, Serialize};
 
-    use crate::{parsers::MessageStream, HeaderValue};
+    use crate::{parsers::MessageStream, HeaderValue, Message, MimeHeaders, RfcHeader};
 
     #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
     pub struct ContentTypeMap<'x> {
@@ -533,6 +628,108 @@ mod tests {
         Empty,
     }
 
+    #[test]
+    fn parse_raw_parameters() {
+        let message = Message::parse(
+            concat!(
+                "Content-Type: text/plain; charset=\"us-ascii\"; X-Route=queue-7 (primary);\r\n",
+                "  title*0*=us-ascii'en'Annual%20; title*1=\"report;\\\"final\\\"\";\r\n",
+                "  x-empty=\"\"; malformed; X-Zone = eu\r\n",
+                "\r\n",
+                "Hello\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let parameters = message.raw_parameters(0, RfcHeader::ContentType);
+        assert_eq!(
+            parameters
+                .iter()
+                .map(|p| (p.name.as_ref(), p.value.as_ref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("charset", "\"us-ascii\""),
+                ("X-Route", "queue-7 (primary)"),
+                ("title*0*", "us-ascii'en'Annual%20"),
+                ("title*1", "\"report;\\\"final\\\"\""),
+                ("x-empty", "\"\""),
+                ("X-Zone", "eu")
+            ]
+        );
+        for parameter in &parameters {
+            assert_eq!(
+                message
+                    .raw_bytes(parameter.offset..parameter.offset + parameter.name.len())
+                    .unwrap(),
+                parameter.name.as_bytes()
+            );
+        }
+
+        // Unknown parameters are kept decoded along with the known ones,
+        // empty values are only available as raw parameters
+        assert_eq!(
+            message.content_type().unwrap().attributes().unwrap(),
+            [
+                ("charset".into(), "us-ascii".into()),
+                ("x-route".into(), "queue-7".into()),
+                ("title-language".into(), "en".into()),
+                ("title".into(), "Annual report;\"final\"".into()),
+                ("x-zone".into(), "eu".into())
+            ]
+        );
+
+        assert_eq!(
+            message.raw_parameters(0, RfcHeader::ContentDisposition),
+            vec![]
+        );
+        assert_eq!(message.raw_parameters(1, RfcHeader::ContentType), vec![]);
+
+        // Empty file names do not hide the name parameter
+        let message = Message::parse(
+            concat!(
+                "Content-Type: application/pdf; name=\"report.pdf\"\r\n",
+                "Content-Disposition: attachment; filename=\"\"\r\n",
+                "\r\n",
+                "PDF\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(message.attachment_name(), Some("report.pdf"));
+        assert_eq!(
+            message
+                .raw_parameters(0, RfcHeader::ContentDisposition)
+                .iter()
+                .map(|p| (p.name.as_ref(), p.value.as_ref()))
+                .collect::<Vec<_>>(),
+            vec![("filename", "\"\"")]
+        );
+    }
+
+    #[test]
+    fn sanitize_file_names() {
+        for (input, expected) in [
+            ("report.pdf", Some("report.pdf")),
+            ("../../etc/passwd", Some("passwd")),
+            ("C:\\Users\\jane\\invoice.pdf", Some("invoice.pdf")),
+            ("  résumé.docx. ", Some("résumé.docx")),
+            ("in\u{202e}fdp.exe", Some("infdp.exe")),
+            ("line\r\nbreak.txt", Some("linebreak.txt")),
+            ("what?<now>.txt", Some("what__now_.txt")),
+            ("..", None),
+            ("dir/", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                super::sanitize_file_name(input).as_deref(),
+                expected,
+                "{:?}",
+                input
+            );
+        }
+    }
+
     #[test]
     fn parse_content_fields() {
         let inputs =
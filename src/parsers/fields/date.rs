@@ -9,7 +9,7 @@
  * except according to those terms.
  */
 
-use std::fmt;
+use std::{convert::TryFrom, fmt};
 
 use crate::{parsers::MessageStream, DateTime, HeaderValue};
 
@@ -121,6 +121,153 @@ impl DateTime {
         }
     }
 
+    /// Parses a malformed date on a best-effort basis, accepting two-digit
+    /// years, missing seconds or time zone, zones such as `GMT+0200` or
+    /// `EST`, misspelled week days and the ctime format, such as
+    /// `Mon Oct  2 10:00:00 2023`. Dates without a time zone are assumed to
+    /// be in UTC.
+    pub fn parse_lenient(value: &str) -> Option<Self> {
+        let mut year = None;
+        let mut month = None;
+        let mut day = None;
+        let mut time = None;
+        let mut tz = None;
+        let mut is_pm = None;
+
+        // Remove comments
+        let mut text = String::with_capacity(value.len());
+        let mut comment_count = 0usize;
+        for ch in value.chars() {
+            match ch {
+                '(' => comment_count += 1,
+                ')' => comment_count = comment_count.saturating_sub(1),
+                _ if comment_count == 0 => text.push(ch),
+                _ => (),
+            }
+        }
+
+        let mut tokens = text
+            .split(|ch: char| ch.is_whitespace() || ch == ',')
+            .filter(|token| !token.is_empty())
+            .rev()
+            .collect::<Vec<_>>();
+
+        while let Some(token) = tokens.pop() {
+            let first_ch = token.as_bytes()[0];
+
+            if first_ch.is_ascii_digit() && token.contains('T') {
+                // ISO 8601 date and time
+                if let Some((date, time)) = token.split_once('T') {
+                    tokens.extend([time, date].iter().filter(|token| !token.is_empty()));
+                }
+            } else if first_ch.is_ascii_digit() && token.contains(':') {
+                // Time, optionally followed by a time zone
+                if time.is_none() {
+                    let (hms, zone) = token.split_at(
+                        token
+                            .find(|ch: char| !ch.is_ascii_digit() && ch != ':' && ch != '.')
+                            .unwrap_or(token.len()),
+                    );
+                    let mut hms = hms.split(':').map(|part| {
+                        part.split('.')
+                            .next()
+                            .filter(|part| (1..=2).contains(&part.len()))
+                            .and_then(|part| part.parse::<u8>().ok())
+                    });
+                    if let (Some(Some(hour)), Some(Some(minute))) = (hms.next(), hms.next()) {
+                        time = Some((hour, minute, hms.next().flatten().unwrap_or(0)));
+                        if !zone.is_empty() {
+                            tokens.push(zone);
+                        }
+                    }
+                }
+            } else if first_ch == b'+' || first_ch == b'-' {
+                if tz.is_none() {
+                    tz = lenient_offset(token);
+                }
+            } else if token.bytes().all(|ch| ch.is_ascii_digit()) {
+                if let Ok(value) = token.parse::<u32>() {
+                    if year.is_none() && (token.len() >= 3 || value > 31) {
+                        year = Some(value);
+                    } else if day.is_none() && (1..=31).contains(&value) {
+                        day = Some(value as u8);
+                    } else if year.is_none() {
+                        year = Some(value);
+                    }
+                }
+            } else if first_ch.is_ascii_digit() {
+                // Dates such as 2023-10-02, 02-Oct-2023, 2.10.2023 or 10/02/2023
+                let parts = token.split(['-', '/', '.']).collect::<Vec<_>>();
+                if let [first, second, third] = parts[..] {
+                    let (y, m, d) = if first.len() == 4 {
+                        (first, second, third)
+                    } else if token.contains('/')
+                        && first.parse::<u8>().is_ok_and(|month| month <= 12)
+                    {
+                        (third, first, second)
+                    } else {
+                        (third, second, first)
+                    };
+                    if let (Ok(y), Some(m), Ok(d)) = (
+                        y.parse::<u32>(),
+                        lenient_month(m).or_else(|| m.parse::<u8>().ok()),
+                        d.parse::<u8>(),
+                    ) {
+                        year = Some(y);
+                        month = Some(m);
+                        day = Some(d);
+                    }
+                }
+            } else {
+                let token = token.trim_end_matches('.');
+                let name_len = token
+                    .find(|ch: char| !ch.is_ascii_alphabetic())
+                    .unwrap_or(token.len());
+                let (name, offset) = token.split_at(name_len);
+
+                if name.eq_ignore_ascii_case("am") || name.eq_ignore_ascii_case("pm") {
+                    is_pm = Some(name.eq_ignore_ascii_case("pm"));
+                } else if let Some(zone) = lenient_zone(name) {
+                    if tz.is_none() {
+                        tz = if !offset.is_empty() {
+                            lenient_offset(offset).map(|offset| offset + zone)
+                        } else {
+                            Some(zone)
+                        };
+                    }
+                } else if month.is_none() && offset.is_empty() {
+                    month = lenient_month(name);
+                }
+            }
+        }
+
+        let year = match year? {
+            year @ 0..=49 => year + 2000,
+            year @ 50..=999 => year + 1900,
+            year => year,
+        };
+        let (mut hour, minute, second) = time.unwrap_or((0, 0, 0));
+        match is_pm {
+            Some(true) if hour < 12 => hour += 12,
+            Some(false) if hour == 12 => hour = 0,
+            _ => (),
+        }
+        let tz = tz.unwrap_or(0);
+
+        Some(DateTime {
+            year: u16::try_from(year).ok()?,
+            month: month?,
+            day: day?,
+            hour,
+            minute,
+            second,
+            tz_before_gmt: tz < 0,
+            tz_hour: (tz.abs() / 3600) as u8,
+            tz_minute: ((tz.abs() % 3600) / 60) as u8,
+        })
+        .filter(|dt| dt.is_valid())
+    }
+
     /// Return an RFC822 date
     pub fn to_rfc822(&self) -> String {
         format!(
@@ -212,7 +359,10 @@ impl DateTime {
     /// Creates a DateTime object from a timestamp
     pub fn from_timestamp(timestamp: i64) -> Self {
         // Ported from http://howardhinnant.github.io/date_algorithms.html#civil_from_days
-        let (z, seconds) = ((timestamp / 86400) + 719468, timestamp % 86400);
+        let (z, seconds) = (
+            timestamp.div_euclid(86400) + 719468,
+            timestamp.rem_euclid(86400),
+        );
         let era: i64 = (if z >= 0 { z } else { z - 146096 }) / 146097;
         let doe: u64 = (z - era * 146097) as u64; // [0, 146096]
         let yoe: u64 = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365; // [0, 399]
@@ -236,9 +386,16 @@ impl DateTime {
         }
     }
 
+    // Returns the time zone offset in seconds east of UTC.
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn tz_offset(&self) -> i32 {
+        (self.tz_hour as i32 * 3600 + self.tz_minute as i32 * 60)
+            * if self.tz_before_gmt { -1 } else { 1 }
+    }
+
     /// Returns the day of week where [0, 6] represents [Sun, Sat].
     pub fn day_of_week(&self) -> u8 {
-        (((self.to_timestamp_local() as f64 / 86400.0).floor() as i64 + 4).rem_euclid(7)) as u8
+        ((self.to_timestamp_local().div_euclid(86400) + 4).rem_euclid(7)) as u8
     }
 
     /// Returns the julian day
@@ -254,15 +411,89 @@ impl DateTime {
         c * 146097 / 4 + (year - c * 100) * 1461 / 4 + (month * 153 + 2) / 5 + day + 1721119
     }
 
-    /// Converts the DateTime to the given timezone
+    /// Converts the DateTime to the given timezone, expressed as an offset
+    /// from UTC in seconds.
     pub fn to_timezone(&self, tz: i64) -> DateTime {
         let mut dt = DateTime::from_timestamp(self.to_timestamp() + tz);
         dt.tz_before_gmt = tz < 0;
         let tz = tz.abs();
         dt.tz_hour = (tz / 3600) as u8;
-        dt.tz_minute = (tz % 3600) as u8;
+        dt.tz_minute = ((tz % 3600) / 60) as u8;
         dt
     }
+
+    /// Converts the DateTime to a named timezone from the system time zone
+    /// database, such as `Europe/Madrid`. Returns `None` if the zone could
+    /// not be loaded.
+    #[cfg(feature = "tz_database")]
+    pub fn to_named_timezone(&self, name: &str) -> Option<DateTime> {
+        super::timezone::TimeZone::load(name).map(|tz| tz.convert(self))
+    }
+}
+
+// Parses a time zone offset such as "+0200", "-05:00" or "+2", returning
+// its value in seconds east of UTC.
+fn lenient_offset(value: &str) -> Option<i32> {
+    let (sign, value) = match value.as_bytes().first()? {
+        b'+' => (1, &value[1..]),
+        b'-' => (-1, &value[1..]),
+        _ => return None,
+    };
+    let digits = value.replace(':', "");
+    if digits.is_empty() || digits.len() > 4 || !digits.bytes().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    let value = digits.parse::<i32>().ok()?;
+    let (hours, minutes) = if digits.len() <= 2 {
+        (value, 0)
+    } else {
+        (value / 100, value % 100)
+    };
+
+    if hours < 24 && minutes < 60 {
+        Some(sign * (hours * 3600 + minutes * 60))
+    } else {
+        None
+    }
+}
+
+// Returns the offset in seconds east of UTC of a time zone name.
+fn lenient_zone(name: &str) -> Option<i32> {
+    [
+        ("ut", 0),
+        ("utc", 0),
+        ("gmt", 0),
+        ("z", 0),
+        ("est", -5),
+        ("edt", -4),
+        ("cst", -6),
+        ("cdt", -5),
+        ("mst", -7),
+        ("mdt", -6),
+        ("pst", -8),
+        ("pdt", -7),
+        ("cet", 1),
+        ("cest", 2),
+        ("eet", 2),
+        ("eest", 3),
+        ("bst", 1),
+        ("jst", 9),
+    ]
+    .iter()
+    .find(|(zone, _)| zone.eq_ignore_ascii_case(name))
+    .map(|(_, offset)| offset * 3600)
+}
+
+// Returns the number of a month from its name or its abbreviation.
+fn lenient_month(name: &str) -> Option<u8> {
+    if name.len() >= 3 && name.is_char_boundary(3) {
+        MONTH
+            .iter()
+            .position(|month| month.eq_ignore_ascii_case(&name[..3]))
+            .map(|month| month as u8 + 1)
+    } else {
+        None
+    }
 }
 
 impl PartialOrd for DateTime {
@@ -287,6 +518,88 @@ impl fmt::Display for DateTime {
     }
 }
 
+#[cfg(feature = "chrono")]
+impl From<&DateTime> for chrono::DateTime<chrono::FixedOffset> {
+    /// Converts the DateTime to a `chrono` datetime keeping its time zone,
+    /// which is replaced by UTC when it is out of range.
+    fn from(dt: &DateTime) -> Self {
+        use chrono::TimeZone;
+
+        chrono::FixedOffset::east_opt(dt.tz_offset())
+            .unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap())
+            .from_utc_datetime(&chrono::DateTime::<chrono::Utc>::from(dt).naive_utc())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime> for chrono::DateTime<chrono::FixedOffset> {
+    fn from(dt: DateTime) -> Self {
+        (&dt).into()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<&DateTime> for chrono::DateTime<chrono::Utc> {
+    fn from(dt: &DateTime) -> Self {
+        use chrono::TimeZone;
+
+        chrono::Utc
+            .timestamp_opt(dt.to_timestamp(), 0)
+            .single()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime> for chrono::DateTime<chrono::Utc> {
+    fn from(dt: DateTime) -> Self {
+        (&dt).into()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for DateTime {
+    fn from(dt: chrono::DateTime<Tz>) -> Self {
+        use chrono::Offset;
+
+        DateTime::from_timestamp(dt.timestamp())
+            .to_timezone(dt.offset().fix().local_minus_utc() as i64)
+    }
+}
+
+#[cfg(feature = "time")]
+impl std::convert::TryFrom<&DateTime> for time::OffsetDateTime {
+    type Error = time::error::ComponentRange;
+
+    /// Converts the DateTime to a `time` datetime keeping its time zone,
+    /// which is replaced by UTC when it is out of range. Fails for years
+    /// after 9999 unless the `large-dates` feature of `time` is enabled.
+    fn try_from(dt: &DateTime) -> Result<Self, Self::Error> {
+        time::OffsetDateTime::from_unix_timestamp(dt.to_timestamp()).map(|utc| {
+            utc.to_offset(
+                time::UtcOffset::from_whole_seconds(dt.tz_offset()).unwrap_or(time::UtcOffset::UTC),
+            )
+        })
+    }
+}
+
+#[cfg(feature = "time")]
+impl std::convert::TryFrom<DateTime> for time::OffsetDateTime {
+    type Error = time::error::ComponentRange;
+
+    fn try_from(dt: DateTime) -> Result<Self, Self::Error> {
+        std::convert::TryFrom::try_from(&dt)
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for DateTime {
+    fn from(dt: time::OffsetDateTime) -> Self {
+        DateTime::from_timestamp(dt.unix_timestamp())
+            .to_timezone(dt.offset().whole_seconds() as i64)
+    }
+}
+
 impl<'x> MessageStream<'x> {
     pub fn parse_date(&mut self) -> HeaderValue<'x> {
         let mut pos = 0;
@@ -461,9 +774,9 @@ pub static MONTH_MAP: &[u8; 31] = &[
 
 #[cfg(test)]
 mod tests {
-    use chrono::{FixedOffset, LocalResult, SecondsFormat, TimeZone, Utc};
+    use chrono::{Datelike, FixedOffset, LocalResult, SecondsFormat, TimeZone, Utc};
 
-    use crate::{parsers::MessageStream, HeaderValue};
+    use crate::{parsers::MessageStream, DateTime, HeaderValue};
 
     #[test]
     fn parse_dates() {
@@ -587,4 +900,165 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn parse_lenient_dates() {
+        for (input, expected) in [
+            (
+                "Mon, 2 Oct 2023 10:00:00 +0200",
+                Some("2023-10-02T10:00:00+02:00"),
+            ),
+            (
+                "Mon, 2 Oct 23 10:00:00 +0200 (CEST)",
+                Some("2023-10-02T10:00:00+02:00"),
+            ),
+            (
+                "Sat, 1 Jan 99 23:59:59 -0500",
+                Some("1999-01-01T23:59:59-05:00"),
+            ),
+            (
+                "Mon, 2 Oct 2023 10:00 GMT+0200",
+                Some("2023-10-02T10:00:00+02:00"),
+            ),
+            (
+                "Mon, 2 Oct 2023 10:00:00 UTC-05:30",
+                Some("2023-10-02T10:00:00-05:30"),
+            ),
+            (
+                "Mon, 2 Oct 2023 10:00:00 EDT",
+                Some("2023-10-02T10:00:00-04:00"),
+            ),
+            ("Mon, 2 Oct 2023 10:00:00", Some("2023-10-02T10:00:00Z")),
+            (
+                "Mnday, 2 October 2023 10:00:00 +0000",
+                Some("2023-10-02T10:00:00Z"),
+            ),
+            ("Mon Oct  2 10:00:00 2023", Some("2023-10-02T10:00:00Z")),
+            (
+                "Mon Oct  2 10:00:00 CEST 2023",
+                Some("2023-10-02T10:00:00+02:00"),
+            ),
+            (
+                "2 Oct. 2023 3:05 PM -0700",
+                Some("2023-10-02T15:05:00-07:00"),
+            ),
+            ("12 Oct 2023 12:30 am", Some("2023-10-12T00:30:00Z")),
+            (
+                "2023-10-02 10:00:00 +02:00",
+                Some("2023-10-02T10:00:00+02:00"),
+            ),
+            ("2023-10-02T10:00:00.123Z", Some("2023-10-02T10:00:00Z")),
+            ("02-Oct-2023 10:00:00 +0000", Some("2023-10-02T10:00:00Z")),
+            ("10/22/2023 10:00", Some("2023-10-22T10:00:00Z")),
+            ("22.10.2023 10:00", Some("2023-10-22T10:00:00Z")),
+            (
+                "Mon, 2 Oct 2023 10:00:00 +9900",
+                Some("2023-10-02T10:00:00Z"),
+            ),
+            ("Mon, 31 Feb", None),
+            ("Mon, 2 Foo 2023 10:00:00 +0000", None),
+            ("Mon, 2 Oct 2023 25:00:00 +0000", None),
+            ("2024-01-01T", Some("2024-01-01T00:00:00Z")),
+            ("1T", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                DateTime::parse_lenient(input)
+                    .map(|dt| dt.to_rfc3339())
+                    .as_deref(),
+                expected,
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn convert_timezones() {
+        for (timestamp, offset, expected) in [
+            (0, 0, "Thu, 1 Jan 1970 00:00:00 +0000"),
+            (-1, 0, "Wed, 31 Dec 1969 23:59:59 +0000"),
+            (-86401, 0, "Tue, 30 Dec 1969 23:59:59 +0000"),
+            (0, 19800, "Thu, 1 Jan 1970 05:30:00 +0530"),
+            (0, -12600, "Wed, 31 Dec 1969 20:30:00 -0330"),
+            (1709208000, 0, "Thu, 29 Feb 2024 12:00:00 +0000"),
+            (1709208000, 45900, "Fri, 1 Mar 2024 00:45:00 +1245"),
+            (1709208000, -43200, "Thu, 29 Feb 2024 00:00:00 -1200"),
+        ] {
+            let datetime = DateTime::from_timestamp(timestamp).to_timezone(offset);
+            assert_eq!(datetime.to_rfc822(), expected);
+            assert_eq!(datetime.to_timestamp(), timestamp);
+            assert_eq!(
+                datetime.day_of_week() as u32,
+                chrono::NaiveDate::from_ymd_opt(
+                    datetime.year as i32,
+                    datetime.month as u32,
+                    datetime.day as u32
+                )
+                .unwrap()
+                .weekday()
+                .num_days_from_sunday()
+            );
+        }
+    }
+
+    #[test]
+    fn convert_chrono_time() {
+        use std::convert::TryFrom;
+
+        for (input, expected) in [
+            (
+                "Fri, 1 Mar 2024 00:45:00 +1245",
+                "2024-03-01T00:45:00+12:45",
+            ),
+            (
+                "Wed, 31 Dec 1969 20:30:00 -0330",
+                "1969-12-31T20:30:00-03:30",
+            ),
+            (
+                "Thu, 1 Jan 1970 00:00:00 +0000",
+                "1970-01-01T00:00:00+00:00",
+            ),
+            // Out of range time zone offsets are replaced by UTC
+            (
+                "Thu, 1 Jan 1970 00:00:00 +9900",
+                "1969-12-27T21:00:00+00:00",
+            ),
+        ] {
+            let datetime = DateTime::parse_rfc822(input).unwrap();
+
+            let chrono_datetime = chrono::DateTime::<FixedOffset>::from(&datetime);
+            assert_eq!(
+                chrono_datetime.to_rfc3339_opts(SecondsFormat::Secs, false),
+                expected
+            );
+            assert_eq!(chrono_datetime.timestamp(), datetime.to_timestamp());
+            assert_eq!(
+                chrono::DateTime::<Utc>::from(&datetime).timestamp(),
+                datetime.to_timestamp()
+            );
+
+            let time_datetime = time::OffsetDateTime::try_from(&datetime).unwrap();
+            assert_eq!(time_datetime.unix_timestamp(), datetime.to_timestamp());
+            assert_eq!(
+                time_datetime.offset().whole_seconds(),
+                chrono_datetime.offset().local_minus_utc()
+            );
+
+            if datetime.tz_hour < 24 {
+                assert_eq!(DateTime::from(chrono_datetime), datetime);
+                assert_eq!(DateTime::from(time_datetime), datetime);
+            }
+        }
+
+        let datetime = DateTime {
+            year: 10000,
+            ..DateTime::from_timestamp(0)
+        };
+        assert!(time::OffsetDateTime::try_from(&datetime).is_err());
+        assert_eq!(
+            chrono::DateTime::<Utc>::from(&datetime).to_rfc3339_opts(SecondsFormat::Secs, true),
+            "+10000-01-01T00:00:00Z"
+        );
+    }
 }
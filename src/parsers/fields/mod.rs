@@ -16,4 +16,6 @@ pub mod id;
 pub mod list;
 pub mod raw;
 pub mod thread;
+#[cfg(feature = "tz_database")]
+pub mod timezone;
 pub mod unstructured;
@@ -9,6 +9,8 @@
  * except according to those terms.
  */
 
+use crate::SubjectPrefixes;
+
 static RE_PREFIXES: &[&str] = &[
     "re", "res", "sv", "antw", "ref", "aw", "απ", "השב", "vá", "r", "rif", "bls", "odp", "ynt",
     "atb", "رد", "回复", "转发",
@@ -40,6 +42,61 @@ static FWD_PREFIXES: &[&str] = &[
 ];
 
 pub fn thread_name(text: &str) -> &str {
+    base_subject(text, &|prefix| RE_PREFIXES.contains(&prefix), &|prefix| {
+        FWD_PREFIXES.contains(&prefix)
+    })
+}
+
+impl SubjectPrefixes {
+    /// Creates an empty set of prefixes, removing only the built-in ones.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a reply prefix, such as "Odp" or "Ynt:", matched ignoring case.
+    pub fn reply(mut self, prefix: impl AsRef<str>) -> Self {
+        self.reply.push(normalize_prefix(prefix.as_ref()));
+        self
+    }
+
+    /// Adds a forward prefix, such as "Doorst" or "Tr:", matched ignoring
+    /// case. Forward prefixes are also removed from the end of a subject
+    /// when enclosed in parentheses, such as "(fwd)".
+    pub fn forward(mut self, prefix: impl AsRef<str>) -> Self {
+        self.forward.push(normalize_prefix(prefix.as_ref()));
+        self
+    }
+
+    /// Returns the base subject of a subject, removing both the built-in
+    /// prefixes and the ones added to this set.
+    pub fn thread_name<'x>(&self, text: &'x str) -> &'x str {
+        base_subject(
+            text,
+            &|prefix| RE_PREFIXES.contains(&prefix) || self.reply.iter().any(|p| p == prefix),
+            &|prefix| FWD_PREFIXES.contains(&prefix) || self.forward.iter().any(|p| p == prefix),
+        )
+    }
+
+    /// Returns the base subject of a subject collapsing its whitespace.
+    pub fn normalize_subject(&self, text: &str) -> String {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.thread_name(&text).to_string()
+    }
+}
+
+fn normalize_prefix(prefix: &str) -> String {
+    prefix
+        .trim()
+        .trim_end_matches([':', '\u{ff1a}'])
+        .trim_end()
+        .to_lowercase()
+}
+
+fn base_subject<'x>(
+    text: &'x str,
+    is_reply: &dyn Fn(&str) -> bool,
+    is_forward: &dyn Fn(&str) -> bool,
+) -> &'x str {
     let mut token_start = 0;
     let mut token_end = 0;
 
@@ -63,9 +120,7 @@ pub fn thread_name(text: &str) -> &str {
                             token_end = pos;
                         }
                         let prefix = text[token_start..token_end].to_lowercase();
-                        if RE_PREFIXES.contains(&prefix.as_ref())
-                            || FWD_PREFIXES.contains(&prefix.as_ref())
-                        {
+                        if is_reply(&prefix) || is_forward(&prefix) {
                             seen_header = true;
                         } else {
                             break;
@@ -90,7 +145,8 @@ pub fn thread_name(text: &str) -> &str {
                 seen_blob_header = false;
                 in_blob_ignore = false;
             }
-            ':' if !in_blob => {
+            // Also accept the full width colon used by CJK mail clients
+            ':' | '\u{ff1a}' if !in_blob => {
                 if (seen_header && token_found) || (!seen_header && !token_found) {
                     break;
                 } else if !seen_header {
@@ -98,27 +154,25 @@ pub fn thread_name(text: &str) -> &str {
                         token_end = pos;
                     }
                     let prefix = text[token_start..token_end].to_lowercase();
-                    if !RE_PREFIXES.contains(&prefix.as_ref())
-                        && !FWD_PREFIXES.contains(&prefix.as_ref())
-                    {
+                    if !is_reply(&prefix) && !is_forward(&prefix) {
                         break;
                     }
                 } else {
                     seen_header = false;
                 }
-                thread_name_start = pos + 1;
+                thread_name_start = pos + ch.len_utf8();
                 token_found = false;
             }
-            ':' if in_blob && !in_blob_ignore => {
+            ':' | '\u{ff1a}' if in_blob && !in_blob_ignore => {
                 if token_end == 0 {
                     token_end = pos;
                 }
 
                 let prefix = text[token_start..token_end].to_lowercase();
-                if FWD_PREFIXES.contains(&prefix.as_ref()) {
+                if is_forward(&prefix) {
                     token_found = false;
                     seen_blob_header = true;
-                } else if seen_blob_header && RE_PREFIXES.contains(&prefix.as_ref()) {
+                } else if seen_blob_header && is_reply(&prefix) {
                     token_found = false;
                 } else {
                     in_blob_ignore = true;
@@ -144,23 +198,27 @@ pub fn thread_name(text: &str) -> &str {
     if last_blob_end > thread_name_start
         || (fwd_start > 0 && last_blob_end > fwd_start && fwd_start > thread_name_start)
     {
-        let result = trim_trailing_fwd(&text[last_blob_end..]);
+        let result = trim_trailing_fwd_with(&text[last_blob_end..], is_forward);
         if !result.is_empty() {
             return result;
         }
     }
 
     if fwd_start > 0 && thread_name_start < fwd_start {
-        let result = trim_trailing_fwd(&text[fwd_start..fwd_end]);
+        let result = trim_trailing_fwd_with(&text[fwd_start..fwd_end], is_forward);
         if !result.is_empty() {
             return result;
         }
     }
 
-    trim_trailing_fwd(&text[thread_name_start..])
+    trim_trailing_fwd_with(&text[thread_name_start..], is_forward)
 }
 
 pub fn trim_trailing_fwd(text: &str) -> &str {
+    trim_trailing_fwd_with(text, &|prefix| FWD_PREFIXES.contains(&prefix))
+}
+
+fn trim_trailing_fwd_with<'x>(text: &'x str, is_forward: &dyn Fn(&str) -> bool) -> &'x str {
     let mut in_parentheses = false;
     let mut trim_end = true;
     let mut end_found = false;
@@ -174,9 +232,7 @@ pub fn trim_trailing_fwd(text: &str) -> &str {
             '(' if !end_found => {
                 if in_parentheses {
                     in_parentheses = false;
-                    if fwd_end - pos > 2
-                        && FWD_PREFIXES.contains(&text[pos + 1..fwd_end].to_lowercase().as_ref())
-                    {
+                    if fwd_end - pos > 2 && is_forward(&text[pos + 1..fwd_end].to_lowercase()) {
                         text_end = pos;
                         trim_end = true;
                         continue;
@@ -220,7 +276,10 @@ pub fn trim_trailing_fwd(text: &str) -> &str {
 
 #[cfg(test)]
 mod tests {
-    use crate::parsers::fields::thread::{thread_name, trim_trailing_fwd};
+    use crate::{
+        parsers::fields::thread::{thread_name, trim_trailing_fwd},
+        SubjectPrefixes,
+    };
 
     #[test]
     fn parse_thread_name() {
@@ -277,6 +336,52 @@ mod tests {
         for (input, expected) in tests {
             assert_eq!(thread_name(input), expected, "{input:?}");
         }
+
+        let prefixes = SubjectPrefixes::new().reply("Antwort").reply("wtrl");
+        for (input, expected) in [
+            ("ynt: hello", "hello"),
+            ("Antwort: Wtrl: hello", "hello"),
+            ("[list] WTRL[2]: re: hello", "hello"),
+            ("other: hello", "other: hello"),
+        ] {
+            assert_eq!(prefixes.thread_name(input), expected, "{input:?}");
+        }
+
+        for (input, expected) in [
+            ("Re: Fwd:  hello\n\t world ", "hello world"),
+            ("AW:\tWTRL: hallo  welt", "hallo welt"),
+            ("  ", ""),
+        ] {
+            assert_eq!(prefixes.normalize_subject(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn custom_subject_prefixes() {
+        let prefixes = SubjectPrefixes::new()
+            .reply("Wtrl:")
+            .reply(" ODPOWIEDZ ")
+            .forward("Doorsturen\u{ff1a}");
+
+        for (input, expected) in [
+            ("Re: hello", "hello"),
+            ("WTRL: odpowiedz: hello", "hello"),
+            ("[Doorsturen: re: hello] world", "world"),
+            ("[wtrl: hello]", "[wtrl: hello]"),
+            ("hello (doorsturen)", "hello"),
+            ("hello (wtrl)", "hello (wtrl)"),
+            ("回复\u{ff1a}你好", "你好"),
+            ("Doorsturen\u{ff1a} 回复: 你好", "你好"),
+            ("other: hello", "other: hello"),
+        ] {
+            assert_eq!(prefixes.thread_name(input), expected, "{input:?}");
+        }
+
+        assert_eq!(thread_name("回复\u{ff1a}转发\u{ff1a} 你好"), "你好");
+        assert_eq!(
+            prefixes.normalize_subject("Wtrl:  hello \r\n world"),
+            "hello world"
+        );
     }
 
     #[test]
@@ -0,0 +1,486 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{convert::TryInto, path::PathBuf};
+
+use crate::DateTime;
+
+/// A named time zone read from the system time zone database (TZif files,
+/// RFC 8536), used to find the UTC offset in effect at a given time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeZone {
+    transitions: Vec<(i64, i32)>,
+    initial_offset: i32,
+    rule: Option<Rule>,
+}
+
+// POSIX TZ rule, used for the times after the last transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    std_offset: i32,
+    dst: Option<DstRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DstRule {
+    offset: i32,
+    start: (RuleDate, i32),
+    end: (RuleDate, i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDate {
+    // Jn: day of the year [1, 365], February 29th is never counted.
+    Julian(u16),
+    // n: zero-based day of the year [0, 365].
+    Day(u16),
+    // Mm.w.d: day d [0, 6] of week w [1, 5] of month m [1, 12].
+    MonthWeekDay(u8, u8, u8),
+}
+
+impl TimeZone {
+    /// Loads a time zone by name, such as `America/New_York`, from the
+    /// directory set in the `TZDIR` environment variable or from
+    /// `/usr/share/zoneinfo`.
+    pub fn load(name: &str) -> Option<Self> {
+        if name.is_empty()
+            || name.starts_with('/')
+            || name.split('/').any(|part| part.is_empty() || part == "..")
+        {
+            return None;
+        }
+        let mut path = std::env::var_os("TZDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/usr/share/zoneinfo"));
+        path.push(name);
+        TimeZone::parse(&std::fs::read(path).ok()?)
+    }
+
+    /// Parses the contents of a TZif file.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let header = TzifHeader::parse(bytes)?;
+        if bytes[4] < b'2' {
+            return header.parse_data(bytes, TzifHeader::SIZE, 4, None);
+        }
+
+        // Version 2+ files repeat the data with 64-bit times, followed by
+        // a POSIX TZ rule between newlines.
+        let pos = TzifHeader::SIZE + header.data_len(4);
+        let header = TzifHeader::parse(bytes.get(pos..)?)?;
+        let data_start = pos + TzifHeader::SIZE;
+        let footer_start = data_start + header.data_len(8);
+        let rule = bytes
+            .get(footer_start..)
+            .and_then(|footer| footer.strip_prefix(b"\n"))
+            .and_then(|footer| footer.split(|&ch| ch == b'\n').next())
+            .and_then(|rule| std::str::from_utf8(rule).ok())
+            .and_then(Rule::parse);
+        header.parse_data(bytes, data_start, 8, rule)
+    }
+
+    /// Creates a time zone from a POSIX TZ rule, such as
+    /// `CET-1CEST,M3.5.0,M10.5.0/3`.
+    pub fn from_posix(rule: &str) -> Option<Self> {
+        let rule = Rule::parse(rule)?;
+        Some(TimeZone {
+            transitions: Vec::new(),
+            initial_offset: rule.std_offset,
+            rule: rule.into(),
+        })
+    }
+
+    /// Returns the offset from UTC in seconds in effect at the given Unix
+    /// timestamp.
+    pub fn offset_at(&self, timestamp: i64) -> i32 {
+        match self
+            .transitions
+            .partition_point(|(time, _)| *time <= timestamp)
+        {
+            0 if self.transitions.is_empty() => self
+                .rule
+                .as_ref()
+                .map_or(self.initial_offset, |rule| rule.offset_at(timestamp)),
+            0 => self.initial_offset,
+            pos if pos == self.transitions.len() && self.rule.is_some() => {
+                self.rule.as_ref().unwrap().offset_at(timestamp)
+            }
+            pos => self.transitions[pos - 1].1,
+        }
+    }
+
+    /// Converts a DateTime to this time zone.
+    pub fn convert(&self, datetime: &DateTime) -> DateTime {
+        datetime.to_timezone(self.offset_at(datetime.to_timestamp()) as i64)
+    }
+}
+
+struct TzifHeader {
+    isutcnt: usize,
+    isstdcnt: usize,
+    leapcnt: usize,
+    timecnt: usize,
+    typecnt: usize,
+    charcnt: usize,
+}
+
+impl TzifHeader {
+    const SIZE: usize = 44;
+
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::SIZE || !bytes.starts_with(b"TZif") {
+            return None;
+        }
+        let count = |pos: usize| read_i32(bytes, 20 + pos * 4).map(|count| count as u32 as usize);
+        Some(TzifHeader {
+            isutcnt: count(0)?,
+            isstdcnt: count(1)?,
+            leapcnt: count(2)?,
+            timecnt: count(3)?,
+            typecnt: count(4)?,
+            charcnt: count(5)?,
+        })
+    }
+
+    fn data_len(&self, time_size: usize) -> usize {
+        self.timecnt * (time_size + 1)
+            + self.typecnt * 6
+            + self.charcnt
+            + self.leapcnt * (time_size + 4)
+            + self.isstdcnt
+            + self.isutcnt
+    }
+
+    fn parse_data(
+        &self,
+        bytes: &[u8],
+        pos: usize,
+        time_size: usize,
+        rule: Option<Rule>,
+    ) -> Option<TimeZone> {
+        if self.typecnt == 0 || bytes.len() < pos + self.data_len(time_size) {
+            return None;
+        }
+        let types_start = pos + self.timecnt * (time_size + 1);
+        let type_offset = |idx: usize| {
+            if idx < self.typecnt {
+                read_i32(bytes, types_start + idx * 6)
+            } else {
+                None
+            }
+        };
+
+        let mut transitions = Vec::with_capacity(self.timecnt);
+        for idx in 0..self.timecnt {
+            let time_pos = pos + idx * time_size;
+            let time = if time_size == 8 {
+                i64::from_be_bytes(bytes.get(time_pos..time_pos + 8)?.try_into().ok()?)
+            } else {
+                read_i32(bytes, time_pos)? as i64
+            };
+            let type_idx = bytes[pos + self.timecnt * time_size + idx] as usize;
+            transitions.push((time, type_offset(type_idx)?));
+        }
+
+        Some(TimeZone {
+            transitions,
+            initial_offset: type_offset(0)?,
+            rule,
+        })
+    }
+}
+
+impl Rule {
+    fn parse(rule: &str) -> Option<Self> {
+        let mut rule = rule.as_bytes();
+        parse_name(&mut rule)?;
+        let std_offset = -parse_offset(&mut rule)?;
+        if rule.is_empty() {
+            return Some(Rule {
+                std_offset,
+                dst: None,
+            });
+        }
+
+        parse_name(&mut rule)?;
+        let offset = if rule.first() != Some(&b',') {
+            -parse_offset(&mut rule)?
+        } else {
+            std_offset + 3600
+        };
+        let start = parse_transition(&mut rule)?;
+        let end = parse_transition(&mut rule)?;
+
+        if rule.is_empty() {
+            Some(Rule {
+                std_offset,
+                dst: DstRule { offset, start, end }.into(),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn offset_at(&self, timestamp: i64) -> i32 {
+        let dst = if let Some(dst) = &self.dst {
+            dst
+        } else {
+            return self.std_offset;
+        };
+
+        let year = DateTime::from_timestamp(timestamp + self.std_offset as i64).year;
+        let start = transition_time(year, dst.start) - self.std_offset as i64;
+        let end = transition_time(year, dst.end) - dst.offset as i64;
+        let is_dst = if start < end {
+            timestamp >= start && timestamp < end
+        } else {
+            timestamp < end || timestamp >= start
+        };
+
+        if is_dst {
+            dst.offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+// Local time in seconds of a DST transition in the given year.
+fn transition_time(year: u16, (date, time): (RuleDate, i32)) -> i64 {
+    let jan_1 = days_from_civil(year, 1, 1);
+    let day = match date {
+        RuleDate::Julian(day) => {
+            let is_leap = days_from_civil(year, 3, 1) - days_from_civil(year, 2, 28) == 2;
+            jan_1 + day as i64 - 1 + i64::from(is_leap && day >= 60)
+        }
+        RuleDate::Day(day) => jan_1 + day as i64,
+        RuleDate::MonthWeekDay(month, week, weekday) => {
+            let first = days_from_civil(year, month, 1);
+            let month_len = if month == 12 {
+                31
+            } else {
+                days_from_civil(year, month + 1, 1) - first
+            };
+            let first_weekday = (first + 4).rem_euclid(7);
+            let mut day = (weekday as i64 - first_weekday).rem_euclid(7) + (week as i64 - 1) * 7;
+            while day >= month_len {
+                day -= 7;
+            }
+            first + day
+        }
+    };
+    day * 86400 + time as i64
+}
+
+fn days_from_civil(year: u16, month: u8, day: u8) -> i64 {
+    DateTime {
+        year,
+        month,
+        day,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        tz_before_gmt: false,
+        tz_hour: 0,
+        tz_minute: 0,
+    }
+    .to_timestamp_local()
+    .div_euclid(86400)
+}
+
+fn read_i32(bytes: &[u8], pos: usize) -> Option<i32> {
+    Some(i32::from_be_bytes(
+        bytes.get(pos..pos + 4)?.try_into().ok()?,
+    ))
+}
+
+fn parse_name(rule: &mut &[u8]) -> Option<()> {
+    let len = if rule.first() == Some(&b'<') {
+        rule.iter().position(|&ch| ch == b'>')? + 1
+    } else {
+        rule.iter()
+            .position(|ch| !ch.is_ascii_alphabetic())
+            .unwrap_or(rule.len())
+    };
+    if len > 0 {
+        *rule = &rule[len..];
+        Some(())
+    } else {
+        None
+    }
+}
+
+fn parse_number(rule: &mut &[u8]) -> Option<i32> {
+    let len = rule
+        .iter()
+        .position(|ch| !ch.is_ascii_digit())
+        .unwrap_or(rule.len());
+    if (1..=3).contains(&len) {
+        let number = std::str::from_utf8(&rule[..len]).ok()?.parse().ok()?;
+        *rule = &rule[len..];
+        Some(number)
+    } else {
+        None
+    }
+}
+
+// [+-]hh[:mm[:ss]], positive west of Greenwich.
+fn parse_offset(rule: &mut &[u8]) -> Option<i32> {
+    let sign = match rule.first() {
+        Some(b'-') => -1,
+        Some(b'+') => 1,
+        _ => 0,
+    };
+    if sign != 0 {
+        *rule = &rule[1..];
+    }
+    let mut offset = parse_number(rule)? * 3600;
+    for multiplier in [60, 1] {
+        if let Some(value) = rule.strip_prefix(b":") {
+            *rule = value;
+            offset += parse_number(rule)? * multiplier;
+        } else {
+            break;
+        }
+    }
+    Some(if sign < 0 { -offset } else { offset })
+}
+
+// ,date[/time]
+fn parse_transition(rule: &mut &[u8]) -> Option<(RuleDate, i32)> {
+    *rule = rule.strip_prefix(b",")?;
+    let date = match rule.first()? {
+        b'M' => {
+            *rule = &rule[1..];
+            let month = parse_number(rule)?;
+            *rule = rule.strip_prefix(b".")?;
+            let week = parse_number(rule)?;
+            *rule = rule.strip_prefix(b".")?;
+            let weekday = parse_number(rule)?;
+            if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+                return None;
+            }
+            RuleDate::MonthWeekDay(month as u8, week as u8, weekday as u8)
+        }
+        b'J' => {
+            *rule = &rule[1..];
+            match parse_number(rule)? {
+                day @ 1..=365 => RuleDate::Julian(day as u16),
+                _ => return None,
+            }
+        }
+        _ => match parse_number(rule)? {
+            day @ 0..=365 => RuleDate::Day(day as u16),
+            _ => return None,
+        },
+    };
+    let time = if let Some(value) = rule.strip_prefix(b"/") {
+        *rule = value;
+        parse_offset(rule)?
+    } else {
+        7200
+    };
+    Some((date, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parsers::fields::timezone::TimeZone, DateTime};
+
+    fn utc(timestamp: i64) -> DateTime {
+        DateTime::from_timestamp(timestamp)
+    }
+
+    #[test]
+    fn posix_rules() {
+        let new_york = TimeZone::from_posix("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        for (timestamp, expected_offset) in [
+            (1678604399, -5 * 3600), // 2023-03-12T06:59:59Z
+            (1678604400, -4 * 3600), // 2023-03-12T07:00:00Z
+            (1699163999, -4 * 3600), // 2023-11-05T05:59:59Z
+            (1699164000, -5 * 3600), // 2023-11-05T06:00:00Z
+        ] {
+            assert_eq!(
+                new_york.offset_at(timestamp),
+                expected_offset,
+                "{timestamp}"
+            );
+        }
+        assert_eq!(
+            new_york.convert(&utc(1678604400)).to_rfc822(),
+            "Sun, 12 Mar 2023 03:00:00 -0400"
+        );
+
+        let sydney = TimeZone::from_posix("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(
+            sydney.convert(&utc(1673740800)).to_rfc822(),
+            "Sun, 15 Jan 2023 11:00:00 +1100"
+        );
+        assert_eq!(
+            sydney.convert(&utc(1688169600)).to_rfc822(),
+            "Sat, 1 Jul 2023 10:00:00 +1000"
+        );
+
+        let kolkata = TimeZone::from_posix("IST-5:30").unwrap();
+        assert_eq!(
+            kolkata.convert(&utc(0)).to_rfc822(),
+            "Thu, 1 Jan 1970 05:30:00 +0530"
+        );
+        assert_eq!(
+            TimeZone::from_posix("<-03>3").unwrap().offset_at(0),
+            -3 * 3600
+        );
+
+        for invalid in ["", "EST", "EST5EDT,M13.1.0,M11.1.0", "EST5EDT,M3.2.0"] {
+            assert_eq!(TimeZone::from_posix(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parse_tzif() {
+        // Version 2 file with a transition from LMT to CET in 1893 and the
+        // current rules in the footer.
+        let mut tzif = Vec::new();
+        let header = |tzif: &mut Vec<u8>| {
+            tzif.extend_from_slice(b"TZif2");
+            tzif.extend_from_slice(&[0; 15]);
+            for count in [0u32, 0, 0, 1, 2, 8] {
+                tzif.extend_from_slice(&count.to_be_bytes());
+            }
+        };
+        let data = |tzif: &mut Vec<u8>, time: &[u8]| {
+            tzif.extend_from_slice(time);
+            tzif.push(1);
+            tzif.extend_from_slice(&3208i32.to_be_bytes());
+            tzif.extend_from_slice(&[0, 0]);
+            tzif.extend_from_slice(&3600i32.to_be_bytes());
+            tzif.extend_from_slice(&[0, 4]);
+            tzif.extend_from_slice(b"LMT\0CET\0");
+        };
+        header(&mut tzif);
+        data(&mut tzif, &(-2422051408i64 as i32).to_be_bytes());
+        header(&mut tzif);
+        data(&mut tzif, &(-2422051408i64).to_be_bytes());
+        tzif.extend_from_slice(b"\nCET-1CEST,M3.5.0,M10.5.0/3\n");
+
+        let tz = TimeZone::parse(&tzif).unwrap();
+        assert_eq!(tz.offset_at(-2422051409), 3208);
+        assert_eq!(tz.offset_at(0), 3600);
+        assert_eq!(tz.offset_at(1688169600), 7200);
+        assert_eq!(
+            tz.convert(&utc(1703980800)).to_rfc822(),
+            "Sun, 31 Dec 2023 01:00:00 +0100"
+        );
+
+        assert_eq!(TimeZone::parse(&tzif[..50]), None);
+        assert_eq!(TimeZone::parse(b"TZjf"), None);
+        assert_eq!(TimeZone::load("../etc/passwd"), None);
+    }
+}
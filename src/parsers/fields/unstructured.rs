@@ -91,6 +91,40 @@ impl<'x> MessageStream<'x> {
 
         HeaderValue::Empty
     }
+
+    /// Parses a Content-Location field (RFC 2557), a URI that may be
+    /// enclosed in angle brackets or quotes and folded anywhere. Any
+    /// whitespace within the URI is removed.
+    pub fn parse_content_location(&mut self) -> HeaderValue<'x> {
+        match self.parse_unstructured() {
+            HeaderValue::Text(text) => {
+                let location = text.trim();
+                let location = location
+                    .strip_prefix('<')
+                    .and_then(|location| location.strip_suffix('>'))
+                    .or_else(|| {
+                        location
+                            .strip_prefix('"')
+                            .and_then(|location| location.strip_suffix('"'))
+                    })
+                    .unwrap_or(location);
+                if location.len() == text.len() && !location.contains(char::is_whitespace) {
+                    return HeaderValue::Text(text);
+                }
+
+                let location = location
+                    .chars()
+                    .filter(|ch| !ch.is_whitespace())
+                    .collect::<String>();
+                if !location.is_empty() {
+                    HeaderValue::Text(location.into())
+                } else {
+                    HeaderValue::Empty
+                }
+            }
+            value => value,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -211,4 +245,31 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn parse_content_location() {
+        for (input, expected_result) in [
+            (
+                "http://www.example.com/image.png\n",
+                "http://www.example.com/image.png",
+            ),
+            (
+                " <http://www.example.com/\n images/chart.png>\r\n",
+                "http://www.example.com/images/chart.png",
+            ),
+            ("\"chart.png\"\n", "chart.png"),
+        ] {
+            assert_eq!(
+                MessageStream::new(input.as_bytes())
+                    .parse_content_location()
+                    .unwrap_text(),
+                expected_result,
+                "Failed to parse '{input:?}'",
+            );
+        }
+        assert_eq!(
+            MessageStream::new(b"<>\n").parse_content_location(),
+            crate::HeaderValue::Empty
+        );
+    }
 }
@@ -0,0 +1,109 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Regression fixtures: raw messages stored next to the expected result of
+//! parsing them, as used by the test suite of this crate.
+//!
+//! A fixture is an `.eml` file, parsed twice: as stored, with the result
+//! expected in a `.json` file with the same name, and with its bare line
+//! feeds converted to CRLF, expected in a `.crlf.json` file. The expected
+//! results are the messages serialized as pretty-printed JSON.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::Message;
+
+/// Outcome of [`verify_fixture`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixtureResult {
+    /// Both variants of the message were parsed as expected.
+    Passed,
+    /// The message was parsed differently than expected. The actual
+    /// results were written next to the expected ones, replacing the
+    /// `.json` extension with `.failed`, and their paths are returned.
+    Failed(Vec<PathBuf>),
+}
+
+/// Parses the `.eml` file at `path` and compares the results with the
+/// expected ones.
+pub fn verify_fixture(path: impl AsRef<Path>) -> io::Result<FixtureResult> {
+    let path = path.as_ref();
+    let mut failed = Vec::new();
+    for (expected_path, json) in parse_fixture(path)? {
+        if fs::read(&expected_path)? != json.as_bytes() {
+            let failed_path = expected_path.with_extension("failed");
+            fs::write(&failed_path, json)?;
+            failed.push(failed_path);
+        }
+    }
+
+    Ok(if failed.is_empty() {
+        FixtureResult::Passed
+    } else {
+        FixtureResult::Failed(failed)
+    })
+}
+
+/// Parses the `.eml` file at `path` and writes the results as the expected
+/// ones, replacing any existing.
+pub fn generate_fixture(path: impl AsRef<Path>) -> io::Result<()> {
+    for (expected_path, json) in parse_fixture(path.as_ref())? {
+        fs::write(expected_path, json)?;
+    }
+    Ok(())
+}
+
+/// Returns the paths of the `.eml` files in a directory, sorted by name.
+pub fn fixture_paths(dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "eml") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// Returns the paths of the expected results along with the actual ones.
+fn parse_fixture(path: &Path) -> io::Result<[(PathBuf, String); 2]> {
+    let raw_message = fs::read(path)?;
+    let to_json = |raw_message: &[u8]| {
+        serde_json::to_string_pretty(&Message::parse(raw_message))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    };
+
+    Ok([
+        (path.with_extension("json"), to_json(&raw_message)?),
+        (
+            path.with_extension("crlf.json"),
+            to_json(&add_crlf(&raw_message))?,
+        ),
+    ])
+}
+
+fn add_crlf(bytes: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut last_ch = 0;
+    for &ch in bytes {
+        if ch == b'\n' && last_ch != b'\r' {
+            result.push(b'\r');
+        }
+        result.push(ch);
+        last_ch = ch;
+    }
+
+    result
+}
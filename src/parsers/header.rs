@@ -11,12 +11,31 @@
 
 use std::borrow::Cow;
 
-use crate::{Header, HeaderName, RfcHeader};
+use crate::{Header, HeaderName, HeaderValue, RfcHeader};
 
 use super::MessageStream;
 
 impl<'x> MessageStream<'x> {
     pub fn parse_headers(&mut self, headers: &mut Vec<Header<'x>>) -> bool {
+        self.parse_headers_limited(headers, usize::MAX, &mut Vec::new())
+    }
+
+    /// Parses the header fields, truncating the values longer than
+    /// `max_header_len` bytes. The offset and length of the values that
+    /// were truncated are added to `truncated`.
+    ///
+    /// Returns `true` when the end of the header section was found, which
+    /// is either an empty line, which may end in LF or CRLF, a line made
+    /// only of whitespace, or a line that is not a header field following
+    /// at least one header field. The stream is left at the start of the
+    /// body, which in the last case is the line that is not a header field.
+    /// Returns `false` when the header fields run to the end of the data.
+    pub fn parse_headers_limited(
+        &mut self,
+        headers: &mut Vec<Header<'x>>,
+        max_header_len: usize,
+        truncated: &mut Vec<(usize, usize)>,
+    ) -> bool {
         loop {
             loop {
                 match self.peek() {
@@ -35,51 +54,44 @@ impl<'x> MessageStream<'x> {
             }
 
             let offset_field = self.offset();
+            self.checkpoint();
 
             if let Some(header_name) = self.parse_header_name() {
                 let from_offset = self.offset();
-                let value = if let HeaderName::Rfc(rfc_name) = &header_name {
-                    match rfc_name {
-                        RfcHeader::Subject
-                        | RfcHeader::Comments
-                        | RfcHeader::ContentDescription
-                        | RfcHeader::ContentLocation
-                        | RfcHeader::ContentTransferEncoding => self.parse_unstructured(),
-                        RfcHeader::From
-                        | RfcHeader::To
-                        | RfcHeader::Cc
-                        | RfcHeader::Bcc
-                        | RfcHeader::ReplyTo
-                        | RfcHeader::Sender
-                        | RfcHeader::ResentTo
-                        | RfcHeader::ResentFrom
-                        | RfcHeader::ResentBcc
-                        | RfcHeader::ResentCc
-                        | RfcHeader::ResentSender
-                        | RfcHeader::ListArchive
-                        | RfcHeader::ListHelp
-                        | RfcHeader::ListId
-                        | RfcHeader::ListOwner
-                        | RfcHeader::ListPost
-                        | RfcHeader::ListSubscribe
-                        | RfcHeader::ListUnsubscribe => self.parse_address(),
-                        RfcHeader::Date | RfcHeader::ResentDate => self.parse_date(),
-                        RfcHeader::MessageId
-                        | RfcHeader::References
-                        | RfcHeader::InReplyTo
-                        | RfcHeader::ReturnPath
-                        | RfcHeader::ContentId
-                        | RfcHeader::ResentMessageId => self.parse_id(),
-                        RfcHeader::Keywords | RfcHeader::ContentLanguage => {
-                            self.parse_comma_separared()
-                        }
-                        RfcHeader::Received | RfcHeader::MimeVersion => self.parse_raw(),
-                        RfcHeader::ContentType | RfcHeader::ContentDisposition => {
-                            self.parse_content_type()
-                        }
-                    }
+                let value = if let Some(field_end) = self.long_field_end(max_header_len) {
+                    // Parse the value up to a safe boundary and skip the rest
+                    let cut =
+                        truncation_point(self.data, from_offset, from_offset + max_header_len);
+                    let mut value = self.data[from_offset..cut].to_vec();
+                    value.push(b'\n');
+                    let mut stream = MessageStream::new(&value);
+                    stream.word_fallback = self.word_fallback;
+                    let value = stream.parse_header_value(&header_name).into_owned();
+                    self.skip_bytes(field_end - from_offset);
+                    truncated.push((from_offset, field_end - from_offset));
+                    value
                 } else {
-                    self.parse_raw()
+                    let mut value = self.parse_header_value(&header_name);
+
+                    if let Some(field_end) = whitespace_line(self.data, from_offset, self.offset())
+                    {
+                        // A line made only of whitespace ends the header section
+                        let mut stream = MessageStream::new(&self.data[..field_end]);
+                        stream.word_fallback = self.word_fallback;
+                        stream.skip_bytes(from_offset);
+                        value = stream.parse_header_value(&header_name);
+                        self.restore_pos = field_end;
+                        self.restore();
+                    } else if self.data.last() != Some(&b'\n') && self.is_eof() {
+                        // Values are parsed up to a line break
+                        let mut bytes = self.data[from_offset..].to_vec();
+                        bytes.push(b'\n');
+                        let mut stream = MessageStream::new(&bytes);
+                        stream.word_fallback = self.word_fallback;
+                        value = stream.parse_header_value(&header_name).into_owned();
+                    }
+
+                    value
                 };
 
                 headers.push(Header {
@@ -89,18 +101,90 @@ impl<'x> MessageStream<'x> {
                     offset_start: from_offset,
                     offset_end: self.offset(),
                 });
+            } else if !headers.is_empty() {
+                // A line that is not a header field starts the body
+                self.restore();
+                return true;
             } else if self.is_eof() {
                 return false;
             }
         }
     }
 
+    fn parse_header_value(&mut self, header_name: &HeaderName<'_>) -> HeaderValue<'x> {
+        if let HeaderName::Rfc(rfc_name) = header_name {
+            match rfc_name {
+                RfcHeader::Subject
+                | RfcHeader::Comments
+                | RfcHeader::ContentDescription
+                | RfcHeader::ContentTransferEncoding => self.parse_unstructured(),
+                RfcHeader::ContentLocation => self.parse_content_location(),
+                RfcHeader::From
+                | RfcHeader::To
+                | RfcHeader::Cc
+                | RfcHeader::Bcc
+                | RfcHeader::ReplyTo
+                | RfcHeader::Sender
+                | RfcHeader::ResentTo
+                | RfcHeader::ResentFrom
+                | RfcHeader::ResentBcc
+                | RfcHeader::ResentCc
+                | RfcHeader::ResentSender
+                | RfcHeader::ListArchive
+                | RfcHeader::ListHelp
+                | RfcHeader::ListId
+                | RfcHeader::ListOwner
+                | RfcHeader::ListPost
+                | RfcHeader::ListSubscribe
+                | RfcHeader::ListUnsubscribe => self.parse_address(),
+                RfcHeader::Date | RfcHeader::ResentDate => self.parse_date(),
+                RfcHeader::MessageId
+                | RfcHeader::References
+                | RfcHeader::InReplyTo
+                | RfcHeader::ReturnPath
+                | RfcHeader::ContentId
+                | RfcHeader::ResentMessageId => self.parse_id(),
+                RfcHeader::Keywords | RfcHeader::ContentLanguage => self.parse_comma_separared(),
+                RfcHeader::Received | RfcHeader::MimeVersion => self.parse_raw(),
+                RfcHeader::ContentType | RfcHeader::ContentDisposition => self.parse_content_type(),
+            }
+        } else {
+            self.parse_raw()
+        }
+    }
+
+    // Returns the offset of the end of the header field starting at the
+    // current position if its value is longer than `max_len` bytes.
+    fn long_field_end(&self, max_len: usize) -> Option<usize> {
+        let from_offset = self.offset();
+        if self.data.len() - from_offset <= max_len {
+            return None;
+        }
+        let mut field_end = from_offset;
+        loop {
+            match self.data[field_end..].iter().position(|&ch| ch == b'\n') {
+                Some(pos) => {
+                    field_end += pos + 1;
+                    if !matches!(self.data.get(field_end), Some(b' ' | b'\t')) {
+                        break;
+                    }
+                }
+                None => {
+                    field_end = self.data.len();
+                    break;
+                }
+            }
+        }
+        if field_end - from_offset > max_len {
+            Some(field_end)
+        } else {
+            None
+        }
+    }
+
     pub fn parse_header_name(&mut self) -> Option<HeaderName<'x>> {
         let mut token_start: usize = 0;
         let mut token_end: usize = 0;
-        let mut token_len: usize = 0;
-        let mut token_hash: usize = 0;
-        let mut last_ch: u8 = 0;
 
         while let Some(&ch) = self.next() {
             match ch {
@@ -116,24 +200,8 @@ impl<'x> MessageStream<'x> {
                     if !ch.is_ascii_whitespace() {
                         if token_start == 0 {
                             token_start = self.offset();
-                            token_end = token_start;
-                        } else {
-                            token_end = self.offset();
-                            last_ch = ch;
-                        }
-
-                        if let 0 | 9 = token_len {
-                            token_hash += {
-                                #[cfg(feature = "ludicrous_mode")]
-                                unsafe {
-                                    *HDR_HASH.get_unchecked(ch.to_ascii_lowercase() as usize)
-                                }
-
-                                #[cfg(not(feature = "ludicrous_mode"))]
-                                HDR_HASH[ch.to_ascii_lowercase() as usize]
-                            } as usize;
                         }
-                        token_len += 1;
+                        token_end = self.offset();
                     }
                 }
             }
@@ -142,83 +210,164 @@ impl<'x> MessageStream<'x> {
         if token_start != 0 {
             let field = self.bytes(token_start - 1..token_end);
 
-            if (2..=25).contains(&token_len) {
-                token_hash += token_len + {
-                    #[cfg(feature = "ludicrous_mode")]
-                    unsafe {
-                        *HDR_HASH.get_unchecked(last_ch.to_ascii_lowercase() as usize)
-                    }
-
-                    #[cfg(not(feature = "ludicrous_mode"))]
-                    HDR_HASH[last_ch.to_ascii_lowercase() as usize]
-                } as usize;
-
-                if (4..=72).contains(&token_hash) {
-                    let token_hash = token_hash - 4;
-
-                    if field.eq_ignore_ascii_case(HDR_NAMES[token_hash]) {
-                        return Some(HeaderName::Rfc(HDR_MAP[token_hash]));
-                    }
-                }
+            if let Some(rfc_header) = rfc_header(field) {
+                Some(HeaderName::Rfc(rfc_header))
+            } else {
+                Some(HeaderName::Other(String::from_utf8_lossy(field)))
             }
-            Some(HeaderName::Other(String::from_utf8_lossy(field)))
         } else {
             None
         }
     }
 }
 
-impl<'x> HeaderName<'x> {
-    /// Parse a header name
-    pub fn parse(data: impl Into<Cow<'x, str>>) -> Option<HeaderName<'x>> {
-        let mut token_hash: usize = 0;
-        let mut last_ch: u8 = 0;
-        let data = data.into();
-        let data_ = data.as_bytes();
-
-        for (pos, &ch) in data_.iter().enumerate() {
-            if ch.is_ascii_alphanumeric() || [b'_', b'-'].contains(&ch) {
-                if let 0 | 9 = pos {
-                    token_hash += {
-                        #[cfg(feature = "ludicrous_mode")]
-                        unsafe {
-                            *HDR_HASH.get_unchecked(ch.to_ascii_lowercase() as usize)
-                        }
+// Returns the RFC header field matching a header field name, using a perfect
+// hash of its length and of its first, tenth and last characters.
+#[inline(always)]
+fn rfc_header(name: &[u8]) -> Option<RfcHeader> {
+    if !(2..=25).contains(&name.len()) {
+        return None;
+    }
 
-                        #[cfg(not(feature = "ludicrous_mode"))]
-                        HDR_HASH[ch.to_ascii_lowercase() as usize]
-                    } as usize;
-                }
-                last_ch = ch;
-            } else {
-                return None;
-            }
+    let hash = |ch: u8| -> usize {
+        #[cfg(feature = "ludicrous_mode")]
+        unsafe {
+            *HDR_HASH.get_unchecked(ch.to_ascii_lowercase() as usize) as usize
         }
 
-        if (2..=25).contains(&data.len()) {
-            token_hash += data.len() + {
-                #[cfg(feature = "ludicrous_mode")]
-                unsafe {
-                    *HDR_HASH.get_unchecked(last_ch.to_ascii_lowercase() as usize)
-                }
+        #[cfg(not(feature = "ludicrous_mode"))]
+        {
+            HDR_HASH[ch.to_ascii_lowercase() as usize] as usize
+        }
+    };
+    let token_hash = name.len()
+        + hash(name[0])
+        + name.get(9).map_or(0, |&ch| hash(ch))
+        + hash(name[name.len() - 1]);
 
-                #[cfg(not(feature = "ludicrous_mode"))]
-                HDR_HASH[last_ch.to_ascii_lowercase() as usize]
-            } as usize;
+    if (4..=72).contains(&token_hash) {
+        let token_hash = token_hash - 4;
 
-            if (4..=72).contains(&token_hash) {
-                let token_hash = token_hash - 4;
+        if name.eq_ignore_ascii_case(HDR_NAMES[token_hash]) {
+            return Some(HDR_MAP[token_hash]);
+        }
+    }
 
-                if data_.eq_ignore_ascii_case(HDR_NAMES[token_hash]) {
-                    return HeaderName::Rfc(HDR_MAP[token_hash]).into();
-                }
-            }
+    None
+}
+
+/// Parses the value of a single header field, without its name and colon,
+/// as it would be parsed in a message, such as the value of a header field
+/// returned by an IMAP server or a milter callback. Folded values and
+/// encoded-words are decoded, and the value does not need to end with a
+/// line break.
+///
+/// ```
+/// use mail_parser::{parsers::header::parse_header_value, Addr, HeaderValue};
+///
+/// assert_eq!(
+///     parse_header_value("From", b"=?utf-8?q?Jos=C3=A9?= <jose@example.com>"),
+///     HeaderValue::Address(Addr::new("Jos\u{e9}".into(), "jose@example.com"))
+/// );
+/// ```
+pub fn parse_header_value<'x>(name: &str, value: &'x [u8]) -> HeaderValue<'x> {
+    let name = HeaderName::parse(name).unwrap_or(HeaderName::Other("".into()));
+    if value.ends_with(b"\n") {
+        MessageStream::new(value).parse_header_value(&name)
+    } else {
+        let mut value = value.to_vec();
+        value.push(b'\n');
+        MessageStream::new(&value)
+            .parse_header_value(&name)
+            .into_owned()
+    }
+}
+
+/// Parses a block of header fields outside of a message, such as the
+/// contents of a text/rfc822-headers part or headers stored apart from the
+/// message body. Parsing stops at the first empty line, and the block does
+/// not need to end with a line break. Offsets are relative to the start of
+/// the block.
+///
+/// ```
+/// use mail_parser::{parsers::header::parse_header_block, GetHeader, RfcHeader};
+///
+/// let headers = parse_header_block(b"Subject: Hello\r\nX-Queue-Id: 4F2A1\r\n");
+/// assert_eq!(headers.len(), 2);
+/// assert_eq!(
+///     headers.rfc(&RfcHeader::Subject).and_then(|v| v.as_text_ref()),
+///     Some("Hello")
+/// );
+/// ```
+pub fn parse_header_block(bytes: &[u8]) -> Vec<Header<'_>> {
+    let mut headers = Vec::new();
+    MessageStream::new(bytes).parse_headers(&mut headers);
+    headers
+}
+
+// Returns the offset of the first line made only of whitespace, other than
+// an empty line, between `start` and `end`.
+fn whitespace_line(data: &[u8], start: usize, end: usize) -> Option<usize> {
+    let mut pos = start;
+    while let Some(line_end) = data[pos..end].iter().position(|&ch| ch == b'\n') {
+        pos += line_end + 1;
+        if matches!(data.get(pos), Some(b' ' | b'\t'))
+            && data[pos..]
+                .iter()
+                .take_while(|&&ch| ch != b'\n')
+                .all(|ch| ch.is_ascii_whitespace())
+        {
+            return Some(pos);
         }
+    }
+    None
+}
 
-        if !data.is_empty() {
-            HeaderName::Other(data).into()
-        } else {
+// Returns the offset at or before `max_end` where a header value starting
+// at `start` can be cut without splitting a UTF-8 sequence or an encoded
+// word.
+fn truncation_point(data: &[u8], start: usize, max_end: usize) -> usize {
+    let mut end = max_end;
+    while end > start && ((data[end] & 0xc0) == 0x80 || data[end - 1..end + 1] == *b"=?") {
+        end -= 1;
+    }
+    if let Some(word_start) = data[start..end]
+        .windows(2)
+        .rposition(|window| window == b"=?")
+        .map(|pos| start + pos + 2)
+    {
+        // An encoded word is complete once the "?=" following its charset
+        // and encoding is found.
+        if !data[word_start..end]
+            .iter()
+            .enumerate()
+            .filter(|(_, &ch)| ch == b'?')
+            .nth(2)
+            .is_some_and(|(pos, _)| {
+                word_start + pos + 1 < end && data[word_start + pos + 1] == b'='
+            })
+        {
+            end = word_start - 2;
+        }
+    }
+    end
+}
+
+impl<'x> HeaderName<'x> {
+    /// Parse a header name
+    pub fn parse(data: impl Into<Cow<'x, str>>) -> Option<HeaderName<'x>> {
+        let data = data.into();
+
+        if data.is_empty()
+            || !data
+                .bytes()
+                .all(|ch| ch.is_ascii_alphanumeric() || [b'_', b'-'].contains(&ch))
+        {
             None
+        } else if let Some(rfc_header) = rfc_header(data.as_bytes()) {
+            HeaderName::Rfc(rfc_header).into()
+        } else {
+            HeaderName::Other(data).into()
         }
     }
 }
@@ -229,9 +378,91 @@ impl From<RfcHeader> for u8 {
     }
 }
 
+/// Returns the header fields matching the predicate, in order of appearance.
+pub fn find_headers<'y, 'x>(
+    headers: &'y [Header<'x>],
+    mut predicate: impl FnMut(&Header<'x>) -> bool,
+) -> Vec<&'y Header<'x>> {
+    headers.iter().filter(|header| predicate(header)).collect()
+}
+
+/// Returns the header fields whose name starts with the provided prefix
+/// (case-insensitive), in order of appearance, e.g. "X-Spam-".
+pub fn headers_with_prefix<'y, 'x>(headers: &'y [Header<'x>], prefix: &str) -> Vec<&'y Header<'x>> {
+    let prefix = prefix.as_bytes();
+    headers
+        .iter()
+        .filter(|header| {
+            header
+                .name
+                .as_str()
+                .as_bytes()
+                .get(..prefix.len())
+                .is_some_and(|name| name.eq_ignore_ascii_case(prefix))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{parsers::MessageStream, HeaderName, RfcHeader};
+    use crate::{parsers::MessageStream, GetHeader, HeaderName, HeaderValue, Message, RfcHeader};
+
+    use super::{find_headers, headers_with_prefix};
+
+    #[test]
+    fn find_matching_headers() {
+        let message = Message::parse(
+            concat!(
+                "X-Spam-Status: No, score=-0.1\n",
+                "From: john@example.com\n",
+                "x-spam-score: -0.1\n",
+                "X-Mailer: Mutt\n",
+                "X-Spam-Flag: NO\n",
+                "X-Spa: truncated\n",
+                "Subject: test\n",
+                "\n",
+                "Hi!\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let headers = &message.parts[0].headers;
+
+        assert_eq!(
+            headers_with_prefix(headers, "X-Spam-")
+                .into_iter()
+                .map(|h| h.name())
+                .collect::<Vec<_>>(),
+            ["X-Spam-Status", "x-spam-score", "X-Spam-Flag"]
+        );
+        assert_eq!(
+            headers_with_prefix(message.headers(), "x-")
+                .into_iter()
+                .map(|h| h.name())
+                .collect::<Vec<_>>(),
+            [
+                "X-Spam-Status",
+                "x-spam-score",
+                "X-Mailer",
+                "X-Spam-Flag",
+                "X-Spa"
+            ]
+        );
+        assert!(headers_with_prefix(headers, "X-Virus-").is_empty());
+
+        assert_eq!(
+            find_headers(headers, |h| matches!(h.name, HeaderName::Rfc(_))
+                || h.value == HeaderValue::Text("NO".into()))
+            .into_iter()
+            .map(|h| h.name())
+            .collect::<Vec<_>>(),
+            ["From", "X-Spam-Flag", "Subject"]
+        );
+        assert_eq!(
+            find_headers(headers, |h| h.name == HeaderName::Rfc(RfcHeader::Subject)).len(),
+            1
+        );
+    }
 
     #[test]
     fn header_name_parse() {
@@ -258,6 +489,142 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn rfc_header_names() {
+        for rfc_header in super::HDR_MAP {
+            let name = rfc_header.as_str();
+            for name in [name.to_string(), name.to_lowercase(), name.to_uppercase()] {
+                assert_eq!(
+                    HeaderName::parse(name.as_str()),
+                    Some(HeaderName::Rfc(*rfc_header)),
+                    "{:?}",
+                    name
+                );
+                assert_eq!(
+                    MessageStream::new(format!("{name}: value\n").as_bytes()).parse_header_name(),
+                    Some(HeaderName::Rfc(*rfc_header)),
+                    "{:?}",
+                    name
+                );
+            }
+        }
+
+        for name in [
+            "Subjects",
+            "X-Subject",
+            "Sub ject",
+            "Content-",
+            "List-Unsubscribed",
+            "Content-Transfer-Encodings",
+            "Resent-Message-ID2",
+            "Mime_Version",
+        ] {
+            assert_eq!(
+                MessageStream::new(format!("{name}: value\n").as_bytes()).parse_header_name(),
+                Some(HeaderName::Other(name.into())),
+                "{:?}",
+                name
+            );
+        }
+        assert_eq!(HeaderName::parse("Sub ject"), None);
+        assert_eq!(HeaderName::parse(""), None);
+    }
+
+    #[test]
+    fn parse_standalone_header_block() {
+        use super::parse_header_block;
+
+        let headers = parse_header_block(
+            concat!(
+                "Return-Path: <sender@example.com>\n",
+                "Received: from mx.example.com\n",
+                "  by mail.example.org; Tue, 1 Jul 2003 10:52:37 +0200\n",
+                "Subject: =?utf-8?q?caf=C3=A9?=\n",
+                "X-Stored-Flags: \\Seen"
+            )
+            .as_bytes(),
+        );
+        assert_eq!(
+            headers
+                .iter()
+                .map(|header| header.name())
+                .collect::<Vec<_>>(),
+            ["Return-Path", "Received", "Subject", "X-Stored-Flags"]
+        );
+        assert_eq!(headers[0].offset_field, 0);
+        assert_eq!(
+            headers.rfc(&RfcHeader::Subject),
+            Some(&HeaderValue::Text("caf\u{e9}".into()))
+        );
+        assert_eq!(
+            headers
+                .header("X-Stored-Flags")
+                .map(|header| header.value()),
+            Some(&HeaderValue::Text("\\Seen".into()))
+        );
+
+        // Parsing stops at the end of the header section
+        let headers = parse_header_block(b"Subject: Hi\r\n\r\nX-Body: not a header\r\n");
+        assert_eq!(headers.len(), 1);
+
+        assert!(parse_header_block(b"").is_empty());
+    }
+
+    #[test]
+    fn parse_single_header_value() {
+        use super::parse_header_value;
+        use crate::{Addr, DateTime};
+
+        for (name, value, expected_result) in [
+            (
+                "Subject",
+                &b"=?iso-8859-1?q?caf=E9?=\r\n and more"[..],
+                HeaderValue::Text("caf\u{e9} and more".into()),
+            ),
+            (
+                "to",
+                b"John <john@example.com>, jane@example.com\r\n",
+                HeaderValue::AddressList(vec![
+                    Addr::new("John".into(), "john@example.com"),
+                    Addr::new(None, "jane@example.com"),
+                ]),
+            ),
+            (
+                "Date",
+                b"Tue, 1 Jul 2003 10:52:37 +0200",
+                HeaderValue::DateTime(DateTime {
+                    year: 2003,
+                    month: 7,
+                    day: 1,
+                    hour: 10,
+                    minute: 52,
+                    second: 37,
+                    tz_before_gmt: false,
+                    tz_hour: 2,
+                    tz_minute: 0,
+                }),
+            ),
+            (
+                "Message-ID",
+                b" <1234@local.machine.example>",
+                HeaderValue::Text("1234@local.machine.example".into()),
+            ),
+            (
+                "X-Custom",
+                b"  some\n  folded value",
+                HeaderValue::Text("some\n  folded value".into()),
+            ),
+            ("Subject", b"", HeaderValue::Empty),
+        ] {
+            assert_eq!(
+                parse_header_value(name, value),
+                expected_result,
+                "Failed for {:?}",
+                name
+            );
+        }
+    }
 }
 
 /*
@@ -283,7 +650,7 @@ static HDR_PARSER: &[(bool, for<'x> fn(&mut MessageStream<'x>) -> HeaderValue<'x
     (false, MessageStream::parse_unstructured), // ContentDescription = 16,
     (false, MessageStream::parse_id),           // ContentId = 17,
     (false, MessageStream::parse_comma_separared), // ContentLanguage = 18
-    (false, MessageStream::parse_unstructured), // ContentLocation = 19
+    (false, MessageStream::parse_content_location), // ContentLocation = 19
     (false, MessageStream::parse_unstructured), // ContentTransferEncoding = 20,
     (false, MessageStream::parse_content_type), // ContentType = 21,
     (false, MessageStream::parse_content_type), // ContentDisposition = 22,
@@ -0,0 +1,498 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{borrow::Cow, ops::Range};
+
+use crate::{
+    CalendarAddress, CalendarEvent, CalendarMethod, CalendarTime, DateTime, Message, MessagePart,
+    MimeHeaders, PartType,
+};
+
+/// An RFC5545 content line: name *(";" param) ":" value
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ContentLine<'x> {
+    pub name: Cow<'x, str>,
+    pub params: Vec<(Cow<'x, str>, Cow<'x, str>)>,
+    pub value: Cow<'x, str>,
+}
+
+impl<'x> ContentLine<'x> {
+    pub fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    pub fn param(&self, name: &str) -> Option<&Cow<'x, str>> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+}
+
+/// Unfolds and splits an iCalendar or vCard object into content lines.
+pub(crate) fn parse_content_lines(text: &str) -> Vec<ContentLine<'_>> {
+    let mut lines: Vec<Cow<str>> = Vec::new();
+
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Some(continuation) = line.strip_prefix([' ', '\t']) {
+            if let Some(last_line) = lines.last_mut() {
+                last_line.to_mut().push_str(continuation);
+                continue;
+            }
+        }
+        if !line.trim().is_empty() {
+            lines.push(line.into());
+        }
+    }
+
+    lines.into_iter().filter_map(parse_content_line).collect()
+}
+
+fn parse_content_line(line: Cow<'_, str>) -> Option<ContentLine<'_>> {
+    let bytes = line.as_bytes();
+    let name_end = bytes.iter().position(|&ch| ch == b';' || ch == b':')?;
+    let mut params = Vec::new();
+    let mut pos = name_end;
+
+    while bytes[pos] == b';' {
+        let name_start = pos + 1;
+        pos = name_start
+            + bytes[name_start..]
+                .iter()
+                .position(|&ch| matches!(ch, b'=' | b';' | b':'))?;
+        if bytes[pos] != b'=' {
+            // vCard 2.1 allows type values without a parameter name
+            if pos > name_start {
+                params.push(("TYPE".into(), sub_str(&line, name_start..pos)));
+            }
+            continue;
+        }
+
+        let value_start = pos + 1;
+        let mut in_quote = false;
+        pos = value_start;
+        loop {
+            match bytes.get(pos)? {
+                b'"' => in_quote = !in_quote,
+                b';' | b':' if !in_quote => break,
+                _ => (),
+            }
+            pos += 1;
+        }
+
+        let value = if pos - value_start > 1 && bytes[value_start] == b'"' && bytes[pos - 1] == b'"'
+        {
+            value_start + 1..pos - 1
+        } else {
+            value_start..pos
+        };
+        params.push((
+            sub_str(&line, name_start..value_start - 1),
+            sub_str(&line, value),
+        ));
+    }
+
+    // Property names may be prefixed by a vCard group name
+    let name_start = bytes[..name_end]
+        .iter()
+        .position(|&ch| ch == b'.')
+        .map_or(0, |pos| pos + 1);
+
+    Some(ContentLine {
+        name: sub_str(&line, name_start..name_end),
+        params,
+        value: sub_str(&line, pos + 1..line.len()),
+    })
+}
+
+fn sub_str<'x>(text: &Cow<'x, str>, range: Range<usize>) -> Cow<'x, str> {
+    match text {
+        Cow::Borrowed(text) => text[range].trim().into(),
+        Cow::Owned(text) => text[range].trim().to_string().into(),
+    }
+}
+
+/// Removes the backslash escapes from an RFC5545 TEXT value.
+pub(crate) fn unescape_text(value: Cow<'_, str>) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return value;
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => result.push('\n'),
+                Some(ch) => result.push(ch),
+                None => (),
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+    result.into()
+}
+
+/// Returns the contents of a text part, or of a binary part holding UTF-8 text.
+pub(crate) fn part_text<'y>(part: &'y MessagePart<'_>) -> Option<&'y str> {
+    match &part.body {
+        PartType::Text(text) => Some(text.as_ref()),
+        PartType::Binary(bytes) | PartType::InlineBinary(bytes) => std::str::from_utf8(bytes).ok(),
+        _ => None,
+    }
+}
+
+impl<'x> Message<'x> {
+    /// Returns the events contained in the text/calendar parts of the message.
+    pub fn calendar_parts(&self) -> Vec<CalendarEvent<'_>> {
+        let mut events = Vec::new();
+
+        for (part_id, part) in self.parts.iter().enumerate() {
+            if !part.content_type().is_some_and(|ct| {
+                matches!(
+                    (ct.ctype(), ct.subtype()),
+                    ("text", Some("calendar")) | ("application", Some("ics"))
+                )
+            }) {
+                continue;
+            }
+
+            if let Some(text) = part_text(part) {
+                events.extend(CalendarEvent::parse(text).into_iter().map(|mut event| {
+                    event.part_id = part_id;
+                    event
+                }));
+            }
+        }
+
+        events
+    }
+}
+
+impl<'x> CalendarEvent<'x> {
+    /// Parses the VEVENT components of an iCalendar object.
+    pub fn parse(text: &'x str) -> Vec<CalendarEvent<'x>> {
+        let mut events = Vec::new();
+        let mut method = None;
+        let mut event: Option<CalendarEvent> = None;
+        let mut depth = 0usize;
+        let mut event_depth = 0;
+
+        for line in parse_content_lines(text) {
+            if line.is("BEGIN") {
+                depth += 1;
+                if line.value.eq_ignore_ascii_case("VEVENT") && event.is_none() {
+                    event = CalendarEvent {
+                        method: method.clone(),
+                        ..Default::default()
+                    }
+                    .into();
+                    event_depth = depth;
+                }
+                continue;
+            } else if line.is("END") {
+                if depth == event_depth && line.value.eq_ignore_ascii_case("VEVENT") {
+                    events.extend(event.take());
+                }
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+
+            let event = match &mut event {
+                Some(event) if depth == event_depth => event,
+                None if line.is("METHOD") => {
+                    method = CalendarMethod::parse(line.value).into();
+                    continue;
+                }
+                _ => continue,
+            };
+
+            match line.name.to_ascii_uppercase().as_str() {
+                "UID" => event.uid = line.value.into(),
+                "SEQUENCE" => event.sequence = line.value.parse().ok(),
+                "STATUS" => event.status = line.value.into(),
+                "SUMMARY" => event.summary = unescape_text(line.value).into(),
+                "LOCATION" => event.location = unescape_text(line.value).into(),
+                "DTSTART" => event.dtstart = CalendarTime::from_line(line).into(),
+                "DTEND" => event.dtend = CalendarTime::from_line(line).into(),
+                "ORGANIZER" => event.organizer = CalendarAddress::from_line(line).into(),
+                "ATTENDEE" => event.attendees.push(CalendarAddress::from_line(line)),
+                _ => (),
+            }
+        }
+
+        events
+    }
+}
+
+impl<'x> CalendarMethod<'x> {
+    pub fn parse(value: Cow<'x, str>) -> Self {
+        match value.to_ascii_uppercase().as_str() {
+            "PUBLISH" => CalendarMethod::Publish,
+            "REQUEST" => CalendarMethod::Request,
+            "REPLY" => CalendarMethod::Reply,
+            "ADD" => CalendarMethod::Add,
+            "CANCEL" => CalendarMethod::Cancel,
+            "REFRESH" => CalendarMethod::Refresh,
+            "COUNTER" => CalendarMethod::Counter,
+            "DECLINECOUNTER" => CalendarMethod::DeclineCounter,
+            _ => CalendarMethod::Other(value),
+        }
+    }
+}
+
+impl<'x> CalendarTime<'x> {
+    fn from_line(line: ContentLine<'x>) -> Self {
+        CalendarTime {
+            tzid: line.param("TZID").cloned(),
+            value: line.value,
+        }
+    }
+
+    /// Returns `true` if the value is a DATE rather than a DATE-TIME.
+    pub fn is_date(&self) -> bool {
+        !self.value.contains(['T', 't'])
+    }
+
+    /// Returns `true` if the value is expressed in UTC time.
+    pub fn is_utc(&self) -> bool {
+        self.value.ends_with(['Z', 'z'])
+    }
+
+    /// Converts the value to a `DateTime`. Values in local time, either
+    /// floating or with a TZID, are returned without a timezone offset.
+    pub fn to_datetime(&self) -> Option<DateTime> {
+        let value = self.value.trim_end_matches(['Z', 'z']);
+        let (date, time) = value.split_once(['T', 't']).unwrap_or((value, "000000"));
+        if date.len() != 8 || time.len() != 6 {
+            return None;
+        }
+
+        let number = |text: &str, range: Range<usize>| text.get(range)?.parse::<u16>().ok();
+        Some(DateTime {
+            year: number(date, 0..4)?,
+            month: number(date, 4..6)? as u8,
+            day: number(date, 6..8)? as u8,
+            hour: number(time, 0..2)? as u8,
+            minute: number(time, 2..4)? as u8,
+            second: number(time, 4..6)? as u8,
+            tz_before_gmt: false,
+            tz_hour: 0,
+            tz_minute: 0,
+        })
+    }
+}
+
+impl<'x> CalendarAddress<'x> {
+    fn from_line(line: ContentLine<'x>) -> Self {
+        CalendarAddress {
+            name: line.param("CN").cloned(),
+            role: line.param("ROLE").cloned(),
+            partstat: line.param("PARTSTAT").cloned(),
+            rsvp: line
+                .param("RSVP")
+                .map(|rsvp| rsvp.eq_ignore_ascii_case("TRUE")),
+            address: match line.value {
+                Cow::Borrowed(value) => strip_mailto(value).into(),
+                Cow::Owned(value) => strip_mailto(&value).to_string().into(),
+            },
+        }
+    }
+}
+
+fn strip_mailto(value: &str) -> &str {
+    value
+        .get(..7)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("mailto:"))
+        .and_then(|_| value.get(7..))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CalendarAddress, CalendarEvent, CalendarMethod, CalendarTime, DateTime, Message, TextKind,
+    };
+
+    #[test]
+    fn parse_calendar_parts() {
+        let message = Message::parse(
+            concat!(
+                "From: organizer@example.com\r\n",
+                "Subject: Invitation: Project sync\r\n",
+                "Content-Type: multipart/alternative; boundary=\"b1\"\r\n",
+                "\r\n",
+                "--b1\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "You have been invited.\r\n",
+                "--b1\r\n",
+                "Content-Type: text/calendar; charset=utf-8; method=REQUEST\r\n",
+                "\r\n",
+                "BEGIN:VCALENDAR\r\n",
+                "VERSION:2.0\r\n",
+                "PRODID:-//Example//EN\r\n",
+                "METHOD:REQUEST\r\n",
+                "BEGIN:VTIMEZONE\r\n",
+                "TZID:Europe/Madrid\r\n",
+                "BEGIN:STANDARD\r\n",
+                "DTSTART:19701025T030000\r\n",
+                "END:STANDARD\r\n",
+                "END:VTIMEZONE\r\n",
+                "BEGIN:VEVENT\r\n",
+                "UID:040000008200E00074C5B7101A82E008@example.com\r\n",
+                "SEQUENCE:2\r\n",
+                "SUMMARY:Project sync\\, weekly\r\n",
+                "LOCATION:Room 1\\nBuilding A\r\n",
+                "DTSTART;TZID=Europe/Madrid:20221011T100000\r\n",
+                "DTEND;TZID=Europe/Madrid:20221011T110000\r\n",
+                "ORGANIZER;CN=\"Doe, Jane\":mailto:organizer@example.com\r\n",
+                "ATTENDEE;ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TRUE;CN=John\r\n",
+                "  Smith:MAILTO:john@example.com\r\n",
+                "ATTENDEE;CN=Room 1;CUTYPE=ROOM:mailto:room1@example.com\r\n",
+                "BEGIN:VALARM\r\n",
+                "ACTION:DISPLAY\r\n",
+                "SUMMARY:Reminder\r\n",
+                "END:VALARM\r\n",
+                "STATUS:CONFIRMED\r\n",
+                "END:VEVENT\r\n",
+                "END:VCALENDAR\r\n",
+                "--b1--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let events = message.calendar_parts();
+        assert_eq!(
+            events,
+            vec![CalendarEvent {
+                part_id: 2,
+                method: Some(CalendarMethod::Request),
+                uid: Some("040000008200E00074C5B7101A82E008@example.com".into()),
+                sequence: Some(2),
+                status: Some("CONFIRMED".into()),
+                summary: Some("Project sync, weekly".into()),
+                location: Some("Room 1\nBuilding A".into()),
+                dtstart: Some(CalendarTime {
+                    value: "20221011T100000".into(),
+                    tzid: Some("Europe/Madrid".into()),
+                }),
+                dtend: Some(CalendarTime {
+                    value: "20221011T110000".into(),
+                    tzid: Some("Europe/Madrid".into()),
+                }),
+                organizer: Some(CalendarAddress {
+                    name: Some("Doe, Jane".into()),
+                    address: "organizer@example.com".into(),
+                    ..Default::default()
+                }),
+                attendees: vec![
+                    CalendarAddress {
+                        name: Some("John Smith".into()),
+                        address: "john@example.com".into(),
+                        role: Some("REQ-PARTICIPANT".into()),
+                        partstat: Some("NEEDS-ACTION".into()),
+                        rsvp: Some(true),
+                    },
+                    CalendarAddress {
+                        name: Some("Room 1".into()),
+                        address: "room1@example.com".into(),
+                        ..Default::default()
+                    }
+                ],
+            }]
+        );
+
+        let dtstart = events[0].dtstart.as_ref().unwrap();
+        assert!(!dtstart.is_date());
+        assert!(!dtstart.is_utc());
+        assert_eq!(
+            dtstart.to_datetime(),
+            DateTime::parse_rfc3339("2022-10-11T10:00:00Z")
+        );
+        assert_eq!(
+            CalendarTime {
+                value: "20221011".into(),
+                tzid: None
+            }
+            .to_datetime(),
+            DateTime::parse_rfc3339("2022-10-11T00:00:00Z")
+        );
+
+        assert_eq!(
+            message.part(2).unwrap().text_kind(),
+            Some(TextKind::Calendar)
+        );
+        assert_eq!(message.part(1).unwrap().text_kind(), Some(TextKind::Plain));
+
+        // Legacy charsets and application/ics parts are decoded as text
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=\"b1\"\r\n",
+                "\r\n",
+                "--b1\r\n",
+                "Content-Type: text/calendar; charset=iso-8859-1\r\n",
+                "Content-Transfer-Encoding: quoted-printable\r\n",
+                "\r\n",
+                "BEGIN:VCALENDAR\r\n",
+                "BEGIN:VEVENT\r\n",
+                "SUMMARY:R=E9union\r\n",
+                "END:VEVENT\r\n",
+                "END:VCALENDAR\r\n",
+                "--b1\r\n",
+                "Content-Type: application/ics; charset=windows-1252; name=\"invite.ics\"\r\n",
+                "Content-Disposition: attachment; filename=\"invite.ics\"\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "QkVHSU46VkNBTEVOREFSDQpCRUdJTjpWRVZFTlQNClNVTU1BUlk6k0Fu5WxpcyBk6XRhaWxs6WWU\r\n",
+                "DQpFTkQ6VkVWRU5UDQpFTkQ6VkNBTEVOREFSDQo=\r\n",
+                "--b1--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            message
+                .calendar_parts()
+                .into_iter()
+                .map(|event| event.summary.unwrap().into_owned())
+                .collect::<Vec<_>>(),
+            [
+                "R\u{e9}union",
+                "\u{201c}An\u{e5}lis d\u{e9}taill\u{e9}e\u{201d}"
+            ]
+        );
+        assert_eq!(
+            message.part(2).unwrap().text_kind(),
+            Some(TextKind::Calendar)
+        );
+
+        let events = CalendarEvent::parse(concat!(
+            "BEGIN:VCALENDAR\n",
+            "METHOD:CANCEL\n",
+            "BEGIN:VEVENT\n",
+            "UID:1\n",
+            "DTSTART:20221011T080000Z\n",
+            "END:VEVENT\n",
+            "BEGIN:VEVENT\n",
+            "UID:2\n",
+            "END:VEVENT\n",
+            "END:VCALENDAR\n",
+        ));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].method, Some(CalendarMethod::Cancel));
+        assert!(events[0].dtstart.as_ref().unwrap().is_utc());
+        assert_eq!(events[1].uid.as_deref(), Some("2"));
+    }
+}
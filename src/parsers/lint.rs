@@ -0,0 +1,264 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{
+    encoders::MAX_LINE_LEN, GetHeader, HeaderValue, LintFinding, LintKind, LintSeverity, Message,
+    MessagePartId, MimeHeaders, PartType, RfcHeader,
+};
+
+// RFC 2046, Section 5.1.1: boundaries have at most 70 characters.
+const MAX_BOUNDARY_LEN: usize = 70;
+
+impl Message<'_> {
+    /// Checks the message against the rules of RFC 5322 and RFC 2045-2049
+    /// and common practice, returning the problems found sorted by part.
+    /// Built messages can be checked by parsing their output.
+    ///
+    /// The parts of nested messages are not checked beyond their transfer
+    /// encoding and line lengths, they can be linted separately.
+    pub fn lint(&self) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        let mut add = |part_id: MessagePartId, kind: LintKind| {
+            findings.push(LintFinding {
+                severity: match kind {
+                    LintKind::MissingMessageId | LintKind::MissingTextAlternative => {
+                        LintSeverity::Warning
+                    }
+                    _ => LintSeverity::Error,
+                },
+                part_id,
+                kind,
+            })
+        };
+        let root = if let Some(root) = self.parts.first() {
+            root
+        } else {
+            return findings;
+        };
+
+        // Header
+        match root.headers.rfc(&RfcHeader::Date) {
+            None => add(0, LintKind::MissingDate),
+            Some(HeaderValue::DateTime(_)) => (),
+            Some(_) => add(0, LintKind::InvalidDate),
+        }
+        if root.headers.rfc(&RfcHeader::From).is_none() {
+            add(0, LintKind::MissingFrom);
+        }
+        if root.headers.rfc(&RfcHeader::MessageId).is_none() {
+            add(0, LintKind::MissingMessageId);
+        }
+        if root.headers.rfc(&RfcHeader::MimeVersion).is_none()
+            && (root.headers.rfc(&RfcHeader::ContentType).is_some()
+                || root
+                    .headers
+                    .rfc(&RfcHeader::ContentTransferEncoding)
+                    .is_some())
+        {
+            add(0, LintKind::MissingMimeVersion);
+        }
+        if self.html_body.iter().any(|&part_id| {
+            matches!(
+                self.parts.get(part_id).map(|part| &part.body),
+                Some(PartType::Html(_))
+            )
+        }) && !self.text_body.iter().any(|&part_id| {
+            matches!(
+                self.parts.get(part_id).map(|part| &part.body),
+                Some(PartType::Text(_))
+            )
+        }) {
+            add(0, LintKind::MissingTextAlternative);
+        }
+
+        // Parts
+        let raw_message = self.raw_message.as_ref();
+        for (part_id, part) in self.parts.iter().enumerate() {
+            if let PartType::Multipart(_) = &part.body {
+                if let Some(boundary) = part
+                    .content_type()
+                    .and_then(|ct| ct.attribute("boundary"))
+                    .filter(|boundary| !is_valid_boundary(boundary))
+                {
+                    add(
+                        part_id,
+                        LintKind::InvalidBoundary {
+                            boundary: boundary.to_string(),
+                        },
+                    );
+                }
+            } else if part
+                .content_transfer_encoding()
+                .map_or(true, |encoding| encoding.eq_ignore_ascii_case("7bit"))
+            {
+                if let Some(offset) = raw_message
+                    .get(part.offset_body..part.offset_end)
+                    .and_then(|body| body.iter().position(|&ch| ch >= 0x80 || ch == 0))
+                {
+                    add(
+                        part_id,
+                        LintKind::Unencoded8Bit {
+                            offset: part.offset_body + offset,
+                        },
+                    );
+                }
+            }
+        }
+
+        let mut offset = 0;
+        let mut last_part_id = None;
+        for line in raw_message.split_inclusive(|&ch| ch == b'\n') {
+            let length = line
+                .strip_suffix(b"\n")
+                .map_or(line, |line| line.strip_suffix(b"\r").unwrap_or(line))
+                .len();
+            if length > MAX_LINE_LEN {
+                // Report the first line of each part only
+                let part_id = self.part_at(offset);
+                if last_part_id != Some(part_id) {
+                    add(part_id, LintKind::LineTooLong { offset, length });
+                    last_part_id = Some(part_id);
+                }
+            }
+            offset += line.len();
+        }
+
+        findings.sort_by_key(|finding| finding.part_id);
+        findings
+    }
+
+    // Returns the innermost part containing an offset of the raw message.
+    fn part_at(&self, offset: usize) -> MessagePartId {
+        self.parts
+            .iter()
+            .rposition(|part| (part.offset_header..part.offset_end).contains(&offset))
+            .unwrap_or(0)
+    }
+}
+
+// RFC 2046, Section 5.1.1: boundaries are made of "bchars" and may contain
+// spaces, except at the end.
+fn is_valid_boundary(boundary: &str) -> bool {
+    (1..=MAX_BOUNDARY_LEN).contains(&boundary.len())
+        && !boundary.ends_with(' ')
+        && boundary
+            .bytes()
+            .all(|ch| ch.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&ch))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LintFinding, LintKind, LintSeverity, Message};
+
+    #[test]
+    fn lint_messages() {
+        let raw_message = concat!(
+            "From: jane@example.com\r\n",
+            "Date: Sat, 20 Nov 2021 14:22:01 -0800\r\n",
+            "Message-ID: <1@example.com>\r\n",
+            "MIME-Version: 1.0\r\n",
+            "Content-Type: multipart/alternative; boundary=\"a\"\r\n\r\n",
+            "--a\r\n",
+            "Content-Type: text/plain\r\n\r\n",
+            "Hello\r\n",
+            "--a\r\n",
+            "Content-Type: text/html\r\n\r\n",
+            "<p>Hello</p>\r\n",
+            "--a--\r\n"
+        );
+        assert_eq!(
+            Message::parse(raw_message.as_bytes()).unwrap().lint(),
+            Vec::new()
+        );
+
+        let long_line = "a".repeat(1000);
+        let raw_message = format!(
+            concat!(
+                "From: jane@example.com\r\n",
+                "Date: yesterday\r\n",
+                "Content-Type: multipart/mixed; boundary=\"b@{{b}}\"\r\n\r\n",
+                "--b@{{b}}\r\n",
+                "Content-Type: text/html; charset=utf-8\r\n\r\n",
+                "<p>Caf\u{e9}</p>\r\n",
+                "--b@{{b}}\r\n",
+                "Content-Type: text/plain\r\n",
+                "Content-Disposition: attachment\r\n",
+                "Content-Transfer-Encoding: 8bit\r\n\r\n",
+                "Caf\u{e9}\r\n{}\r\n{}\r\n",
+                "--b@{{b}}--\r\n"
+            ),
+            long_line, long_line
+        );
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+        let long_line_offset = raw_message.find(&long_line).unwrap();
+        assert_eq!(
+            message.lint(),
+            vec![
+                LintFinding {
+                    severity: LintSeverity::Error,
+                    part_id: 0,
+                    kind: LintKind::InvalidDate
+                },
+                LintFinding {
+                    severity: LintSeverity::Warning,
+                    part_id: 0,
+                    kind: LintKind::MissingMessageId
+                },
+                LintFinding {
+                    severity: LintSeverity::Error,
+                    part_id: 0,
+                    kind: LintKind::MissingMimeVersion
+                },
+                LintFinding {
+                    severity: LintSeverity::Warning,
+                    part_id: 0,
+                    kind: LintKind::MissingTextAlternative
+                },
+                LintFinding {
+                    severity: LintSeverity::Error,
+                    part_id: 0,
+                    kind: LintKind::InvalidBoundary {
+                        boundary: "b@{b}".to_string()
+                    }
+                },
+                LintFinding {
+                    severity: LintSeverity::Error,
+                    part_id: 1,
+                    kind: LintKind::Unencoded8Bit {
+                        offset: raw_message.find("\u{e9}</p>").unwrap()
+                    }
+                },
+                LintFinding {
+                    severity: LintSeverity::Error,
+                    part_id: 2,
+                    kind: LintKind::LineTooLong {
+                        offset: long_line_offset,
+                        length: 1000
+                    }
+                },
+            ]
+        );
+
+        assert_eq!(
+            Message::parse(b"Subject: hi\n\nHello\n")
+                .unwrap()
+                .lint()
+                .into_iter()
+                .map(|finding| finding.kind)
+                .collect::<Vec<_>>(),
+            [
+                LintKind::MissingDate,
+                LintKind::MissingFrom,
+                LintKind::MissingMessageId
+            ]
+        );
+    }
+}
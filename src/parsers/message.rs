@@ -9,15 +9,29 @@
  * except according to those terms.
  */
 
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    io::{self, Read},
+};
 
 use crate::{
-    decoders::{charsets::map::charset_decoder, DecodeFnc},
-    ContentType, Encoding, GetHeader, HeaderValue, Message, MessagePart, MessagePartId, PartType,
-    RfcHeader,
+    decoders::{
+        base64::{base64_decode, base64_error_offset},
+        charsets::{
+            charset_replacements, detect::detect_charset, map::charset_decoder, utf8_replacements,
+            DecoderFnc,
+        },
+        quoted_printable::{quoted_printable_decode, quoted_printable_error_offset},
+        transfer_decoder,
+        uuencode::find_uuencoded,
+        DecodeFnc,
+    },
+    ContentStatistics, ContentType, DateTime, DecodeError, Encoding, GetHeader, Header, HeaderName,
+    HeaderValue, Message, MessagePart, MessagePartId, MimeTypeCorrection, ParseOptions,
+    ParseWarning, PartType, RfcHeader, ScanVerdict,
 };
 
-use super::MessageStream;
+use super::{trim_ascii, MessageStream};
 
 const MAX_NESTED_ENCODED: usize = 3;
 
@@ -27,6 +41,8 @@ enum MimeType {
     MultipartAlernative,
     MultipartRelated,
     MultipartDigest,
+    MultipartReport,
+    MultipartFormData,
     TextPlain,
     TextHtml,
     TextOther,
@@ -52,6 +68,8 @@ fn mime_type(
                     Some("alternative") => MimeType::MultipartAlernative,
                     Some("related") => MimeType::MultipartRelated,
                     Some("digest") => MimeType::MultipartDigest,
+                    Some("report") => MimeType::MultipartReport,
+                    Some("form-data") => MimeType::MultipartFormData,
                     _ => MimeType::Other,
                 },
             ),
@@ -61,6 +79,10 @@ fn mime_type(
                 _ => (false, false, true, MimeType::TextOther),
             },
             "image" | "audio" | "video" => (false, true, false, MimeType::Inline),
+            // iCalendar objects are text, whatever their media type
+            "application" if content_type.subtype() == Some("ics") => {
+                (false, false, true, MimeType::TextOther)
+            }
             "message" if [Some("rfc822"), Some("global")].contains(&content_type.subtype()) => {
                 (false, false, false, MimeType::Message)
             }
@@ -125,43 +147,252 @@ impl<'x> Message<'x> {
     /// if no headers are found None is returned.
     ///
     pub fn parse(raw_message: &'x [u8]) -> Option<Message<'x>> {
-        Message::parse_(raw_message, MAX_NESTED_ENCODED)
+        Message::parse_(
+            raw_message,
+            &ParseOptions::default(),
+            MAX_NESTED_ENCODED,
+            0,
+            &mut 0,
+        )
+    }
+
+    /// Parses a byte slice containing the RFC5322 raw message using the
+    /// provided options. See [`Message::parse`].
+    pub fn parse_with_options(
+        raw_message: &'x [u8],
+        options: &ParseOptions,
+    ) -> Option<Message<'x>> {
+        Message::parse_(raw_message, options, MAX_NESTED_ENCODED, 0, &mut 0)
+    }
+
+    /// Reads a raw message from `reader` and parses it using the provided
+    /// options. See [`Message::parse`].
+    ///
+    /// With the `decompression` feature enabled, messages stored as gzip or
+    /// Zstandard files are detected by their magic number and decompressed
+    /// transparently, up to [`ParseOptions::max_decompressed_size`] bytes.
+    pub fn parse_from_reader(
+        mut reader: impl Read,
+        options: &ParseOptions,
+    ) -> io::Result<Option<Message<'static>>> {
+        let mut raw_message = Vec::new();
+        reader.read_to_end(&mut raw_message)?;
+
+        #[cfg(feature = "decompression")]
+        let raw_message = decompress(raw_message, options.max_decompressed_size)?;
+
+        Ok(Message::parse_with_options(&raw_message, options).map(|message| message.into_owned()))
     }
 
-    fn parse_(raw_message: &'x [u8], depth: usize) -> Option<Message<'x>> {
+    fn parse_(
+        raw_message: &'x [u8],
+        mut options: &ParseOptions,
+        depth: usize,
+        level: usize,
+        total_parts: &mut usize,
+    ) -> Option<Message<'x>> {
         let mut stream = MessageStream::new(raw_message);
+        stream.word_fallback = word_fallback(options);
 
         let mut message = Message::new();
 
         let mut state = MessageParserState::new();
         let mut state_stack = Vec::with_capacity(4);
+        let mut options_stack = Vec::new();
+        let mut pending_ends = 0;
+        let mut has_collision = false;
 
         let mut part_headers = Vec::new();
+        let mut truncated_headers = Vec::new();
+        let mut uuencoded_files = Vec::new();
 
         'outer: loop {
+            if options
+                .max_parts
+                .is_some_and(|max_parts| *total_parts >= max_parts)
+            {
+                if stream.offset() < stream.data.len() {
+                    message.warnings.push(ParseWarning::MaxPartsExceeded {
+                        offset: stream.offset(),
+                    });
+                }
+                break;
+            }
+
             // Parse headers
             state.offset_header = stream.offset();
-            if !stream.parse_headers(&mut part_headers) {
+            if !stream.parse_headers_limited(
+                &mut part_headers,
+                options.max_header_len.unwrap_or(usize::MAX),
+                &mut truncated_headers,
+            ) {
                 break;
             }
+            for (offset, length) in truncated_headers.drain(..) {
+                message.warnings.push(ParseWarning::HeaderTruncated {
+                    offset,
+                    part_id: message.parts.len(),
+                    length,
+                });
+            }
             state.offset_body = stream.offset();
+            if let Some(warning) = header_separator_warning(
+                stream.data,
+                state.offset_header,
+                state.offset_body,
+                message.parts.len(),
+            ) {
+                message.warnings.push(warning);
+            }
+            if part_headers.first().is_some_and(|header| {
+                header.offset_field > state.offset_header
+                    && matches!(stream.data.get(state.offset_header), Some(b' ' | b'\t'))
+            }) {
+                message.warnings.push(ParseWarning::InvalidHeaderFolding {
+                    offset: state.offset_header,
+                    part_id: message.parts.len(),
+                });
+            }
 
+            *total_parts += 1;
             state.parts += 1;
             state.sub_part_ids.push(message.parts.len());
 
+            if !options.mime_type_corrections.is_empty() {
+                correct_mime_type(&mut part_headers, &options.mime_type_corrections);
+            }
+
+            if options.lenient_dates {
+                parse_lenient_dates(&mut part_headers, stream.data);
+            }
+
+            // Fast path for messages made of a single text part
+            if message.parts.is_empty() && state_stack.is_empty() {
+                if let Some(text) = simple_text_body(&part_headers, &stream, options) {
+                    message.text_body.push(0);
+                    if options.generate_alternatives {
+                        message.html_body.push(0);
+                    }
+                    message.parts.push(MessagePart {
+                        headers: std::mem::take(&mut part_headers),
+                        encoding: Encoding::None,
+                        is_encoding_problem: false,
+                        body: PartType::Text(text.into()),
+                        offset_header: state.offset_header,
+                        offset_body: state.offset_body,
+                        offset_end: raw_message.len(),
+                        line_count: 0,
+                        statistics: options
+                            .content_statistics
+                            .then(|| ContentStatistics::new(text.as_bytes())),
+                        preamble: None,
+                        epilogue: None,
+                    });
+                    message.raw_message = raw_message.into();
+                    count_lines(&mut message);
+                    scan_part(&mut message, 0, options);
+                    return Some(message);
+                }
+            }
+
             let content_type = part_headers
                 .rfc(&RfcHeader::ContentType)
                 .and_then(|c| c.as_content_type_ref());
 
-            let (is_multipart, mut is_inline, mut is_text, mut mime_type) =
+            let (mut is_multipart, mut is_inline, mut is_text, mut mime_type) =
                 mime_type(content_type, &state.mime_type);
 
+            if (is_multipart || mime_type == MimeType::Message)
+                && options
+                    .max_depth
+                    .is_some_and(|max_depth| level + state_stack.len() >= max_depth)
+            {
+                message.warnings.push(ParseWarning::MaxDepthExceeded {
+                    offset: state.offset_header,
+                    part_id: message.parts.len(),
+                });
+                is_multipart = false;
+                is_inline = false;
+                is_text = false;
+                mime_type = MimeType::Other;
+            }
+
+            // Return the parts of the skipped content types undecoded
+            let is_skipped = !is_multipart
+                && (!options.skipped_content_types.is_empty()
+                    || !options.decoded_content_types.is_empty())
+                && {
+                    let (c_type, c_subtype) = content_type.map_or_else(
+                        || {
+                            if mime_type == MimeType::Message {
+                                ("message", "rfc822")
+                            } else {
+                                ("text", "plain")
+                            }
+                        },
+                        |ct| (ct.ctype(), ct.subtype().unwrap_or_default()),
+                    );
+                    matches_content_type(&options.skipped_content_types, c_type, c_subtype)
+                        || (!options.decoded_content_types.is_empty()
+                            && !matches_content_type(
+                                &options.decoded_content_types,
+                                c_type,
+                                c_subtype,
+                            ))
+                };
+            if is_skipped {
+                is_inline = false;
+                is_text = false;
+                mime_type = MimeType::Other;
+            }
+
+            // The machine-readable part of a report (RFC 6522) is kept as a
+            // binary part, leaving the first part as the report's body
+            if state.mime_type == MimeType::MultipartReport
+                && state.parts == 2
+                && !is_multipart
+                && mime_type != MimeType::Message
+            {
+                is_inline = false;
+                is_text = false;
+                mime_type = MimeType::Other;
+            }
+
             if is_multipart {
                 if let Some(mime_boundary) =
                     content_type.map_or_else(|| None, |f| f.attribute("boundary"))
                 {
+                    if state_stack
+                        .iter()
+                        .map(|(state, _)| state)
+                        .chain([&state])
+                        .filter_map(|state| state.mime_boundary.as_deref())
+                        .any(|boundary| {
+                            boundary.starts_with(mime_boundary.as_bytes())
+                                || mime_boundary.as_bytes().starts_with(boundary)
+                        })
+                    {
+                        message.warnings.push(ParseWarning::BoundaryCollision {
+                            offset: state.offset_header,
+                            part_id: message.parts.len(),
+                        });
+                        has_collision = true;
+                    }
+
                     if stream.seek_next_part(mime_boundary.as_bytes()) {
                         let part_id = message.parts.len();
+                        // The fields and files of a form (RFC 7578) are not
+                        // part of the message body
+                        let is_body = mime_type != MimeType::MultipartFormData;
+                        let preamble = if options.preserve_preamble {
+                            preamble(
+                                stream.data,
+                                state.offset_body,
+                                stream.offset() - mime_boundary.len() - 2,
+                            )
+                        } else {
+                            None
+                        };
                         let new_state = MessageParserState {
                             in_alternative: state.in_alternative
                                 || mime_type == MimeType::MultipartAlernative,
@@ -169,8 +400,8 @@ impl<'x> Message<'x> {
                             mime_boundary: mime_boundary.as_bytes().to_vec().into(),
                             html_parts: message.html_body.len(),
                             text_parts: message.text_body.len(),
-                            need_html_body: state.need_html_body,
-                            need_text_body: state.need_text_body,
+                            need_html_body: state.need_html_body && is_body,
+                            need_text_body: state.need_text_body && is_body,
                             part_id,
                             ..Default::default()
                         };
@@ -180,6 +411,10 @@ impl<'x> Message<'x> {
                             offset_header: state.offset_header,
                             offset_body: state.offset_body,
                             offset_end: 0,
+                            line_count: 0,
+                            statistics: None,
+                            preamble,
+                            epilogue: None,
                             is_encoding_problem: false,
                             encoding: Encoding::None,
                             body: PartType::default(),
@@ -195,21 +430,42 @@ impl<'x> Message<'x> {
                 }
             }
 
-            let (mut encoding, decode_fnc): (Encoding, DecodeFnc) = match part_headers
-                .rfc(&RfcHeader::ContentTransferEncoding)
-            {
-                Some(HeaderValue::Text(encoding)) if encoding.eq_ignore_ascii_case("base64") => {
-                    (Encoding::Base64, MessageStream::decode_base64_mime)
-                }
-                Some(HeaderValue::Text(encoding))
-                    if encoding.eq_ignore_ascii_case("quoted-printable") =>
+            if let Some(header) = part_headers.header("Content-Transfer-Encoding") {
+                if let Some(value) = header
+                    .value
+                    .as_text_ref()
+                    .filter(|value| Encoding::parse(value).is_none())
                 {
-                    (
-                        Encoding::QuotedPrintable,
-                        MessageStream::decode_quoted_printable_mime,
-                    )
+                    message
+                        .warnings
+                        .push(ParseWarning::UnknownTransferEncoding {
+                            offset: header.offset_start,
+                            part_id: message.parts.len(),
+                            encoding: value.trim().to_string(),
+                        });
                 }
-                _ => (Encoding::None, MessageStream::mime_part),
+            }
+
+            let (mut encoding, decode_fnc) = transfer_decoder(
+                part_headers
+                    .rfc(&RfcHeader::ContentTransferEncoding)
+                    .and_then(|encoding| encoding.as_text_ref()),
+                options.unknown_encoding_as_binary,
+            )
+            .unwrap_or_else(|| {
+                is_inline = false;
+                is_text = false;
+                mime_type = MimeType::Other;
+                (Encoding::None, MessageStream::mime_part)
+            });
+
+            // Keep the transfer encoding of binary parts
+            let decode_fnc: DecodeFnc = if is_skipped
+                || (!options.decode_attachments && !is_text && mime_type != MimeType::Message)
+            {
+                MessageStream::mime_part
+            } else {
+                decode_fnc
             };
 
             if mime_type == MimeType::Message && encoding == Encoding::None {
@@ -229,22 +485,68 @@ impl<'x> Message<'x> {
                     offset_header: state.offset_header,
                     offset_body: state.offset_body,
                     offset_end: 0,
+                    line_count: 0,
+                    statistics: None,
+                    preamble: None,
+                    epilogue: None,
                     body: PartType::default(), // Temp value, will be replaced later.
                 });
                 state_stack.push((state, message.into()));
+                options_stack.push(options);
+                options = options.nested();
+                stream.word_fallback = word_fallback(options);
                 message = Message::new();
                 state = new_state;
                 continue;
             }
 
-            let (offset_end, mut bytes) = decode_fnc(
-                &mut stream,
-                state.mime_boundary.as_deref().unwrap_or(&b""[..]),
-            );
+            // When boundaries collide, a delimiter of an enclosing multipart
+            // ends the part and the multiparts nested inside that one.
+            let ancestor_delimiter = if has_collision {
+                state.mime_boundary.as_deref().and_then(|mime_boundary| {
+                    let ancestors = state_stack
+                        .iter()
+                        .rev()
+                        .filter_map(|(state, _)| state.mime_boundary.as_deref())
+                        .enumerate()
+                        .filter(|(_, boundary)| *boundary != mime_boundary)
+                        .map(|(depth, boundary)| (depth + 1, boundary))
+                        .collect::<Vec<_>>();
+                    stream.find_ancestor_delimiter(mime_boundary, &ancestors)
+                })
+            } else {
+                None
+            };
+
+            let (offset_end, mut bytes) = if let Some((content_end, depth)) = ancestor_delimiter {
+                let mut part_stream = MessageStream::new(&stream.data[..content_end]);
+                part_stream.skip_bytes(stream.offset());
+
+                let (offset_end, bytes) = decode_fnc(
+                    &mut part_stream,
+                    state.mime_boundary.as_deref().unwrap_or(&b""[..]),
+                );
+                if offset_end != usize::MAX {
+                    stream.skip_bytes(part_stream.offset() - stream.offset());
+                    (offset_end, bytes)
+                } else {
+                    let mut part_stream = MessageStream::new(&stream.data[..content_end]);
+                    part_stream.skip_bytes(stream.offset());
+                    stream.skip_bytes(content_end - stream.offset());
+                    pending_ends = depth;
+                    decode_fnc(&mut part_stream, &b""[..])
+                }
+            } else {
+                decode_fnc(
+                    &mut stream,
+                    state.mime_boundary.as_deref().unwrap_or(&b""[..]),
+                )
+            };
 
             // Attempt to recover contents of an invalid message
             let mut is_encoding_problem = offset_end == usize::MAX;
             if is_encoding_problem {
+                let failed_encoding = encoding;
                 encoding = Encoding::None;
                 mime_type = MimeType::TextOther;
                 is_inline = false;
@@ -255,18 +557,58 @@ impl<'x> Message<'x> {
                 state.offset_end = offset_end;
                 bytes = stream.data[state.offset_body..state.offset_end].into();
 
+                let error = match failed_encoding {
+                    Encoding::Base64 => Some(DecodeError::InvalidBase64 {
+                        offset: state.offset_body
+                            + base64_error_offset(&bytes).unwrap_or(bytes.len()),
+                    }),
+                    Encoding::QuotedPrintable => Some(DecodeError::InvalidQuotedPrintable {
+                        offset: state.offset_body
+                            + quoted_printable_error_offset(&bytes).unwrap_or(bytes.len()),
+                    }),
+                    Encoding::None => None,
+                };
+                if let Some(error) = error {
+                    message.warnings.push(ParseWarning::DecodeError {
+                        part_id: message.parts.len(),
+                        error,
+                    });
+                }
+
                 if !boundary_found {
                     state.mime_boundary = None;
                 }
             } else {
                 state.offset_end = offset_end;
+
+                if options.detect_encoding
+                    && !is_skipped
+                    && (options.decode_attachments || is_text)
+                    && encoding == Encoding::None
+                    && part_headers
+                        .rfc(&RfcHeader::ContentTransferEncoding)
+                        .is_none()
+                {
+                    if let Some((detected_encoding, decoded_bytes)) =
+                        detect_transfer_encoding(bytes.as_ref())
+                    {
+                        message.warnings.push(ParseWarning::DetectedEncoding {
+                            offset: state.offset_body,
+                            part_id: message.parts.len(),
+                            encoding: detected_encoding,
+                        });
+                        encoding = detected_encoding;
+                        bytes = decoded_bytes.into();
+                    }
+                }
             }
 
+            let statistics = (options.content_statistics && mime_type != MimeType::Message)
+                .then(|| ContentStatistics::new(&bytes));
+
             let body_part = if mime_type != MimeType::Message {
                 let is_inline = is_inline
-                    && part_headers
-                        .rfc(&RfcHeader::ContentDisposition)
-                        .map_or_else(|| true, |d| !d.content_type().is_attachment())
+                    && !is_attachment(&part_headers)
                     && (state.parts == 1
                         || (state.mime_type != MimeType::MultipartRelated
                             && (mime_type == MimeType::Inline
@@ -298,29 +640,94 @@ impl<'x> Message<'x> {
                     };
 
                 if is_text {
-                    let text = match (
-                        bytes,
-                        content_type.and_then(|ct| {
-                            ct.attribute("charset")
-                                .and_then(|c| charset_decoder(c.as_bytes()))
-                        }),
-                    ) {
-                        (Cow::Owned(vec), Some(charset_decoder)) => charset_decoder(&vec).into(),
-                        (Cow::Owned(vec), None) => String::from_utf8(vec)
-                            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
-                            .into(),
-                        (Cow::Borrowed(bytes), Some(charset_decoder)) => {
-                            charset_decoder(bytes).into()
+                    let charset = content_type.and_then(|ct| ct.attribute("charset"));
+                    let decoder = charset.and_then(|c| charset_decoder(c.as_bytes()));
+                    if let Some(charset) = charset.filter(|charset| {
+                        decoder.is_none() && !is_utf8_charset(charset) && !charset.is_empty()
+                    }) {
+                        message.warnings.push(ParseWarning::UnknownCharset {
+                            offset: part_headers
+                                .header("Content-Type")
+                                .map_or(state.offset_header, |header| header.offset_start),
+                            part_id: message.parts.len(),
+                            charset: charset.to_string(),
+                        });
+                    }
+                    let mut replacements = (0, 0);
+                    let text = match (bytes, decoder) {
+                        (bytes, Some(charset_decoder)) => {
+                            let text = charset_decoder(&bytes);
+                            replacements =
+                                charset_replacements(charset.unwrap_or_default(), &bytes, &text);
+                            detected_charset_text(&bytes, &mut replacements, options)
+                                .unwrap_or(text)
+                                .into()
                         }
-                        (Cow::Borrowed(bytes), None) => String::from_utf8_lossy(bytes),
+                        (bytes, None) => match charset
+                            .filter(|charset| !is_utf8_charset(charset))
+                            .zip(options.charset_fallback.as_ref())
+                            .and_then(|(charset, fallback)| {
+                                let text = (fallback.0)(charset, &bytes)?;
+                                replacements = charset_replacements(charset, &bytes, &text);
+                                Some(
+                                    detected_charset_text(&bytes, &mut replacements, options)
+                                        .unwrap_or(text),
+                                )
+                            }) {
+                            Some(text) => text.into(),
+                            None => match bytes {
+                                Cow::Owned(vec) => String::from_utf8(vec)
+                                    .unwrap_or_else(|e| {
+                                        replacements = utf8_replacements(e.as_bytes());
+                                        detected_charset_text(
+                                            e.as_bytes(),
+                                            &mut replacements,
+                                            options,
+                                        )
+                                        .unwrap_or_else(
+                                            || String::from_utf8_lossy(e.as_bytes()).into_owned(),
+                                        )
+                                    })
+                                    .into(),
+                                Cow::Borrowed(bytes) => {
+                                    let text = String::from_utf8_lossy(bytes);
+                                    if let Cow::Owned(_) = text {
+                                        replacements = utf8_replacements(bytes);
+                                        detected_charset_text(bytes, &mut replacements, options)
+                                            .map_or(text, Cow::Owned)
+                                    } else {
+                                        text
+                                    }
+                                }
+                            },
+                        },
                     };
 
+                    let (count, replaced_bytes) = replacements;
+                    if count > 0 {
+                        message.warnings.push(ParseWarning::DecodeError {
+                            part_id: message.parts.len(),
+                            error: DecodeError::CharsetReplacement {
+                                count,
+                                bytes: replaced_bytes,
+                            },
+                        });
+                    }
+
                     let is_html = mime_type == MimeType::TextHtml;
 
-                    if add_to_html && !is_html {
-                        message.html_body.push(message.parts.len());
-                    } else if add_to_text && is_html {
-                        message.text_body.push(message.parts.len());
+                    let text = if options.decode_uuencode && mime_type == MimeType::TextPlain {
+                        extract_uuencoded(text, &mut uuencoded_files)
+                    } else {
+                        text
+                    };
+
+                    if options.generate_alternatives {
+                        if add_to_html && !is_html {
+                            message.html_body.push(message.parts.len());
+                        } else if add_to_text && is_html {
+                            message.text_body.push(message.parts.len());
+                        }
                     }
 
                     if add_to_html && is_html {
@@ -356,7 +763,13 @@ impl<'x> Message<'x> {
                 message.attachments.push(message.parts.len());
 
                 if depth != 0 {
-                    if let Some(nested_message) = Message::parse_(bytes.as_ref(), depth - 1) {
+                    if let Some(nested_message) = Message::parse_(
+                        bytes.as_ref(),
+                        options.nested(),
+                        depth - 1,
+                        level + state_stack.len() + 1,
+                        total_parts,
+                    ) {
                         PartType::Message(Message {
                             html_body: nested_message.html_body,
                             text_body: nested_message.text_body,
@@ -366,6 +779,7 @@ impl<'x> Message<'x> {
                                 .into_iter()
                                 .map(|p| p.into_owned())
                                 .collect(),
+                            warnings: nested_message.warnings,
                             raw_message: bytes.into_owned().into(),
                         })
                     } else {
@@ -379,6 +793,7 @@ impl<'x> Message<'x> {
             };
 
             // Add part
+            let part_id = message.parts.len();
             message.parts.push(MessagePart {
                 headers: std::mem::take(&mut part_headers),
                 encoding,
@@ -387,14 +802,39 @@ impl<'x> Message<'x> {
                 offset_header: state.offset_header,
                 offset_body: state.offset_body,
                 offset_end: state.offset_end,
+                line_count: 0,
+                statistics,
+                preamble: None,
+                epilogue: None,
             });
 
+            let mut is_stopped = !scan_part(&mut message, part_id, options);
+
+            // Add uuencoded files as attachments
+            for (name, contents) in uuencoded_files.drain(..) {
+                state.sub_part_ids.push(message.parts.len());
+                message.attachments.push(message.parts.len());
+                let part_id = message.parts.len();
+                let mut part = uuencoded_part(name, contents, &state);
+                if options.content_statistics {
+                    part.statistics = ContentStatistics::new(part.contents()).into();
+                }
+                message.parts.push(part);
+                is_stopped = is_stopped || !scan_part(&mut message, part_id, options);
+            }
+
+            if is_stopped {
+                break 'outer;
+            }
+
             if state.mime_boundary.is_some() {
                 // Currently processing a MIME part
                 'inner: loop {
-                    if let MimeType::Message = state.mime_type {
+                    while let MimeType::Message = state.mime_type {
                         // Finished processing a nested message, restore parent message from stack
                         if let Some((mut prev_state, Some(mut prev_message))) = state_stack.pop() {
+                            options = options_stack.pop().unwrap_or(options);
+                            stream.word_fallback = word_fallback(options);
                             let offset_end = state
                                 .mime_boundary
                                 .as_ref()
@@ -428,10 +868,20 @@ impl<'x> Message<'x> {
                         }
                     }
 
-                    if stream.is_multipart_end() {
+                    if pending_ends > 0 || stream.is_multipart_end() {
                         // End of MIME part reached
+                        if pending_ends > 0 {
+                            message
+                                .warnings
+                                .push(ParseWarning::MissingBoundaryTerminator {
+                                    offset: stream.offset(),
+                                    part_id: state.part_id,
+                                });
+                        }
+                        pending_ends = pending_ends.saturating_sub(1);
 
                         if MimeType::MultipartAlernative == state.mime_type
+                            && options.generate_alternatives
                             && state.need_html_body
                             && state.need_text_body
                         {
@@ -448,7 +898,7 @@ impl<'x> Message<'x> {
                             if state.html_parts == message.html_body.len()
                                 && state.text_parts != message.text_body.len()
                             {
-                                for &part_id in &message.text_body[state.html_parts..] {
+                                for &part_id in &message.text_body[state.text_parts..] {
                                     message.html_body.push(part_id);
                                 }
                             }
@@ -458,17 +908,26 @@ impl<'x> Message<'x> {
                             // Add headers and substructure to parent part
                             part.body =
                                 PartType::Multipart(std::mem::take(&mut state.sub_part_ids));
+                            let epilogue_start = stream.offset();
 
                             // Restore ancestor's state
                             if let Some((prev_state, _)) = state_stack.pop() {
                                 state = prev_state;
 
-                                if let Some(ref mime_boundary) = state.mime_boundary {
+                                if pending_ends > 0 {
+                                    // Closed by the delimiter of an ancestor
+                                    part.offset_end = stream.offset();
+                                    continue 'inner;
+                                } else if let Some(ref mime_boundary) = state.mime_boundary {
                                     // Ancestor has a MIME boundary, seek it.
                                     if let Some(offset) =
                                         stream.seek_next_part_offset(mime_boundary)
                                     {
                                         part.offset_end = offset;
+                                        if options.preserve_preamble {
+                                            part.epilogue =
+                                                epilogue(stream.data, epilogue_start, offset);
+                                        }
                                         continue 'inner;
                                     }
                                 }
@@ -476,6 +935,10 @@ impl<'x> Message<'x> {
 
                             // This part has no boundary, update end offset
                             part.offset_end = stream.offset();
+                            if options.preserve_preamble {
+                                part.epilogue =
+                                    epilogue(stream.data, epilogue_start, stream.data.len());
+                            }
                         } else {
                             debug_assert!(false, "Invalid part ID, could not find multipart.");
                         }
@@ -507,6 +970,14 @@ impl<'x> Message<'x> {
             } else if let Some(part) = message.parts.get_mut(state.part_id) {
                 part.offset_end = stream.offset();
                 part.body = PartType::Multipart(state.sub_part_ids);
+                if stream.offset() >= stream.data.len() {
+                    message
+                        .warnings
+                        .push(ParseWarning::MissingBoundaryTerminator {
+                            offset: stream.offset(),
+                            part_id: state.part_id,
+                        });
+                }
             } else {
                 debug_assert!(false, "This should not have happened.");
             }
@@ -517,6 +988,7 @@ impl<'x> Message<'x> {
 
         if !message.is_empty() {
             message.parts[0].offset_end = message.raw_message.len();
+            count_lines(&mut message);
             Some(message)
         } else if !part_headers.is_empty() {
             // Message without a body
@@ -528,6 +1000,10 @@ impl<'x> Message<'x> {
                 offset_header: 0,
                 offset_body: message.raw_message.len(),
                 offset_end: message.raw_message.len(),
+                line_count: 0,
+                statistics: None,
+                preamble: None,
+                epilogue: None,
             });
             Some(message)
         } else {
@@ -536,11 +1012,434 @@ impl<'x> Message<'x> {
     }
 }
 
+// Passes a part to the scanner set in the options, returning `false` when
+// parsing must stop. Nested messages are scanned as they are parsed, so
+// only their verdict is checked here.
+fn scan_part(message: &mut Message<'_>, part_id: MessagePartId, options: &ParseOptions) -> bool {
+    let scanner = match &options.part_scanner {
+        Some(scanner) => scanner,
+        None => return true,
+    };
+    let verdict = match &message.parts[part_id].body {
+        PartType::Multipart(_) => return true,
+        PartType::Message(nested) => return nested.scan_stopped().is_none(),
+        _ => scanner.scan(part_id, &message.parts[part_id]),
+    };
+
+    match verdict {
+        ScanVerdict::Continue => true,
+        ScanVerdict::Stop(reason) => {
+            message
+                .warnings
+                .push(ParseWarning::ScanStopped { part_id, reason });
+            false
+        }
+    }
+}
+
+// Returns the preamble of a multipart part, from the start of its body to
+// the line break preceding its first delimiter.
+fn preamble(data: &[u8], start: usize, delimiter_start: usize) -> Option<Cow<'_, [u8]>> {
+    let mut end = delimiter_start;
+    if end > start && data.get(end - 1) == Some(&b'\n') {
+        end -= 1;
+        if end > start && data.get(end - 1) == Some(&b'\r') {
+            end -= 1;
+        }
+    }
+    data.get(start..end)
+        .filter(|preamble| !preamble.is_empty())
+        .map(Cow::Borrowed)
+}
+
+// Returns the epilogue of a multipart part, from the line following its
+// closing delimiter to `end`.
+fn epilogue(data: &[u8], closing_end: usize, end: usize) -> Option<Cow<'_, [u8]>> {
+    let start = data
+        .get(closing_end..)
+        .and_then(|rest| rest.iter().position(|&ch| ch == b'\n'))
+        .map_or(data.len(), |pos| closing_end + pos + 1);
+    data.get(start..end)
+        .filter(|epilogue| !epilogue.is_empty())
+        .map(Cow::Borrowed)
+}
+
+// Returns the decoder of the charset used for the encoded words declaring
+// an unknown charset.
+fn word_fallback(options: &ParseOptions) -> Option<DecoderFnc> {
+    options
+        .encoded_word_charset
+        .as_ref()
+        .and_then(|charset| charset_decoder(charset.as_bytes()))
+}
+
+// Counts the lines of the undecoded body of each part, including those of
+// nested messages.
+fn count_lines(message: &mut Message<'_>) {
+    for part in &mut message.parts {
+        part.line_count = message
+            .raw_message
+            .get(part.offset_body..part.offset_end)
+            .map_or(0, |body| {
+                body.iter().filter(|&&ch| ch == b'\n').count()
+                    + usize::from(!body.is_empty() && !body.ends_with(b"\n"))
+            });
+        if let PartType::Message(nested) = &mut part.body {
+            count_lines(nested);
+        }
+    }
+}
+
+// Parses the Date and Resent-Date header fields again using the lenient date
+// parser when the strict parser did not return a valid date, keeping the
+// strictly parsed value when that one fails too.
+fn parse_lenient_dates(headers: &mut [Header], data: &[u8]) {
+    for header in headers {
+        if matches!(
+            header.name,
+            HeaderName::Rfc(RfcHeader::Date | RfcHeader::ResentDate)
+        ) && !matches!(&header.value, HeaderValue::DateTime(date) if date.is_valid())
+        {
+            if let Some(date) = data
+                .get(header.offset_start..header.offset_end)
+                .and_then(|value| std::str::from_utf8(value).ok())
+                .and_then(DateTime::parse_lenient)
+            {
+                header.value = HeaderValue::DateTime(date);
+            }
+        }
+    }
+}
+
+// Returns a warning if the header section ending at `offset_body` was not
+// terminated by an empty line.
+fn header_separator_warning(
+    data: &[u8],
+    offset_header: usize,
+    offset_body: usize,
+    part_id: MessagePartId,
+) -> Option<ParseWarning> {
+    let line_end = offset_body.checked_sub(1)?;
+    let line_start = data[offset_header..line_end]
+        .iter()
+        .rposition(|&ch| ch == b'\n')
+        .map_or(offset_header, |pos| offset_header + pos + 1);
+    let line = &data[line_start..line_end];
+
+    if line.is_empty() || line == b"\r" {
+        None
+    } else if line.iter().all(|ch| ch.is_ascii_whitespace()) {
+        Some(ParseWarning::WhitespaceHeaderSeparator {
+            offset: line_start,
+            part_id,
+        })
+    } else {
+        Some(ParseWarning::MissingHeaderSeparator {
+            offset: offset_body,
+            part_id,
+        })
+    }
+}
+
+// Returns the body of a message without MIME structure, or with a single
+// inline text/plain part, when it is valid UTF-8 and needs no decoding.
+fn simple_text_body<'x>(
+    headers: &[Header],
+    stream: &MessageStream<'x>,
+    options: &ParseOptions,
+) -> Option<&'x str> {
+    if options.detect_encoding
+        || options.decode_uuencode
+        || !options.skipped_content_types.is_empty()
+        || !options.decoded_content_types.is_empty()
+    {
+        return None;
+    }
+
+    let content_type = headers
+        .rfc(&RfcHeader::ContentType)
+        .and_then(|ct| ct.as_content_type_ref());
+    let is_simple = mime_type(content_type, &MimeType::default()).3 == MimeType::TextPlain
+        && content_type
+            .and_then(|ct| ct.attribute("charset"))
+            .map_or(true, is_utf8_charset)
+        && headers
+            .rfc(&RfcHeader::ContentTransferEncoding)
+            .map_or(true, |encoding| {
+                encoding
+                    .as_text_ref()
+                    .is_some_and(|encoding| Encoding::parse(encoding) == Some(Encoding::None))
+            })
+        && !is_attachment(headers);
+
+    if is_simple {
+        std::str::from_utf8(stream.data.get(stream.offset()..)?).ok()
+    } else {
+        None
+    }
+}
+
+// Returns true if the Content-Disposition of a part marks it as an
+// attachment, keeping it out of the message bodies.
+fn is_attachment(headers: &[Header]) -> bool {
+    headers
+        .rfc(&RfcHeader::ContentDisposition)
+        .and_then(|disposition| disposition.as_content_type_ref())
+        .is_some_and(|disposition| disposition.is_attachment())
+}
+
+// Removes the uuencoded blocks from a text part, returning the decoded files.
+fn extract_uuencoded<'x>(text: Cow<'x, str>, files: &mut Vec<(String, Vec<u8>)>) -> Cow<'x, str> {
+    let blocks = find_uuencoded(text.as_ref());
+    if blocks.is_empty() {
+        return text;
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut last_pos = 0;
+    for block in blocks {
+        result.push_str(&text[last_pos..block.range.start]);
+        last_pos = block.range.end;
+        files.push((block.name.to_string(), block.contents));
+    }
+    result.push_str(&text[last_pos..]);
+
+    result.into()
+}
+
+// Returns true if a content type matches any of the patterns, which are
+// either "*", a type such as "video" or "video/*", or a full content type.
+fn matches_content_type(patterns: &[String], c_type: &str, c_subtype: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        let (p_type, p_subtype) = pattern.split_once('/').unwrap_or((pattern, "*"));
+        (p_type == "*" || p_type.eq_ignore_ascii_case(c_type))
+            && (p_subtype == "*" || p_subtype.eq_ignore_ascii_case(c_subtype))
+    })
+}
+
+// Applies the first matching MIME type correction to the Content-Type of a
+// part, based on its file name extension.
+fn correct_mime_type(headers: &mut [Header], corrections: &[MimeTypeCorrection]) {
+    let extension = match headers
+        .rfc(&RfcHeader::ContentDisposition)
+        .and_then(|cd| cd.as_content_type_ref())
+        .and_then(|cd| cd.attribute("filename"))
+        .or_else(|| {
+            headers
+                .rfc(&RfcHeader::ContentType)
+                .and_then(|ct| ct.as_content_type_ref())
+                .and_then(|ct| ct.attribute("name"))
+        })
+        .and_then(|name| name.rsplit_once('.'))
+    {
+        Some((_, extension)) => extension.to_string(),
+        None => return,
+    };
+
+    for header in headers.iter_mut() {
+        if let (HeaderName::Rfc(RfcHeader::ContentType), HeaderValue::ContentType(ct)) =
+            (&header.name, &mut header.value)
+        {
+            if ct.c_type.eq_ignore_ascii_case("multipart")
+                || ct.c_type.eq_ignore_ascii_case("message")
+            {
+                break;
+            }
+
+            if let Some(correction) = corrections.iter().find(|correction| {
+                correction.extension.eq_ignore_ascii_case(&extension)
+                    && (correction.declared == "*"
+                        || correction.declared.split_once('/').is_some_and(
+                            |(c_type, c_subtype)| {
+                                c_type.eq_ignore_ascii_case(&ct.c_type)
+                                    && ct
+                                        .c_subtype
+                                        .as_ref()
+                                        .is_some_and(|ct| c_subtype.eq_ignore_ascii_case(ct))
+                            },
+                        ))
+            }) {
+                let (c_type, c_subtype) = correction
+                    .effective
+                    .split_once('/')
+                    .unwrap_or((correction.effective.as_str(), ""));
+                ct.c_type = c_type.to_ascii_lowercase().into();
+                ct.c_subtype = if !c_subtype.is_empty() {
+                    Some(c_subtype.to_ascii_lowercase().into())
+                } else {
+                    None
+                };
+            }
+            break;
+        }
+    }
+}
+
+// Detects base64 or quoted-printable contents, base64 is only accepted as a
+// single block of lines of equal length using the base64 alphabet, while
+// quoted-printable requires valid escapes and soft line breaks.
+fn detect_transfer_encoding(bytes: &[u8]) -> Option<(Encoding, Vec<u8>)> {
+    let bytes = trim_ascii(bytes);
+    if bytes.len() >= 64 && is_base64_block(bytes) {
+        return base64_decode(bytes).map(|bytes| (Encoding::Base64, bytes));
+    }
+
+    let mut escapes = 0;
+    let mut soft_breaks = 0;
+    let mut iter = bytes.iter().enumerate();
+    while let Some((pos, &ch)) = iter.next() {
+        if ch == b'=' {
+            match &bytes[pos + 1..] {
+                [] => (),
+                [b'\n', ..] | [b'\r', b'\n', ..] => soft_breaks += 1,
+                [hex1, hex2, ..] if hex1.is_ascii_hexdigit() && hex2.is_ascii_hexdigit() => {
+                    escapes += 1;
+                    iter.nth(1);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    if soft_breaks > 0 || escapes >= 3 {
+        quoted_printable_decode(bytes).map(|bytes| (Encoding::QuotedPrintable, bytes))
+    } else {
+        None
+    }
+}
+
+fn is_base64_block(bytes: &[u8]) -> bool {
+    let mut lines = bytes
+        .split(|&ch| ch == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .peekable();
+    let line_len = lines.peek().map_or(0, |line| line.len());
+    let mut is_last = false;
+
+    line_len % 4 == 0
+        && lines.all(|line| {
+            let is_valid = !is_last
+                && !line.is_empty()
+                && line.len() <= line_len
+                && line
+                    .iter()
+                    .all(|&ch| ch.is_ascii_alphanumeric() || matches!(ch, b'+' | b'/' | b'='));
+            is_last = line.len() < line_len || line.ends_with(b"=");
+            is_valid
+        })
+}
+
+// Decodes a text using the detected charset when enabled and decoding it
+// produced replacement characters, updating their count.
+fn detected_charset_text(
+    bytes: &[u8],
+    replacements: &mut (usize, usize),
+    options: &ParseOptions,
+) -> Option<String> {
+    if !options.detect_charset || replacements.0 == 0 {
+        return None;
+    }
+    Some(match detect_charset(bytes)? {
+        "utf-8" => {
+            *replacements = utf8_replacements(bytes);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+        charset => {
+            let text = charset_decoder(charset.as_bytes())?(bytes);
+            *replacements = charset_replacements(charset, bytes, &text);
+            text
+        }
+    })
+}
+
+// UTF-8 and its subsets are decoded natively, the charset fallback is not
+// invoked for them.
+fn is_utf8_charset(charset: &str) -> bool {
+    ["utf-8", "utf8", "us-ascii", "ascii", "csascii", "csutf8"]
+        .iter()
+        .any(|label| charset.eq_ignore_ascii_case(label))
+}
+
+// Builds an attachment for a uuencoded file, its offsets point to the
+// text part that contained it.
+fn uuencoded_part<'x>(
+    name: String,
+    contents: Vec<u8>,
+    state: &MessageParserState,
+) -> MessagePart<'x> {
+    let header = |header_name, value| Header {
+        name: HeaderName::Rfc(header_name),
+        value: HeaderValue::ContentType(value),
+        offset_field: state.offset_body,
+        offset_start: state.offset_body,
+        offset_end: state.offset_body,
+    };
+
+    MessagePart {
+        headers: vec![
+            header(
+                RfcHeader::ContentType,
+                ContentType {
+                    c_type: "application".into(),
+                    c_subtype: Some("octet-stream".into()),
+                    attributes: vec![("name".into(), name.clone().into())].into(),
+                },
+            ),
+            header(
+                RfcHeader::ContentDisposition,
+                ContentType {
+                    c_type: "attachment".into(),
+                    c_subtype: None,
+                    attributes: vec![("filename".into(), name.into())].into(),
+                },
+            ),
+        ],
+        is_encoding_problem: false,
+        body: PartType::Binary(contents.into()),
+        encoding: Encoding::None,
+        offset_header: state.offset_body,
+        offset_body: state.offset_body,
+        offset_end: state.offset_end,
+        line_count: 0,
+        statistics: None,
+        preamble: None,
+        epilogue: None,
+    }
+}
+
+// Decompresses gzip and Zstandard files, other data is returned as is.
+#[cfg(feature = "decompression")]
+fn decompress(bytes: Vec<u8>, max_size: usize) -> io::Result<Vec<u8>> {
+    let decompressed = if bytes.starts_with(&[0x1f, 0x8b]) {
+        crate::decoders::inflate::gunzip(&bytes, max_size)
+    } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        crate::decoders::zstd::unzstd(&bytes, max_size)
+    } else {
+        return Ok(bytes);
+    };
+
+    decompressed
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Failed to decompress message."))
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{fs, path::PathBuf};
-
-    use crate::parsers::message::Message;
+    use std::{
+        fs,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        parsers::{
+            fixture::{fixture_paths, verify_fixture, FixtureResult},
+            message::Message,
+        },
+        Addr, BodyPreference, BodyRef, ContentType, DecodeError, Encoding, HeaderValue,
+        MessagePart, MimeHeaders, MimeNode, NestedOptions, ParseOptions, ParseWarning, PartRef,
+        PartType, ScanVerdict, SubjectPrefixes,
+    };
 
     #[test]
     fn parse_full_messages() {
@@ -549,80 +1448,2059 @@ mod tests {
             test_dir.push("tests");
             test_dir.push(test_suite);
 
-            let mut tests_run = 0;
+            let file_names = fixture_paths(&test_dir).unwrap();
+            assert!(
+                !file_names.is_empty(),
+                "Did not find any tests to run in folder {}.",
+                test_dir.display()
+            );
+
+            for file_name in file_names {
+                if let FixtureResult::Failed(failed) = verify_fixture(&file_name).unwrap() {
+                    panic!(
+                        "Test failed for {}, parsed message saved to {:?}",
+                        file_name.display(),
+                        failed
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        for test_suite in ["rfc", "legacy", "thirdparty", "malformed"] {
+            let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            test_dir.push("tests");
+            test_dir.push(test_suite);
 
             for file_name in fs::read_dir(&test_dir).unwrap() {
-                let mut file_name = file_name.unwrap().path();
+                let file_name = file_name.unwrap().path();
                 if file_name.extension().map_or(false, |e| e == "eml") {
-                    let raw_original = fs::read(&file_name).unwrap();
-                    tests_run += 1;
-
-                    // Test without CRs
-                    let raw_message = strip_crlf(&raw_original);
-                    file_name.set_extension("json");
-                    let expected_result = fs::read(&file_name).unwrap();
-
-                    let message = Message::parse(&raw_message).unwrap();
-                    let json_message = serde_json::to_string_pretty(&message).unwrap();
-
-                    if json_message.as_bytes() != expected_result {
-                        file_name.set_extension("failed");
-                        fs::write(&file_name, json_message.as_bytes()).unwrap();
-                        panic!(
-                            "Test failed, parsed message saved to {}",
-                            file_name.display()
-                        );
-                    }
+                    let raw_message = fs::read(&file_name).unwrap();
+                    let mut message = Message::parse(&raw_message).unwrap();
 
-                    // Test with CRs
-                    let raw_message = add_crlf(&raw_original);
-                    file_name.set_extension("crlf.json");
-                    let expected_result = fs::read(&file_name).unwrap();
-
-                    let message = Message::parse(&raw_message).unwrap();
-                    let json_message = serde_json::to_string_pretty(&message).unwrap();
-
-                    if json_message.as_bytes() != expected_result {
-                        file_name.set_extension("crlf.failed");
-                        fs::write(&file_name, json_message.as_bytes()).unwrap();
-                        panic!(
-                            "Test failed, parsed message saved to {}",
-                            file_name.display()
-                        );
-                    }
+                    // The raw message and the transfer encodings are not serialized
+                    message.raw_message = Default::default();
+                    clear_encodings(&mut message);
+
+                    let json_message = serde_json::to_vec(&message).unwrap();
+                    let cached: Message<'static> =
+                        serde_json::from_reader(json_message.as_slice()).unwrap();
+                    assert_eq!(cached, message, "{}", file_name.display());
+
+                    let bincode_message = bincode::serialize(&message).unwrap();
+                    let cached: Message<'static> = bincode::deserialize(&bincode_message).unwrap();
+                    assert_eq!(cached, message, "{}", file_name.display());
                 }
             }
+        }
+    }
 
-            assert!(
-                tests_run > 0,
-                "Did not find any tests to run in folder {}.",
-                test_dir.display()
+    #[test]
+    fn typed_accessors() {
+        let message = Message::parse(
+            concat!(
+                "From: Jane <jane@example.com>\r\n",
+                "To: john@example.com, jim@example.com\r\n",
+                "Cc: Friends: ann@example.com, bob@example.com;\r\n",
+                "Subject: Hi\r\n",
+                "Date: Tue, 1 Jul 2003 10:52:37 +0200\r\n",
+                "Message-ID: <id3@example.com>\r\n",
+                "In-Reply-To: <id2@example.com>\r\n",
+                "References: <id1@example.com> <id2@example.com>\r\n",
+                "\r\n",
+                "Hello\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let from = message.from_address().unwrap();
+        assert_eq!(from.first().unwrap().name.as_deref(), Some("Jane"));
+        assert_eq!(from.as_list().unwrap().len(), 1);
+        assert_eq!(
+            message
+                .to_address()
+                .and_then(|to| to.as_list())
+                .unwrap()
+                .iter()
+                .map(|addr| addr.address.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            ["john@example.com", "jim@example.com"]
+        );
+        let cc = message.cc_address().unwrap();
+        assert_eq!(cc.as_list(), None);
+        assert_eq!(cc.as_group().unwrap()[0].name.as_deref(), Some("Friends"));
+        assert_eq!(
+            cc.first().unwrap().address.as_deref(),
+            Some("ann@example.com")
+        );
+        assert_eq!(message.bcc_address(), None);
+        assert_eq!(message.reply_to_address(), None);
+        assert_eq!(message.subject(), Some("Hi"));
+        assert_eq!(
+            message.date().unwrap().to_rfc3339(),
+            "2003-07-01T10:52:37+02:00"
+        );
+        assert_eq!(message.message_id(), Some("id3@example.com"));
+        assert_eq!(message.in_reply_to_ids(), ["id2@example.com"]);
+        assert_eq!(
+            message.reference_ids(),
+            ["id1@example.com", "id2@example.com"]
+        );
+    }
+
+    #[test]
+    fn walk_mime_tree() {
+        let raw_message = concat!(
+            "Subject: Forward\n",
+            "Content-Type: multipart/mixed; boundary=\"a\"\n",
+            "\n",
+            "--a\n",
+            "Content-Type: text/plain\n",
+            "\n",
+            "See below.\n",
+            "--a\n",
+            "Content-Type: message/rfc822\n",
+            "\n",
+            "Subject: Original\n",
+            "Content-Type: multipart/alternative; boundary=\"b\"\n",
+            "\n",
+            "--b\n",
+            "Content-Type: text/plain\n",
+            "\n",
+            "Hi\n",
+            "--b\n",
+            "Content-Type: text/html\n",
+            "\n",
+            "<p>Hi</p>\n",
+            "--b--\n",
+            "--a\n",
+            "Content-Type: image/png\n",
+            "\n",
+            "PNG\n",
+            "--a--\n"
+        );
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+
+        let describe = |part: PartRef<'_>| {
+            let content_type = part.content_type().unwrap();
+            format!(
+                "{} {}/{} {}",
+                part.depth(),
+                content_type.ctype(),
+                content_type.subtype().unwrap_or_default(),
+                match part {
+                    PartRef::Multipart { .. } => "multipart",
+                    PartRef::Message { nested, .. } => nested.subject().unwrap_or_default(),
+                    PartRef::Leaf { .. } => "leaf",
+                }
+            )
+        };
+
+        assert_eq!(
+            message.walk().map(describe).collect::<Vec<_>>(),
+            [
+                "0 multipart/mixed multipart",
+                "1 text/plain leaf",
+                "1 message/rfc822 Original",
+                "2 multipart/alternative multipart",
+                "3 text/plain leaf",
+                "3 text/html leaf",
+                "1 image/png leaf",
+            ]
+        );
+        assert_eq!(
+            message.parts().map(describe).collect::<Vec<_>>(),
+            [
+                "0 multipart/mixed multipart",
+                "1 text/plain leaf",
+                "1 message/rfc822 Original",
+                "1 image/png leaf",
+            ]
+        );
+
+        let html = message
+            .walk()
+            .find(|part| part.part().is_text_html())
+            .unwrap();
+        assert_eq!(html.message().subject(), Some("Original"));
+        assert_eq!(html.part_id(), 2);
+    }
+
+    #[test]
+    fn mime_tree_links() {
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=\"a\"\n",
+                "\n",
+                "--a\n",
+                "Content-Type: multipart/alternative; boundary=\"b\"\n",
+                "\n",
+                "--b\n",
+                "Content-Type: text/plain\n",
+                "\n",
+                "Hi\n",
+                "--b\n",
+                "Content-Type: text/html\n",
+                "\n",
+                "<p>Hi</p>\n",
+                "--b--\n",
+                "--a\n",
+                "Content-Type: message/rfc822\n",
+                "\n",
+                "Subject: Original\n",
+                "Content-Type: text/plain\n",
+                "\n",
+                "Original\n",
+                "--a--\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        fn describe(node: &MimeNode<'_>) -> String {
+            let content_type = node.part().content_type().unwrap();
+            let mut result = format!(
+                "{}/{}",
+                content_type.ctype(),
+                content_type.subtype().unwrap_or_default()
+            );
+            if !node.children.is_empty() {
+                result.push_str(&format!(
+                    "({})",
+                    node.children
+                        .iter()
+                        .map(describe)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ));
+            }
+            result
+        }
+
+        let tree = message.mime_tree();
+        assert_eq!(
+            describe(&tree),
+            "multipart/mixed(multipart/alternative(text/plain text/html) message/rfc822(text/plain))"
+        );
+        assert_eq!(tree.part_count(), 6);
+        let nested = &tree.children[1].children[0];
+        assert_eq!(nested.part_id, 0);
+        assert_eq!(nested.message.subject(), Some("Original"));
+
+        assert_eq!(message.parent_id(0), None);
+        assert_eq!(message.parent_id(1), Some(0));
+        assert_eq!(message.parent_id(3), Some(1));
+        assert_eq!(message.parent_id(4), Some(0));
+        assert_eq!(message.parent_id(5), None);
+        assert_eq!(nested.message.parent_id(0), None);
+
+        // Deeply nested parts
+        let depth = 1_500;
+        let mut raw_message = String::new();
+        for level in 0..depth {
+            raw_message.push_str(&format!(
+                "Content-Type: multipart/mixed; boundary=\"{level}\"\n\n--{level}\n"
+            ));
+        }
+        raw_message.push_str("Content-Type: text/plain\n\nHi\n");
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+        assert_eq!(message.parts.len(), depth + 1);
+        assert_eq!(message.mime_tree().part_count(), 1000);
+    }
+
+    #[test]
+    fn body_markdown() {
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/alternative; boundary=\"a\"\n\n",
+                "--a\n",
+                "Content-Type: text/plain\n\n",
+                "Hello *world*\nbye\n",
+                "--a\n",
+                "Content-Type: text/html\n\n",
+                "<p>Hello <b>world</b></p><p>bye</p>\n",
+                "--a--\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(message.body_markdown(0).unwrap(), "Hello **world**\n\nbye");
+
+        let message = Message::parse(b"Subject: hi\n\nHello *world*\nbye\n").unwrap();
+        assert_eq!(
+            message.body_markdown(0).unwrap(),
+            "Hello \\*world\\*  \nbye  \n"
+        );
+    }
+
+    #[test]
+    fn body_refs() {
+        let raw_message = concat!(
+            "Content-Type: multipart/mixed; boundary=\"m\"\n\n",
+            "--m\n",
+            "Content-Type: text/html\n\n",
+            "<p>Hello</p>\n",
+            "--m\n",
+            "Content-Type: image/png\n",
+            "Content-Disposition: inline\n",
+            "Content-Transfer-Encoding: base64\n\n",
+            "iVBORw==\n",
+            "--m\n",
+            "Content-Type: text/plain\n\n",
+            "Bye\n",
+            "--m--\n"
+        );
+        let mut message = Message::parse(raw_message.as_bytes()).unwrap();
+
+        let text_body = message.text_body_refs().collect::<Vec<_>>();
+        assert!(
+            matches!(
+                text_body.as_slice(),
+                [
+                    BodyRef::Html {
+                        part_id: 1,
+                        html: "<p>Hello</p>",
+                        ..
+                    },
+                    BodyRef::InlineBinary {
+                        part_id: 2,
+                        contents: b"\x89PNG",
+                        ..
+                    },
+                    BodyRef::Text {
+                        part_id: 3,
+                        text: "Bye",
+                        ..
+                    },
+                ]
+            ),
+            "{:?}",
+            text_body
+        );
+        assert!(text_body
+            .iter()
+            .all(|body| std::ptr::eq(body.part(), &message.parts[body.part_id()])));
+        assert_eq!(text_body[1].contents(), b"\x89PNG");
+
+        // Ids that don't refer to a body part are skipped
+        message.html_body = vec![0, 100, 2];
+        assert_eq!(
+            message
+                .html_body_refs()
+                .map(|body| body.part_id())
+                .collect::<Vec<_>>(),
+            [2]
+        );
+    }
+
+    #[test]
+    fn resolve_content_ids() {
+        let raw_message = concat!(
+            "Subject: Chart\n",
+            "Content-Type: multipart/related; boundary=\"r\"\n",
+            "\n",
+            "--r\n",
+            "Content-Type: text/html\n",
+            "\n",
+            "<img src=\"cid:chart%40example.com\" alt=\"chart\">",
+            "<img src=cid:missing@example.com><a href=\"https://example.com\">x</a>\n",
+            "--r\n",
+            "Content-Type: image/png\n",
+            "Content-ID: <chart@example.com>\n",
+            "Content-Location: <https://example.com/\n images/chart.png>\n",
+            "Content-Transfer-Encoding: base64\n",
+            "\n",
+            "iVBORw==\n",
+            "--r--\n"
+        );
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+
+        let chart = message.part_by_content_id("<chart@example.com>").unwrap();
+        assert_eq!(chart.contents(), b"\x89PNG");
+        assert_eq!(message.part_by_content_id("chart@example.com"), Some(chart));
+        assert_eq!(message.part_by_content_id("missing@example.com"), None);
+        assert_eq!(
+            chart.content_location(),
+            Some("https://example.com/images/chart.png")
+        );
+        assert_eq!(
+            message.part_by_content_location("https://example.com/images/chart.png"),
+            Some(chart)
+        );
+
+        assert_eq!(
+            message
+                .body_html_resolved(0, |part| Some(format!(
+                    "/attachments/{}",
+                    part.content_id().unwrap()
+                )))
+                .unwrap(),
+            concat!(
+                "<img src=\"/attachments/chart@example.com\" alt=\"chart\">",
+                "<img src=cid:missing@example.com><a href=\"https://example.com\">x</a>"
+            )
+        );
+        assert_eq!(
+            message.body_html_inlined(0).unwrap(),
+            concat!(
+                "<img src=\"data:image/png;base64,iVBORw==\" alt=\"chart\">",
+                "<img src=cid:missing@example.com><a href=\"https://example.com\">x</a>"
+            )
+        );
+    }
+
+    #[test]
+    fn parse_uuencoded_attachments() {
+        let raw_message = concat!(
+            "From: john@example.com\n",
+            "Subject: Files\n",
+            "\n",
+            "Here are the files.\n",
+            "begin 644 cat.txt\n",
+            "#0V%T\n",
+            "`\n",
+            "end\n",
+            "Bye.\n"
+        );
+
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+        assert_eq!(message.attachment_count(), 0);
+        assert!(message.body_text(0).unwrap().contains("begin 644 cat.txt"));
+
+        let message = Message::parse_with_options(
+            raw_message.as_bytes(),
+            &ParseOptions::new().decode_uuencode(true),
+        )
+        .unwrap();
+        assert_eq!(message.body_text(0).unwrap(), "Here are the files.\nBye.\n");
+        assert_eq!(message.attachment_count(), 1);
+        let attachment = message.attachment(0).unwrap();
+        assert_eq!(attachment.attachment_name(), Some("cat.txt"));
+        assert_eq!(attachment.contents(), b"Cat");
+    }
+
+    #[test]
+    fn parse_with_charset_fallback() {
+        let raw_message = concat!(
+            "Subject: test\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain; charset=x-rot13\r\n",
+            "\r\n",
+            "Uryyb jbeyq\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain; charset=us-ascii\r\n",
+            "\r\n",
+            "Plain text\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain; charset=x-unknown\r\n",
+            "\r\n",
+            "Unknown charset\r\n",
+            "--b--\r\n"
+        )
+        .as_bytes();
+
+        let options = ParseOptions::default().charset_fallback(|charset, bytes| {
+            assert_ne!(charset, "us-ascii");
+            if charset == "x-rot13" {
+                Some(
+                    bytes
+                        .iter()
+                        .map(|&ch| match ch {
+                            b'a'..=b'z' => ((ch - b'a' + 13) % 26 + b'a') as char,
+                            b'A'..=b'Z' => ((ch - b'A' + 13) % 26 + b'A') as char,
+                            _ => ch as char,
+                        })
+                        .collect(),
+                )
+            } else {
+                None
+            }
+        });
+
+        let message = Message::parse_with_options(raw_message, &options).unwrap();
+        assert_eq!(message.body_text(0).unwrap(), "Hello world");
+        assert_eq!(message.body_text(1).unwrap(), "Plain text");
+        assert_eq!(message.body_text(2).unwrap(), "Unknown charset");
+
+        let message = Message::parse(raw_message).unwrap();
+        assert_eq!(message.body_text(0).unwrap(), "Uryyb jbeyq");
+    }
+
+    #[test]
+    fn parse_with_encoded_word_charset() {
+        let raw_message = concat!(
+            "From: =?ks_c_5601?B?x9GxuQ==?= <kim@example.com>\r\n",
+            "Subject: =?x-unknown?B?x9GxuQ==?= =?x-unknown?B?7ZWc6rWt?=\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "\r\n",
+            "Subject: =?x-unknown?B?x9GxuQ==?=\r\n",
+            "\r\n",
+            "Nested\r\n",
+            "--b--\r\n"
+        )
+        .as_bytes();
+
+        let message = Message::parse_with_options(
+            raw_message,
+            &ParseOptions::new().encoded_word_charset("euc-kr"),
+        )
+        .unwrap();
+        // Words that are valid UTF-8 are not affected
+        assert_eq!(message.subject(), Some("한국한국"));
+        assert_eq!(
+            message
+                .from()
+                .as_address()
+                .unwrap()
+                .first()
+                .unwrap()
+                .name
+                .as_deref(),
+            Some("한국")
+        );
+        assert_eq!(message.parts[1].message().unwrap().subject(), Some("한국"));
+
+        let message = Message::parse(raw_message).unwrap();
+        let subject = message.subject().unwrap();
+        assert!(subject.contains('\u{fffd}') && subject.ends_with("한국"));
+
+        // Values parsed apart from the rest of the header: truncated, before
+        // a whitespace-only separator and at the end of the message
+        for (raw_message, options) in [
+            (
+                "Subject: =?x-unknown?B?x9GxuQ==?= =?x-unknown?B?x9GxuQ==?=\r\n\r\nBody",
+                ParseOptions::new().max_header_len(34),
+            ),
+            (
+                "Subject: =?x-unknown?B?x9GxuQ==?=\r\n \r\nBody",
+                ParseOptions::new(),
+            ),
+            ("Subject: =?x-unknown?B?x9GxuQ==?=", ParseOptions::new()),
+        ] {
+            let message = Message::parse_with_options(
+                raw_message.as_bytes(),
+                &options.encoded_word_charset("euc-kr"),
+            )
+            .unwrap();
+            assert_eq!(message.subject(), Some("한국"), "{raw_message:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "decompression")]
+    fn parse_from_reader() {
+        let options = ParseOptions::new();
+        for raw_message in [
+            &b"From: john@example.org\r\nSubject: Archived\r\n\r\nHello, hello, hello, hello world!\r\n"[..],
+            // gzip
+            b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03s+\xca\xcf\xb5R\xc8\xca\xcf\xc8sH\xadH\xcc-\
+              \xc8I\xd5\xcb/J\xe7\xe5\n.M\xcaJM.\xb1Rp,J\xce\xc8,KM\xe1\xe5\xe2\xe5\xf2H\xcd\xc9\
+              \xc9\xd7Q\xc8\xc0\xa4\x14\xca\xf3\x8brR\x14y\xb9\x00\x92\xeb\xf2\xd9P\x00\x00\x00",
+            // Zstandard
+            b"(\xb5/\xfd$P5\x02\x00\xe4\x03From: john@example.org\r\nSubject: Archived\r\n\r\n\
+              Hello, h world!\r\n\x01\x00%\xaf@\x01\x85\x03*2",
+        ] {
+            let message = Message::parse_from_reader(raw_message, &options)
+                .unwrap()
+                .unwrap();
+            assert_eq!(message.subject(), Some("Archived"));
+            assert_eq!(
+                message.body_text(0).as_deref(),
+                Some("Hello, hello, hello, hello world!\r\n")
             );
+
+            // Decompressed size limit
+            let result = Message::parse_from_reader(raw_message, &options.clone().max_decompressed_size(79));
+            if raw_message.starts_with(b"From") {
+                assert!(result.unwrap().is_some());
+            } else {
+                assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+            }
         }
+
+        assert_eq!(
+            Message::parse_from_reader(&b"(\xb5/\xfd$P5\x02\x00"[..], &options)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn parse_with_preamble() {
+        let raw_message = concat!(
+            "Subject: preamble\r\n",
+            "Content-Type: multipart/mixed; boundary=\"a\"\r\n",
+            "\r\n",
+            "This is a multi-part message in MIME format.\r\n",
+            "\r\n",
+            "--a\r\n",
+            "Content-Type: multipart/alternative; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Hello\r\n",
+            "--b--  \r\n",
+            "Inner epilogue\r\n",
+            "--a\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "World\r\n",
+            "--a--\r\n",
+            "Generated by a list server.\r\n"
+        )
+        .as_bytes();
+
+        let message = Message::parse(raw_message).unwrap();
+        assert!(message
+            .parts
+            .iter()
+            .all(|part| part.preamble().is_none() && part.epilogue().is_none()));
+
+        let message =
+            Message::parse_with_options(raw_message, &ParseOptions::new().preserve_preamble(true))
+                .unwrap();
+        assert_eq!(
+            message.parts[0].preamble(),
+            Some(&b"This is a multi-part message in MIME format.\r\n"[..])
+        );
+        assert_eq!(
+            message.parts[0].epilogue(),
+            Some(&b"Generated by a list server.\r\n"[..])
+        );
+        assert_eq!(message.parts[1].preamble(), None);
+        assert_eq!(message.parts[1].epilogue(), Some(&b"Inner epilogue"[..]));
+        assert_eq!(message.body_text(0).unwrap(), "Hello");
+        assert_eq!(message.body_text(1).unwrap(), "World");
+        assert!(message.parts[2..]
+            .iter()
+            .all(|part| part.preamble().is_none() && part.epilogue().is_none()));
     }
 
-    fn add_crlf(bytes: &[u8]) -> Vec<u8> {
-        let mut result = Vec::with_capacity(bytes.len());
-        let mut last_ch = 0;
-        for &ch in bytes {
-            if ch == b'\n' && last_ch != b'\r' {
-                result.push(b'\r');
+    #[test]
+    fn parse_warnings() {
+        let raw_message = concat!(
+            " Subject: folded\r\n",
+            "Content-Type: multipart/mixed; boundary=\"a\"\r\n",
+            "\r\n",
+            "--a\r\n",
+            "Content-Type: text/plain; charset=x-martian\r\n",
+            "\r\n",
+            "Hello\r\n",
+            "--a\r\n",
+            "Content-Type: application/octet-stream\r\n",
+            "Content-Transfer-Encoding: x-gzip64\r\n",
+            "\r\n",
+            "H4sI\r\n"
+        )
+        .as_bytes();
+
+        let message = Message::parse(raw_message).unwrap();
+        assert_eq!(message.subject(), Some("folded"));
+        assert_eq!(message.body_text(0).unwrap(), "Hello");
+        assert_eq!(
+            message.warnings(),
+            &[
+                ParseWarning::InvalidHeaderFolding {
+                    offset: 0,
+                    part_id: 0
+                },
+                ParseWarning::UnknownCharset {
+                    offset: 83,
+                    part_id: 1,
+                    charset: "x-martian".into()
+                },
+                ParseWarning::UnknownTransferEncoding {
+                    offset: 195,
+                    part_id: 2,
+                    encoding: "x-gzip64".into()
+                },
+                ParseWarning::MissingBoundaryTerminator {
+                    offset: raw_message.len(),
+                    part_id: 0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_with_charset_detection() {
+        let mut raw_message = concat!(
+            "Subject: test\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+        )
+        .as_bytes()
+        .to_vec();
+        raw_message.extend_from_slice(b"\xcf\xf0\xe8\xe2\xe5\xf2, \xec\xe8\xf0\r\n");
+        raw_message.extend_from_slice(
+            b"--b\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nCaf\xe9 cr\xe8me\r\n",
+        );
+        raw_message.extend_from_slice(
+            "--b\r\nContent-Type: text/plain; charset=koi8-r\r\n\r\nПривет\r\n--b--\r\n".as_bytes(),
+        );
+
+        let message =
+            Message::parse_with_options(&raw_message, &ParseOptions::new().detect_charset(true))
+                .unwrap();
+        assert_eq!(message.body_text(0).unwrap(), "Привет, мир");
+        assert_eq!(message.body_text(1).unwrap(), "Café crème");
+        // Labels that decode without replacement characters are trusted
+        assert_ne!(message.body_text(2).unwrap(), "Привет");
+        assert!(message.warnings.is_empty(), "{:?}", message.warnings);
+
+        let message = Message::parse(&raw_message).unwrap();
+        assert_eq!(
+            message.body_text(0).unwrap(),
+            "\u{fffd}\u{fffd}\u{fffd}\u{fffd}\u{fffd}\u{fffd}, \u{fffd}\u{fffd}\u{fffd}"
+        );
+        assert_eq!(message.warnings.len(), 2);
+    }
+
+    #[test]
+    fn parse_boundary_collisions() {
+        let message = Message::parse(
+            concat!(
+                "Subject: test\r\n",
+                "Content-Type: multipart/mixed; boundary=\"boundary-outer\"\r\n",
+                "\r\n",
+                "--boundary-outer\r\n",
+                "Content-Type: multipart/alternative; boundary=\"boundary\"\r\n",
+                "\r\n",
+                "--boundary\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Hello\r\n",
+                "--boundary\r\n",
+                "Content-Type: text/html\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "PGI+SGVsbG88L2I+\r\n",
+                "--boundary-outer\r\n",
+                "Content-Type: application/octet-stream\r\n",
+                "\r\n",
+                "data\r\n",
+                "--boundary-outer--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.warnings,
+            vec![
+                ParseWarning::BoundaryCollision {
+                    offset: 93,
+                    part_id: 1
+                },
+                ParseWarning::MissingBoundaryTerminator {
+                    offset: 290,
+                    part_id: 1
+                }
+            ]
+        );
+        assert_eq!(message.parts[0].sub_parts(), Some(&[1, 4][..]));
+        assert_eq!(message.parts[1].sub_parts(), Some(&[2, 3][..]));
+        assert_eq!(message.body_text(0).unwrap(), "Hello");
+        assert_eq!(message.body_html(0).unwrap(), "<b>Hello</b>");
+        assert_eq!(message.parts[4].contents(), b"data");
+
+        // Delimiters of an enclosing multipart close the nested messages
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=\"2\"\n\n",
+                "--2\n",
+                "Content-Type: message/rfc822\n\n",
+                "Subject: x\n",
+                "Content-Type: text/plain\n\n",
+                "hello\n",
+                "--2\n",
+                "Content-Type: multipart/mixed; boundary=\"22\"\n\n",
+                "--22\n",
+                "Content-Type: message/rfc822\n\n",
+                "Subject: x\n",
+                "Content-Type: message/rfc822\n\n",
+                "Subject: y\n",
+                "Content-Type: text/plain\n\n",
+                "hello\n",
+                "--2--\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(message.parts[0].sub_parts(), Some(&[1, 2][..]));
+        assert_eq!(message.parts[2].sub_parts(), Some(&[3][..]));
+        assert_eq!(
+            message.parts[3].message().unwrap().parts[0]
+                .message()
+                .unwrap()
+                .body_text(0)
+                .unwrap(),
+            "hello"
+        );
+
+        // Parts are scanned once, regardless of the number of collisions
+        for (outer, inner) in [("b", "b"), ("b", "bb")] {
+            let mut raw_message = format!(
+                concat!(
+                    "Content-Type: multipart/mixed; boundary=\"{}\"\n\n",
+                    "--{}\n",
+                    "Content-Type: multipart/mixed; boundary=\"{}\"\n\n"
+                ),
+                outer, outer, inner
+            );
+            for _ in 0..20000 {
+                raw_message.push_str(&format!("--{}\nContent-Type: text/plain\n\nhello\n", inner));
             }
-            result.push(ch);
-            last_ch = ch;
+            raw_message.push_str(&format!("--{}--\n--{}--\n", inner, outer));
+
+            let time = Instant::now();
+            let message = Message::parse(raw_message.as_bytes()).unwrap();
+            assert!(
+                time.elapsed() < Duration::from_secs(5),
+                "{:?} {:?}",
+                (outer, inner),
+                time.elapsed()
+            );
+            assert_eq!(message.parts.len(), 20002);
+            assert_eq!(message.parts[1].sub_parts().unwrap().len(), 20000);
         }
+    }
+
+    #[test]
+    fn parse_with_detected_encoding() {
+        let raw_message = concat!(
+            "Subject: test\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "\r\n",
+            "VGhpcyBtZXNzYWdlIHdhcyBzZW50IHdpdGhvdXQgYSBDb250ZW50LVRyYW5zZmVyLUVuY29k\r\n",
+            "aW5nIGhlYWRlci4=\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "\r\n",
+            "Caf=C3=A9 au lait, cr=C3=A8me br=C3=BBl=C3=A9e and a very long line th=\r\n",
+            "at was wrapped.\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "If x = 1 then y=2, not quoted-printable.\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "AVeryLongWordThatIsNotBase64ButLooksLikeItBecauseItHasNoSpaces\r\n",
+            "--b--\r\n"
+        )
+        .as_bytes();
+
+        let message =
+            Message::parse_with_options(raw_message, &ParseOptions::new().detect_encoding(true))
+                .unwrap();
+        assert_eq!(
+            message.body_text(0).unwrap(),
+            "This message was sent without a Content-Transfer-Encoding header."
+        );
+        assert_eq!(
+            message.body_text(1).unwrap(),
+            "Café au lait, crème brûlée and a very long line that was wrapped."
+        );
+        assert_eq!(
+            message.body_text(2).unwrap(),
+            "If x = 1 then y=2, not quoted-printable."
+        );
+        assert_eq!(
+            message.body_text(3).unwrap(),
+            "AVeryLongWordThatIsNotBase64ButLooksLikeItBecauseItHasNoSpaces"
+        );
+        assert_eq!(
+            message.warnings,
+            vec![
+                ParseWarning::DetectedEncoding {
+                    offset: 110,
+                    part_id: 1,
+                    encoding: Encoding::Base64
+                },
+                ParseWarning::DetectedEncoding {
+                    offset: 250,
+                    part_id: 2,
+                    encoding: Encoding::QuotedPrintable
+                }
+            ]
+        );
+        assert_eq!(message.parts[1].encoding, Encoding::Base64);
+
+        let message = Message::parse(raw_message).unwrap();
+        assert!(message.body_text(0).unwrap().starts_with("VGhpcyBt"));
+        assert!(message.warnings.is_empty());
+    }
 
-        result
+    #[test]
+    fn parse_with_mime_type_corrections() {
+        let raw_message = concat!(
+            "Subject: test\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: application/octet-stream\r\n",
+            "Content-Disposition: attachment; filename=\"Report.PDF\"\r\n",
+            "\r\n",
+            "%PDF-1.4\r\n",
+            "--b\r\n",
+            "Content-Type: application/octet-stream; name=\"notes.txt\"\r\n",
+            "\r\n",
+            "Some notes\r\n",
+            "--b\r\n",
+            "Content-Type: application/x-msdownload; name=\"invoice.pdf\"\r\n",
+            "\r\n",
+            "MZ\r\n",
+            "--b\r\n",
+            "Content-Type: image/jpg; name=\"photo.jpg\"\r\n",
+            "\r\n",
+            "JFIF\r\n",
+            "--b--\r\n"
+        )
+        .as_bytes();
+
+        let options = ParseOptions::default()
+            .correct_mime_type("application/octet-stream", "pdf", "application/pdf")
+            .correct_mime_type("application/octet-stream", "txt", "text/plain")
+            .correct_mime_type("*", "jpg", "image/jpeg");
+        let message = Message::parse_with_options(raw_message, &options).unwrap();
+
+        let content_types = message.parts[1..]
+            .iter()
+            .map(|part| {
+                let ct = part.content_type().unwrap();
+                format!("{}/{}", ct.ctype(), ct.subtype().unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            content_types,
+            [
+                "application/pdf",
+                "text/plain",
+                "application/x-msdownload",
+                "image/jpeg"
+            ]
+        );
+        assert_eq!(
+            message.parts[2].content_type().unwrap().attribute("name"),
+            Some("notes.txt")
+        );
+        assert_eq!(message.parts[2].text_contents(), Some("Some notes"));
+    }
+
+    #[test]
+    fn raw_ranges() {
+        let raw_message = concat!(
+            "From: jane@example.com\r\n",
+            "Subject: Hello\r\n",
+            "  world\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "SGVsbG8gd29ybGQ=\r\n",
+            "--b--\r\n"
+        );
+        let message = Message::parse(raw_message.as_bytes()).unwrap();
+
+        let subject = &message.headers()[1];
+        assert_eq!(
+            message.raw_bytes(subject.raw_range()).unwrap(),
+            b"Subject: Hello\r\n  world\r\n"
+        );
+        assert_eq!(
+            message.raw_bytes(subject.raw_value_range()).unwrap(),
+            b" Hello\r\n  world\r\n"
+        );
+
+        let part = &message.parts[1];
+        assert_eq!(
+            message.raw_bytes(part.raw_header_range()).unwrap(),
+            b"Content-Type: text/plain\r\nContent-Transfer-Encoding: base64\r\n\r\n"
+        );
+        assert_eq!(
+            message.raw_bytes(part.raw_body_range()).unwrap(),
+            b"SGVsbG8gd29ybGQ="
+        );
+        assert_eq!(part.contents(), b"Hello world");
+        assert_eq!(
+            message.raw_bytes(message.parts[0].raw_range()).unwrap(),
+            raw_message.as_bytes()
+        );
+        assert_eq!(message.raw_bytes(0..usize::MAX), None);
     }
 
-    fn strip_crlf(bytes: &[u8]) -> Vec<u8> {
-        let mut result = Vec::with_capacity(bytes.len());
-        for &ch in bytes {
-            if !ch != b'\r' {
-                result.push(ch);
+    #[test]
+    fn parse_nested_with_options() {
+        let raw_message = concat!(
+            "Subject: test\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain; charset=x-upper\r\n",
+            "\r\n",
+            "Parent\r\n",
+            "--b\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "\r\n",
+            "Subject: nested\r\n",
+            "Content-Type: text/plain; charset=x-upper\r\n",
+            "\r\n",
+            "Nested\r\n",
+            "--b\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "U3ViamVjdDogZW5jb2RlZA0KQ29udGVudC1UeXBlOiB0ZXh0L3BsYWluOyBjaGFyc2V0PXgtdXBw\r\n",
+            "ZXINCg0KRW5jb2RlZA0K\r\n",
+            "--b--\r\n"
+        )
+        .as_bytes();
+
+        let options = ParseOptions::default()
+            .charset_fallback(|_, bytes| Some(String::from_utf8_lossy(bytes).to_uppercase()));
+
+        for (nested_options, expected_result) in [
+            (NestedOptions::Inherit, ["PARENT", "NESTED", "ENCODED"]),
+            (NestedOptions::Default, ["PARENT", "Nested", "Encoded"]),
+            (
+                NestedOptions::Custom(
+                    ParseOptions::default()
+                        .charset_fallback(|_, bytes| {
+                            Some(String::from_utf8_lossy(bytes).to_lowercase())
+                        })
+                        .into(),
+                ),
+                ["PARENT", "nested", "encoded"],
+            ),
+        ] {
+            let options = options.clone().nested_messages(nested_options);
+            let message = Message::parse_with_options(raw_message, &options).unwrap();
+            let mut result = vec![message.body_text(0).unwrap().into_owned()];
+            for part_id in [2, 3] {
+                result.push(
+                    message.parts[part_id]
+                        .message()
+                        .unwrap()
+                        .body_text(0)
+                        .unwrap()
+                        .trim_end()
+                        .to_string(),
+                );
             }
+            assert_eq!(result, expected_result);
+        }
+    }
+
+    #[test]
+    fn parse_with_limits() {
+        let raw_message = concat!(
+            "Subject: limits\r\n",
+            "Content-Type: multipart/mixed; boundary=\"a\"\r\n",
+            "\r\n",
+            "--a\r\n",
+            "Content-Type: text/html\r\n",
+            "\r\n",
+            "<p>Hello</p>\r\n",
+            "--a\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: image/png\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "iVBORw==\r\n",
+            "--b--\r\n",
+            "--a\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "\r\n",
+            "Subject: nested\r\n",
+            "\r\n",
+            "Nested\r\n",
+            "--a\r\n",
+            "Content-Type: application/octet-stream\r\n",
+            "Content-Transfer-Encoding: x-custom\r\n",
+            "\r\n",
+            "Custom\r\n",
+            "--a--\r\n"
+        )
+        .as_bytes();
+
+        // Default options
+        let message = Message::parse(raw_message).unwrap();
+        assert_eq!(message.parts.len(), 6);
+        assert_eq!(message.text_body, vec![1, 3]);
+        assert_eq!(message.parts[3].contents(), b"\x89PNG");
+        assert!(message.parts[4].message().is_some());
+        assert_eq!(
+            message.warnings(),
+            &[ParseWarning::UnknownTransferEncoding {
+                offset: 381,
+                part_id: 5,
+                encoding: "x-custom".into()
+            }]
+        );
+
+        // Maximum depth
+        let message =
+            Message::parse_with_options(raw_message, &ParseOptions::new().max_depth(1)).unwrap();
+        assert_eq!(message.parts.len(), 5);
+        assert!(message.parts[2].contents().starts_with(b"--b\r\n"));
+        assert!(message.parts[3].contents().starts_with(b"Subject: nested"));
+        assert_eq!(message.attachments, vec![2, 3, 4]);
+        assert_eq!(
+            message.warnings,
+            vec![
+                ParseWarning::MaxDepthExceeded {
+                    offset: 115,
+                    part_id: 2
+                },
+                ParseWarning::MaxDepthExceeded {
+                    offset: 251,
+                    part_id: 3
+                },
+                ParseWarning::UnknownTransferEncoding {
+                    offset: 381,
+                    part_id: 4,
+                    encoding: "x-custom".into()
+                }
+            ]
+        );
+
+        // Maximum number of parts
+        let message =
+            Message::parse_with_options(raw_message, &ParseOptions::new().max_parts(3)).unwrap();
+        assert_eq!(message.parts.len(), 3);
+        assert_eq!(
+            message.warnings,
+            vec![ParseWarning::MaxPartsExceeded { offset: 167 }]
+        );
+
+        // Do not decode attachments, do not generate alternatives and
+        // treat unknown encodings as binary
+        let message = Message::parse_with_options(
+            raw_message,
+            &ParseOptions::new()
+                .decode_attachments(false)
+                .generate_alternatives(false)
+                .unknown_encoding_as_binary(true),
+        )
+        .unwrap();
+        assert_eq!(message.text_body, vec![3]);
+        assert_eq!(message.html_body, vec![1, 3]);
+        assert_eq!(message.parts[3].contents(), b"iVBORw==");
+        assert_eq!(message.parts[3].encoding, Encoding::Base64);
+        assert_eq!(message.parts[5].contents(), b"Custom");
+        assert!(matches!(message.parts[5].body, PartType::Binary(_)));
+
+        // Skip decoding images and nested messages
+        let message = Message::parse_with_options(
+            raw_message,
+            &ParseOptions::new()
+                .skip_content_type("image")
+                .skip_content_type("Message/RFC822"),
+        )
+        .unwrap();
+        assert_eq!(message.parts.len(), 6);
+        assert_eq!(message.text_body, vec![1]);
+        assert_eq!(message.attachments, vec![3, 4, 5]);
+        assert_eq!(message.parts[3].contents(), b"iVBORw==");
+        assert_eq!(message.parts[3].encoding, Encoding::Base64);
+        assert!(message.parts[4].message().is_none());
+        assert!(message.parts[4].contents().starts_with(b"Subject: nested"));
+        assert_eq!(message.parts[5].contents(), b"Custom");
+
+        // Decode text parts only
+        let message = Message::parse_with_options(
+            raw_message,
+            &ParseOptions::new().decode_content_type("text/*"),
+        )
+        .unwrap();
+        assert_eq!(message.body_html(0).unwrap(), "<p>Hello</p>");
+        assert_eq!(message.parts[3].contents(), b"iVBORw==");
+        assert!(message.parts[4].message().is_none());
+        assert!(matches!(message.parts[5].body, PartType::Binary(_)));
+    }
+
+    #[test]
+    fn parse_with_part_scanner() {
+        let raw_message = concat!(
+            "Subject: scan\r\n",
+            "Content-Type: multipart/mixed; boundary=\"a\"\r\n",
+            "\r\n",
+            "--a\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Hello\r\n",
+            "--a\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "\r\n",
+            "Subject: nested\r\n",
+            "\r\n",
+            "Nested\r\n",
+            "--a\r\n",
+            "Content-Type: application/octet-stream; name=\"eicar.com\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "WDVPIVAlQEFQWzRc\r\n",
+            "--a\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Bye\r\n",
+            "--a--\r\n"
+        )
+        .as_bytes();
+
+        let scan = |pattern: &'static [u8]| {
+            let scanned = Arc::new(Mutex::new(Vec::new()));
+            let scanned_ = scanned.clone();
+            let message = Message::parse_with_options(
+                raw_message,
+                &ParseOptions::new().part_scanner(move |part_id, part: &MessagePart<'_>| {
+                    let contents = part.contents();
+                    scanned_
+                        .lock()
+                        .unwrap()
+                        .push((part_id, String::from_utf8_lossy(contents).into_owned()));
+                    if contents
+                        .windows(pattern.len())
+                        .any(|window| window == pattern)
+                    {
+                        ScanVerdict::Stop("EICAR-Test-File".to_string())
+                    } else {
+                        ScanVerdict::Continue
+                    }
+                }),
+            )
+            .unwrap();
+            let scanned = scanned.lock().unwrap().clone();
+            (message, scanned)
+        };
+
+        // All parts are scanned, including those of nested messages
+        let (message, scanned) = scan(b"not found");
+        assert_eq!(
+            scanned,
+            vec![
+                (1, "Hello".to_string()),
+                (0, "Nested".to_string()),
+                (3, "X5O!P%@AP[4\\".to_string()),
+                (4, "Bye".to_string())
+            ]
+        );
+        assert_eq!(message.parts.len(), 5);
+        assert_eq!(message.scan_stopped(), None);
+
+        // Parsing stops at the first part with a verdict
+        let (message, scanned) = scan(b"X5O!");
+        assert_eq!(scanned.len(), 3);
+        assert_eq!(message.parts.len(), 4);
+        assert_eq!(message.attachments, vec![2, 3]);
+        assert_eq!(message.scan_stopped(), Some((3, "EICAR-Test-File")));
+        assert_eq!(
+            message.warnings,
+            vec![ParseWarning::ScanStopped {
+                part_id: 3,
+                reason: "EICAR-Test-File".to_string()
+            }]
+        );
+
+        // A verdict in a nested message stops parsing the enclosing message
+        let (message, scanned) = scan(b"Nested");
+        assert_eq!(scanned.len(), 2);
+        assert_eq!(message.parts.len(), 3);
+        assert_eq!(message.scan_stopped(), Some((0, "EICAR-Test-File")));
+    }
+
+    #[test]
+    fn parse_multipart_subtypes() {
+        // Unknown subtypes are parsed as multipart/mixed, keeping their tree
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/x-custom; boundary=\"a\"\r\n",
+                "\r\n",
+                "--a\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Hello\r\n",
+                "--a\r\n",
+                "Content-Type: multipart/x-inner; boundary=\"b\"\r\n",
+                "\r\n",
+                "--b\r\n",
+                "Content-Type: image/png\r\n",
+                "\r\n",
+                "PNG\r\n",
+                "--b--\r\n",
+                "--a--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(message.parts[0].sub_parts(), Some(&[1, 2][..]));
+        assert_eq!(message.parts[2].sub_parts(), Some(&[3][..]));
+        assert_eq!(message.text_body, vec![1, 3]);
+        assert_eq!(message.attachments, vec![3]);
+
+        // The fields and files of a form are attachments
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/form-data; boundary=\"a\"\r\n",
+                "\r\n",
+                "--a\r\n",
+                "Content-Disposition: form-data; name=\"field\"\r\n",
+                "\r\n",
+                "value\r\n",
+                "--a\r\n",
+                "Content-Disposition: form-data; name=\"files\"\r\n",
+                "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+                "\r\n",
+                "--b\r\n",
+                "Content-Disposition: file; filename=\"notes.txt\"\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Notes\r\n",
+                "--b--\r\n",
+                "--a--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(message.parts[0].sub_parts(), Some(&[1, 2][..]));
+        assert_eq!(message.parts[2].sub_parts(), Some(&[3][..]));
+        assert_eq!(message.text_body, Vec::<usize>::new());
+        assert_eq!(message.html_body, Vec::<usize>::new());
+        assert_eq!(message.attachments, vec![1, 3]);
+        assert_eq!(message.parts[1].text_contents(), Some("value"));
+        assert_eq!(message.parts[3].attachment_name(), Some("notes.txt"));
+    }
+
+    #[test]
+    fn parse_decode_errors() {
+        let raw_message = b"Subject: decode errors\r\n\
+            Content-Type: multipart/mixed; boundary=\"a\"\r\n\
+            \r\n\
+            --a\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\
+            \r\n\
+            Caf\xe9 \xef\xbf\xbd\r\n\
+            --a\r\n\
+            Content-Type: text/plain\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            \r\n\
+            Caf=C3=A9 =XY\r\n\
+            --a\r\n\
+            Content-Type: application/octet-stream\r\n\
+            Content-Transfer-Encoding: base64\r\n\
+            \r\n\
+            SGVsbG8*\r\n\
+            --a--\r\n";
+
+        let message = Message::parse(raw_message).unwrap();
+        assert_eq!(
+            message.decode_errors(1).collect::<Vec<_>>(),
+            vec![&DecodeError::CharsetReplacement { count: 1, bytes: 1 }]
+        );
+        assert_eq!(
+            message.decode_errors(2).collect::<Vec<_>>(),
+            vec![&DecodeError::InvalidQuotedPrintable { offset: 217 }]
+        );
+        assert_eq!(
+            message.decode_errors(3).collect::<Vec<_>>(),
+            vec![&DecodeError::InvalidBase64 { offset: 311 }]
+        );
+        assert_eq!(message.decode_errors(0).count(), 0);
+        assert_eq!(message.replaced_bytes(1), 1);
+        assert_eq!(message.replaced_bytes(2), 0);
+    }
+
+    #[test]
+    fn parse_long_headers() {
+        let raw_message = concat!(
+            "Subject: Hello =?utf-8?q?caf=C3=A9?= w\u{f6}rld =?utf-8?q?long_word?=\n more\n",
+            "From: john@example.org\n\n",
+            "Body\n"
+        )
+        .as_bytes();
+
+        for (max_header_len, expected_subject) in [
+            (22, "Hello"),
+            (31, "Hello caf\u{e9} w"),
+            (40, "Hello caf\u{e9} w\u{f6}rld"),
+            (63, "Hello caf\u{e9} w\u{f6}rld long word more"),
+        ] {
+            let message = Message::parse_with_options(
+                raw_message,
+                &ParseOptions::new().max_header_len(max_header_len),
+            )
+            .unwrap();
+            assert_eq!(
+                message.subject(),
+                Some(expected_subject),
+                "{}",
+                max_header_len
+            );
+            assert_eq!(
+                message
+                    .from()
+                    .as_address()
+                    .unwrap()
+                    .first()
+                    .unwrap()
+                    .address
+                    .as_deref(),
+                Some("john@example.org")
+            );
+            assert_eq!(
+                message.warnings,
+                vec![ParseWarning::HeaderTruncated {
+                    offset: 8,
+                    part_id: 0,
+                    length: 64
+                }]
+            );
+        }
+
+        let message =
+            Message::parse_with_options(raw_message, &ParseOptions::new().max_header_len(64))
+                .unwrap();
+        assert_eq!(
+            message.subject(),
+            Some("Hello caf\u{e9} w\u{f6}rld long word more")
+        );
+        assert!(message.warnings.is_empty());
+    }
+
+    #[test]
+    fn best_body() {
+        for (raw_message, expected_html, expected_text) in [
+            (
+                concat!(
+                    "Content-Type: multipart/alternative; boundary=\"a\"\n\n",
+                    "--a\nContent-Type: text/plain\n\nHello *world*\n",
+                    "--a\nContent-Type: text/html\n\n<p>Hello <b>world</b></p>\n",
+                    "--a--\n"
+                ),
+                "<p>Hello <b>world</b></p>",
+                "Hello *world*",
+            ),
+            (
+                concat!(
+                    "Content-Type: multipart/mixed; boundary=\"a\"\n\n",
+                    "--a\nContent-Type: text/html\n\n",
+                    "<!DOCTYPE html><html><head><title>Hi</title></head>",
+                    "<body><p>Hello</p></body></html>\n",
+                    "--a\nContent-Type: text/html\n\n<html><body><p>John</p></body></html>\n",
+                    "--a--\n"
+                ),
+                "<p>Hello</p>\n<p>John</p>",
+                "Hello\nJohn",
+            ),
+            (
+                concat!(
+                    "Content-Type: multipart/mixed; boundary=\"a\"\n\n",
+                    "--a\nContent-Type: text/plain\n\nHello\n",
+                    "--a\nContent-Type: image/png\nContent-Disposition: inline\n\nPNG\n",
+                    "--a\nContent-Type: text/plain\n\n-- \nJohn\n",
+                    "--a--\n"
+                ),
+                "Hello\n-- <br/>John",
+                "Hello\n-- \nJohn",
+            ),
+            (
+                concat!(
+                    "Content-Type: multipart/alternative; boundary=\"a\"\n\n",
+                    "--a\nContent-Type: text/plain\n\nHello\n",
+                    "--a\nContent-Type: text/html\n\n  \n",
+                    "--a--\n"
+                ),
+                "<html><body>Hello</body></html>",
+                "Hello",
+            ),
+        ] {
+            let message = Message::parse(raw_message.as_bytes()).unwrap();
+            assert_eq!(
+                message.best_body(BodyPreference::Html).as_deref(),
+                Some(expected_html),
+                "{}",
+                raw_message
+            );
+            assert_eq!(
+                message.best_body(BodyPreference::Text).as_deref(),
+                Some(expected_text),
+                "{}",
+                raw_message
+            );
+        }
+
+        let message = Message::parse(b"Subject: empty\n\n").unwrap();
+        assert_eq!(message.best_body(BodyPreference::Html), None);
+    }
+
+    #[test]
+    fn subject_normalized() {
+        let message =
+            Message::parse(b"Subject: Re: =?utf-8?q?AW=3A_Caf=C3=A9?=\r\n  \t menu  \r\n\r\nBody")
+                .unwrap();
+        assert_eq!(
+            message.subject_normalized().as_deref(),
+            Some("Caf\u{e9} menu")
+        );
+
+        let message = Message::parse(b"Subject: Odg: Fwd: Lunch\r\n\r\nBody").unwrap();
+        assert_eq!(
+            message.subject_normalized().as_deref(),
+            Some("Odg: Fwd: Lunch")
+        );
+        assert_eq!(
+            message
+                .subject_normalized_with(&SubjectPrefixes::new().reply("Odg:"))
+                .as_deref(),
+            Some("Lunch")
+        );
+        assert_eq!(
+            Message::parse(b"From: john@example.com\r\n\r\nBody")
+                .unwrap()
+                .subject_normalized(),
+            None
+        );
+    }
+
+    #[test]
+    fn original_originators() {
+        let message = Message::parse(
+            concat!(
+                "From: \"John via List\" <list@example.org>\r\n",
+                "Original-From: John <john@example.com>\r\n",
+                "X-Original-Sender: john@example.com\r\n",
+                "Original-Recipient: rfc822; jane@example.net\r\n",
+                "\r\n",
+                "Body"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            message.original_from(),
+            HeaderValue::Address(Addr::new("John".into(), "john@example.com"))
+        );
+        assert_eq!(
+            message.x_original_sender(),
+            HeaderValue::Address(Addr::new(None, "john@example.com"))
+        );
+        assert_eq!(message.original_recipient(), Some("jane@example.net"));
+
+        let message = Message::parse(b"From: john@example.com\r\n\r\nBody").unwrap();
+        assert_eq!(message.original_from(), HeaderValue::Empty);
+        assert_eq!(message.x_original_sender(), HeaderValue::Empty);
+        assert_eq!(message.original_recipient(), None);
+    }
+
+    #[test]
+    fn parse_simple_text_messages() {
+        // A skipped content type that never matches disables the fast path
+        // without changing the result
+        let slow_path = ParseOptions::new().skip_content_type("application/x-none");
+
+        for (raw_message, expected_text) in [
+            (
+                &b"Subject: test\r\n\r\nHello, world!\r\n"[..],
+                Some("Hello, world!\r\n"),
+            ),
+            (b"Subject: test\n\n", Some("")),
+            (b"Subject: test\n\n\xffHello", Some("\u{fffd}Hello")),
+            (
+                b"Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\nCaf\xc3\xa9",
+                Some("Caf\u{e9}"),
+            ),
+            (
+                b"Content-Type: TEXT/PLAIN; charset=US-ASCII\r\nContent-Disposition: inline\r\n\r\nHello",
+                Some("Hello"),
+            ),
+            (
+                b"Content-Type: text/plain; charset=iso-8859-1\r\n\r\nCaf\xe9",
+                Some("Caf\u{e9}"),
+            ),
+            (
+                b"Content-Transfer-Encoding: quoted-printable\r\n\r\nCaf=C3=A9",
+                Some("Caf\u{e9}"),
+            ),
+            (b"Content-Disposition: attachment\r\n\r\nHello", None),
+            (b"Content-Disposition:\r\n\r\nHello", Some("Hello")),
+            (b"Content-Type:\r\n\r\nHello", Some("Hello")),
+        ] {
+            let message = Message::parse(raw_message).unwrap();
+
+            assert_eq!(
+                message,
+                Message::parse_with_options(raw_message, &slow_path).unwrap(),
+                "{:?}",
+                raw_message
+            );
+            assert_eq!(message.body_text(0).as_deref(), expected_text);
+            assert_eq!(message.parts[0].offset_end, raw_message.len());
         }
+    }
 
-        result
+    #[test]
+    fn parse_lenient_dates() {
+        let raw_message = concat!(
+            "Received: from mx.example.com by mx.example.org;\r\n",
+            "  Tue, 3 Oct 2023 09:00:00 +0000\r\n",
+            "Received: from host.example.com by mx.example.com;\r\n",
+            "  Tue, 3 Oct 2023 08:59:00 +0000\r\n",
+            "Date: Mon Oct  2 10:00:00 2023\r\n",
+            "Resent-Date: 2023-10-02 11:00 +0200\r\n",
+            "\r\n",
+            "Hello"
+        )
+        .as_bytes();
+
+        let message = Message::parse(raw_message).unwrap();
+        assert_eq!(message.date(), None);
+        assert!(!message
+            .resent_date()
+            .as_datetime_ref()
+            .is_some_and(|date| date.is_valid()));
+        assert_eq!(
+            message.received_date().map(|date| date.to_rfc3339()),
+            Some("2023-10-03T09:00:00Z".to_string())
+        );
+
+        let message =
+            Message::parse_with_options(raw_message, &ParseOptions::new().lenient_dates(true))
+                .unwrap();
+        assert_eq!(
+            message.date().map(|date| date.to_rfc3339()),
+            Some("2023-10-02T10:00:00Z".to_string())
+        );
+        assert_eq!(
+            message
+                .resent_date()
+                .as_datetime_ref()
+                .map(|date| date.to_rfc3339()),
+            Some("2023-10-02T11:00:00+02:00".to_string())
+        );
+        assert_eq!(message.received_date().as_ref(), message.date());
+
+        // Valid dates are left as parsed by the strict parser
+        let raw_message = b"Date: Tue, 1 Jul 03 10:52:37 +0200\r\n\r\nHello";
+        assert_eq!(
+            Message::parse_with_options(raw_message, &ParseOptions::new().lenient_dates(true))
+                .unwrap()
+                .date(),
+            Message::parse(raw_message).unwrap().date()
+        );
+
+        assert_eq!(
+            Message::parse(b"Received: from a by b; 2024-01-01T\r\n\r\nHello")
+                .unwrap()
+                .received_date()
+                .map(|date| date.to_rfc3339()),
+            Some("2024-01-01T00:00:00Z".to_string())
+        );
+        assert_eq!(
+            Message::parse(b"Received: from a by b; 1T\r\n\r\nHello")
+                .unwrap()
+                .received_date(),
+            None
+        );
+        assert_eq!(
+            Message::parse(b"Subject: test\r\n\r\nHello")
+                .unwrap()
+                .received_date(),
+            None
+        );
+    }
+
+    #[test]
+    fn resent_blocks() {
+        let message = Message::parse(
+            concat!(
+                "Received: from mx.example.org by mx.example.net;\r\n",
+                "  Wed, 4 Oct 2023 10:00:00 +0000\r\n",
+                "Resent-From: Jane <jane@example.org>\r\n",
+                "Resent-To: team@example.net\r\n",
+                "Resent-Cc: bob@example.net\r\n",
+                "Resent-Date: Wed, 4 Oct 2023 10:00:00 +0000\r\n",
+                "Resent-Message-ID: <resent-2@example.org>\r\n",
+                "Resent-From: John <john@example.com>\r\n",
+                "Resent-Sender: mailer@example.com\r\n",
+                "Resent-To: jane@example.org\r\n",
+                "Resent-Date: Tue, 3 Oct 2023 10:00:00 +0000\r\n",
+                "From: alice@example.com\r\n",
+                "Subject: Report\r\n",
+                "\r\n",
+                "Hello"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let blocks = message.resent_blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[0].from,
+            Some(&HeaderValue::Address(Addr::new(
+                "Jane".into(),
+                "jane@example.org"
+            )))
+        );
+        assert_eq!(
+            blocks[0].cc,
+            Some(&HeaderValue::Address(Addr::new(None, "bob@example.net")))
+        );
+        assert_eq!(
+            blocks[0].date.map(|date| date.to_rfc3339()).as_deref(),
+            Some("2023-10-04T10:00:00Z")
+        );
+        assert_eq!(blocks[0].message_id, Some("resent-2@example.org"));
+        assert_eq!(blocks[0].sender, None);
+        assert_eq!(
+            blocks[1].sender,
+            Some(&HeaderValue::Address(Addr::new(None, "mailer@example.com")))
+        );
+        assert_eq!(
+            blocks[1].to,
+            Some(&HeaderValue::Address(Addr::new(None, "jane@example.org")))
+        );
+        assert_eq!(blocks[1].message_id, None);
+        assert_eq!(
+            message.resent_cc(),
+            &HeaderValue::Address(Addr::new(None, "bob@example.net"))
+        );
+
+        assert!(Message::parse(b"Subject: test\r\n\r\nHello")
+            .unwrap()
+            .resent_blocks()
+            .is_empty());
+    }
+
+    #[test]
+    fn attachment_file_names() {
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n",
+                "--b\r\n",
+                "Content-Type: application/pdf\r\n",
+                "Content-Disposition: attachment;\r\n",
+                " filename*0*=utf-8''..%2F..%2Fr%C3%A9;\r\n",
+                " filename*1=sum\u{e9}.pdf\r\n\r\n",
+                "PDF\r\n",
+                "--b\r\n",
+                "Content-Type: text/plain; name=\"=?utf-8?q?notes=3F.txt?=\"\r\n",
+                "Content-Disposition: attachment; filename=\"..\"\r\n\r\n",
+                "Notes\r\n",
+                "--b\r\n",
+                "Content-Type: image/png\r\n\r\n",
+                "PNG\r\n",
+                "--b--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message
+                .attachments()
+                .map(|part| part.attachment_file_name())
+                .collect::<Vec<_>>(),
+            vec![
+                Some("r\u{e9}sum\u{e9}.pdf".into()),
+                Some("notes_.txt".into()),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn attachments_iter() {
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=\"outer\"\r\n\r\n",
+                "--outer\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "Body\r\n",
+                "--outer\r\n",
+                "Content-Type: application/pdf; name=\"a.pdf\"\r\n",
+                "Content-Disposition: attachment\r\n\r\n",
+                "PDF\r\n",
+                "--outer\r\n",
+                "Content-Type: message/rfc822\r\n\r\n",
+                "Subject: forwarded\r\n",
+                "Content-Type: multipart/mixed; boundary=\"inner\"\r\n\r\n",
+                "--inner\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "Inner body\r\n",
+                "--inner\r\n",
+                "Content-Type: application/pdf; name=\"copy.pdf\"\r\n",
+                "Content-Disposition: attachment\r\n\r\n",
+                "PDF\r\n",
+                "--inner\r\n",
+                "Content-Type: image/png; name=\"b.png\"\r\n",
+                "Content-Disposition: attachment\r\n\r\n",
+                "PNG\r\n",
+                "--inner--\r\n",
+                "--outer--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let summary =
+            |(name, content_type, contents): (Option<&str>, Option<&ContentType>, &[u8])| {
+                (
+                    name.map(str::to_string),
+                    content_type.and_then(|ct| ct.subtype()).map(str::to_string),
+                    contents.to_vec(),
+                )
+            };
+
+        assert_eq!(
+            message.attachments_iter().map(summary).collect::<Vec<_>>(),
+            vec![
+                (Some("a.pdf".into()), Some("pdf".into()), b"PDF".to_vec()),
+                (Some("copy.pdf".into()), Some("pdf".into()), b"PDF".to_vec()),
+                (Some("b.png".into()), Some("png".into()), b"PNG".to_vec()),
+            ]
+        );
+        assert_eq!(
+            message
+                .attachments_iter()
+                .deduplicate()
+                .map(summary)
+                .collect::<Vec<_>>(),
+            vec![
+                (Some("a.pdf".into()), Some("pdf".into()), b"PDF".to_vec()),
+                (Some("b.png".into()), Some("png".into()), b"PNG".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_header_separators() {
+        for (raw_message, body, warning) in [
+            ("Subject: hi\n\nBody\n", Some("Body\n"), None),
+            ("Subject: hi\r\n\r\nBody", Some("Body"), None),
+            (
+                "Subject: hi\n \nBody\n",
+                Some("Body\n"),
+                Some(ParseWarning::WhitespaceHeaderSeparator {
+                    offset: 12,
+                    part_id: 0,
+                }),
+            ),
+            (
+                "Subject: hi\r\n\t \r\nBody",
+                Some("Body"),
+                Some(ParseWarning::WhitespaceHeaderSeparator {
+                    offset: 13,
+                    part_id: 0,
+                }),
+            ),
+            (
+                "From: mailer-daemon@example.com\nSubject: hi\nThe message could not be delivered\n",
+                Some("The message could not be delivered\n"),
+                Some(ParseWarning::MissingHeaderSeparator {
+                    offset: 44,
+                    part_id: 0,
+                }),
+            ),
+            ("Subject: hi\n", None, None),
+            ("Subject: hi", None, None),
+        ] {
+            let message = Message::parse(raw_message.as_bytes()).unwrap();
+
+            assert_eq!(message.subject(), Some("hi"), "{:?}", raw_message);
+            assert_eq!(message.body_text(0).as_deref(), body, "{:?}", raw_message);
+            assert_eq!(
+                message.warnings.first(),
+                warning.as_ref(),
+                "{:?}",
+                raw_message
+            );
+        }
+    }
+
+    #[test]
+    fn parse_eai() {
+        let message = Message::parse(
+            concat!(
+                "From: \"J\u{f6}hn\" <\u{7528}\u{6237}@\u{4f8b}\u{5b50}.\u{5e7f}\u{544a}>\r\n",
+                "To: \u{d1}and\u{fa} <\u{f1}and\u{fa}@ejemplo.es>\r\n",
+                "Subject: \u{41f}\u{440}\u{438}\u{432}\u{435}\u{442}\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: multipart/report; report-type=disposition-notification;\r\n",
+                " boundary=\"b\"\r\n\r\n",
+                "--b\r\n",
+                "Content-Type: message/global\r\n\r\n",
+                "From: \u{442}\u{435}\u{441}\u{442}@\u{43f}\u{440}\u{438}\u{43c}\u{435}\u{440}.\u{440}\u{444}\r\n",
+                "Subject: \u{fc}n\u{ef}code\r\n\r\n",
+                "Body\r\n",
+                "--b\r\n",
+                "Content-Type: message/global-disposition-notification\r\n\r\n",
+                "Final-Recipient: utf-8;\u{f1}and\u{fa}@ejemplo.es\r\n",
+                "Disposition: manual-action/MDN-sent-manually; displayed\r\n\r\n",
+                "--b--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert!(message.requires_smtputf8());
+        assert_eq!(
+            message
+                .from()
+                .as_address()
+                .unwrap()
+                .first()
+                .unwrap()
+                .address
+                .as_deref(),
+            Some("\u{7528}\u{6237}@\u{4f8b}\u{5b50}.\u{5e7f}\u{544a}")
+        );
+        assert_eq!(
+            message
+                .to()
+                .as_address()
+                .unwrap()
+                .first()
+                .unwrap()
+                .address
+                .as_deref(),
+            Some("\u{f1}and\u{fa}@ejemplo.es")
+        );
+        assert_eq!(
+            message.subject(),
+            Some("\u{41f}\u{440}\u{438}\u{432}\u{435}\u{442}")
+        );
+
+        let nested = message.part(1).unwrap().message().unwrap();
+        assert_eq!(
+            nested.from().as_address().unwrap().first().unwrap().address.as_deref(),
+            Some("\u{442}\u{435}\u{441}\u{442}@\u{43f}\u{440}\u{438}\u{43c}\u{435}\u{440}.\u{440}\u{444}")
+        );
+        assert_eq!(nested.subject(), Some("\u{fc}n\u{ef}code"));
+        assert_eq!(
+            message.mdn().unwrap().final_recipient.as_deref(),
+            Some("\u{f1}and\u{fa}@ejemplo.es")
+        );
+
+        // UTF-8 in the body or in encoded words does not require SMTPUTF8
+        assert!(!Message::parse(
+            concat!(
+                "Subject: =?utf-8?q?=C3=BCn=C3=AFcode?=\r\n",
+                "Content-Type: text/plain; charset=utf-8\r\n",
+                "Content-Transfer-Encoding: 8bit\r\n\r\n",
+                "\u{fc}n\u{ef}code\r\n"
+            )
+            .as_bytes()
+        )
+        .unwrap()
+        .requires_smtputf8());
+    }
+
+    #[test]
+    fn detect_attachment_media_type() {
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n",
+                "--b\r\n",
+                "Content-Type: application/pdf; name=\"invoice.pdf.exe\"\r\n",
+                "Content-Transfer-Encoding: base64\r\n\r\n",
+                "TVqQAAMAAAAEAAAA//8AAA==\r\n",
+                "--b\r\n",
+                "Content-Type: application/pdf; name=\"invoice.pdf\"\r\n\r\n",
+                "%PDF-1.4\r\n",
+                "--b\r\n",
+                "Content-Type: application/octet-stream\r\n\r\n",
+                "random data\r\n",
+                "--b--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let exe = message.attachment(0).unwrap();
+        assert_eq!(exe.detected_media_type(), Some("application/x-msdownload"));
+        assert!(exe.is_media_type_mismatch());
+        let pdf = message.attachment(1).unwrap();
+        assert_eq!(pdf.detected_media_type(), Some("application/pdf"));
+        assert!(!pdf.is_media_type_mismatch());
+        let unknown = message.attachment(2).unwrap();
+        assert_eq!(unknown.detected_media_type(), None);
+        assert!(!unknown.is_media_type_mismatch());
+    }
+
+    #[test]
+    fn part_sizes_and_lines() {
+        let message = Message::parse(
+            concat!(
+                "Subject: sizes\r\n",
+                "Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n",
+                "--b\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "line 1\r\n",
+                "line 2\r\n",
+                "--b\r\n",
+                "Content-Type: application/octet-stream\r\n",
+                "Content-Transfer-Encoding: base64\r\n\r\n",
+                "SGVsbG8g\r\n",
+                "V29ybGQ=\r\n",
+                "--b\r\n",
+                "Content-Type: message/rfc822\r\n\r\n",
+                "Subject: nested\r\n",
+                "\r\n",
+                "body\r\n",
+                "--b--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        for (part_id, size, encoded_size, lines) in [
+            (1, 14, 14, 2),
+            (2, 11, 18, 2),
+            (3, 23, 23, 3),
+            (0, 220, 220, 18),
+        ] {
+            let part = message.part(part_id).unwrap();
+            assert_eq!(
+                (part.size(), part.encoded_size(), part.lines()),
+                (size, encoded_size, lines),
+                "part {}",
+                part_id
+            );
+        }
+        let nested = message.part(3).unwrap().message().unwrap();
+        assert_eq!(nested.part(0).unwrap().lines(), 1);
+    }
+
+    fn clear_encodings(message: &mut Message<'_>) {
+        for part in &mut message.parts {
+            part.encoding = Default::default();
+            if let PartType::Message(nested) = &mut part.body {
+                nested.raw_message = Default::default();
+                clear_encodings(nested);
+            }
+        }
     }
 }
@@ -130,6 +130,57 @@ impl<'x> MessageStream<'x> {
         }
     }
 
+    /// Finds the first delimiter line matching one of the `ancestors`
+    /// boundaries that precedes the next occurrence of `boundary`, returning
+    /// the end of the content preceding it and the id of the matching
+    /// ancestor boundary.
+    pub fn find_ancestor_delimiter(
+        &self,
+        boundary: &[u8],
+        ancestors: &[(usize, &[u8])],
+    ) -> Option<(usize, usize)> {
+        if ancestors.is_empty() {
+            return None;
+        }
+
+        let start = self.offset();
+        let mut last_ch = b'\n';
+        let mut before_last_ch = b'\n';
+
+        for (pos, &ch) in self.data.iter().enumerate().skip(start) {
+            if ch == b'-' && last_ch == b'-' {
+                let line_start = pos - 1;
+                let bytes = &self.data[pos + 1..];
+
+                if line_start == start || before_last_ch == b'\n' {
+                    for &(id, ancestor) in ancestors {
+                        if bytes.starts_with(ancestor)
+                            && self.is_delimiter_end(pos + 1 + ancestor.len())
+                        {
+                            let mut content_end = line_start;
+                            if content_end > start && self.data[content_end - 1] == b'\n' {
+                                content_end -= 1;
+                                if content_end > start && self.data[content_end - 1] == b'\r' {
+                                    content_end -= 1;
+                                }
+                            }
+                            return Some((content_end, id));
+                        }
+                    }
+                }
+
+                if bytes.starts_with(boundary) {
+                    return None;
+                }
+            }
+
+            before_last_ch = last_ch;
+            last_ch = ch;
+        }
+
+        None
+    }
+
     pub fn is_multipart_end(&mut self) -> bool {
         self.checkpoint();
 
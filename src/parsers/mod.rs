@@ -11,17 +11,43 @@
 
 use std::{iter::Peekable, ops::Range, slice::Iter};
 
+use crate::decoders::charsets::DecoderFnc;
+
+#[cfg(feature = "dmarc")]
+pub mod dmarc;
+pub mod external_body;
+pub mod features;
 pub mod fields;
+#[cfg(feature = "fixtures")]
+pub mod fixture;
 pub mod header;
+pub mod icalendar;
+pub mod lint;
 pub mod message;
 pub mod mime;
+pub mod originators;
+pub mod partition;
+pub mod pgp;
+pub mod policy;
 pub mod preview;
+pub mod render;
+pub mod report;
+pub mod smime;
+#[cfg(feature = "content_sniffing")]
+pub mod sniff;
+pub mod statistics;
+pub mod summary;
+#[cfg(feature = "dmarc")]
+pub mod tlsrpt;
+pub mod validate;
+pub mod vcard;
 
 pub struct MessageStream<'x> {
     data: &'x [u8],
     iter: Peekable<Iter<'x, u8>>,
     pos: usize,
     restore_pos: usize,
+    pub(crate) word_fallback: Option<DecoderFnc>,
 }
 
 impl<'x> MessageStream<'x> {
@@ -31,6 +57,7 @@ impl<'x> MessageStream<'x> {
             iter: data.iter().peekable(),
             pos: 0,
             restore_pos: 0,
+            word_fallback: None,
         }
     }
 
@@ -93,6 +120,18 @@ impl<'x> MessageStream<'x> {
         }
     }
 
+    /// Returns `true` if the bytes at `pos` up to the end of the line are
+    /// either empty, whitespace or "--" followed by whitespace.
+    pub fn is_delimiter_end(&self, pos: usize) -> bool {
+        let bytes = self.data.get(pos..).unwrap_or_default();
+        bytes
+            .strip_prefix(b"--")
+            .unwrap_or(bytes)
+            .iter()
+            .take_while(|&&ch| ch != b'\n')
+            .all(|ch| ch.is_ascii_whitespace())
+    }
+
     #[inline(always)]
     pub fn try_skip_char(&mut self, ch: u8) -> bool {
         if self.peek_char(ch) {
@@ -155,3 +194,16 @@ impl<'x> Iterator for MessageStream<'x> {
         self.iter.next()
     }
 }
+
+// Removes the leading and trailing ASCII whitespace of a byte slice.
+pub(crate) fn trim_ascii(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|ch| !ch.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|ch| !ch.is_ascii_whitespace())
+        .map_or(start, |end| end + 1);
+    &bytes[start..end]
+}
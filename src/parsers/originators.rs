@@ -0,0 +1,167 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{
+    GetHeader, Header, HeaderDiff, HeaderValue, Message, PartType, RfcHeader, SubjectPrefixes,
+};
+
+// Header fields identifying the author and origin of a message.
+static ORIGINATOR_HEADERS: &[RfcHeader] = &[
+    RfcHeader::From,
+    RfcHeader::Date,
+    RfcHeader::MessageId,
+    RfcHeader::Subject,
+];
+
+impl<'x> Message<'x> {
+    /// Compares the From, Date, Message-ID and Subject header fields of the
+    /// message with those of the first message attached to it, such as the
+    /// original of a forwarded message or of a delivery status
+    /// notification, returning the fields that differ. Returns `None` when
+    /// no message is attached.
+    ///
+    /// See [`originator_diff`] for comparing the header fields returned by
+    /// [`MessagePart::rfc822_headers`](crate::MessagePart::rfc822_headers)
+    /// when a bounce only includes the header of the original message.
+    pub fn originator_diff(&self) -> Option<Vec<HeaderDiff<'_>>> {
+        let original =
+            self.attachments
+                .iter()
+                .find_map(|&part_id| match &self.parts.get(part_id)?.body {
+                    PartType::Message(message) => Some(message),
+                    _ => None,
+                })?;
+
+        Some(originator_diff(
+            &self.parts.first()?.headers,
+            &original.parts.first()?.headers,
+        ))
+    }
+}
+
+/// Compares the From, Date, Message-ID and Subject header fields of an
+/// outer message with those of the original message it contains, returning
+/// the fields that differ or are missing from only one of them.
+///
+/// Addresses are compared in their normalized form, dates by their
+/// timestamp and subjects without their reply and forward prefixes, so
+/// that "Fwd: Invoice" matches "Invoice".
+pub fn originator_diff<'y>(
+    outer: &'y [Header<'y>],
+    inner: &'y [Header<'y>],
+) -> Vec<HeaderDiff<'y>> {
+    ORIGINATOR_HEADERS
+        .iter()
+        .filter_map(|header| {
+            let outer = outer.rfc(header);
+            let inner = inner.rfc(header);
+            let is_same = match (outer, inner) {
+                (Some(outer), Some(inner)) => is_same_value(header, outer, inner),
+                (None, None) => true,
+                _ => false,
+            };
+
+            (!is_same).then_some(HeaderDiff {
+                header: *header,
+                outer,
+                inner,
+            })
+        })
+        .collect()
+}
+
+fn is_same_value(header: &RfcHeader, outer: &HeaderValue<'_>, inner: &HeaderValue<'_>) -> bool {
+    match header {
+        RfcHeader::From => match (outer.as_address(), inner.as_address()) {
+            (Some(outer), Some(inner)) => outer
+                .iter()
+                .map(|addr| addr.normalized_address())
+                .eq(inner.iter().map(|addr| addr.normalized_address())),
+            _ => outer == inner,
+        },
+        RfcHeader::Date => match (outer.as_datetime_ref(), inner.as_datetime_ref()) {
+            (Some(outer), Some(inner)) => outer.to_timestamp() == inner.to_timestamp(),
+            _ => outer == inner,
+        },
+        RfcHeader::Subject => match (outer.as_text_ref(), inner.as_text_ref()) {
+            (Some(outer), Some(inner)) => {
+                let prefixes = SubjectPrefixes::default();
+                prefixes.normalize_subject(outer) == prefixes.normalize_subject(inner)
+            }
+            _ => outer == inner,
+        },
+        _ => outer == inner,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parsers::header::parse_header_block, Message, RfcHeader};
+
+    use super::originator_diff;
+
+    #[test]
+    fn compare_originators() {
+        let message = Message::parse(
+            concat!(
+                "From: Bank <alerts@bank.example>\r\n",
+                "Date: Tue, 1 Jul 2003 10:52:37 +0200\r\n",
+                "Message-ID: <outer@bank.example>\r\n",
+                "Subject: Fwd: Verify your account\r\n",
+                "Content-Type: multipart/mixed; boundary=\"a\"\r\n",
+                "\r\n",
+                "--a\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "See below\r\n",
+                "--a\r\n",
+                "Content-Type: message/rfc822\r\n",
+                "\r\n",
+                "From: Bank <ALERTS@Bank.Example>\r\n",
+                "Date: Tue, 1 Jul 2003 08:52:37 +0000\r\n",
+                "Message-ID: <inner@other.example>\r\n",
+                "Subject: Verify  your account\r\n",
+                "\r\n",
+                "Click here\r\n",
+                "--a--\r\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let diff = message.originator_diff().unwrap();
+        assert_eq!(
+            diff.iter().map(|diff| diff.header).collect::<Vec<_>>(),
+            vec![RfcHeader::From, RfcHeader::MessageId]
+        );
+        assert_eq!(
+            diff[1].outer.unwrap().as_text_ref(),
+            Some("outer@bank.example")
+        );
+        assert_eq!(
+            diff[1].inner.unwrap().as_text_ref(),
+            Some("inner@other.example")
+        );
+
+        let outer = parse_header_block(b"From: john@example.com\r\nSubject: Undelivered\r\n\r\n");
+        let inner = parse_header_block(b"From: john@example.com\r\nSubject: Hello\r\n\r\n");
+        let diff = originator_diff(&outer, &inner);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].header, RfcHeader::Subject);
+
+        assert_eq!(
+            Message::parse(b"From: john@example.com\r\n\r\nHi")
+                .unwrap()
+                .originator_diff(),
+            None
+        );
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{Domain, Message, PartitionKey, PartitionSource};
+
+impl<'x> Message<'x> {
+    /// Derives a key for distributing the processing of messages across
+    /// workers from the first of `sources` present in the message, so that
+    /// all the messages from the same sender domain, mailing list or
+    /// conversation are handled by the same worker. Returns `None` when
+    /// none of the sources is present.
+    ///
+    /// Conversations are identified by the message id of their first
+    /// message only, replies missing the References and In-Reply-To header
+    /// fields start a new conversation.
+    pub fn partition_key(&self, sources: &[PartitionSource]) -> Option<PartitionKey> {
+        sources.iter().find_map(|&source| {
+            let value = match source {
+                PartitionSource::SenderDomain => match self.from_address()?.first()?.domain()? {
+                    Domain::Name(name) => name.trim_end_matches('.').to_ascii_lowercase(),
+                    Domain::Ipv4(addr) => addr.to_string(),
+                    Domain::Ipv6(addr) => addr.to_string(),
+                    Domain::Literal(literal) => literal.to_ascii_lowercase(),
+                },
+                PartitionSource::ListId => self
+                    .list_id()
+                    .as_address()?
+                    .first()?
+                    .address
+                    .as_deref()?
+                    .trim()
+                    .trim_end_matches('.')
+                    .to_ascii_lowercase(),
+                PartitionSource::Thread => self
+                    .reference_ids()
+                    .into_iter()
+                    .chain(self.in_reply_to_ids())
+                    .chain(self.message_id())
+                    .next()?
+                    .to_string(),
+            };
+
+            Some(PartitionKey { source, value }).filter(|key| !key.value.is_empty())
+        })
+    }
+}
+
+impl PartitionKey {
+    /// Returns the partition of the key out of `partitions`, from zero to
+    /// `partitions - 1`. The key is hashed with 64-bit FNV-1a, which unlike
+    /// the hashers of the standard library gives the same partition across
+    /// processes, platforms and releases.
+    pub fn partition(&self, partitions: u32) -> u32 {
+        let source: &[u8] = match self.source {
+            PartitionSource::SenderDomain => b"domain",
+            PartitionSource::ListId => b"list",
+            PartitionSource::Thread => b"thread",
+        };
+        let hash = source
+            .iter()
+            .chain(b":")
+            .chain(self.value.as_bytes())
+            .fold(0xcbf29ce484222325u64, |hash, &ch| {
+                (hash ^ ch as u64).wrapping_mul(0x100000001b3)
+            });
+
+        (hash % partitions.max(1) as u64) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Message, PartitionKey, PartitionSource};
+
+    #[test]
+    fn partition_keys() {
+        let message = Message::parse(
+            concat!(
+                "From: John <John@Mail.EXAMPLE.com.>\r\n",
+                "List-ID: Developers <DEV.lists.example.org>\r\n",
+                "Message-ID: <3@example.com>\r\n",
+                "In-Reply-To: <2@example.com>\r\n",
+                "References: <1@example.com> <2@example.com>\r\n",
+                "\r\n",
+                "Hi\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        for (source, expected) in [
+            (PartitionSource::SenderDomain, "mail.example.com"),
+            (PartitionSource::ListId, "dev.lists.example.org"),
+            (PartitionSource::Thread, "1@example.com"),
+        ] {
+            assert_eq!(
+                message.partition_key(&[source]),
+                Some(PartitionKey {
+                    source,
+                    value: expected.to_string()
+                })
+            );
+        }
+
+        let message =
+            Message::parse(b"From: [192.168.1.1]\r\nMessage-ID: <1@example.com>\r\n\r\nHi\r\n")
+                .unwrap();
+        assert_eq!(
+            message.partition_key(&[PartitionSource::ListId, PartitionSource::Thread]),
+            Some(PartitionKey {
+                source: PartitionSource::Thread,
+                value: "1@example.com".to_string()
+            })
+        );
+        assert_eq!(message.partition_key(&[PartitionSource::ListId]), None);
+        assert_eq!(message.partition_key(&[]), None);
+
+        for (source, value, expected) in [
+            (
+                PartitionSource::SenderDomain,
+                "example.com",
+                [0, 2, 178, 2724404178],
+            ),
+            (
+                PartitionSource::SenderDomain,
+                "mail.example.com",
+                [0, 9, 321, 518284286],
+            ),
+            (
+                PartitionSource::ListId,
+                "dev.lists.example.org",
+                [0, 3, 435, 4059903895],
+            ),
+            (
+                PartitionSource::Thread,
+                "1@example.com",
+                [0, 13, 917, 3496592002],
+            ),
+        ] {
+            let key = PartitionKey {
+                source,
+                value: value.to_string(),
+            };
+            assert_eq!(key.partition(0), 0);
+            assert_eq!(
+                [1, 16, 1000, u32::MAX].map(|partitions| key.partition(partitions)),
+                expected,
+                "{value}"
+            );
+        }
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{Message, MessagePart, MessagePartId, MimeHeaders, PgpInfo, PgpType};
+
+impl<'x> Message<'x> {
+    /// Returns the PGP/MIME structure of the message, if any. The first
+    /// multipart/signed or multipart/encrypted part found is returned,
+    /// starting from the root part.
+    pub fn pgp(&self) -> Option<PgpInfo<'_>> {
+        self.parts
+            .iter()
+            .enumerate()
+            .find_map(|(part_id, part)| self.pgp_part(part_id, part))
+    }
+
+    fn pgp_part<'y>(&'y self, part_id: usize, part: &'y MessagePart<'_>) -> Option<PgpInfo<'y>> {
+        let content_type = part.content_type()?;
+        if content_type.ctype() != "multipart" {
+            return None;
+        }
+        let protocol = content_type.attribute("protocol")?;
+        let sub_parts = part.sub_parts()?;
+
+        match content_type.subtype()? {
+            "signed" if protocol.eq_ignore_ascii_case("application/pgp-signature") => {
+                let content_part_id = *sub_parts.first()?;
+                let signature_part_id = self.find_sub_part(&sub_parts[1..], "pgp-signature")?;
+
+                Some(PgpInfo {
+                    pgp_type: PgpType::Signed,
+                    part_id,
+                    content_part_id,
+                    signature_part_id: signature_part_id.into(),
+                    data: self.parts[signature_part_id].contents(),
+                    micalg: content_type.attribute("micalg"),
+                })
+            }
+            "encrypted" if protocol.eq_ignore_ascii_case("application/pgp-encrypted") => {
+                let control_part_id = self.find_sub_part(sub_parts, "pgp-encrypted")?;
+                let content_part_id = sub_parts
+                    .iter()
+                    .copied()
+                    .skip_while(|&part_id| part_id != control_part_id)
+                    .nth(1)?;
+
+                Some(PgpInfo {
+                    pgp_type: PgpType::Encrypted,
+                    part_id,
+                    content_part_id,
+                    signature_part_id: None,
+                    data: self.parts[content_part_id].contents(),
+                    micalg: None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn find_sub_part(&self, sub_parts: &[MessagePartId], subtype: &str) -> Option<MessagePartId> {
+        sub_parts.iter().copied().find(|&part_id| {
+            self.parts
+                .get(part_id)
+                .and_then(|part| part.content_type())
+                .is_some_and(|ct| ct.ctype() == "application" && ct.subtype() == Some(subtype))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Message, PgpType};
+
+    #[test]
+    fn parse_pgp() {
+        let message = Message::parse(
+            concat!(
+                "From: jane@example.com\r\n",
+                "Subject: Signed message\r\n",
+                "Content-Type: multipart/signed; micalg=pgp-sha256;\r\n",
+                "    protocol=\"application/pgp-signature\"; boundary=\"signed\"\r\n",
+                "\r\n",
+                "--signed\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Hello world\r\n",
+                "--signed\r\n",
+                "Content-Type: application/pgp-signature; name=\"signature.asc\"\r\n",
+                "\r\n",
+                "-----BEGIN PGP SIGNATURE-----\r\n",
+                "\r\n",
+                "iQEzBAEBCAAdFiEE\r\n",
+                "-----END PGP SIGNATURE-----\r\n",
+                "--signed--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let pgp = message.pgp().unwrap();
+        assert_eq!(pgp.pgp_type, PgpType::Signed);
+        assert_eq!(pgp.part_id, 0);
+        assert_eq!(pgp.content_part_id, 1);
+        assert_eq!(pgp.signature_part_id, Some(2));
+        assert_eq!(pgp.micalg, Some("pgp-sha256"));
+        assert_eq!(
+            pgp.data,
+            concat!(
+                "-----BEGIN PGP SIGNATURE-----\r\n",
+                "\r\n",
+                "iQEzBAEBCAAdFiEE\r\n",
+                "-----END PGP SIGNATURE-----"
+            )
+            .as_bytes()
+        );
+        assert_eq!(message.smime(), None);
+
+        let message = Message::parse(
+            concat!(
+                "From: jane@example.com\r\n",
+                "Subject: Encrypted message\r\n",
+                "Content-Type: multipart/encrypted; boundary=\"encrypted\";\r\n",
+                "    protocol=\"application/pgp-encrypted\"\r\n",
+                "\r\n",
+                "--encrypted\r\n",
+                "Content-Type: application/pgp-encrypted\r\n",
+                "\r\n",
+                "Version: 1\r\n",
+                "\r\n",
+                "--encrypted\r\n",
+                "Content-Type: application/octet-stream\r\n",
+                "\r\n",
+                "-----BEGIN PGP MESSAGE-----\r\n",
+                "\r\n",
+                "hQEMA5fT\r\n",
+                "-----END PGP MESSAGE-----\r\n",
+                "\r\n",
+                "--encrypted--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let pgp = message.pgp().unwrap();
+        assert_eq!(pgp.pgp_type, PgpType::Encrypted);
+        assert_eq!(pgp.part_id, 0);
+        assert_eq!(pgp.content_part_id, 2);
+        assert_eq!(pgp.signature_part_id, None);
+        assert_eq!(pgp.micalg, None);
+        assert!(pgp.data.starts_with(b"-----BEGIN PGP MESSAGE-----\r\n"));
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{
+    AttachmentLimits, AttachmentReport, AttachmentViolation, Message, MessagePart, MessagePartId,
+    MimeHeaders, PartType,
+};
+
+impl Message<'_> {
+    /// Checks the attachments of the message against the provided limits,
+    /// returning their count, total size and the rules they break.
+    ///
+    /// Sizes are those of the decoded contents, see [`MessagePart::size`],
+    /// and a nested message counts as a single attachment. The file name
+    /// extensions of the attachments of nested messages are checked too,
+    /// while attachments without a file name are never rejected by their
+    /// extension.
+    pub fn attachment_policy_report(&self, limits: &AttachmentLimits) -> AttachmentReport {
+        let mut report = AttachmentReport {
+            count: self.attachments.len(),
+            ..Default::default()
+        };
+
+        if limits.max_count.is_some_and(|max| report.count > max) {
+            report.violations.push(AttachmentViolation::TooMany {
+                count: report.count,
+            });
+        }
+
+        for &part_id in &self.attachments {
+            let part = if let Some(part) = self.parts.get(part_id) {
+                part
+            } else {
+                continue;
+            };
+
+            let size = part.size();
+            report.total_size += size;
+            if limits.max_size.is_some_and(|max| size > max) {
+                report
+                    .violations
+                    .push(AttachmentViolation::TooLarge { part_id, size });
+            }
+
+            if !limits.blocked_extensions.is_empty() || !limits.allowed_extensions.is_empty() {
+                check_extension(part_id, part, limits, &mut report.violations);
+            }
+        }
+
+        if limits
+            .max_total_size
+            .is_some_and(|max| report.total_size > max)
+        {
+            report.violations.push(AttachmentViolation::TotalTooLarge {
+                size: report.total_size,
+            });
+        }
+
+        report
+    }
+}
+
+fn check_extension(
+    part_id: MessagePartId,
+    part: &MessagePart<'_>,
+    limits: &AttachmentLimits,
+    violations: &mut Vec<AttachmentViolation>,
+) {
+    if let PartType::Message(nested) = &part.body {
+        for nested_part in nested
+            .attachments
+            .iter()
+            .filter_map(|&nested_part_id| nested.parts.get(nested_part_id))
+        {
+            check_extension(part_id, nested_part, limits, violations);
+        }
+        return;
+    }
+
+    let file_name = if let Some(file_name) = part.attachment_file_name() {
+        file_name
+    } else {
+        return;
+    };
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension)
+        .unwrap_or_default();
+    let matches = |extensions: &[String]| {
+        extensions.iter().any(|allowed| {
+            allowed
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(extension)
+        })
+    };
+
+    if matches(&limits.blocked_extensions)
+        || (!limits.allowed_extensions.is_empty() && !matches(&limits.allowed_extensions))
+    {
+        violations.push(AttachmentViolation::Extension {
+            part_id,
+            file_name: file_name.into_owned(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AttachmentLimits, AttachmentViolation, Message};
+
+    #[test]
+    fn attachment_policy() {
+        let message = Message::parse(
+            concat!(
+                "Subject: attachments\r\n",
+                "Content-Type: multipart/mixed; boundary=\"a\"\r\n",
+                "\r\n",
+                "--a\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Hello\r\n",
+                "--a\r\n",
+                "Content-Type: application/pdf; name=\"report.PDF\"\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "JVBERi0xLjcK\r\n",
+                "--a\r\n",
+                "Content-Type: application/octet-stream\r\n",
+                "Content-Disposition: attachment; filename=\"invoice.pdf.exe.\"\r\n",
+                "\r\n",
+                "MZ\r\n",
+                "--a\r\n",
+                "Content-Type: message/rfc822\r\n",
+                "\r\n",
+                "Subject: forwarded\r\n",
+                "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+                "\r\n",
+                "--b\r\n",
+                "Content-Type: application/zip; name=\"archive.zip\"\r\n",
+                "\r\n",
+                "PK\r\n",
+                "--b--\r\n",
+                "--a--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let report = message.attachment_policy_report(&AttachmentLimits::new());
+        assert_eq!(report.count, 3);
+        assert_eq!(report.total_size, 9 + 2 + message.parts[4].encoded_size());
+        assert!(report.violations.is_empty());
+
+        let report = message.attachment_policy_report(
+            &AttachmentLimits::new()
+                .max_count(2)
+                .max_size(8)
+                .max_total_size(10)
+                .block_extension("EXE"),
+        );
+        assert_eq!(
+            report.violations,
+            vec![
+                AttachmentViolation::TooMany { count: 3 },
+                AttachmentViolation::TooLarge {
+                    part_id: 2,
+                    size: 9
+                },
+                AttachmentViolation::Extension {
+                    part_id: 3,
+                    file_name: "invoice.pdf.exe".into()
+                },
+                AttachmentViolation::TooLarge {
+                    part_id: 4,
+                    size: message.parts[4].size()
+                },
+                AttachmentViolation::TotalTooLarge {
+                    size: report.total_size
+                }
+            ]
+        );
+
+        // Attachments of nested messages are reported as the nested message
+        let report = message.attachment_policy_report(
+            &AttachmentLimits::new()
+                .allow_extension("pdf")
+                .allow_extension(".exe"),
+        );
+        assert_eq!(
+            report.violations,
+            vec![AttachmentViolation::Extension {
+                part_id: 4,
+                file_name: "archive.zip".into()
+            }]
+        );
+    }
+}
@@ -24,9 +24,16 @@ pub fn preview_text<'x>(text: Cow<'_, str>, mut max_len: usize) -> Cow<'x, str>
             max_len -= 3;
         }
         let mut result = String::with_capacity(max_len);
+        let mut cluster = ClusterBoundary::default();
         for ch in text.chars() {
+            let is_boundary = cluster.is_boundary(ch);
             if ch.len_utf8() + result.len() > max_len {
+                if !is_boundary {
+                    result.truncate(cluster.start);
+                }
                 break;
+            } else if is_boundary {
+                cluster.start = result.len();
             }
             result.push(ch);
         }
@@ -54,6 +61,8 @@ pub fn truncate_html<'x>(html: Cow<'_, str>, mut max_len: usize) -> Cow<'x, str>
         let mut in_tag = false;
         let mut in_comment = false;
         let mut last_tag_end_pos = 0;
+        let mut entity_start = None;
+        let mut cluster = ClusterBoundary::default();
         for (pos, ch) in html.char_indices() {
             let mut set_last_tag = 0;
             match ch {
@@ -78,12 +87,16 @@ pub fn truncate_html<'x>(html: Cow<'_, str>, mut max_len: usize) -> Cow<'x, str>
                 }
                 _ => (),
             }
+            if !in_tag && cluster.is_boundary(ch) && entity_start.is_none() {
+                cluster.start = pos;
+            }
             if ch.len_utf8() + pos > max_len {
                 result.push_str(
                     &html[0..if (in_tag || set_last_tag > 0) && last_tag_end_pos > 0 {
                         last_tag_end_pos
                     } else {
-                        pos
+                        // Do not split character references or clusters
+                        entity_start.unwrap_or(cluster.start).max(last_tag_end_pos)
                     }],
                 );
                 if add_dots {
@@ -93,6 +106,11 @@ pub fn truncate_html<'x>(html: Cow<'_, str>, mut max_len: usize) -> Cow<'x, str>
             } else if set_last_tag > 0 {
                 last_tag_end_pos = set_last_tag;
             }
+            entity_start = match ch {
+                '&' if !in_tag => Some(pos),
+                _ if ch.is_ascii_alphanumeric() || ch == '#' => entity_start,
+                _ => None,
+            };
         }
         result.into()
     } else {
@@ -100,6 +118,72 @@ pub fn truncate_html<'x>(html: Cow<'_, str>, mut max_len: usize) -> Cow<'x, str>
     }
 }
 
+// Finds the boundaries of grapheme clusters, approximating the rules of
+// UAX #29 for combining marks, variation selectors, emoji modifiers, emoji
+// ZWJ sequences and flags.
+#[derive(Default)]
+struct ClusterBoundary {
+    start: usize,
+    last_ch: Option<char>,
+    regional_indicators: usize,
+}
+
+impl ClusterBoundary {
+    // Returns true if a cluster starts with this character.
+    fn is_boundary(&mut self, ch: char) -> bool {
+        let is_regional_indicator = ('\u{1f1e6}'..='\u{1f1ff}').contains(&ch);
+        let is_boundary = match self.last_ch {
+            None => true,
+            Some('\r') => ch != '\n',
+            Some('\u{200d}') => !is_pictographic(ch) && !is_extend(ch),
+            Some(_) if is_extend(ch) => false,
+            Some(_) if is_regional_indicator => self.regional_indicators % 2 == 0,
+            Some(_) => true,
+        };
+        self.regional_indicators = if is_regional_indicator {
+            self.regional_indicators + 1
+        } else {
+            0
+        };
+        self.last_ch = Some(ch);
+        is_boundary
+    }
+}
+
+fn is_extend(ch: char) -> bool {
+    matches!(ch,
+        '\u{0300}'..='\u{036f}'
+        | '\u{0483}'..='\u{0489}'
+        | '\u{0591}'..='\u{05bd}'
+        | '\u{064b}'..='\u{065f}'
+        | '\u{0900}'..='\u{0903}'
+        | '\u{093a}'..='\u{094f}'
+        | '\u{0e31}'
+        | '\u{0e34}'..='\u{0e3a}'
+        | '\u{0e47}'..='\u{0e4e}'
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{200c}'..='\u{200d}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{3099}'..='\u{309a}'
+        | '\u{fe00}'..='\u{fe0f}'
+        | '\u{fe20}'..='\u{fe2f}'
+        | '\u{1f3fb}'..='\u{1f3ff}'
+        | '\u{e0020}'..='\u{e007f}'
+        | '\u{e0100}'..='\u{e01ef}'
+    )
+}
+
+fn is_pictographic(ch: char) -> bool {
+    matches!(ch,
+        '\u{2194}'..='\u{21aa}'
+        | '\u{2300}'..='\u{23ff}'
+        | '\u{2600}'..='\u{27bf}'
+        | '\u{2b00}'..='\u{2bff}'
+        | '\u{1f000}'..='\u{1faff}'
+    )
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -182,5 +266,48 @@ mod tests {
         ] {
             assert_eq!(super::truncate_html(html.into(), 25), expected_result);
         }
+
+        // Character references and grapheme clusters are not split
+        for (html, expected_result) in [
+            ("<p>fish &amp; chips and more</p>", "<p>fish ..."),
+            ("<p>nice \u{1f44d}\u{1f3fd}!!</p>", "<p>nice ..."),
+        ] {
+            assert_eq!(super::truncate_html(html.into(), 15), expected_result);
+        }
+    }
+
+    #[test]
+    fn text_preview_clusters() {
+        for (text, max_len, expected_result) in [
+            // Emoji with a skin tone modifier
+            ("Thanks \u{1f44d}\u{1f3fd} again", 14, "Thanks ..."),
+            (
+                "Thanks \u{1f44d}\u{1f3fd} again",
+                18,
+                "Thanks \u{1f44d}\u{1f3fd}...",
+            ),
+            // Family emoji ZWJ sequence
+            (
+                "Hi \u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467} all",
+                20,
+                "Hi ...",
+            ),
+            // Flags are pairs of regional indicators
+            (
+                "Go \u{1f1ea}\u{1f1f8}\u{1f1eb}\u{1f1f7}!",
+                17,
+                "Go \u{1f1ea}\u{1f1f8}...",
+            ),
+            // Combining accents
+            ("Cafe\u{301} au lait", 8, "Caf..."),
+            ("Cafe\u{301} au lait", 9, "Cafe\u{301}..."),
+        ] {
+            assert_eq!(
+                super::preview_text(text.into(), max_len),
+                expected_result,
+                "Failed for {:?}",
+                text
+            );
+        }
     }
 }
@@ -0,0 +1,485 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::fmt::Write;
+
+use crate::{
+    decoders::html::{find_ignore_case, parse_html_tag, rewrite_html_tags, sanitize_html},
+    encoders::base64::base64_encode,
+    Addr, GetHeader, HeaderValue, Message, MessagePart, MimeHeaders, RfcHeader,
+};
+
+// Header fields displayed, in order.
+static HEADERS: &[(RfcHeader, &str)] = &[
+    (RfcHeader::From, "From"),
+    (RfcHeader::Sender, "Sender"),
+    (RfcHeader::ReplyTo, "Reply-To"),
+    (RfcHeader::To, "To"),
+    (RfcHeader::Cc, "Cc"),
+    (RfcHeader::Date, "Date"),
+    (RfcHeader::Subject, "Subject"),
+];
+
+// Elements of the body document that are dropped when embedding it, along
+// with their contents for those that are never displayed.
+static DOCUMENT_TAGS: &[&str] = &["html", "head", "body", "meta", "base"];
+static DOCUMENT_TAGS_WITH_CONTENTS: &[&str] = &["title"];
+
+impl<'x> Message<'x> {
+    /// Renders the message as a standalone HTML document containing a table
+    /// with its main header fields, its HTML bodies, or its text bodies
+    /// converted to HTML, with active content removed and isolated in
+    /// sandboxed frames, and a list of its attachments. Attachments and the images referenced by the bodies
+    /// are embedded as `data:` URIs.
+    pub fn to_html_document(&'x self) -> String {
+        self.to_html_document_resolved(data_uri)
+    }
+
+    /// Renders the message as a standalone HTML document, see
+    /// [`Message::to_html_document`], linking to attachments and images
+    /// using the URL returned by `resolve`, such as the location where the
+    /// part is served from. Attachments are listed without a link when it
+    /// returns `None`.
+    pub fn to_html_document_resolved(
+        &'x self,
+        mut resolve: impl FnMut(&'x MessagePart<'x>) -> Option<String>,
+    ) -> String {
+        let mut html = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">");
+        let _ = write!(
+            html,
+            "<title>{}</title></head><body>\n<table class=\"headers\">\n",
+            escape_html(self.subject().unwrap_or_default())
+        );
+        for (name, value) in self.display_headers() {
+            let _ = writeln!(
+                html,
+                "<tr><th>{}:</th><td>{}</td></tr>",
+                name,
+                escape_html(&value)
+            );
+        }
+        html.push_str("</table>\n");
+
+        for (pos, part_id) in self.html_body.iter().enumerate() {
+            let part = &self.parts[*part_id];
+            let mut body = String::new();
+            if let Some(html) = self.body_html_resolved(pos, &mut resolve) {
+                body = sanitize_html(&html);
+            } else if part.content_type().is_some_and(|ct| ct.ctype() == "image") {
+                if let Some(url) = resolve(part) {
+                    body = format!("<img src=\"{}\">", escape_html(&url));
+                }
+            }
+            // Bodies are isolated in a sandboxed frame, so that their style
+            // sheets and refresh directives cannot affect the document.
+            let _ = writeln!(
+                html,
+                "<iframe class=\"body\" sandbox srcdoc=\"{}\"></iframe>",
+                escape_html(&body)
+            );
+        }
+
+        if !self.attachments.is_empty() {
+            html.push_str("<ul class=\"attachments\">\n");
+            for (pos, part_id) in self.attachments.iter().enumerate() {
+                let part = &self.parts[*part_id];
+                let name = escape_html(&attachment_display_name(part, pos));
+                html.push_str("<li>");
+                match resolve(part) {
+                    Some(url) => {
+                        let _ = write!(
+                            html,
+                            "<a href=\"{}\" download=\"{}\">{}</a>",
+                            escape_html(&url),
+                            name,
+                            name
+                        );
+                    }
+                    None => html.push_str(&name),
+                }
+                let _ = writeln!(
+                    html,
+                    " ({}, {} bytes)</li>",
+                    escape_html(&media_type(part)),
+                    part.size()
+                );
+            }
+            html.push_str("</ul>\n");
+        }
+
+        html.push_str("</body></html>\n");
+        html
+    }
+
+    /// Renders the message as printable plain text containing its main
+    /// header fields, its text bodies, or its HTML bodies converted to text,
+    /// and an inventory of its attachments. Line endings are normalized to
+    /// LF and the output only depends on the contents of the message, making
+    /// it suitable for generating PDF documents or discovery exports.
+    pub fn to_printable_text(&'x self) -> String {
+        let mut text = String::new();
+        for (name, value) in self.display_headers() {
+            let _ = writeln!(text, "{}: {}", name, value);
+        }
+
+        for pos in 0..self.text_body.len() {
+            if let Some(body) = self.body_text(pos) {
+                text.push('\n');
+                text.push_str(body.replace("\r\n", "\n").trim_end());
+                text.push('\n');
+            }
+        }
+
+        if !self.attachments.is_empty() {
+            text.push_str("\nAttachments:\n");
+            for (pos, part_id) in self.attachments.iter().enumerate() {
+                let part = &self.parts[*part_id];
+                let _ = writeln!(
+                    text,
+                    "  {}. {} ({}, {} bytes)",
+                    pos + 1,
+                    attachment_display_name(part, pos),
+                    media_type(part),
+                    part.size()
+                );
+            }
+        }
+
+        text
+    }
+
+    // Returns the main header fields as (name, value) pairs for display.
+    fn display_headers(&self) -> Vec<(&'static str, String)> {
+        let headers = self.headers();
+        HEADERS
+            .iter()
+            .filter_map(|(header, name)| {
+                let value = match headers.rfc(header)? {
+                    HeaderValue::Text(text) => text.to_string(),
+                    HeaderValue::DateTime(date) => date.to_rfc822(),
+                    value => display_addresses(value)?,
+                };
+                Some((*name, value))
+            })
+            .collect()
+    }
+}
+
+// Renders an address header value as shown to users, without the quoting
+// and encoding required in a message.
+fn display_addresses(value: &HeaderValue<'_>) -> Option<String> {
+    let mut result = String::new();
+    for (pos, group) in value
+        .as_address()?
+        .as_group()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        if pos > 0 {
+            result.push_str(", ");
+        }
+        match &group.name {
+            Some(name) => {
+                let _ = write!(result, "{}: ", name);
+                write_addresses(&mut result, &group.addresses);
+                result.push(';');
+            }
+            None => write_addresses(&mut result, &group.addresses),
+        }
+    }
+    if let Some(list) = value.as_address()?.as_list() {
+        write_addresses(&mut result, list);
+    }
+    Some(result)
+}
+
+fn write_addresses(result: &mut String, addresses: &[Addr<'_>]) {
+    for (pos, addr) in addresses.iter().enumerate() {
+        if pos > 0 {
+            result.push_str(", ");
+        }
+        match (&addr.name, &addr.address) {
+            (Some(name), Some(address)) => {
+                let _ = write!(result, "{} <{}>", name, address);
+            }
+            (Some(value), None) | (None, Some(value)) => result.push_str(value),
+            (None, None) => (),
+        }
+    }
+}
+
+// Returns the file name of an attachment, the subject of a nested message
+// or a name derived from its position.
+fn attachment_display_name(part: &MessagePart<'_>, pos: usize) -> String {
+    part.attachment_file_name()
+        .map(|name| name.into_owned())
+        .or_else(|| {
+            part.message()
+                .filter(|message| !message.parts.is_empty())
+                .and_then(|message| message.subject())
+                .map(String::from)
+        })
+        .unwrap_or_else(|| format!("attachment-{}", pos + 1))
+}
+
+fn media_type(part: &MessagePart<'_>) -> String {
+    match part.content_type() {
+        Some(content_type) => match content_type.subtype() {
+            Some(subtype) => format!("{}/{}", content_type.ctype(), subtype),
+            None => content_type.ctype().to_string(),
+        },
+        None if part.is_message() => "message/rfc822".to_string(),
+        None => "application/octet-stream".to_string(),
+    }
+}
+
+// Returns a `data:` URI holding the contents of a part.
+pub(crate) fn data_uri(part: &MessagePart<'_>) -> Option<String> {
+    let mut uri = String::from("data:");
+    match part.content_type() {
+        Some(content_type) => {
+            uri.push_str(content_type.ctype());
+            if let Some(subtype) = content_type.subtype() {
+                uri.push('/');
+                uri.push_str(subtype);
+            }
+        }
+        None => uri.push_str("application/octet-stream"),
+    }
+    uri.push_str(";base64,");
+    uri.push_str(std::str::from_utf8(&base64_encode(part.contents())).ok()?);
+    Some(uri)
+}
+
+// Removes the document structure of an HTML body so it can be embedded
+// within another document.
+pub(crate) fn strip_document(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let input = rewrite_html_tags(input, |tag, result| {
+        if DOCUMENT_TAGS_WITH_CONTENTS.contains(&tag.name.as_str())
+            && !tag.is_close
+            && !tag.is_self_closing
+        {
+            find_ignore_case(bytes, tag.end, format!("</{}", tag.name).as_bytes())
+                .and_then(|end| parse_html_tag(bytes, end))
+                .map_or(bytes.len(), |tag| tag.end)
+        } else {
+            if !DOCUMENT_TAGS.contains(&tag.name.as_str()) {
+                result.push_str(&input[tag.start..tag.end.min(bytes.len())]);
+            }
+            tag.end
+        }
+    });
+    match find_ignore_case(input.as_bytes(), 0, b"<!doctype") {
+        Some(start) if input[..start].trim().is_empty() => input[start..]
+            .find('>')
+            .map_or(String::new(), |end| input[start + end + 1..].to_string()),
+        _ => input,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Message;
+
+    #[test]
+    fn render_html_document() {
+        let message = Message::parse(
+            concat!(
+                "From: \"Art Vandelay\" <art@vandelay.com>\n",
+                "To: Colleagues: james@vandelay.com;, jane@example.com\n",
+                "Subject: Imports & <exports>\n",
+                "Date: Sat, 20 Nov 2021 14:22:01 -0800\n",
+                "Content-Type: multipart/mixed; boundary=\"festivus\"\n",
+                "\n",
+                "--festivus\n",
+                "Content-Type: text/html\n",
+                "\n",
+                "<!DOCTYPE html><html><head><title>Hi</title></head>",
+                "<body onload=\"evil()\"><p>Hello <img src=\"cid:pixel\"></p>",
+                "<script>alert(1)</script></body></html>\n",
+                "--festivus\n",
+                "Content-Type: image/gif\n",
+                "Content-ID: <pixel>\n",
+                "Content-Disposition: attachment; filename=\"pixel.gif\"\n",
+                "Content-Transfer-Encoding: base64\n",
+                "\n",
+                "R0lGODlhAQABAAAAACw=\n",
+                "--festivus--\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.to_html_document(),
+            concat!(
+                "<!DOCTYPE html>\n",
+                "<html><head><meta charset=\"utf-8\">",
+                "<title>Imports &amp; &lt;exports&gt;</title></head><body>\n",
+                "<table class=\"headers\">\n",
+                "<tr><th>From:</th><td>Art Vandelay &lt;art@vandelay.com&gt;</td></tr>\n",
+                "<tr><th>To:</th><td>Colleagues: james@vandelay.com;, ",
+                "jane@example.com</td></tr>\n",
+                "<tr><th>Date:</th><td>Sat, 20 Nov 2021 14:22:01 -0800</td></tr>\n",
+                "<tr><th>Subject:</th><td>Imports &amp; &lt;exports&gt;</td></tr>\n",
+                "</table>\n",
+                "<iframe class=\"body\" sandbox srcdoc=\"&lt;!DOCTYPE html&gt;&lt;html&gt;",
+                "&lt;head&gt;&lt;title&gt;Hi&lt;/title&gt;&lt;/head&gt;&lt;body&gt;",
+                "&lt;p&gt;Hello &lt;img src=&quot;data:image/gif;base64,",
+                "R0lGODlhAQABAAAAACw=&quot;&gt;&lt;/p&gt;&lt;/body&gt;&lt;/html&gt;",
+                "\"></iframe>\n",
+                "<ul class=\"attachments\">\n",
+                "<li><a href=\"data:image/gif;base64,R0lGODlhAQABAAAAACw=\" ",
+                "download=\"pixel.gif\">pixel.gif</a> (image/gif, 14 bytes)</li>\n",
+                "</ul>\n",
+                "</body></html>\n"
+            )
+        );
+
+        assert_eq!(
+            message.to_html_document_resolved(|_| None),
+            concat!(
+                "<!DOCTYPE html>\n",
+                "<html><head><meta charset=\"utf-8\">",
+                "<title>Imports &amp; &lt;exports&gt;</title></head><body>\n",
+                "<table class=\"headers\">\n",
+                "<tr><th>From:</th><td>Art Vandelay &lt;art@vandelay.com&gt;</td></tr>\n",
+                "<tr><th>To:</th><td>Colleagues: james@vandelay.com;, ",
+                "jane@example.com</td></tr>\n",
+                "<tr><th>Date:</th><td>Sat, 20 Nov 2021 14:22:01 -0800</td></tr>\n",
+                "<tr><th>Subject:</th><td>Imports &amp; &lt;exports&gt;</td></tr>\n",
+                "</table>\n",
+                "<iframe class=\"body\" sandbox srcdoc=\"&lt;!DOCTYPE html&gt;&lt;html&gt;",
+                "&lt;head&gt;&lt;title&gt;Hi&lt;/title&gt;&lt;/head&gt;&lt;body&gt;",
+                "&lt;p&gt;Hello &lt;img src=&quot;cid:pixel&quot;&gt;&lt;/p&gt;",
+                "&lt;/body&gt;&lt;/html&gt;\"></iframe>\n",
+                "<ul class=\"attachments\">\n",
+                "<li>pixel.gif (image/gif, 14 bytes)</li>\n",
+                "</ul>\n",
+                "</body></html>\n"
+            )
+        );
+
+        let message = Message::parse(
+            concat!(
+                "Subject: Restyled\n",
+                "Content-Type: text/html\n",
+                "\n",
+                "<meta http-equiv=\"refresh\" content=\"0; url=https://evil.example.com\">",
+                "<link rel=\"stylesheet\" href=\"http://evil.example.com/a.css\">",
+                "<style>table { display: none }</style>\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let html = message.to_html_document();
+        for tag in ["<meta http-equiv", "<link", "<style"] {
+            assert!(!html.contains(tag), "{}", html);
+        }
+    }
+
+    #[test]
+    fn render_printable_text() {
+        let message = Message::parse(
+            concat!(
+                "From: Art Vandelay <art@vandelay.com>\r\n",
+                "To: jane@example.com\r\n",
+                "Subject: Latex imports\r\n",
+                "Date: Sat, 20 Nov 2021 14:22:01 -0800\r\n",
+                "Content-Type: multipart/mixed; boundary=\"festivus\"\r\n",
+                "\r\n",
+                "--festivus\r\n",
+                "Content-Type: text/html\r\n",
+                "\r\n",
+                "<p>Hello,</p><p>See the <b>attached</b> report.</p>\r\n",
+                "--festivus\r\n",
+                "Content-Type: application/pdf; name=\"report.pdf\"\r\n",
+                "\r\n",
+                "%PDF-1.4\r\n",
+                "--festivus\r\n",
+                "Content-Type: message/rfc822\r\n",
+                "\r\n",
+                "Subject: Forwarded\r\n",
+                "\r\n",
+                "Nested\r\n",
+                "--festivus--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.to_printable_text(),
+            concat!(
+                "From: Art Vandelay <art@vandelay.com>\n",
+                "To: jane@example.com\n",
+                "Date: Sat, 20 Nov 2021 14:22:01 -0800\n",
+                "Subject: Latex imports\n",
+                "\n",
+                "Hello,\n",
+                "\n",
+                "See the attached report.\n",
+                "\n",
+                "Attachments:\n",
+                "  1. report.pdf (application/pdf, 8 bytes)\n",
+                "  2. Forwarded (message/rfc822, 28 bytes)\n",
+            )
+        );
+
+        let message = Message::parse(b"Subject: hello\n\nworld\n").unwrap();
+        assert_eq!(message.to_printable_text(), "Subject: hello\n\nworld\n");
+
+        // Truncated nested messages have no parts
+        let message = Message::parse(
+            concat!(
+                "Subject: x\n",
+                "Content-Type: multipart/mixed; boundary=\"b\"\n\n",
+                "--b\n",
+                "Content-Type: text/plain\n\n",
+                "hi\n",
+                "--b\n",
+                "Content-Type: message/rfc822\n\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert!(message
+            .to_html_document()
+            .contains(">attachment-1</a> (message/rfc822, 0 bytes)</li>"));
+        assert_eq!(
+            message.to_printable_text(),
+            concat!(
+                "Subject: x\n",
+                "\n",
+                "hi\n",
+                "\n",
+                "Attachments:\n",
+                "  1. attachment-1 (message/rfc822, 0 bytes)\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,553 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::borrow::Cow;
+
+use crate::{
+    ActionMode, DateTime, Disposition, DispositionType, FeedbackReport, FeedbackType, GetHeader,
+    Header, HeaderName, HeaderValue, Mdn, Message, MessagePart, MimeHeaders, PartType, RfcHeader,
+    SendingMode,
+};
+
+use super::header::parse_header_block;
+
+impl<'x> Message<'x> {
+    /// Returns the report-type parameter of a `multipart/report` message
+    /// (RFC 6522), such as "delivery-status" or "disposition-notification".
+    pub fn report_type(&self) -> Option<&str> {
+        let content_type = self
+            .parts
+            .first()?
+            .headers
+            .rfc(&RfcHeader::ContentType)?
+            .as_content_type_ref()?;
+
+        if content_type.ctype() == "multipart" && content_type.subtype() == Some("report") {
+            content_type.attribute("report-type")
+        } else {
+            None
+        }
+    }
+
+    /// Returns the contents of the machine-readable part of a
+    /// `multipart/report` message of the requested report type.
+    pub(crate) fn report_part(&self, report_type: &str, subtype: &str) -> Option<&[u8]> {
+        if !self
+            .report_type()
+            .is_some_and(|rt| rt.eq_ignore_ascii_case(report_type))
+        {
+            return None;
+        }
+
+        if let PartType::Multipart(part_ids) = &self.parts.first()?.body {
+            let part = part_ids
+                .iter()
+                .filter_map(|&part_id| self.parts.get(part_id))
+                .find(|part| {
+                    part.headers
+                        .rfc(&RfcHeader::ContentType)
+                        .and_then(|ct| ct.as_content_type_ref())
+                        .is_some_and(|ct| {
+                            // RFC 6533 defines internationalized variants
+                            ct.ctype() == "message"
+                                && ct.subtype().is_some_and(|st| {
+                                    st == subtype || st.strip_prefix("global-") == Some(subtype)
+                                })
+                        })
+                })?;
+
+            match &part.body {
+                PartType::Text(text) | PartType::Html(text) => Some(text.as_bytes()),
+                PartType::Binary(bytes) | PartType::InlineBinary(bytes) => Some(bytes.as_ref()),
+                PartType::Message(message) => Some(message.raw_message.as_ref()),
+                PartType::Multipart(_) => None,
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl<'x> MessagePart<'x> {
+    /// Returns the header fields contained in a text/rfc822-headers part,
+    /// such as those of the original message returned in a delivery status
+    /// notification (RFC 6522), or `None` for other parts. The
+    /// message/rfc822-headers and message/global-headers (RFC 6533) media
+    /// types are recognized too.
+    pub fn rfc822_headers(&self) -> Option<Vec<Header<'_>>> {
+        let content_type = self.content_type()?;
+        let is_headers = match content_type.ctype() {
+            "text" => content_type.subtype() == Some("rfc822-headers"),
+            "message" => matches!(
+                content_type.subtype(),
+                Some("rfc822-headers" | "global-headers")
+            ),
+            _ => false,
+        };
+        if !is_headers {
+            return None;
+        }
+
+        Some(parse_header_block(self.body_bytes()))
+    }
+}
+
+/// Parses a block of report fields, returning their names and unfolded values.
+pub(crate) fn parse_report_fields(bytes: &[u8]) -> Vec<(Cow<'_, str>, Cow<'_, str>)> {
+    parse_header_block(bytes)
+        .into_iter()
+        .filter_map(|header| {
+            if let HeaderValue::Text(value) = header.value {
+                Some((
+                    match header.name {
+                        HeaderName::Rfc(name) => name.as_str().into(),
+                        HeaderName::Other(name) => name,
+                    },
+                    if value.contains('\n') {
+                        value.replace(['\r', '\n'], "").into()
+                    } else {
+                        value
+                    },
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+impl<'x> Mdn<'x> {
+    /// Parses the body of a `message/disposition-notification` part.
+    pub fn parse(bytes: &'x [u8]) -> Option<Mdn<'x>> {
+        let mut mdn = Mdn::default();
+        let mut has_fields = false;
+
+        for (name, value) in parse_report_fields(bytes) {
+            has_fields = true;
+            match name.to_ascii_lowercase().as_str() {
+                "reporting-ua" => mdn.reporting_ua = value.into(),
+                "mdn-gateway" => mdn.mdn_gateway = value.into(),
+                "original-recipient" => mdn.original_recipient = recipient(value).into(),
+                "final-recipient" => mdn.final_recipient = recipient(value).into(),
+                "original-message-id" => mdn.original_message_id = unbracket(value).into(),
+                "disposition" => mdn.disposition = Disposition::parse(&value),
+                "failure" => mdn.failure.push(value),
+                "warning" => mdn.warning.push(value),
+                "error" => mdn.error.push(value),
+                _ => mdn.extension_fields.push((name, value)),
+            }
+        }
+
+        if has_fields {
+            Some(mdn)
+        } else {
+            None
+        }
+    }
+}
+
+impl Disposition<'static> {
+    /// Parses the value of an RFC8098 Disposition field, for example
+    /// "manual-action/MDN-sent-manually; displayed/error".
+    pub fn parse(value: &str) -> Option<Disposition<'static>> {
+        let (mode, disposition_type) = value.split_once(';')?;
+        let (action_mode, sending_mode) = mode.split_once('/')?;
+        let (disposition_type, modifiers) = disposition_type
+            .split_once('/')
+            .unwrap_or((disposition_type, ""));
+
+        Disposition {
+            action_mode: match action_mode.trim().to_ascii_lowercase().as_str() {
+                "manual-action" => ActionMode::ManualAction,
+                "automatic-action" => ActionMode::AutomaticAction,
+                _ => return None,
+            },
+            sending_mode: match sending_mode.trim().to_ascii_lowercase().as_str() {
+                "mdn-sent-manually" => SendingMode::MdnSentManually,
+                "mdn-sent-automatically" => SendingMode::MdnSentAutomatically,
+                _ => return None,
+            },
+            disposition_type: match disposition_type.trim().to_ascii_lowercase().as_str() {
+                "displayed" => DispositionType::Displayed,
+                "deleted" => DispositionType::Deleted,
+                "dispatched" => DispositionType::Dispatched,
+                "processed" => DispositionType::Processed,
+                "" => return None,
+                other => DispositionType::Other(other.to_string().into()),
+            },
+            modifiers: modifiers
+                .split(',')
+                .map(|modifier| modifier.trim())
+                .filter(|modifier| !modifier.is_empty())
+                .map(|modifier| modifier.to_string().into())
+                .collect(),
+        }
+        .into()
+    }
+}
+
+impl<'x> FeedbackReport<'x> {
+    /// Parses the body of a `message/feedback-report` part.
+    pub fn parse(bytes: &'x [u8]) -> Option<FeedbackReport<'x>> {
+        let mut report = FeedbackReport::default();
+        let mut has_fields = false;
+
+        for (name, value) in parse_report_fields(bytes) {
+            has_fields = true;
+            match name.to_ascii_lowercase().as_str() {
+                "feedback-type" => {
+                    report.feedback_type = match value.to_ascii_lowercase().as_str() {
+                        "abuse" => FeedbackType::Abuse,
+                        "auth-failure" => FeedbackType::AuthFailure,
+                        "fraud" => FeedbackType::Fraud,
+                        "not-spam" => FeedbackType::NotSpam,
+                        "virus" => FeedbackType::Virus,
+                        "other" => FeedbackType::Other,
+                        _ => FeedbackType::Unknown(value),
+                    }
+                    .into()
+                }
+                "user-agent" => report.user_agent = value.into(),
+                "version" => report.version = value.into(),
+                "arrival-date" | "received-date" => {
+                    report.arrival_date = DateTime::parse_rfc822(&value)
+                }
+                "incidents" => report.incidents = value.parse().ok(),
+                "original-envelope-id" => report.original_envelope_id = value.into(),
+                "original-mail-from" => report.original_mail_from = unbracket(value).into(),
+                "original-rcpt-to" => report.original_rcpt_to.push(unbracket(value)),
+                "reporting-mta" => report.reporting_mta = recipient(value).into(),
+                "source-ip" => report.source_ip = value.into(),
+                "source-port" => report.source_port = value.parse().ok(),
+                "authentication-results" => report.authentication_results.push(value),
+                "reported-domain" => report.reported_domain.push(value),
+                "reported-uri" => report.reported_uri.push(value),
+                _ => report.extension_fields.push((name, value)),
+            }
+        }
+
+        if has_fields {
+            Some(report)
+        } else {
+            None
+        }
+    }
+}
+
+// Removes the angle brackets around a message id or address
+fn unbracket(value: Cow<'_, str>) -> Cow<'_, str> {
+    match value {
+        Cow::Borrowed(value) => value.trim_start_matches('<').trim_end_matches('>').into(),
+        Cow::Owned(value) => value
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_string()
+            .into(),
+    }
+}
+
+// Removes the address-type from a recipient field, i.e. "rfc822; user@domain"
+fn recipient(value: Cow<'_, str>) -> Cow<'_, str> {
+    match value {
+        Cow::Borrowed(value) => value
+            .split_once(';')
+            .map_or(value, |(_, address)| address.trim())
+            .into(),
+        Cow::Owned(value) => value
+            .split_once(';')
+            .map_or(value.as_str(), |(_, address)| address.trim())
+            .to_string()
+            .into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ActionMode, DateTime, Disposition, DispositionType, FeedbackReport, FeedbackType,
+        GetHeader, HeaderValue, Mdn, Message, PartType, RfcHeader, SendingMode,
+    };
+
+    #[test]
+    fn parse_mdn() {
+        let message = Message::parse(
+            concat!(
+                "Date: Wed, 20 Sep 1995 00:19:00 (EDT) -0400\r\n",
+                "From: Joe Recipient <Joe_Recipient@example.com>\r\n",
+                "Message-Id: <199509200019.12345@example.com>\r\n",
+                "Subject: Disposition notification\r\n",
+                "To: Jane Sender <Jane_Sender@example.org>\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: multipart/report; report-type=disposition-notification;\r\n",
+                "\tboundary=\"RAA14128.773615765/example.com\"\r\n",
+                "\r\n",
+                "--RAA14128.773615765/example.com\r\n",
+                "\r\n",
+                "The message sent on 1995 Sep 19 at 13:30:00 (EDT) -0400 to Joe\r\n",
+                "Recipient <Joe_Recipient@example.com> with subject \"First draft of\r\n",
+                "report\" has been displayed.\r\n",
+                "\r\n",
+                "--RAA14128.773615765/example.com\r\n",
+                "content-type: message/disposition-notification\r\n",
+                "\r\n",
+                "Reporting-UA: joes-pc.cs.example.com; Foomail 97.1\r\n",
+                "Original-Recipient: rfc822;Joe_Recipient@example.com\r\n",
+                "Final-Recipient: rfc822;Joe_Recipient@example.com\r\n",
+                "Original-Message-ID: <199509192301.23456@example.org>\r\n",
+                "Disposition: manual-action/MDN-sent-manually;\r\n",
+                " displayed/error, other\r\n",
+                "Error: Unable to\r\n",
+                " display attachment\r\n",
+                "X-Custom-Field: value\r\n",
+                "\r\n",
+                "--RAA14128.773615765/example.com--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.mdn().unwrap(),
+            Mdn {
+                reporting_ua: Some("joes-pc.cs.example.com; Foomail 97.1".into()),
+                mdn_gateway: None,
+                original_recipient: Some("Joe_Recipient@example.com".into()),
+                final_recipient: Some("Joe_Recipient@example.com".into()),
+                original_message_id: Some("199509192301.23456@example.org".into()),
+                disposition: Some(Disposition {
+                    action_mode: ActionMode::ManualAction,
+                    sending_mode: SendingMode::MdnSentManually,
+                    disposition_type: DispositionType::Displayed,
+                    modifiers: vec!["error".into(), "other".into()],
+                }),
+                failure: vec![],
+                warning: vec![],
+                error: vec!["Unable to display attachment".into()],
+                extension_fields: vec![("X-Custom-Field".into(), "value".into())],
+            }
+        );
+
+        for (input, expected_result) in [
+            (
+                "automatic-action/MDN-sent-automatically; deleted",
+                Some(Disposition {
+                    action_mode: ActionMode::AutomaticAction,
+                    sending_mode: SendingMode::MdnSentAutomatically,
+                    disposition_type: DispositionType::Deleted,
+                    modifiers: vec![],
+                }),
+            ),
+            (
+                "Manual-Action / MDN-Sent-Manually ; Denied",
+                Some(Disposition {
+                    action_mode: ActionMode::ManualAction,
+                    sending_mode: SendingMode::MdnSentManually,
+                    disposition_type: DispositionType::Other("denied".into()),
+                    modifiers: vec![],
+                }),
+            ),
+            ("displayed", None),
+            ("manual-action/MDN-sent-manually;", None),
+        ] {
+            assert_eq!(Disposition::parse(input), expected_result, "{input}");
+        }
+
+        assert_eq!(
+            Message::parse(b"Subject: not a report\r\n\r\nhello")
+                .unwrap()
+                .mdn(),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_feedback_report() {
+        let message = Message::parse(
+            concat!(
+                "From: <abusedesk@example.com>\r\n",
+                "Date: Thu, 8 Mar 2005 17:40:36 EDT\r\n",
+                "Subject: FW: Earn money\r\n",
+                "To: <abuse@example.net>\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: multipart/report; report-type=feedback-report;\r\n",
+                "     boundary=\"part1_13d.2e68ed54_boundary\"\r\n",
+                "\r\n",
+                "--part1_13d.2e68ed54_boundary\r\n",
+                "Content-Type: text/plain; charset=\"US-ASCII\"\r\n",
+                "Content-Transfer-Encoding: 7bit\r\n",
+                "\r\n",
+                "This is an email abuse report for an email message received from IP\r\n",
+                "192.0.2.1 on Thu, 8 Mar 2005 14:00:00 EDT.\r\n",
+                "\r\n",
+                "--part1_13d.2e68ed54_boundary\r\n",
+                "Content-Type: message/feedback-report\r\n",
+                "\r\n",
+                "Feedback-Type: abuse\r\n",
+                "User-Agent: SomeGenerator/1.0\r\n",
+                "Version: 1\r\n",
+                "Original-Mail-From: <somespammer@example.net>\r\n",
+                "Original-Rcpt-To: <user@example.com>\r\n",
+                "Arrival-Date: Thu, 8 Mar 2005 14:00:00 EDT\r\n",
+                "Reporting-MTA: dns; mail.example.com\r\n",
+                "Source-IP: 192.0.2.1\r\n",
+                "Authentication-Results: mail.example.com;\r\n",
+                "              spf=fail smtp.mail=somespammer@example.com\r\n",
+                "Reported-Domain: example.net\r\n",
+                "Reported-Uri: http://example.net/earn_money.html\r\n",
+                "Reported-Uri: mailto:user@example.com\r\n",
+                "Incidents: 3\r\n",
+                "Removal-Recipient: user@example.com\r\n",
+                "\r\n",
+                "--part1_13d.2e68ed54_boundary\r\n",
+                "Content-Type: message/rfc822\r\n",
+                "Content-Disposition: inline\r\n",
+                "\r\n",
+                "From: <somespammer@example.net>\r\n",
+                "Received: from mailserver.example.net (mailserver.example.net\r\n",
+                "        [192.0.2.1]) by example.com with ESMTP id M63d4137594e46;\r\n",
+                "        Thu, 08 Mar 2005 14:00:00 -0400\r\n",
+                "To: <Undisclosed Recipients>\r\n",
+                "Subject: Earn money\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-type: text/plain\r\n",
+                "Message-ID: 8787KJKJ3K4J3K4J3K4J3.mail@example.net\r\n",
+                "Date: Thu, 02 Sep 2004 12:31:03 -0500\r\n",
+                "\r\n",
+                "Spam Spam Spam\r\n",
+                "Spam Spam Spam\r\n",
+                "--part1_13d.2e68ed54_boundary--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.feedback_report().unwrap(),
+            FeedbackReport {
+                feedback_type: Some(FeedbackType::Abuse),
+                user_agent: Some("SomeGenerator/1.0".into()),
+                version: Some("1".into()),
+                arrival_date: DateTime::parse_rfc822("Thu, 8 Mar 2005 14:00:00 EDT"),
+                incidents: Some(3),
+                original_envelope_id: None,
+                original_mail_from: Some("somespammer@example.net".into()),
+                original_rcpt_to: vec!["user@example.com".into()],
+                reporting_mta: Some("mail.example.com".into()),
+                source_ip: Some("192.0.2.1".into()),
+                source_port: None,
+                authentication_results: vec![
+                    "mail.example.com;              spf=fail smtp.mail=somespammer@example.com"
+                        .into()
+                ],
+                reported_domain: vec!["example.net".into()],
+                reported_uri: vec![
+                    "http://example.net/earn_money.html".into(),
+                    "mailto:user@example.com".into()
+                ],
+                extension_fields: vec![("Removal-Recipient".into(), "user@example.com".into())],
+            }
+        );
+        assert!(message.mdn().is_none());
+        assert!(message.feedback_report().unwrap().arrival_date.is_some());
+    }
+
+    #[test]
+    fn parse_rfc822_headers() {
+        let message = Message::parse(
+            concat!(
+                "From: MAILER-DAEMON@example.com\r\n",
+                "Content-Type: multipart/report; report-type=delivery-status;\r\n",
+                "    boundary=\"b\"\r\n\r\n",
+                "--b\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "Your message could not be delivered.\r\n",
+                "--b\r\n",
+                "Content-Type: text/rfc822-headers\r\n\r\n",
+                "From: sender@example.com\r\n",
+                "Subject: Quarterly\r\n",
+                " report\r\n",
+                "Message-ID: <1234@example.com>\r\n",
+                "--b\r\n",
+                "Content-Type: message/rfc822-headers\r\n",
+                "Content-Transfer-Encoding: base64\r\n\r\n",
+                "U3ViamVjdDogSGVsbG8NCg==\r\n",
+                "--b--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let headers = message.parts[2].rfc822_headers().unwrap();
+        assert_eq!(headers.len(), 3);
+        assert_eq!(
+            headers
+                .rfc(&RfcHeader::From)
+                .and_then(|from| from.as_address())
+                .and_then(|from| from.first()?.address.as_deref()),
+            Some("sender@example.com")
+        );
+        assert_eq!(
+            headers.rfc(&RfcHeader::Subject),
+            Some(&HeaderValue::Text("Quarterly report".into()))
+        );
+        assert_eq!(
+            headers.rfc(&RfcHeader::MessageId),
+            Some(&HeaderValue::Text("1234@example.com".into()))
+        );
+
+        let headers = message.parts[3].rfc822_headers().unwrap();
+        assert_eq!(
+            headers.rfc(&RfcHeader::Subject),
+            Some(&HeaderValue::Text("Hello".into()))
+        );
+
+        assert_eq!(message.parts[1].rfc822_headers(), None);
+        assert_eq!(message.parts[0].rfc822_headers(), None);
+    }
+
+    #[test]
+    fn parse_report_structure() {
+        let message = Message::parse(
+            concat!(
+                "From: MAILER-DAEMON@example.com\r\n",
+                "Content-Type: multipart/report; report-type=delivery-status;\r\n",
+                "    boundary=\"b\"\r\n\r\n",
+                "--b\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "Your message could not be delivered.\r\n",
+                "--b\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "Reporting-MTA: dns; mx.example.com\r\n",
+                "--b\r\n",
+                "Content-Type: text/rfc822-headers\r\n\r\n",
+                "Subject: Hello\r\n",
+                "--b--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(message.report_type(), Some("delivery-status"));
+        assert_eq!(message.text_body, vec![1]);
+        assert_eq!(message.attachments, vec![2, 3]);
+        assert_eq!(
+            message.parts[2].body,
+            PartType::Binary((&b"Reporting-MTA: dns; mx.example.com"[..]).into())
+        );
+        assert!(message.parts[3].is_text());
+        assert_eq!(
+            Message::parse(b"Subject: hi\r\n\r\nhi")
+                .unwrap()
+                .report_type(),
+            None
+        );
+    }
+}
@@ -0,0 +1,224 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{Message, MessagePart, MimeHeaders, PartType, SmimeInfo, SmimeType};
+
+impl<'x> Message<'x> {
+    /// Returns the S/MIME structure of the message, if any. The first
+    /// multipart/signed or application/pkcs7-mime part found is returned,
+    /// starting from the root part.
+    pub fn smime(&self) -> Option<SmimeInfo<'_>> {
+        self.parts
+            .iter()
+            .enumerate()
+            .find_map(|(part_id, part)| self.smime_part(part_id, part))
+    }
+
+    fn smime_part<'y>(
+        &'y self,
+        part_id: usize,
+        part: &'y MessagePart<'_>,
+    ) -> Option<SmimeInfo<'y>> {
+        let content_type = part.content_type()?;
+
+        match (content_type.ctype(), content_type.subtype()?) {
+            ("multipart", "signed")
+                if content_type
+                    .attribute("protocol")
+                    .is_some_and(is_pkcs7_signature) =>
+            {
+                let sub_parts = part.sub_parts()?;
+                let content_part_id = *sub_parts.first()?;
+                let signature_part_id = sub_parts.iter().skip(1).copied().find(|&part_id| {
+                    self.parts
+                        .get(part_id)
+                        .and_then(|part| part.content_type())
+                        .is_some_and(|ct| {
+                            ct.ctype() == "application"
+                                && matches!(
+                                    ct.subtype(),
+                                    Some("pkcs7-signature" | "x-pkcs7-signature")
+                                )
+                        })
+                })?;
+
+                Some(SmimeInfo {
+                    smime_type: SmimeType::DetachedSignature,
+                    part_id,
+                    content_part_id: content_part_id.into(),
+                    signature_part_id: signature_part_id.into(),
+                    cms: self.parts[signature_part_id].contents(),
+                    micalg: content_type.attribute("micalg"),
+                })
+            }
+            ("application", "pkcs7-mime" | "x-pkcs7-mime")
+                if !matches!(part.body, PartType::Multipart(_)) =>
+            {
+                Some(SmimeInfo {
+                    smime_type: content_type
+                        .attribute("smime-type")
+                        .map_or(SmimeType::Unknown, SmimeType::parse),
+                    part_id,
+                    content_part_id: None,
+                    signature_part_id: None,
+                    cms: part.contents(),
+                    micalg: None,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl SmimeType {
+    pub fn parse(value: &str) -> Self {
+        match value.to_ascii_lowercase().as_str() {
+            "signed-data" => SmimeType::SignedData,
+            "enveloped-data" => SmimeType::EnvelopedData,
+            "authenveloped-data" => SmimeType::AuthEnvelopedData,
+            "compressed-data" => SmimeType::CompressedData,
+            "certs-only" => SmimeType::CertsOnly,
+            _ => SmimeType::Unknown,
+        }
+    }
+}
+
+fn is_pkcs7_signature(value: &str) -> bool {
+    value.eq_ignore_ascii_case("application/pkcs7-signature")
+        || value.eq_ignore_ascii_case("application/x-pkcs7-signature")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Message, SmimeType};
+
+    #[test]
+    fn parse_smime() {
+        let message = Message::parse(
+            concat!(
+                "From: jane@example.com\r\n",
+                "Subject: Signed message\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: multipart/signed; protocol=\"application/pkcs7-signature\";\r\n",
+                "    micalg=sha-256; boundary=\"signed\"\r\n",
+                "\r\n",
+                "This is an S/MIME signed message\r\n",
+                "\r\n",
+                "--signed\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Hello world\r\n",
+                "--signed\r\n",
+                "Content-Type: application/pkcs7-signature; name=smime.p7s\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "Content-Disposition: attachment; filename=smime.p7s\r\n",
+                "\r\n",
+                "MIAGCSqGSIb3DQEHAqCAMIACAQEx\r\n",
+                "\r\n",
+                "--signed--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let smime = message.smime().unwrap();
+        assert_eq!(smime.smime_type, SmimeType::DetachedSignature);
+        assert_eq!(smime.part_id, 0);
+        assert_eq!(smime.content_part_id, Some(1));
+        assert_eq!(smime.signature_part_id, Some(2));
+        assert_eq!(smime.micalg, Some("sha-256"));
+        assert_eq!(
+            smime.cms,
+            b"\x30\x80\x06\x09\x2a\x86\x48\x86\xf7\x0d\x01\x07\x02\xa0\x80\x30\x80\x02\x01\x01\x31"
+        );
+        assert_eq!(message.body_text(0).unwrap(), "Hello world");
+
+        let message = Message::parse(
+            concat!(
+                "From: jane@example.com\r\n",
+                "Subject: Encrypted message\r\n",
+                "Content-Type: application/pkcs7-mime; smime-type=enveloped-data;\r\n",
+                "    name=smime.p7m\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "MIAGCSqGSIb3DQEHA6CAMIACAQAx\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let smime = message.smime().unwrap();
+        assert_eq!(smime.smime_type, SmimeType::EnvelopedData);
+        assert_eq!(smime.part_id, 0);
+        assert_eq!(smime.content_part_id, None);
+        assert_eq!(smime.micalg, None);
+        assert_eq!(&smime.cms[..4], b"\x30\x80\x06\x09");
+
+        // PGP/MIME signatures are not S/MIME
+        let message = Message::parse(
+            concat!(
+                "Content-Type: multipart/signed; protocol=\"application/pgp-signature\";\r\n",
+                "    micalg=pgp-sha256; boundary=\"signed\"\r\n",
+                "\r\n",
+                "--signed\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Hello world\r\n",
+                "--signed\r\n",
+                "Content-Type: application/pgp-signature\r\n",
+                "\r\n",
+                "-----BEGIN PGP SIGNATURE-----\r\n",
+                "--signed--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(message.smime(), None);
+    }
+
+    #[test]
+    fn signed_content() {
+        let message = Message::parse(
+            concat!(
+                "From: jane@example.com\r\n",
+                "Content-Type: multipart/signed; protocol=\"application/pkcs7-signature\";\r\n",
+                "    micalg=sha-256; boundary=\"signed\"\r\n",
+                "\r\n",
+                "--signed\r\n",
+                "Content-Type: text/plain; charset=utf-8\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "SGVsbG8gd29ybGQ=\r\n",
+                "--signed\r\n",
+                "Content-Type: application/pkcs7-signature; name=smime.p7s\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "MIAGCSqGSIb3DQEHAqCAMIACAQEx\r\n",
+                "--signed--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(message.body_text(0).unwrap(), "Hello world");
+        assert_eq!(
+            std::str::from_utf8(message.signed_content(0).unwrap()).unwrap(),
+            concat!(
+                "Content-Type: text/plain; charset=utf-8\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "SGVsbG8gd29ybGQ="
+            )
+        );
+        assert_eq!(message.signed_content(1), None);
+        assert_eq!(message.signed_content(5), None);
+    }
+}
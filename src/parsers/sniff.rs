@@ -0,0 +1,183 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+// Magic numbers as (offset, signature, media type), checked in order.
+static MAGIC: &[(usize, &[u8], &str)] = &[
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (0, b"II*\x00", "image/tiff"),
+    (0, b"MM\x00*", "image/tiff"),
+    (0, b"BM", "image/bmp"),
+    (0, b"\x00\x00\x01\x00", "image/x-icon"),
+    (8, b"WEBP", "image/webp"),
+    (8, b"WAVE", "audio/wav"),
+    (8, b"AVI ", "video/x-msvideo"),
+    (4, b"ftyp", "video/mp4"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"PK\x05\x06", "application/zip"),
+    (0, b"Rar!\x1a\x07", "application/vnd.rar"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"\xfd7zXZ\x00", "application/x-xz"),
+    (
+        0,
+        b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1",
+        "application/x-ole-storage",
+    ),
+    (0, b"{\\rtf", "application/rtf"),
+    (0, b"MZ", "application/x-msdownload"),
+    (0, b"\x7fELF", "application/x-executable"),
+    (0, b"\xfe\xed\xfa\xce", "application/x-mach-binary"),
+    (0, b"\xfe\xed\xfa\xcf", "application/x-mach-binary"),
+    (0, b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+    (0, b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (0, b"\xca\xfe\xba\xbe", "application/java-vm"),
+    (0, b"\x00asm", "application/wasm"),
+];
+
+// Container formats that other media types are stored in, along with the
+// declared types that are consistent with them.
+static CONTAINERS: &[(&str, &[&str])] = &[
+    (
+        "application/zip",
+        &[
+            "application/x-zip-compressed",
+            "application/vnd.openxmlformats-officedocument.*",
+            "application/vnd.oasis.opendocument.*",
+            "application/vnd.ms-*",
+            "application/java-archive",
+            "application/epub+zip",
+            "application/vnd.android.package-archive",
+        ],
+    ),
+    (
+        "application/x-ole-storage",
+        &[
+            "application/msword",
+            "application/vnd.ms-*",
+            "application/vnd.visio",
+        ],
+    ),
+    (
+        "application/gzip",
+        &["application/x-gzip", "application/x-tar"],
+    ),
+    ("application/vnd.rar", &["application/x-rar-compressed"]),
+    ("application/rtf", &["text/rtf", "application/msword"]),
+    ("image/bmp", &["image/x-bmp", "image/x-ms-bmp"]),
+    ("image/x-icon", &["image/vnd.microsoft.icon"]),
+    ("image/jpeg", &["image/pjpeg", "image/jpg"]),
+    (
+        "audio/wav",
+        &["audio/x-wav", "audio/wave", "audio/vnd.wave"],
+    ),
+    ("audio/mpeg", &["audio/mp3"]),
+    ("audio/ogg", &["video/ogg", "application/ogg"]),
+    (
+        "video/mp4",
+        &["audio/mp4", "video/quicktime", "image/hei*", "image/avif"],
+    ),
+    (
+        "application/x-msdownload",
+        &[
+            "application/x-dosexec",
+            "application/vnd.microsoft.portable-executable",
+        ],
+    ),
+];
+
+/// Returns the media type of `contents` detected from its magic number, or
+/// `None` when it matches no known signature.
+pub fn detect_media_type(contents: &[u8]) -> Option<&'static str> {
+    MAGIC.iter().find_map(|(offset, magic, media_type)| {
+        contents
+            .get(*offset..*offset + magic.len())
+            .filter(|bytes| bytes == magic)
+            .map(|_| *media_type)
+    })
+}
+
+/// Returns `true` when a part declared as `declared` ("type/subtype") may
+/// contain data detected as `detected`. Parts declared as
+/// application/octet-stream are consistent with any contents.
+pub fn is_media_type_consistent(declared: &str, detected: &str) -> bool {
+    declared.eq_ignore_ascii_case(detected)
+        || declared.eq_ignore_ascii_case("application/octet-stream")
+        || CONTAINERS
+            .iter()
+            .filter(|(container, _)| *container == detected)
+            .flat_map(|(_, types)| types.iter())
+            .any(|media_type| match media_type.strip_suffix('*') {
+                Some(prefix) => declared
+                    .get(..prefix.len())
+                    .is_some_and(|p| p.eq_ignore_ascii_case(prefix)),
+                None => declared.eq_ignore_ascii_case(media_type),
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_media_type, is_media_type_consistent};
+
+    #[test]
+    fn sniff_media_type() {
+        for (contents, expected) in [
+            (&b"%PDF-1.7\n%\xe2\xe3"[..], Some("application/pdf")),
+            (b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR", Some("image/png")),
+            (
+                b"MZ\x90\x00\x03\x00\x00\x00",
+                Some("application/x-msdownload"),
+            ),
+            (b"RIFF\x24\x08\x00\x00WEBPVP8 ", Some("image/webp")),
+            (b"\x00\x00\x00\x18ftypmp42", Some("video/mp4")),
+            (b"PK\x03\x04\x14\x00\x06\x00", Some("application/zip")),
+            (b"RIFF", None),
+            (b"Hello, world", None),
+            (b"", None),
+        ] {
+            assert_eq!(detect_media_type(contents), expected, "{:?}", contents);
+        }
+    }
+
+    #[test]
+    fn media_type_consistency() {
+        for (declared, detected, expected) in [
+            ("application/pdf", "application/pdf", true),
+            ("Application/PDF", "application/pdf", true),
+            ("application/pdf", "application/x-msdownload", false),
+            ("image/png", "image/jpeg", false),
+            ("application/octet-stream", "application/x-msdownload", true),
+            (
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+                "application/zip",
+                true,
+            ),
+            ("application/msword", "application/x-ole-storage", true),
+            ("application/msword", "application/zip", false),
+            ("image/jpg", "image/jpeg", true),
+        ] {
+            assert_eq!(
+                is_media_type_consistent(declared, detected),
+                expected,
+                "{} {}",
+                declared,
+                detected
+            );
+        }
+    }
+}
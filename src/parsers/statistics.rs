@@ -0,0 +1,177 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{ContentStatistics, Message, MessagePart, PartType};
+
+impl ContentStatistics {
+    /// Computes the statistics of decoded contents.
+    pub fn new(contents: &[u8]) -> Self {
+        let mut counts = [0usize; 256];
+        for &ch in contents {
+            counts[ch as usize] += 1;
+        }
+
+        let size = contents.len();
+        let entropy = if size > 0 {
+            let size = size as f64;
+            counts
+                .iter()
+                .filter(|&&count| count > 0)
+                .map(|&count| {
+                    let p = count as f64 / size;
+                    -p * p.log2()
+                })
+                .sum::<f64>()
+                .max(0.0)
+        } else {
+            0.0
+        };
+
+        ContentStatistics {
+            size,
+            eight_bit_bytes: counts[0x80..].iter().sum(),
+            entropy,
+        }
+    }
+
+    /// Returns the fraction of decoded bytes with the high bit set, from 0
+    /// to 1.
+    pub fn eight_bit_ratio(&self) -> f64 {
+        if self.size > 0 {
+            self.eight_bit_bytes as f64 / self.size as f64
+        } else {
+            0.0
+        }
+    }
+
+    // Combines the statistics of two parts, weighting their entropy by size.
+    fn merge(self, other: ContentStatistics) -> Self {
+        let size = self.size + other.size;
+        ContentStatistics {
+            size,
+            eight_bit_bytes: self.eight_bit_bytes + other.eight_bit_bytes,
+            entropy: if size > 0 {
+                (self.entropy * self.size as f64 + other.entropy * other.size as f64) / size as f64
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+impl<'x> MessagePart<'x> {
+    /// Returns the statistics of the decoded contents of this part, which
+    /// are only collected for the parts other than multipart parts and
+    /// nested messages when [`ParseOptions::content_statistics`] is
+    /// enabled.
+    ///
+    /// [`ParseOptions::content_statistics`]: crate::ParseOptions::content_statistics
+    pub fn statistics(&self) -> Option<&ContentStatistics> {
+        self.statistics.as_ref()
+    }
+}
+
+impl<'x> Message<'x> {
+    /// Returns the combined statistics of the decoded contents of all the
+    /// parts of the message, including those of nested messages. The
+    /// entropy is the average of the entropy of each part weighted by its
+    /// size. Returns `None` when no statistics were collected.
+    pub fn content_statistics(&self) -> Option<ContentStatistics> {
+        self.parts.iter().fold(None, |total, part| {
+            let statistics = match &part.body {
+                PartType::Message(nested) => nested.content_statistics(),
+                _ => part.statistics,
+            };
+            match (total, statistics) {
+                (Some(total), Some(statistics)) => Some(total.merge(statistics)),
+                (total, statistics) => total.or(statistics),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ContentStatistics, Message, ParseOptions};
+
+    #[test]
+    fn compute_content_statistics() {
+        let statistics = ContentStatistics::new(b"");
+        assert_eq!(statistics.size, 0);
+        assert_eq!(statistics.entropy, 0.0);
+        assert_eq!(statistics.eight_bit_ratio(), 0.0);
+
+        let statistics = ContentStatistics::new(b"aaaa");
+        assert_eq!(statistics.entropy, 0.0);
+
+        let statistics = ContentStatistics::new(b"abab\xff\xfe\xff\xfe");
+        assert_eq!(statistics.size, 8);
+        assert_eq!(statistics.eight_bit_bytes, 4);
+        assert_eq!(statistics.eight_bit_ratio(), 0.5);
+        assert_eq!(statistics.entropy, 2.0);
+
+        let contents = (0..=255u8).collect::<Vec<_>>();
+        assert_eq!(ContentStatistics::new(&contents).entropy, 8.0);
+    }
+
+    #[test]
+    fn parse_with_content_statistics() {
+        let raw_message = concat!(
+            "Subject: statistics\r\n",
+            "Content-Type: multipart/mixed; boundary=\"a\"\r\n",
+            "\r\n",
+            "--a\r\n",
+            "Content-Type: text/plain; charset=iso-8859-1\r\n",
+            "Content-Transfer-Encoding: quoted-printable\r\n",
+            "\r\n",
+            "caf=E9\r\n",
+            "--a\r\n",
+            "Content-Type: application/octet-stream\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "AAEC/w==\r\n",
+            "--a\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "\r\n",
+            "Subject: nested\r\n",
+            "\r\n",
+            "aaaa\r\n",
+            "--a--\r\n"
+        )
+        .as_bytes();
+
+        let message = Message::parse(raw_message).unwrap();
+        assert!(message.parts.iter().all(|part| part.statistics().is_none()));
+        assert_eq!(message.content_statistics(), None);
+
+        let message =
+            Message::parse_with_options(raw_message, &ParseOptions::new().content_statistics(true))
+                .unwrap();
+        assert_eq!(message.parts[0].statistics(), None);
+
+        // Statistics are computed before charset decoding
+        let text = message.parts[1].statistics().unwrap();
+        assert_eq!((text.size, text.eight_bit_bytes), (4, 1));
+        assert_eq!(text.entropy, 2.0);
+
+        let binary = message.parts[2].statistics().unwrap();
+        assert_eq!((binary.size, binary.eight_bit_bytes), (4, 1));
+
+        assert_eq!(message.parts[3].statistics(), None);
+        let nested = message.parts[3].message().unwrap();
+        let nested_text = nested.parts[0].statistics().unwrap();
+        assert_eq!((nested_text.size, nested_text.entropy), (4, 0.0));
+
+        let total = message.content_statistics().unwrap();
+        assert_eq!((total.size, total.eight_bit_bytes), (12, 2));
+        assert_eq!(total.entropy, 4.0 / 3.0);
+    }
+}
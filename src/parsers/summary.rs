@@ -0,0 +1,142 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{Addr, HeaderName, HeaderValue, Message, MessageSummary, MimeHeaders, RfcHeader};
+
+use super::fields::thread::thread_name;
+
+impl<'x> Message<'x> {
+    /// Returns a summary of the message, reading the header fields in a
+    /// single pass. `preview_len` is the maximum length of the body preview,
+    /// use zero to skip generating it.
+    pub fn summary(&self, preview_len: usize) -> MessageSummary<'_> {
+        let mut summary = MessageSummary {
+            size: self.raw_message.len(),
+            ..Default::default()
+        };
+
+        for header in self.parts.first().map_or(&[][..], |part| &part.headers) {
+            if let HeaderName::Rfc(name) = &header.name {
+                match name {
+                    RfcHeader::From => summary.from = first_address(&header.value),
+                    RfcHeader::To => summary.to_count = address_count(&header.value),
+                    RfcHeader::Subject => summary.subject = header.value.as_text_ref(),
+                    RfcHeader::Date => summary.date = header.value.as_datetime_ref(),
+                    RfcHeader::MessageId => summary.message_id = header.value.as_text_ref(),
+                    RfcHeader::InReplyTo => {
+                        summary.in_reply_to = header.value.as_text_list().unwrap_or_default()
+                    }
+                    RfcHeader::References => {
+                        summary.references = header.value.as_text_list().unwrap_or_default()
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        summary.thread_name = summary.subject.map(thread_name);
+        summary.has_attachments = self.attachments.iter().any(|&part_id| {
+            self.parts.get(part_id).is_some_and(|part| {
+                part.attachment_name().is_some()
+                    || part
+                        .content_disposition()
+                        .is_some_and(|cd| cd.is_attachment())
+            })
+        });
+        if preview_len > 0 {
+            summary.preview = self.body_preview(preview_len);
+        }
+
+        summary
+    }
+}
+
+fn first_address<'y, 'x>(value: &'y HeaderValue<'x>) -> Option<&'y Addr<'x>> {
+    match value {
+        HeaderValue::Address(addr) => Some(addr),
+        HeaderValue::AddressList(list) => list.first(),
+        HeaderValue::Group(group) => group.addresses.first(),
+        HeaderValue::GroupList(list) => list.iter().find_map(|group| group.addresses.first()),
+        _ => None,
+    }
+}
+
+fn address_count(value: &HeaderValue<'_>) -> usize {
+    match value {
+        HeaderValue::Address(_) => 1,
+        HeaderValue::AddressList(list) => list.len(),
+        HeaderValue::Group(group) => group.addresses.len(),
+        HeaderValue::GroupList(list) => list.iter().map(|group| group.addresses.len()).sum(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Addr, DateTime, Message, MessageSummary};
+
+    #[test]
+    fn message_summary() {
+        let message = Message::parse(
+            concat!(
+                "From: Art Vandelay <art@vandelay.com>, george@vandelay.com\n",
+                "To: \"Colleagues\": \"James Smythe\" <james@vandelay.com>; Friends:\n",
+                "    jane@example.com, john@example.com;\n",
+                "Subject: Re: Fwd: Why not both importing AND exporting?\n",
+                "Date: Sat, 20 Nov 2021 14:22:01 -0800\n",
+                "Message-ID: <abc@vandelay.com>\n",
+                "In-Reply-To: <xyz@example.com>\n",
+                "References: <123@example.com> <xyz@example.com>\n",
+                "Content-Type: multipart/mixed; boundary=\"festivus\"\n",
+                "\n",
+                "--festivus\n",
+                "Content-Type: text/plain\n",
+                "\n",
+                "I was thinking about quitting the exporting.\n",
+                "--festivus\n",
+                "Content-Type: image/gif\n",
+                "Content-Disposition: attachment; filename=\"book.gif\"\n",
+                "Content-Transfer-Encoding: base64\n",
+                "\n",
+                "R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7\n",
+                "--festivus--\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let from = Addr::new("Art Vandelay".into(), "art@vandelay.com");
+        assert_eq!(
+            message.summary(20),
+            MessageSummary {
+                from: Some(&from),
+                to_count: 3,
+                subject: Some("Re: Fwd: Why not both importing AND exporting?"),
+                date: DateTime::parse_rfc822("Sat, 20 Nov 2021 14:22:01 -0800").as_ref(),
+                message_id: Some("abc@vandelay.com"),
+                thread_name: Some("Why not both importing AND exporting?"),
+                in_reply_to: vec!["xyz@example.com"],
+                references: vec!["123@example.com", "xyz@example.com"],
+                has_attachments: true,
+                preview: Some("I was thinking ab...".into()),
+                size: message.raw_message.len(),
+            }
+        );
+
+        let message = Message::parse(b"Subject: hello\n\nworld").unwrap();
+        let summary = message.summary(0);
+        assert_eq!(summary.subject, Some("hello"));
+        assert_eq!(summary.from, None);
+        assert_eq!(summary.to_count, 0);
+        assert!(!summary.has_attachments);
+        assert_eq!(summary.preview, None);
+    }
+}
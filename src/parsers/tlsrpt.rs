@@ -0,0 +1,141 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{decoders::inflate::gunzip, Message, MimeHeaders, TlsReportJson};
+
+use super::dmarc::MAX_REPORT_SIZE;
+
+impl Message<'_> {
+    /// Returns the decompressed JSON of the SMTP TLS reports (RFC 8460)
+    /// attached to the message, which report failures to negotiate TLS
+    /// with MTA-STS or DANE policies. Reports are found in
+    /// `application/tlsrpt+json` and `application/tlsrpt+gzip` parts, and
+    /// in `application/gzip` or `application/json` parts named `.json.gz`
+    /// or `.json` as sent by some reporters. Only JSON objects containing
+    /// a `policies` member are returned, and files decompressing to more
+    /// than 32 MiB are ignored.
+    pub fn tls_report_json(&self) -> Vec<TlsReportJson> {
+        let mut reports = Vec::new();
+
+        for (part_id, part) in self.parts.iter().enumerate() {
+            if part.is_multipart() || part.is_message() {
+                continue;
+            }
+            let file_name = part.attachment_name();
+            let has_extension = |extension: &str| {
+                file_name.is_some_and(|name| {
+                    name.len() > extension.len()
+                        && name.as_bytes()[name.len() - extension.len()..]
+                            .eq_ignore_ascii_case(extension.as_bytes())
+                })
+            };
+            let is_gzip = if let Some(is_gzip) =
+                part.content_type()
+                    .and_then(|ct| match (ct.ctype(), ct.subtype()?) {
+                        ("application", "tlsrpt+gzip") => Some(true),
+                        ("application", "tlsrpt+json") => Some(false),
+                        ("application", "gzip" | "x-gzip" | "octet-stream")
+                            if has_extension(".json.gz") =>
+                        {
+                            Some(true)
+                        }
+                        ("application", "json" | "octet-stream") if has_extension(".json") => {
+                            Some(false)
+                        }
+                        _ => None,
+                    }) {
+                is_gzip
+            } else {
+                continue;
+            };
+
+            let json = if is_gzip {
+                gunzip(part.contents(), MAX_REPORT_SIZE)
+            } else {
+                Some(part.contents().to_vec())
+            };
+            if let Some(json) = json.filter(|json| is_tls_report(json)) {
+                reports.push(TlsReportJson {
+                    part_id,
+                    file_name: file_name.map(|name| name.to_string()),
+                    json,
+                });
+            }
+        }
+
+        reports
+    }
+}
+
+// Returns true if a document looks like a JSON object with a `policies`
+// member, which all TLS reports have.
+fn is_tls_report(json: &[u8]) -> bool {
+    json.iter().find(|ch| !ch.is_ascii_whitespace()) == Some(&b'{')
+        && json
+            .windows(b"\"policies\"".len())
+            .any(|window| window == b"\"policies\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Message, TlsReportJson};
+
+    #[test]
+    fn tls_report_json() {
+        let message = Message::parse(
+            concat!(
+                "From: tlsrpt@company-x.example\n",
+                "Subject: Report Domain: example.com Submitter: company-x.example\n",
+                "TLS-Report-Domain: example.com\n",
+                "TLS-Report-Submitter: company-x.example\n",
+                "Content-Type: multipart/report; report-type=\"tlsrpt\"; boundary=\"b\"\n\n",
+                "--b\n",
+                "Content-Type: text/plain\n\n",
+                "This is an aggregate TLS report from company-x.example\n",
+                "--b\n",
+                "Content-Type: application/tlsrpt+gzip\n",
+                "Content-Disposition: attachment;\n",
+                " filename=\"company-x.example!example.com!1459468800!1459555199.json.gz\"\n",
+                "Content-Transfer-Encoding: base64\n\n",
+                "H4sIAAAAAAACA3WOvQ7CMAyE38UzRmmbFtoJiVdgQCAGk5oqEnWi1EMB8e6EnxXpprvvTveAkAYS\n",
+                "fyf1QVBoZOhgG8ZIcsM9LKAnZUwkQw4eMCklxben/oOWpmjQWDTFzpjuo0NusfR/qLLq6jbrAM8F\n",
+                "uCBKTtHLJWRs0gkTx5DUy7Bxvxfzkmca45Xz7jdF32e6Nk1ty5XDsuortCtHeG7YYGsbJsNrZ8/v\n",
+                "SgxX7zxP0B1PzxfQ6t288AAAAA==\n",
+                "--b\n",
+                "Content-Type: application/json; name=\"other.json\"\n",
+                "Content-Transfer-Encoding: base64\n\n",
+                "eyJmb28iOjF9\n",
+                "--b--\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.tls_report_json(),
+            [TlsReportJson {
+                part_id: 2,
+                file_name: Some(
+                    "company-x.example!example.com!1459468800!1459555199.json.gz".to_string()
+                ),
+                json: concat!(
+                    "{\"organization-name\":\"Company-X\",\"date-range\":{",
+                    "\"start-datetime\":\"2016-04-01T00:00:00Z\",",
+                    "\"end-datetime\":\"2016-04-01T23:59:59Z\"},",
+                    "\"contact-info\":\"sts-reporting@company-x.example\",",
+                    "\"report-id\":\"5065427c-23d3-47ca-b6e0-946ea0e8c4be\",\"policies\":[]}"
+                )
+                .as_bytes()
+                .to_vec(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{
+    Message, ParseOptions, ParseWarning, PartRef, Validation, ValidationError, ValidationLimits,
+    Verdict,
+};
+
+impl Message<'_> {
+    /// Checks whether a raw message can be accepted, such as at the end of
+    /// the SMTP DATA command, without keeping the parsed message around.
+    ///
+    /// The size limits are checked before parsing, and the message is then
+    /// parsed without decoding its attachments, so their transfer encoding
+    /// is not verified.
+    pub fn validate(raw_message: &[u8], limits: &ValidationLimits) -> Validation {
+        let mut validation = Validation {
+            verdict: Verdict::Parseable,
+            size: raw_message.len(),
+            header_size: header_size(raw_message, limits.max_header_size),
+            part_count: 0,
+            message_id: None,
+            from: None,
+        };
+        if limits
+            .max_size
+            .is_some_and(|max_size| raw_message.len() > max_size)
+        {
+            validation.verdict = Verdict::Unparseable(ValidationError::TooLarge);
+            return validation;
+        } else if limits
+            .max_header_size
+            .is_some_and(|max_header_size| validation.header_size > max_header_size)
+        {
+            validation.verdict = Verdict::Unparseable(ValidationError::HeaderTooLarge);
+            return validation;
+        }
+
+        let mut options = ParseOptions::new()
+            .decode_attachments(false)
+            .generate_alternatives(false);
+        options.max_depth = limits.max_depth;
+        options.max_parts = limits.max_parts;
+        let message = if let Some(message) = Message::parse_with_options(raw_message, &options)
+            .filter(|message| {
+                message
+                    .parts
+                    .first()
+                    .is_some_and(|part| !part.headers.is_empty())
+            }) {
+            message
+        } else {
+            validation.verdict = Verdict::Unparseable(ValidationError::NoHeaders);
+            return validation;
+        };
+
+        let mut warnings = message.warnings.clone();
+        for part in message.walk() {
+            validation.part_count += 1;
+            if let PartRef::Message { nested, .. } = part {
+                warnings.extend(nested.warnings.iter().cloned());
+            }
+        }
+        validation.message_id = message.message_id().map(String::from);
+        validation.from = message
+            .from_address()
+            .and_then(|address| address.first()?.address.as_deref().map(String::from));
+
+        validation.verdict = if let Some(error) =
+            warnings.iter().find_map(|warning| match warning {
+                ParseWarning::MaxDepthExceeded { offset, .. } => {
+                    Some(ValidationError::TooDeep { offset: *offset })
+                }
+                ParseWarning::MaxPartsExceeded { offset } => {
+                    Some(ValidationError::TooManyParts { offset: *offset })
+                }
+                _ => None,
+            }) {
+            Verdict::Unparseable(error)
+        } else if !warnings.is_empty() {
+            Verdict::Recoverable(warnings)
+        } else {
+            Verdict::Parseable
+        };
+        validation
+    }
+}
+
+// Returns the size of the header including the blank line ending it, or the
+// size of the message if it has no body. Scanning stops once the limit is
+// exceeded.
+fn header_size(raw_message: &[u8], max_header_size: Option<usize>) -> usize {
+    let mut pos = 0;
+    while pos < raw_message.len() {
+        let line_end = raw_message[pos..]
+            .iter()
+            .position(|&ch| ch == b'\n')
+            .map_or(raw_message.len(), |end| pos + end + 1);
+        if matches!(&raw_message[pos..line_end], b"\n" | b"\r\n")
+            || max_header_size.is_some_and(|max_header_size| line_end > max_header_size)
+        {
+            return line_end;
+        }
+        pos = line_end;
+    }
+    raw_message.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DecodeError, Message, ParseWarning, ValidationError, ValidationLimits, Verdict};
+
+    #[test]
+    fn validate_messages() {
+        let raw_message = concat!(
+            "From: Jane <jane@example.com>\r\n",
+            "Message-ID: <1@example.com>\r\n",
+            "Content-Type: multipart/mixed; boundary=\"a\"\r\n\r\n",
+            "--a\r\n",
+            "Content-Type: text/plain\r\n\r\n",
+            "Hello\r\n",
+            "--a\r\n",
+            "Content-Type: message/rfc822\r\n\r\n",
+            "Subject: nested\r\n",
+            "Content-Type: text/plain\r\n",
+            "Content-Transfer-Encoding: base64\r\n\r\n",
+            "SGVsbG8=!\r\n",
+            "--a--\r\n"
+        );
+        let limits = ValidationLimits::new();
+
+        let validation = Message::validate(raw_message.as_bytes(), &limits);
+        assert_eq!(validation.size, raw_message.len());
+        assert_eq!(
+            validation.header_size,
+            raw_message.find("\r\n\r\n").unwrap() + 4
+        );
+        assert_eq!(validation.part_count, 4);
+        assert_eq!(validation.message_id.as_deref(), Some("1@example.com"));
+        assert_eq!(validation.from.as_deref(), Some("jane@example.com"));
+        assert!(
+            matches!(
+                &validation.verdict,
+                Verdict::Recoverable(warnings) if matches!(
+                    warnings.as_slice(),
+                    [ParseWarning::DecodeError {
+                        error: DecodeError::InvalidBase64 { .. },
+                        ..
+                    }]
+                )
+            ),
+            "{:?}",
+            validation.verdict
+        );
+
+        // Valid message
+        let valid_message = raw_message.replace("SGVsbG8=!", "SGVsbG8=");
+        assert_eq!(
+            Message::validate(valid_message.as_bytes(), &limits).verdict,
+            Verdict::Parseable
+        );
+
+        // Limits
+        for (limits, expected) in [
+            (
+                ValidationLimits::new().max_size(100),
+                ValidationError::TooLarge,
+            ),
+            (
+                ValidationLimits::new().max_header_size(40),
+                ValidationError::HeaderTooLarge,
+            ),
+        ] {
+            let validation = Message::validate(raw_message.as_bytes(), &limits);
+            assert_eq!(validation.verdict, Verdict::Unparseable(expected));
+            assert_eq!(validation.part_count, 0);
+        }
+        assert!(matches!(
+            Message::validate(
+                raw_message.as_bytes(),
+                &ValidationLimits::new().max_depth(1)
+            )
+            .verdict,
+            Verdict::Unparseable(ValidationError::TooDeep { .. })
+        ));
+        assert!(matches!(
+            Message::validate(
+                raw_message.as_bytes(),
+                &ValidationLimits::new().max_parts(2)
+            )
+            .verdict,
+            Verdict::Unparseable(ValidationError::TooManyParts { .. })
+        ));
+
+        // Messages without headers
+        assert_eq!(
+            Message::validate(b"\r\nno headers", &limits).verdict,
+            Verdict::Unparseable(ValidationError::NoHeaders)
+        );
+        assert_eq!(
+            Message::validate(b"", &limits).verdict,
+            Verdict::Unparseable(ValidationError::NoHeaders)
+        );
+    }
+}
@@ -0,0 +1,261 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::borrow::Cow;
+
+use crate::{Message, MimeHeaders, VCard, VCardValue};
+
+use super::icalendar::{parse_content_lines, part_text, unescape_text, ContentLine};
+
+impl<'x> Message<'x> {
+    /// Returns the contacts contained in the text/vcard parts of the message.
+    pub fn vcards(&self) -> Vec<VCard<'_>> {
+        let mut vcards = Vec::new();
+
+        for (part_id, part) in self.parts.iter().enumerate() {
+            if !part.content_type().is_some_and(|ct| {
+                ct.ctype() == "text"
+                    && matches!(ct.subtype(), Some("vcard" | "x-vcard" | "directory"))
+            }) {
+                continue;
+            }
+
+            if let Some(text) = part_text(part) {
+                vcards.extend(VCard::parse(text).into_iter().map(|mut vcard| {
+                    vcard.part_id = part_id;
+                    vcard
+                }));
+            }
+        }
+
+        vcards
+    }
+}
+
+impl<'x> VCard<'x> {
+    /// Parses the vCard objects contained in a text.
+    pub fn parse(text: &'x str) -> Vec<VCard<'x>> {
+        let mut vcards = Vec::new();
+        let mut vcard: Option<VCard> = None;
+
+        for line in parse_content_lines(text) {
+            if line.is("BEGIN") {
+                if line.value.eq_ignore_ascii_case("VCARD") {
+                    vcard = VCard::default().into();
+                }
+                continue;
+            } else if line.is("END") {
+                if line.value.eq_ignore_ascii_case("VCARD") {
+                    vcards.extend(vcard.take());
+                }
+                continue;
+            }
+
+            let vcard = if let Some(vcard) = &mut vcard {
+                vcard
+            } else {
+                continue;
+            };
+
+            match line.name.to_ascii_uppercase().as_str() {
+                "FN" => vcard.name = unescape_text(line.value).into(),
+                "EMAIL" => vcard.emails.push(VCardValue::from_line(line)),
+                "TEL" => vcard.phones.push(VCardValue::from_line(line)),
+                "ORG" => {
+                    vcard.organization = split_components(line.value)
+                        .into_iter()
+                        .map(unescape_text)
+                        .collect()
+                }
+                _ => (),
+            }
+        }
+
+        vcards
+    }
+}
+
+impl<'x> VCardValue<'x> {
+    fn from_line(line: ContentLine<'x>) -> Self {
+        let mut types = Vec::new();
+        for (name, value) in line.params {
+            if name.eq_ignore_ascii_case("TYPE") {
+                if value.contains(',') {
+                    types.extend(
+                        value
+                            .split(',')
+                            .map(|value| Cow::Owned(value.trim().to_string())),
+                    );
+                } else {
+                    types.push(value);
+                }
+            }
+        }
+
+        VCardValue {
+            value: match line.value {
+                Cow::Borrowed(value) => strip_uri_scheme(value).into(),
+                Cow::Owned(value) => strip_uri_scheme(&value).to_string().into(),
+            },
+            types,
+        }
+    }
+
+    /// Returns `true` if the value has the provided type.
+    pub fn has_type(&self, name: &str) -> bool {
+        self.types
+            .iter()
+            .any(|value| value.eq_ignore_ascii_case(name))
+    }
+}
+
+// vCard 4.0 encodes phone numbers and some addresses as URIs
+fn strip_uri_scheme(value: &str) -> &str {
+    for scheme in ["tel:", "mailto:"] {
+        if value
+            .get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        {
+            return &value[scheme.len()..];
+        }
+    }
+    value
+}
+
+// Splits a structured value on the semicolons that are not escaped.
+fn split_components(value: Cow<'_, str>) -> Vec<Cow<'_, str>> {
+    let mut components = Vec::new();
+    let mut start = 0;
+    let mut is_escaped = false;
+
+    for (pos, ch) in value.char_indices() {
+        match ch {
+            '\\' => {
+                is_escaped = !is_escaped;
+                continue;
+            }
+            ';' if !is_escaped => {
+                components.push(component(&value, start..pos));
+                start = pos + 1;
+            }
+            _ => (),
+        }
+        is_escaped = false;
+    }
+    components.push(component(&value, start..value.len()));
+
+    while components
+        .last()
+        .is_some_and(|component| component.is_empty())
+    {
+        components.pop();
+    }
+
+    components
+}
+
+fn component<'x>(value: &Cow<'x, str>, range: std::ops::Range<usize>) -> Cow<'x, str> {
+    match value {
+        Cow::Borrowed(value) => value[range].into(),
+        Cow::Owned(value) => value[range].to_string().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Message, VCard, VCardValue};
+
+    #[test]
+    fn parse_vcards() {
+        let message = Message::parse(
+            concat!(
+                "From: jane@example.com\r\n",
+                "Subject: My contact\r\n",
+                "Content-Type: multipart/mixed; boundary=\"b1\"\r\n",
+                "\r\n",
+                "--b1\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Please find my contact attached.\r\n",
+                "--b1\r\n",
+                "Content-Type: text/vcard; charset=utf-8\r\n",
+                "Content-Disposition: attachment; filename=jane.vcf\r\n",
+                "\r\n",
+                "BEGIN:VCARD\r\n",
+                "VERSION:4.0\r\n",
+                "FN:Jane Doe\\, PhD\r\n",
+                "ORG:Example\\, Inc.;Sales;\r\n",
+                "EMAIL;TYPE=work:jane@example.com\r\n",
+                "item1.EMAIL;TYPE=home,pref:jane.doe@\r\n",
+                " example.org\r\n",
+                "TEL;VALUE=uri;TYPE=\"voice,cell\":tel:+1-555-555-5555\r\n",
+                "END:VCARD\r\n",
+                "--b1\r\n",
+                "Content-Type: text/x-vcard\r\n",
+                "\r\n",
+                "BEGIN:VCARD\r\n",
+                "VERSION:2.1\r\n",
+                "FN:John Smith\r\n",
+                "TEL;WORK;VOICE:+1 555 1234\r\n",
+                "END:VCARD\r\n",
+                "BEGIN:VCARD\r\n",
+                "VERSION:3.0\r\n",
+                "FN:Bob\r\n",
+                "END:VCARD\r\n",
+                "--b1--\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let vcards = message.vcards();
+        assert_eq!(
+            vcards,
+            vec![
+                VCard {
+                    part_id: 2,
+                    name: Some("Jane Doe, PhD".into()),
+                    emails: vec![
+                        VCardValue {
+                            value: "jane@example.com".into(),
+                            types: vec!["work".into()],
+                        },
+                        VCardValue {
+                            value: "jane.doe@example.org".into(),
+                            types: vec!["home".into(), "pref".into()],
+                        }
+                    ],
+                    phones: vec![VCardValue {
+                        value: "+1-555-555-5555".into(),
+                        types: vec!["voice".into(), "cell".into()],
+                    }],
+                    organization: vec!["Example, Inc.".into(), "Sales".into()],
+                },
+                VCard {
+                    part_id: 3,
+                    name: Some("John Smith".into()),
+                    phones: vec![VCardValue {
+                        value: "+1 555 1234".into(),
+                        types: vec!["WORK".into(), "VOICE".into()],
+                    }],
+                    ..Default::default()
+                },
+                VCard {
+                    part_id: 3,
+                    name: Some("Bob".into()),
+                    ..Default::default()
+                }
+            ]
+        );
+        assert!(vcards[1].phones[0].has_type("work"));
+        assert!(!vcards[1].phones[0].has_type("cell"));
+    }
+}
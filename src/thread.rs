@@ -0,0 +1,472 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Message threading using the REFERENCES algorithm of RFC 5256.
+//!
+//! ```
+//! use mail_parser::{thread::Threader, Message};
+//!
+//! let messages = [
+//!     &b"Message-ID: <1@example.com>\nSubject: Lunch\n\nAnyone?"[..],
+//!     b"Message-ID: <2@example.com>\nIn-Reply-To: <1@example.com>\nSubject: Re: Lunch\n\nMe",
+//!     b"Message-ID: <3@example.com>\nSubject: Meeting\n\nAt 3pm",
+//! ]
+//! .iter()
+//! .map(|raw| Message::parse(raw).unwrap())
+//! .collect::<Vec<_>>();
+//!
+//! let mut threader = Threader::new();
+//! for message in &messages {
+//!     threader.add(&message.summary(0));
+//! }
+//! let threads = threader.threads();
+//!
+//! assert_eq!(threads.len(), 2);
+//! assert_eq!(threads[0].message, Some(0));
+//! assert_eq!(threads[0].children[0].message, Some(1));
+//! assert_eq!(threads[1].message, Some(2));
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{MessageSummary, SubjectPrefixes};
+
+// Most references linked per message, the oldest and the most recent ones
+// are kept.
+const MAX_REFERENCES: usize = 100;
+
+// Deepest conversation tree built, replies nested deeper start a new tree.
+const MAX_DEPTH: usize = 1000;
+
+/// Builds conversation trees from the Message-ID, In-Reply-To and
+/// References header fields of a set of messages, following the REFERENCES
+/// algorithm of RFC 5256 (Section 2.2), itself based on the threading
+/// algorithm by Jamie Zawinski. Threads without references in common are
+/// also merged when their base subjects are equal.
+#[derive(Debug, Default)]
+pub struct Threader<'x> {
+    nodes: Vec<Node>,
+    messages: Vec<MessageInfo<'x>>,
+    ids: HashMap<&'x str, usize>,
+    prefixes: SubjectPrefixes,
+}
+
+/// A node of a conversation tree returned by [`Threader::threads`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    /// Index of the message in the order it was added to the threader, or
+    /// `None` for a message that was referenced but not added.
+    pub message: Option<usize>,
+    /// The replies to the message, sorted by date.
+    pub children: Vec<Thread>,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    message: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct MessageInfo<'x> {
+    date: i64,
+    subject: Option<&'x str>,
+}
+
+impl<'x> Threader<'x> {
+    /// Creates an empty threader.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty threader that also removes the provided reply and
+    /// forward prefixes when merging threads by subject.
+    pub fn with_prefixes(prefixes: SubjectPrefixes) -> Self {
+        Self {
+            prefixes,
+            ..Self::default()
+        }
+    }
+
+    /// Adds a message to the threader, returning its index.
+    pub fn add(&mut self, summary: &MessageSummary<'x>) -> usize {
+        // Use the first message id of In-Reply-To when References is missing
+        let references = if !summary.references.is_empty() {
+            &summary.references[..]
+        } else {
+            &summary.in_reply_to[..summary.in_reply_to.len().min(1)]
+        };
+        self.add_message(
+            summary.message_id,
+            references,
+            summary.date.map_or(0, |date| date.to_timestamp()),
+            summary.subject,
+        )
+    }
+
+    /// Adds a message from its message id, the message ids it references
+    /// from the oldest to its parent, its date as a UNIX timestamp and its
+    /// subject, returning its index.
+    pub fn add_message(
+        &mut self,
+        message_id: Option<&'x str>,
+        references: &[&'x str],
+        date: i64,
+        subject: Option<&'x str>,
+    ) -> usize {
+        let message = self.messages.len();
+        self.messages.push(MessageInfo { date, subject });
+
+        // Link the referenced messages to each other
+        let mut parent = None;
+        let skip = references.len().saturating_sub(MAX_REFERENCES);
+        for &reference in references
+            .iter()
+            .take(1)
+            .chain(references.iter().skip(1 + skip))
+        {
+            let node = self.node(reference);
+            if let Some(parent) = parent {
+                if self.nodes[node].parent.is_none() && !self.is_ancestor(node, parent) {
+                    self.set_parent(node, Some(parent));
+                }
+            }
+            parent = Some(node);
+        }
+
+        // Messages with a duplicate or without message id are kept apart
+        let node = match message_id {
+            Some(message_id)
+                if self
+                    .ids
+                    .get(message_id)
+                    .map_or(true, |&node| self.nodes[node].message.is_none()) =>
+            {
+                self.node(message_id)
+            }
+            _ => self.new_node(),
+        };
+        self.nodes[node].message = Some(message);
+        if parent.is_some_and(|parent| parent != node && !self.is_ancestor(node, parent)) {
+            self.set_parent(node, parent);
+        } else if parent.is_some() {
+            self.set_parent(node, None);
+        }
+
+        message
+    }
+
+    /// Returns the conversation trees, sorted by the date of their first
+    /// message.
+    pub fn threads(mut self) -> Vec<Thread> {
+        // Prune the nodes of the messages that were referenced but not
+        // added, promoting their children
+        let roots = (0..self.nodes.len())
+            .filter(|&node| self.nodes[node].parent.is_none())
+            .collect::<Vec<_>>();
+        let roots = roots
+            .into_iter()
+            .flat_map(|node| self.prune(node))
+            .collect::<Vec<_>>();
+
+        // Merge the threads having the same base subject
+        let mut subjects: HashMap<String, usize> = HashMap::new();
+        for &root in &roots {
+            if let Some((base_subject, is_reply)) = self.subject(root) {
+                match subjects.get(&base_subject) {
+                    Some(&other)
+                        if (self.nodes[other].message.is_some()
+                            && self.nodes[root].message.is_none())
+                            || (self.subject(other).is_some_and(|(_, is_reply)| is_reply)
+                                && !is_reply) => {}
+                    Some(_) => continue,
+                    None => {}
+                }
+                subjects.insert(base_subject, root);
+            }
+        }
+        let (mut merged_roots, roots): (Vec<_>, Vec<_>) = roots.into_iter().partition(|&root| {
+            self.subject(root).map_or(true, |(base_subject, _)| {
+                subjects.get(&base_subject) == Some(&root)
+            })
+        });
+        for root in roots {
+            let (base_subject, is_reply) = self.subject(root).unwrap();
+            let other = subjects[&base_subject];
+
+            match (self.nodes[other].message, self.nodes[root].message) {
+                (None, None) => {
+                    for child in std::mem::take(&mut self.nodes[root].children) {
+                        self.set_parent(child, Some(other));
+                    }
+                }
+                (None, Some(_)) => self.set_parent(root, Some(other)),
+                (Some(_), Some(_))
+                    if is_reply && !self.subject(other).is_some_and(|(_, is_reply)| is_reply) =>
+                {
+                    self.set_parent(root, Some(other))
+                }
+                _ => {
+                    // Create a new root holding both threads
+                    let new_root = self.new_node();
+                    self.set_parent(other, Some(new_root));
+                    self.set_parent(root, Some(new_root));
+                    if let Some(root) = merged_roots.iter_mut().find(|root| **root == other) {
+                        *root = new_root;
+                    }
+                    subjects.insert(base_subject, new_root);
+                }
+            }
+        }
+
+        let roots = merged_roots
+            .into_iter()
+            .filter(|&root| {
+                self.nodes[root].message.is_some() || !self.nodes[root].children.is_empty()
+            })
+            .collect::<Vec<_>>();
+        self.build(&roots)
+    }
+
+    fn node(&mut self, message_id: &'x str) -> usize {
+        if let Some(&node) = self.ids.get(message_id) {
+            node
+        } else {
+            let node = self.new_node();
+            self.ids.insert(message_id, node);
+            node
+        }
+    }
+
+    fn new_node(&mut self) -> usize {
+        self.nodes.push(Node::default());
+        self.nodes.len() - 1
+    }
+
+    fn set_parent(&mut self, node: usize, parent: Option<usize>) {
+        if let Some(old_parent) = self.nodes[node].parent {
+            self.nodes[old_parent]
+                .children
+                .retain(|&child| child != node);
+        }
+        self.nodes[node].parent = parent;
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(node);
+        }
+    }
+
+    // Returns true if `node` is `other` or one of its ancestors, or if
+    // `other` is nested too deep to find out.
+    fn is_ancestor(&self, node: usize, mut other: usize) -> bool {
+        for _ in 0..MAX_DEPTH {
+            if node == other {
+                return true;
+            }
+            match self.nodes[other].parent {
+                Some(parent) => other = parent,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    // Removes the empty nodes below the root `root`, returning the nodes
+    // replacing it.
+    fn prune(&mut self, root: usize) -> Vec<usize> {
+        // Depth-first traversal, the pruned children of each node are kept
+        // at the end of `pruned` until the node itself is pruned
+        let mut pruned: Vec<usize> = Vec::new();
+        let children = std::mem::take(&mut self.nodes[root].children);
+        let mut stack = vec![(root, children.into_iter(), 0)];
+        while let Some((node, children, start)) = stack.last_mut() {
+            if let Some(child) = children.next() {
+                let children = std::mem::take(&mut self.nodes[child].children);
+                stack.push((child, children.into_iter(), pruned.len()));
+                continue;
+            }
+
+            let (node, children) = (*node, pruned.split_off(*start));
+            stack.pop();
+            for &child in &children {
+                self.nodes[child].parent = Some(node);
+            }
+            if self.nodes[node].message.is_some() || (stack.is_empty() && children.len() > 1) {
+                self.nodes[node].children = children;
+                pruned.push(node);
+            } else {
+                for &child in &children {
+                    self.nodes[child].parent = self.nodes[node].parent;
+                }
+                pruned.extend(children);
+            }
+        }
+
+        pruned
+    }
+
+    // Returns the base subject of a thread in lowercase and whether it is a
+    // reply or a forward.
+    fn subject(&self, root: usize) -> Option<(String, bool)> {
+        let node = &self.nodes[root];
+        let message = node.message.or_else(|| {
+            node.children
+                .iter()
+                .find_map(|&child| self.nodes[child].message)
+        })?;
+        let subject = self.messages[message].subject?;
+        let base_subject = self.prefixes.thread_name(subject);
+        if !base_subject.is_empty() {
+            Some((
+                base_subject.to_lowercase(),
+                base_subject.len() != subject.trim().len(),
+            ))
+        } else {
+            None
+        }
+    }
+
+    // Returns the sorted conversation trees below the nodes in `roots`.
+    fn build(&self, roots: &[usize]) -> Vec<Thread> {
+        // Depth-first traversal, the trees of the children of each node are
+        // kept at the end of `threads` until the node itself is built
+        let mut threads = Vec::new();
+        let mut stack = vec![(None, roots.iter(), 0)];
+        while let Some((node, children, start)) = stack.last_mut() {
+            if let Some(&child) = children.next() {
+                stack.push((
+                    Some(child),
+                    self.nodes[child].children.iter(),
+                    threads.len(),
+                ));
+                continue;
+            }
+
+            let (node, mut children) = (*node, threads.split_off(*start));
+            stack.pop();
+            children.sort_by_key(|thread| self.sort_key(thread));
+            match node {
+                Some(node) => threads.push(Thread {
+                    message: self.nodes[node].message,
+                    children,
+                }),
+                None => return children,
+            }
+        }
+
+        threads
+    }
+
+    // Threads are sorted by date and then by the order messages were added,
+    // empty nodes use the key of their first child.
+    fn sort_key(&self, thread: &Thread) -> (i64, usize) {
+        match thread.message {
+            Some(message) => (self.messages[message].date, message),
+            None => thread
+                .children
+                .first()
+                .map_or((i64::MAX, usize::MAX), |child| self.sort_key(child)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Thread, Threader};
+    use crate::SubjectPrefixes;
+
+    fn thread(message: Option<usize>, children: Vec<Thread>) -> Thread {
+        Thread { message, children }
+    }
+
+    #[test]
+    fn thread_messages() {
+        let mut threader = Threader::new();
+        for (message_id, references, date, subject) in [
+            // 0: root
+            (Some("1@x"), &[][..], 100, "Lunch"),
+            // 1: reply to a message that was not added
+            (Some("3@x"), &["1@x", "2@x"][..], 300, "Re: Re: Lunch"),
+            // 2: reply to the root, sent before 1
+            (Some("4@x"), &["1@x"][..], 200, "Re: Lunch"),
+            // 3: duplicate message id
+            (Some("4@x"), &["1@x"][..], 250, "Re: Lunch"),
+            // 4: replies to a missing message only
+            (Some("6@x"), &["5@x"][..], 50, "Meeting"),
+            (Some("7@x"), &["5@x"][..], 60, "Re: Meeting"),
+            // 6: same subject as a reply without references
+            (None, &[][..], 400, "Fwd: Lunch"),
+            // 7: unrelated messages with the same subject
+            (Some("8@x"), &[][..], 500, "Hello"),
+            (Some("9@x"), &[][..], 450, "hello"),
+            // 9: loop
+            (Some("10@x"), &["11@x"][..], 600, "Loop"),
+            (Some("11@x"), &["10@x"][..], 610, "Re: Loop"),
+        ] {
+            threader.add_message(message_id, references, date, Some(subject));
+        }
+
+        assert_eq!(
+            threader.threads(),
+            vec![
+                thread(None, vec![thread(Some(4), vec![]), thread(Some(5), vec![])]),
+                thread(
+                    Some(0),
+                    vec![
+                        thread(Some(2), vec![]),
+                        thread(Some(3), vec![]),
+                        thread(Some(1), vec![]),
+                        thread(Some(6), vec![]),
+                    ]
+                ),
+                thread(None, vec![thread(Some(8), vec![]), thread(Some(7), vec![])]),
+                thread(Some(10), vec![thread(Some(9), vec![])]),
+            ]
+        );
+
+        // Long reference lists and reply chains
+        let references = (0..200_000).map(|n| format!("{n}@x")).collect::<Vec<_>>();
+        let references = references.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut threader = Threader::new();
+        threader.add_message(Some("a@x"), &references, 100, None);
+        threader.add_message(Some("b@x"), &references[..1], 200, None);
+        assert_eq!(
+            threader.threads(),
+            vec![thread(
+                None,
+                vec![thread(Some(0), vec![]), thread(Some(1), vec![])]
+            )]
+        );
+
+        let mut threader = Threader::new();
+        for (n, references) in references.windows(2).take(5_000).enumerate() {
+            threader.add_message(Some(references[1]), &references[..1], n as i64, None);
+        }
+        let threads = threader.threads();
+        let mut count = 0;
+        let mut stack = threads.iter().collect::<Vec<_>>();
+        while let Some(thread) = stack.pop() {
+            count += thread.message.is_some() as usize;
+            stack.extend(&thread.children);
+        }
+        assert_eq!(count, 5_000);
+        assert!(threads.len() > 1);
+
+        // Merge threads using locale specific prefixes
+        let mut threader = Threader::with_prefixes(SubjectPrefixes::new().reply("wtrl"));
+        threader.add_message(Some("1@x"), &[], 100, Some("Hallo"));
+        threader.add_message(Some("2@x"), &[], 200, Some("WTRL: Hallo"));
+        assert_eq!(
+            threader.threads(),
+            vec![thread(Some(0), vec![thread(Some(1), vec![])])]
+        );
+    }
+}